@@ -0,0 +1,74 @@
+use crate::{decl, emit, lex};
+use pebblec_parse::lex::io;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Library that the extern block links against, see `#[link]`.
+    pub link: String,
+    /// Identifiers that are skipped within declarations, such as the export macros of a library
+    /// that are defined in a header that is not processed.
+    pub ignore: Vec<String>,
+}
+
+impl Config {
+    pub fn new(link: impl Into<String>) -> Self {
+        Self {
+            link: link.into(),
+            ignore: Vec::new(),
+        }
+    }
+
+    pub fn ignore(mut self, ident: impl Into<String>) -> Self {
+        self.ignore.push(ident.into());
+        self
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("could not open `{file}`: {io}")]
+    Io { file: String, io: std::io::Error },
+    #[error("line {line}: {msg}")]
+    Header { line: usize, msg: String },
+    #[error("failed to format the generated bindings")]
+    Format,
+}
+
+impl Error {
+    pub(crate) fn header(line: usize, msg: impl Into<String>) -> Self {
+        Self::Header {
+            line,
+            msg: msg.into(),
+        }
+    }
+}
+
+/// Generates the bindings of the C header at `path`.
+pub fn bindgen<P: AsRef<Path>>(path: P, config: &Config) -> Result<String, Error> {
+    let path = path.as_ref();
+    let src = io::read_string(path).map_err(|io| Error::Io {
+        file: path.display().to_string(),
+        io,
+    })?;
+    let origin = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    bindgen_string(&origin, &src, config)
+}
+
+/// Generates the bindings of the C header `src`, `origin` names the header in the output.
+pub fn bindgen_string(origin: &str, src: &str, config: &Config) -> Result<String, Error> {
+    let pp = lex::preprocess(src)?;
+    let mut header = decl::parse(&pp.tokens, &config.ignore)?;
+    header.add_defines(pp.defines());
+
+    let bindings = emit::emit(&header, &config.link, origin)?;
+    // there are no items to format
+    if header.is_empty() {
+        return Ok(bindings);
+    }
+    pebble_fmt::fmt::fmt_string(bindings).ok_or(Error::Format)
+}
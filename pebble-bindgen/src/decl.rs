@@ -0,0 +1,774 @@
+//! Parses the declarations of a preprocessed header: function prototypes, structs, unions, enums
+//! and typedefs. Variables, static and inline functions and typedefs that do not name a record
+//! have no binding, so they are recorded in [`Header::skipped`] instead.
+
+use crate::bindgen::Error;
+use crate::eval::{self, IntTy, Scope, Value, builtin_int, int_specifiers};
+use crate::lex::{Tok, Token};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CType {
+    Void,
+    Bool,
+    Int(IntTy),
+    Float(u32),
+    /// A struct or union, by its index in [`Header::records`].
+    Record(usize),
+    Ptr(Box<CType>),
+    /// Arrays without a length have a length of 0.
+    Array(Box<CType>, u64),
+    Func(Box<Proto>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proto {
+    pub ret: CType,
+    pub params: Vec<(Option<String>, CType)>,
+    pub variadic: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub name: String,
+    pub union: bool,
+    /// `None` for a record that is only declared, which is opaque.
+    pub fields: Option<Vec<(String, CType)>>,
+    pub line: usize,
+    /// Named by a typedef, which takes precedence over the tag.
+    typedef: bool,
+    /// Anonymous records that are the type of a field are named after it.
+    parent: Option<(usize, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Func {
+    pub name: String,
+    pub proto: Proto,
+    pub line: usize,
+}
+
+/// An enumerator or a `#define` that evaluates to an integer.
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: String,
+    pub value: Value,
+    /// Written in hex in the header.
+    pub hex: bool,
+    pub line: usize,
+}
+
+/// Why a declaration has no binding.
+#[derive(Debug, Clone)]
+pub enum Skip {
+    /// A `#define` that does not evaluate to an integer, such as a string or a float.
+    Define,
+    /// A variable, which cannot be linked.
+    Var,
+    /// A `static` or inline function, which the library does not export.
+    Func,
+    /// A typedef that does not name a record. Pebble has no type aliases, so it is written as
+    /// its type wherever it is used.
+    Typedef(CType),
+}
+
+/// A declaration that has no binding, see [`Skip`].
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub name: String,
+    pub skip: Skip,
+    pub line: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Header {
+    pub records: Vec<Record>,
+    pub funcs: Vec<Func>,
+    pub consts: Vec<Const>,
+    pub skipped: Vec<Skipped>,
+    typedefs: HashMap<String, CType>,
+    tags: HashMap<String, usize>,
+    enum_tags: HashMap<String, IntTy>,
+    enumerators: HashMap<String, Value>,
+}
+
+impl Scope for Header {
+    fn constant(&self, name: &str) -> Option<Value> {
+        self.enumerators.get(name).copied()
+    }
+
+    fn int_typedef(&self, name: &str) -> Option<IntTy> {
+        match self.typedefs.get(name) {
+            Some(CType::Int(ty)) => Some(*ty),
+            _ => None,
+        }
+    }
+}
+
+impl Header {
+    /// Adds the `#define`s that evaluate to an integer and skips the others, keeping the
+    /// constants in the order that they are declared.
+    pub fn add_defines<'a>(
+        &mut self,
+        defines: impl IntoIterator<Item = (&'a str, usize, Vec<Token>)>,
+    ) {
+        for (name, line, body) in defines {
+            match eval::eval(&body, self) {
+                Some(value) => self.consts.push(Const {
+                    name: name.to_string(),
+                    value,
+                    hex: is_hex(&body),
+                    line,
+                }),
+                None => self.skip(name.to_string(), Skip::Define, line),
+            }
+        }
+        self.consts.sort_by_key(|c| c.line);
+        self.skipped.sort_by_key(|s| s.line);
+    }
+
+    fn skip(&mut self, name: String, skip: Skip, line: usize) {
+        self.skipped.push(Skipped { name, skip, line });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.funcs.is_empty() && self.consts.is_empty()
+    }
+}
+
+pub fn parse(tokens: &[Token], ignore: &[String]) -> Result<Header, Error> {
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        ignore,
+        header: Header::default(),
+    };
+
+    while let Some(token) = parser.peek() {
+        match &token.tok {
+            Tok::Punct(";" | "}") => parser.index += 1,
+            // `extern "C" {` of headers that are shared with C++
+            Tok::Ident(ident)
+                if ident == "extern"
+                    && matches!(
+                        parser.tokens.get(parser.index + 1),
+                        Some(Token {
+                            tok: Tok::Str(_),
+                            ..
+                        })
+                    ) =>
+            {
+                parser.index += 2;
+                parser.eat("{");
+            }
+            _ => parser.external_decl()?,
+        }
+    }
+
+    let mut header = parser.header;
+    for id in 0..header.records.len() {
+        let name = record_name(&header.records, id);
+        header.records[id].name = name;
+    }
+    Ok(header)
+}
+
+/// Anonymous records are named after the field that they are the type of, or by their position
+/// if they are not a field.
+fn record_name(records: &[Record], id: usize) -> String {
+    let record = &records[id];
+    match (&record.parent, record.name.is_empty()) {
+        (_, false) => record.name.clone(),
+        (Some((parent, field)), true) => format!("{}_{}", record_name(records, *parent), field),
+        (None, true) => format!("Anon{id}"),
+    }
+}
+
+fn is_hex(tokens: &[Token]) -> bool {
+    tokens
+        .iter()
+        .any(|t| matches!(t.tok, Tok::Int(lit) if !lit.decimal))
+}
+
+/// Qualifiers and extensions that do not change the bindings.
+const IGNORED: &[&str] = &[
+    "const",
+    "volatile",
+    "restrict",
+    "__restrict",
+    "__restrict__",
+    "register",
+    "auto",
+    "inline",
+    "__inline",
+    "__inline__",
+    "__extension__",
+    "_Noreturn",
+    "__cdecl",
+    "__stdcall",
+];
+
+/// Extensions that are followed by parenthesized arguments, which are skipped with them.
+const IGNORED_CALLS: &[&str] = &["__attribute__", "__declspec", "__asm__", "__asm"];
+
+#[derive(Default)]
+struct Specifiers {
+    typedef: bool,
+    is_static: bool,
+    ty: Option<CType>,
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    ignore: &'a [String],
+    header: Header,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn peek_ident(&self) -> Option<&str> {
+        self.peek().and_then(Token::ident)
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        self.peek().is_some_and(|t| t.is_punct(punct))
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let is = self.is(punct);
+        if is {
+            self.index += 1;
+        }
+        is
+    }
+
+    fn line(&self) -> usize {
+        self.peek()
+            .or(self.tokens.last())
+            .map(|t| t.line)
+            .unwrap_or_default()
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{punct}`")))
+        }
+    }
+
+    fn error(&self, msg: impl Into<String>) -> Error {
+        Error::header(self.line(), msg)
+    }
+
+    /// Skips past the matching close of the delimiter at the current token.
+    fn skip_balanced(&mut self) -> Result<(), Error> {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token.tok {
+                Tok::Punct("(" | "[" | "{") => depth += 1,
+                Tok::Punct(")" | "]" | "}") => depth -= 1,
+                _ => {}
+            }
+            self.index += 1;
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+        Err(self.error("unexpected end of header"))
+    }
+
+    fn skip_ignored(&mut self) -> Result<(), Error> {
+        while let Some(ident) = self.peek_ident() {
+            if IGNORED.contains(&ident) || self.ignore.iter().any(|i| i == ident) {
+                self.index += 1;
+            } else if IGNORED_CALLS.contains(&ident) {
+                self.index += 1;
+                if self.is("(") {
+                    self.skip_balanced()?;
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn external_decl(&mut self) -> Result<(), Error> {
+        let specs = self.specifiers()?;
+        let base = specs.ty.ok_or_else(|| self.error("expected a type"))?;
+        if self.eat(";") {
+            return Ok(());
+        }
+
+        loop {
+            let line = self.line();
+            let (name, ty) = self.declarator(base.clone())?;
+            let name = name.ok_or_else(|| Error::header(line, "expected a name"))?;
+            self.skip_ignored()?;
+
+            if specs.typedef {
+                self.typedef(name, ty, line);
+            } else if let CType::Func(proto) = ty {
+                // inline definitions are not exported
+                if self.is("{") {
+                    self.header.skip(name, Skip::Func, line);
+                    return self.skip_balanced();
+                }
+                if specs.is_static {
+                    self.header.skip(name, Skip::Func, line);
+                } else {
+                    self.header.funcs.push(Func {
+                        name,
+                        proto: *proto,
+                        line,
+                    });
+                }
+            } else {
+                self.header.skip(name, Skip::Var, line);
+                if self.eat("=") {
+                    // variables are not bound, but their initializers are skipped
+                    while !self.is(",") && !self.is(";") {
+                        if self.peek().is_none() {
+                            return Err(self.error("unexpected end of header"));
+                        }
+                        if self.is("{") || self.is("(") {
+                            self.skip_balanced()?;
+                        } else {
+                            self.index += 1;
+                        }
+                    }
+                }
+            }
+
+            if !self.eat(",") {
+                return self.expect(";");
+            }
+        }
+    }
+
+    fn typedef(&mut self, name: String, ty: CType, line: usize) {
+        match ty {
+            CType::Record(id) if !self.header.records[id].typedef => {
+                let record = &mut self.header.records[id];
+                record.name = name.clone();
+                record.typedef = true;
+            }
+            _ => {
+                let skip = Skip::Typedef(ty.clone());
+                self.header.skip(name.clone(), skip, line);
+            }
+        }
+        self.header.typedefs.insert(name, ty);
+    }
+
+    fn specifiers(&mut self) -> Result<Specifiers, Error> {
+        let mut specs = Specifiers::default();
+        let mut words = Vec::new();
+        loop {
+            self.skip_ignored()?;
+            let Some(ident) = self.peek_ident() else {
+                break;
+            };
+
+            match ident {
+                "typedef" => specs.typedef = true,
+                "extern" => {}
+                "static" => specs.is_static = true,
+                "struct" | "union" => {
+                    specs.ty = Some(self.record()?);
+                    continue;
+                }
+                "enum" => {
+                    specs.ty = Some(self.enumeration()?);
+                    continue;
+                }
+                "void" => specs.ty = Some(CType::Void),
+                "_Bool" | "bool" => specs.ty = Some(CType::Bool),
+                "float" => specs.ty = Some(CType::Float(32)),
+                "double" => specs.ty = Some(CType::Float(64)),
+                "signed" | "unsigned" | "char" | "short" | "int" | "long" => {
+                    words.push(ident.to_string())
+                }
+                name if specs.ty.is_none() && words.is_empty() => {
+                    let ty = match self.header.typedefs.get(name) {
+                        Some(ty) => ty.clone(),
+                        None => match builtin_int(name) {
+                            Some(int) => CType::Int(int),
+                            None => return Err(self.error(format!("unknown type `{name}`"))),
+                        },
+                    };
+                    specs.ty = Some(ty);
+                }
+                _ => break,
+            }
+            self.index += 1;
+        }
+
+        if !words.is_empty() {
+            if specs.ty.is_some() {
+                let words = words.join(" ");
+                return Err(self.error(format!("`{words}` is not supported with this type")));
+            }
+            let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+            let int = int_specifiers(&words)
+                .ok_or_else(|| self.error(format!("invalid type `{}`", words.join(" "))))?;
+            specs.ty = Some(CType::Int(int));
+        }
+        Ok(specs)
+    }
+
+    fn record(&mut self) -> Result<CType, Error> {
+        let union = self.peek_ident() == Some("union");
+        let line = self.line();
+        self.index += 1;
+        self.skip_ignored()?;
+
+        let id = match self.peek_ident().map(str::to_string) {
+            Some(tag) => {
+                self.index += 1;
+                match self.header.tags.get(&tag) {
+                    Some(id) => *id,
+                    None => {
+                        let id = self.new_record(tag.clone(), union, line);
+                        self.header.tags.insert(tag, id);
+                        id
+                    }
+                }
+            }
+            None => self.new_record(String::new(), union, line),
+        };
+
+        self.skip_ignored()?;
+        if self.eat("{") {
+            if self.header.records[id].fields.is_some() {
+                return Err(Error::header(
+                    line,
+                    format!("redefinition of `{}`", self.header.records[id].name),
+                ));
+            }
+            let fields = self.fields(id)?;
+            let record = &mut self.header.records[id];
+            record.fields = Some(fields);
+            record.line = line;
+        }
+        Ok(CType::Record(id))
+    }
+
+    fn new_record(&mut self, name: String, union: bool, line: usize) -> usize {
+        self.header.records.push(Record {
+            name,
+            union,
+            fields: None,
+            line,
+            typedef: false,
+            parent: None,
+        });
+        self.header.records.len() - 1
+    }
+
+    fn fields(&mut self, parent: usize) -> Result<Vec<(String, CType)>, Error> {
+        let mut fields = Vec::new();
+        let mut anon = 0;
+        loop {
+            self.skip_ignored()?;
+            if self.eat("}") {
+                return Ok(fields);
+            }
+
+            let specs = self.specifiers()?;
+            let base = specs.ty.ok_or_else(|| self.error("expected a type"))?;
+            if self.eat(";") {
+                // members of an anonymous struct or union are accessed through a named field
+                if let CType::Record(id) = base {
+                    if self.header.records[id].name.is_empty() {
+                        let name = format!("anon{anon}");
+                        anon += 1;
+                        self.header.records[id].parent = Some((parent, name.clone()));
+                        fields.push((name, base));
+                    }
+                }
+                continue;
+            }
+
+            loop {
+                let line = self.line();
+                let (name, ty) = self.declarator(base.clone())?;
+                let name = name.ok_or_else(|| Error::header(line, "expected a field name"))?;
+                self.skip_ignored()?;
+                if self.is(":") {
+                    return Err(self.error("bitfields are not supported"));
+                }
+
+                let mut inner = &ty;
+                while let CType::Array(elem, _) = inner {
+                    inner = elem;
+                }
+                match inner {
+                    CType::Record(id) if self.header.records[*id].name.is_empty() => {
+                        self.header.records[*id].parent = Some((parent, name.clone()));
+                    }
+                    _ => {}
+                }
+
+                if let CType::Array(_, 0) = ty {
+                    return Err(Error::header(
+                        line,
+                        "flexible array members are not supported",
+                    ));
+                }
+                fields.push((name, ty));
+                if !self.eat(",") {
+                    self.expect(";")?;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn enumeration(&mut self) -> Result<CType, Error> {
+        self.index += 1;
+        self.skip_ignored()?;
+        let tag = self.peek_ident().map(str::to_string);
+        if tag.is_some() {
+            self.index += 1;
+        }
+        self.skip_ignored()?;
+
+        if !self.eat("{") {
+            // GCC gives an enum without negative values the type `unsigned int`
+            let ty = tag
+                .and_then(|tag| self.header.enum_tags.get(&tag).copied())
+                .unwrap_or(IntTy::UINT);
+            return Ok(CType::Int(ty));
+        }
+
+        let mut enumerators = Vec::new();
+        let mut next = Value::new(0, IntTy::INT);
+        while !self.eat("}") {
+            let line = self.line();
+            let name = self
+                .peek_ident()
+                .map(str::to_string)
+                .ok_or_else(|| self.error("expected an enumerator"))?;
+            self.index += 1;
+            self.skip_ignored()?;
+
+            let (value, hex) = if self.eat("=") {
+                let start = self.index;
+                let mut depth = 0;
+                while let Some(token) = self.peek() {
+                    match token.tok {
+                        Tok::Punct("," | "}") if depth == 0 => break,
+                        Tok::Punct("(") => depth += 1,
+                        Tok::Punct(")") => depth -= 1,
+                        _ => {}
+                    }
+                    self.index += 1;
+                }
+                let tokens = &self.tokens[start..self.index];
+                let value = eval::eval(tokens, &self.header).ok_or_else(|| {
+                    Error::header(line, format!("value of `{name}` is not a constant"))
+                })?;
+                (value, is_hex(tokens))
+            } else {
+                (next, false)
+            };
+
+            next = Value::new(value.value + 1, value.ty);
+            self.header.enumerators.insert(name.clone(), value);
+            enumerators.push((name, value, hex, line));
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+
+        let negative = enumerators.iter().any(|(_, v, _, _)| v.value < 0);
+        let wide = enumerators
+            .iter()
+            .any(|(_, v, _, _)| v.value > IntTy::UINT.max() || v.value < IntTy::INT.min());
+        let ty = match (negative, wide) {
+            (false, false) => IntTy::UINT,
+            (true, false) => IntTy::INT,
+            (false, true) => IntTy::ULONG,
+            (true, true) => IntTy::LONG,
+        };
+        if let Some(tag) = tag {
+            self.header.enum_tags.insert(tag, ty);
+        }
+        for (name, value, hex, line) in enumerators {
+            self.header.consts.push(Const {
+                name,
+                value: Value::new(value.value, ty),
+                hex,
+                line,
+            });
+        }
+        Ok(CType::Int(ty))
+    }
+
+    /// Parses a declarator, applying its pointers, arrays and parameters to `base`. The name is
+    /// `None` for an abstract declarator, such as an unnamed parameter.
+    fn declarator(&mut self, base: CType) -> Result<(Option<String>, CType), Error> {
+        let mut ty = base;
+        loop {
+            self.skip_ignored()?;
+            if !self.eat("*") {
+                break;
+            }
+            ty = CType::Ptr(Box::new(ty));
+        }
+
+        // `(*name)(...)` binds the name before the suffixes, which apply to the outer type
+        if self.is("(") && self.is_nested_declarator() {
+            let start = self.index + 1;
+            self.skip_balanced()?;
+            let ty = self.suffixes(ty)?;
+            let end = self.index;
+
+            self.index = start;
+            let (name, ty) = self.declarator(ty)?;
+            self.expect(")")?;
+            self.index = end;
+            return Ok((name, ty));
+        }
+
+        let name = self.peek_ident().map(str::to_string);
+        if name.is_some() {
+            self.index += 1;
+        }
+        self.skip_ignored()?;
+        Ok((name, self.suffixes(ty)?))
+    }
+
+    /// Whether the paren at the current token opens a declarator, rather than parameters.
+    fn is_nested_declarator(&self) -> bool {
+        match self.tokens.get(self.index + 1) {
+            Some(token) => match &token.tok {
+                Tok::Punct("*" | "(") => true,
+                Tok::Ident(ident) => {
+                    !self.header.typedefs.contains_key(ident)
+                        && builtin_int(ident).is_none()
+                        && !IGNORED.contains(&ident.as_str())
+                        && !matches!(
+                            ident.as_str(),
+                            "void"
+                                | "_Bool"
+                                | "bool"
+                                | "float"
+                                | "double"
+                                | "signed"
+                                | "unsigned"
+                                | "char"
+                                | "short"
+                                | "int"
+                                | "long"
+                                | "struct"
+                                | "union"
+                                | "enum"
+                        )
+                }
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    fn suffixes(&mut self, ty: CType) -> Result<CType, Error> {
+        enum Suffix {
+            Array(u64),
+            Params(Vec<(Option<String>, CType)>, bool),
+        }
+
+        let mut suffixes = Vec::new();
+        loop {
+            if self.eat("[") {
+                let start = self.index;
+                while !self.is("]") {
+                    if self.peek().is_none() {
+                        return Err(self.error("expected `]`"));
+                    }
+                    self.index += 1;
+                }
+                let tokens = &self.tokens[start..self.index];
+                self.index += 1;
+
+                let len = if tokens.is_empty() {
+                    0
+                } else {
+                    let value = eval::eval(tokens, &self.header)
+                        .ok_or_else(|| self.error("array length is not a constant"))?;
+                    u64::try_from(value.value)
+                        .map_err(|_| self.error("array length is negative"))?
+                };
+                suffixes.push(Suffix::Array(len));
+            } else if self.eat("(") {
+                let (params, variadic) = self.params()?;
+                suffixes.push(Suffix::Params(params, variadic));
+                self.skip_ignored()?;
+            } else {
+                break;
+            }
+        }
+
+        // the suffix closest to the name is the outermost type
+        Ok(suffixes
+            .into_iter()
+            .rev()
+            .fold(ty, |ty, suffix| match suffix {
+                Suffix::Array(len) => CType::Array(Box::new(ty), len),
+                Suffix::Params(params, variadic) => CType::Func(Box::new(Proto {
+                    ret: ty,
+                    params,
+                    variadic,
+                })),
+            }))
+    }
+
+    /// Parses parameters after the opening paren.
+    fn params(&mut self) -> Result<(Vec<(Option<String>, CType)>, bool), Error> {
+        let mut params = Vec::new();
+        if self.eat(")") {
+            return Ok((params, false));
+        }
+        if self.peek_ident() == Some("void")
+            && self
+                .tokens
+                .get(self.index + 1)
+                .is_some_and(|t| t.is_punct(")"))
+        {
+            self.index += 2;
+            return Ok((params, false));
+        }
+
+        loop {
+            if self.eat("...") {
+                self.expect(")")?;
+                return Ok((params, true));
+            }
+
+            let specs = self.specifiers()?;
+            let base = specs
+                .ty
+                .ok_or_else(|| self.error("expected a parameter type"))?;
+            let (name, ty) = self.declarator(base)?;
+            // arrays and functions are passed as pointers
+            let ty = match ty {
+                CType::Array(elem, _) => CType::Ptr(elem),
+                CType::Func(proto) => CType::Ptr(Box::new(CType::Func(proto))),
+                ty => ty,
+            };
+            params.push((name, ty));
+
+            if !self.eat(",") {
+                self.expect(")")?;
+                return Ok((params, false));
+            }
+        }
+    }
+}
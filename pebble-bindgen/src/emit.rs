@@ -0,0 +1,295 @@
+//! Writes the parsed declarations of a header as Pebble items.
+//!
+//! Pointers become optional references, since C does not say which of them may be null. A
+//! reference coerces into an optional, so these are as convenient to pass as `&T`.
+//!
+//! Pebble cannot yet declare structs that refer back to themselves, so a field that points to a
+//! record that leads back to the field's record points to bytes instead.
+//!
+//! Declarations without a binding, and fields whose pointers were written as bytes, are listed in
+//! `// skipped:` comments at the top, so that they are not lost silently.
+
+use crate::bindgen::Error;
+use crate::decl::{CType, Const, Header, Proto, Record, Skip, Skipped};
+use std::fmt::Write;
+
+/// Keywords of Pebble, see `keyword_ident` in `pebblec-parse`.
+const KEYWORDS: &[&str] = &[
+    "let", "if", "else", "true", "false", "null", "for", "in", "fn", "self", "return", "continue",
+    "break", "as", "move", "defer", "struct", "union", "unsafe", "impl", "enum", "loop", "while",
+    "const", "use", "extern",
+];
+
+/// Appends an underscore to names that are keywords in Pebble.
+fn ident(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn emit(header: &Header, link: &str, origin: &str) -> Result<String, Error> {
+    let mut out = format!("// generated by `pebble_bindgen` from `{origin}`\n\n");
+
+    let mut notes = String::new();
+    for skipped in header.skipped.iter() {
+        emit_skipped(&mut notes, header, skipped);
+    }
+    for (id, record) in header.records.iter().enumerate() {
+        emit_erased(&mut notes, header, id, record);
+    }
+    if !notes.is_empty() {
+        out.push_str(&notes);
+        out.push('\n');
+    }
+
+    for konst in header.consts.iter() {
+        emit_const(&mut out, konst);
+    }
+    if !header.consts.is_empty() {
+        out.push('\n');
+    }
+
+    if !header.funcs.is_empty() {
+        writeln!(out, "#[link({link:?})]").unwrap();
+        out.push_str("extern(\"C\") {\n");
+        for func in header.funcs.iter() {
+            if KEYWORDS.contains(&func.name.as_str()) {
+                return Err(Error::header(
+                    func.line,
+                    format!(
+                        "`{}` is a keyword in Pebble and cannot be linked",
+                        func.name
+                    ),
+                ));
+            }
+
+            let params = params(header, &func.proto, true, func.line, None)?;
+            write!(out, "    {}: ({})", func.name, params).unwrap();
+            if func.proto.ret != CType::Void {
+                write!(
+                    out,
+                    " -> {}",
+                    ty(header, &func.proto.ret, true, func.line, None)?
+                )
+                .unwrap();
+            }
+            out.push_str(";\n");
+        }
+        out.push_str("}\n\n");
+    }
+
+    for (id, record) in header.records.iter().enumerate() {
+        emit_record(&mut out, header, id, record)?;
+    }
+
+    Ok(out)
+}
+
+fn emit_const(out: &mut String, konst: &Const) {
+    let value = konst.value.value;
+    let value = match (konst.hex, value < 0) {
+        (true, false) => format!("{value:#x}"),
+        (true, true) => format!("-{:#x}", -value),
+        (false, _) => value.to_string(),
+    };
+    writeln!(
+        out,
+        "{}: const {} = {};",
+        ident(&konst.name),
+        konst.value.ty.as_str(),
+        value
+    )
+    .unwrap();
+}
+
+fn emit_skipped(out: &mut String, header: &Header, skipped: &Skipped) {
+    let name = &skipped.name;
+    match &skipped.skip {
+        Skip::Define => writeln!(out, "// skipped: `#define {name}`, which is not an integer"),
+        Skip::Var => writeln!(out, "// skipped: variable `{name}`, which cannot be linked"),
+        Skip::Func => writeln!(
+            out,
+            "// skipped: function `{name}`, which is static or inline"
+        ),
+        Skip::Typedef(alias) => match ty(header, alias, true, skipped.line, None) {
+            Ok(alias) => writeln!(out, "// skipped: typedef `{name}`, written as `{alias}`"),
+            Err(_) => writeln!(out, "// skipped: typedef `{name}`, which has no binding"),
+        },
+    }
+    .unwrap();
+}
+
+/// Notes the fields of `record` that point to bytes, because their pointee leads back to `record`.
+fn emit_erased(out: &mut String, header: &Header, id: usize, record: &Record) {
+    for (name, field) in record.fields.iter().flatten() {
+        if erases(header, field, id) {
+            writeln!(
+                out,
+                "// skipped: type of `{}.{}`, which leads back to `{}` and points to bytes",
+                record.name, name, record.name
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn emit_record(out: &mut String, header: &Header, id: usize, record: &Record) -> Result<(), Error> {
+    let fields = match &record.fields {
+        Some(fields) if !fields.is_empty() => fields,
+        // opaque records are only ever behind a pointer
+        _ => {
+            writeln!(out, "{}: struct {{}}\n", ident(&record.name)).unwrap();
+            return Ok(());
+        }
+    };
+
+    out.push_str("#[repr(C)]\n");
+    let kind = if record.union { "union" } else { "struct" };
+    writeln!(out, "{}: {} {{", ident(&record.name), kind).unwrap();
+    for (name, field) in fields.iter() {
+        writeln!(
+            out,
+            "    {}: {},",
+            ident(name),
+            ty(header, field, true, record.line, Some(id))?
+        )
+        .unwrap();
+    }
+    out.push_str("}\n\n");
+    Ok(())
+}
+
+fn params(
+    header: &Header,
+    proto: &Proto,
+    names: bool,
+    line: usize,
+    within: Option<usize>,
+) -> Result<String, Error> {
+    let mut params = proto
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, (name, param))| {
+            let param = ty(header, param, true, line, within)?;
+            Ok(match (names, name) {
+                (true, Some(name)) => format!("{}: {}", ident(name), param),
+                (true, None) => format!("arg{i}: {param}"),
+                (false, _) => param,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    if proto.variadic {
+        params.push("...".to_string());
+    }
+    Ok(params.join(", "))
+}
+
+/// `nullable` is set for the outermost type of a parameter, field or return value, where a
+/// pointer becomes an optional. `within` is the record whose field is being written.
+fn ty(
+    header: &Header,
+    ty: &CType,
+    nullable: bool,
+    line: usize,
+    within: Option<usize>,
+) -> Result<String, Error> {
+    let opt = if nullable { "?" } else { "" };
+    Ok(match ty {
+        CType::Void => {
+            return Err(Error::header(
+                line,
+                "`void` is only supported as a return type or behind a pointer",
+            ));
+        }
+        CType::Bool => "bool".to_string(),
+        CType::Int(int) => int.as_str().to_string(),
+        CType::Float(32) => "f32".to_string(),
+        CType::Float(_) => "f64".to_string(),
+        CType::Record(id) => ident(&header.records[*id].name),
+        CType::Ptr(inner) => match inner.as_ref() {
+            CType::Func(proto) => {
+                if proto.variadic {
+                    return Err(Error::header(
+                        line,
+                        "pointers to variadic functions are not supported",
+                    ));
+                }
+                let mut func = format!("{opt}fn({})", params(header, proto, false, line, within)?);
+                if proto.ret != CType::Void {
+                    write!(
+                        func,
+                        " -> {}",
+                        self::ty(header, &proto.ret, true, line, within)?
+                    )
+                    .unwrap();
+                }
+                func
+            }
+            // `void *` has no equivalent, so it points to bytes
+            CType::Void => format!("{opt}&u8"),
+            inner if within.is_some_and(|id| leads_to(header, inner, id)) => format!("{opt}&u8"),
+            inner => format!("{opt}&{}", self::ty(header, inner, false, line, within)?),
+        },
+        CType::Array(_, 0) => {
+            return Err(Error::header(
+                line,
+                "arrays without a length are not supported",
+            ));
+        }
+        CType::Array(elem, len) => {
+            format!("[{}; {}]", self::ty(header, elem, true, line, within)?, len)
+        }
+        CType::Func(_) => {
+            return Err(Error::header(
+                line,
+                "function types are only supported behind a pointer",
+            ));
+        }
+    })
+}
+
+/// Whether `ty` has a pointer that `ty()` writes as `&u8` within the record `within`.
+fn erases(header: &Header, ty: &CType, within: usize) -> bool {
+    match ty {
+        CType::Ptr(inner) => match inner.as_ref() {
+            CType::Func(proto) => {
+                erases(header, &proto.ret, within)
+                    || proto
+                        .params
+                        .iter()
+                        .any(|(_, ty)| erases(header, ty, within))
+            }
+            CType::Void => false,
+            inner => leads_to(header, inner, within) || erases(header, inner, within),
+        },
+        CType::Array(elem, _) => erases(header, elem, within),
+        _ => false,
+    }
+}
+
+/// Whether `ty` refers to the record `target`, through any of its fields.
+fn leads_to(header: &Header, ty: &CType, target: usize) -> bool {
+    let mut visited = Vec::new();
+    let mut tys = vec![ty];
+    while let Some(ty) = tys.pop() {
+        match ty {
+            CType::Record(id) if *id == target => return true,
+            CType::Record(id) if !visited.contains(id) => {
+                visited.push(*id);
+                if let Some(fields) = &header.records[*id].fields {
+                    tys.extend(fields.iter().map(|(_, ty)| ty));
+                }
+            }
+            CType::Ptr(inner) | CType::Array(inner, _) => tys.push(inner),
+            CType::Func(proto) => {
+                tys.push(&proto.ret);
+                tys.extend(proto.params.iter().map(|(_, ty)| ty));
+            }
+            _ => {}
+        }
+    }
+    false
+}
@@ -0,0 +1,355 @@
+//! Evaluates C integer constant expressions, typed with C's rules for literals and arithmetic.
+
+use crate::lex::{IntLit, Tok, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntTy {
+    pub bits: u32,
+    pub signed: bool,
+}
+
+impl IntTy {
+    pub const INT: Self = Self::new(32, true);
+    pub const UINT: Self = Self::new(32, false);
+    pub const LONG: Self = Self::new(64, true);
+    pub const ULONG: Self = Self::new(64, false);
+
+    pub const fn new(bits: u32, signed: bool) -> Self {
+        Self { bits, signed }
+    }
+
+    /// The name of the equivalent Pebble integer.
+    pub fn as_str(&self) -> &'static str {
+        match (self.bits, self.signed) {
+            (8, true) => "i8",
+            (8, false) => "u8",
+            (16, true) => "i16",
+            (16, false) => "u16",
+            (32, true) => "i32",
+            (32, false) => "u32",
+            (64, true) => "i64",
+            (64, false) => "u64",
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn min(&self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(&self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Truncates `value` to this width, as a conversion in C does.
+    pub fn wrap(&self, value: i128) -> i128 {
+        let mask = (1i128 << self.bits) - 1;
+        let value = value & mask;
+        if self.signed && value > self.max() {
+            value - (1 << self.bits)
+        } else {
+            value
+        }
+    }
+
+    /// Integer promotion, anything narrower than `int` is computed as an `int`.
+    fn promote(self) -> Self {
+        if self.bits < 32 { Self::INT } else { self }
+    }
+
+    /// The usual arithmetic conversions of a binary operator.
+    fn common(self, other: Self) -> Self {
+        let (lhs, rhs) = (self.promote(), other.promote());
+        let bits = lhs.bits.max(rhs.bits);
+        let unsigned = (lhs.bits == bits && !lhs.signed) || (rhs.bits == bits && !rhs.signed);
+        Self::new(bits, !unsigned)
+    }
+
+    /// Type of an integer literal, the first of the candidates for its suffix that fits.
+    fn of_literal(lit: &IntLit) -> Self {
+        let candidates: &[Self] = match (lit.unsigned, lit.long, lit.decimal) {
+            (false, false, true) => &[Self::INT, Self::LONG],
+            (false, false, false) => &[Self::INT, Self::UINT, Self::LONG, Self::ULONG],
+            (true, false, _) => &[Self::UINT, Self::ULONG],
+            (false, true, true) => &[Self::LONG],
+            (false, true, false) => &[Self::LONG, Self::ULONG],
+            (true, true, _) => &[Self::ULONG],
+        };
+        candidates
+            .iter()
+            .copied()
+            .find(|ty| lit.value as i128 <= ty.max())
+            .unwrap_or(Self::ULONG)
+    }
+}
+
+/// A typed integer constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Value {
+    pub value: i128,
+    pub ty: IntTy,
+}
+
+impl Value {
+    pub fn new(value: i128, ty: IntTy) -> Self {
+        Self {
+            value: ty.wrap(value),
+            ty,
+        }
+    }
+
+    fn bool(value: bool) -> Self {
+        Self::new(value as i128, IntTy::INT)
+    }
+}
+
+/// Resolves the identifiers within an expression.
+pub trait Scope {
+    /// The value of a constant, such as an enumerator.
+    fn constant(&self, name: &str) -> Option<Value>;
+
+    /// The integer type named by a typedef, for casts.
+    fn int_typedef(&self, name: &str) -> Option<IntTy>;
+}
+
+/// Evaluates all of `tokens` as a constant expression, `None` if it is not one.
+pub fn eval(tokens: &[Token], scope: &dyn Scope) -> Option<Value> {
+    let mut eval = Eval {
+        tokens,
+        index: 0,
+        scope,
+    };
+    let value = eval.ternary()?;
+    (eval.index == tokens.len()).then_some(value)
+}
+
+struct Eval<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    scope: &'a dyn Scope,
+}
+
+/// Binary operators by precedence, loosest first.
+const BINARY: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Eval<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.index).map(|t| &t.tok)
+    }
+
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Tok::Punct(punct)) => Some(punct),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.peek_punct() == Some(punct) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ternary(&mut self) -> Option<Value> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Some(condition);
+        }
+        let then = self.ternary()?;
+        if !self.eat(":") {
+            return None;
+        }
+        let otherwise = self.ternary()?;
+        let ty = then.ty.common(otherwise.ty);
+        let value = if condition.value != 0 {
+            then
+        } else {
+            otherwise
+        };
+        Some(Value::new(value.value, ty))
+    }
+
+    fn binary(&mut self, level: usize) -> Option<Value> {
+        let Some(ops) = BINARY.get(level) else {
+            return self.unary();
+        };
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(op) = self.peek_punct().filter(|op| ops.contains(op)) {
+            self.index += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = binary_op(op, lhs, rhs)?;
+        }
+        Some(lhs)
+    }
+
+    fn unary(&mut self) -> Option<Value> {
+        if let Some(op @ ("-" | "+" | "~" | "!")) = self.peek_punct() {
+            self.index += 1;
+            let inner = self.unary()?;
+            let ty = inner.ty.promote();
+            return Some(match op {
+                "-" => Value::new(-inner.value, ty),
+                "+" => Value::new(inner.value, ty),
+                "~" => Value::new(!inner.value, ty),
+                _ => Value::bool(inner.value == 0),
+            });
+        }
+
+        if self.peek_punct() == Some("(") {
+            if let Some((ty, len)) = self.cast_ty(self.index + 1) {
+                self.index += len + 2;
+                let inner = self.unary()?;
+                return Some(Value::new(inner.value, ty));
+            }
+        }
+
+        self.primary()
+    }
+
+    /// Parses the type name of a cast starting at `index`, along with its length in tokens.
+    fn cast_ty(&self, index: usize) -> Option<(IntTy, usize)> {
+        let mut len = 0;
+        let mut words = Vec::new();
+        while let Some(Token {
+            tok: Tok::Ident(ident),
+            ..
+        }) = self.tokens.get(index + len)
+        {
+            words.push(ident.as_str());
+            len += 1;
+        }
+
+        let ty = match words.as_slice() {
+            [] => return None,
+            [name] => builtin_int(name).or_else(|| self.scope.int_typedef(name))?,
+            words => int_specifiers(words)?,
+        };
+        match self.tokens.get(index + len) {
+            Some(Token {
+                tok: Tok::Punct(")"),
+                ..
+            }) => Some((ty, len)),
+            _ => None,
+        }
+    }
+
+    fn primary(&mut self) -> Option<Value> {
+        let token = self.tokens.get(self.index)?;
+        self.index += 1;
+        match &token.tok {
+            Tok::Int(lit) => Some(Value::new(lit.value as i128, IntTy::of_literal(lit))),
+            Tok::Char(c) => Some(Value::new(*c as i128, IntTy::INT)),
+            Tok::Ident(ident) => self.scope.constant(ident),
+            Tok::Punct("(") => {
+                let value = self.ternary()?;
+                self.eat(")").then_some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn binary_op(op: &str, lhs: Value, rhs: Value) -> Option<Value> {
+    let ty = lhs.ty.common(rhs.ty);
+    let (l, r) = (ty.wrap(lhs.value), ty.wrap(rhs.value));
+    Some(match op {
+        "||" => Value::bool(lhs.value != 0 || rhs.value != 0),
+        "&&" => Value::bool(lhs.value != 0 && rhs.value != 0),
+        "==" => Value::bool(l == r),
+        "!=" => Value::bool(l != r),
+        "<" => Value::bool(l < r),
+        ">" => Value::bool(l > r),
+        "<=" => Value::bool(l <= r),
+        ">=" => Value::bool(l >= r),
+        "|" => Value::new(l | r, ty),
+        "^" => Value::new(l ^ r, ty),
+        "&" => Value::new(l & r, ty),
+        "+" => Value::new(l + r, ty),
+        "-" => Value::new(l - r, ty),
+        "*" => Value::new(l.checked_mul(r)?, ty),
+        "/" => Value::new(l.checked_div(r)?, ty),
+        "%" => Value::new(l.checked_rem(r)?, ty),
+        // shifts take the type of the left operand
+        "<<" | ">>" => {
+            let ty = lhs.ty.promote();
+            let shift = u32::try_from(rhs.value).ok().filter(|s| *s < ty.bits)?;
+            let value = ty.wrap(lhs.value);
+            if op == "<<" {
+                Value::new(value << shift, ty)
+            } else {
+                Value::new(value >> shift, ty)
+            }
+        }
+        _ => unreachable!(),
+    })
+}
+
+/// Integer types of `<stdint.h>` and `<stddef.h>`, assuming a 64-bit Unix target.
+pub fn builtin_int(name: &str) -> Option<IntTy> {
+    Some(match name {
+        "int8_t" => IntTy::new(8, true),
+        "uint8_t" => IntTy::new(8, false),
+        "int16_t" => IntTy::new(16, true),
+        "uint16_t" => IntTy::new(16, false),
+        "int32_t" | "wchar_t" => IntTy::INT,
+        "uint32_t" => IntTy::UINT,
+        "int64_t" | "ssize_t" | "intptr_t" | "ptrdiff_t" | "intmax_t" | "off_t" => IntTy::LONG,
+        "uint64_t" | "size_t" | "uintptr_t" | "uintmax_t" => IntTy::ULONG,
+        _ => return None,
+    })
+}
+
+/// The integer type named by a list of specifiers such as `unsigned long int`, `None` if any
+/// specifier is not part of an integer type.
+///
+/// `char` is unsigned, so that strings match Pebble's `&u8`.
+pub fn int_specifiers(words: &[&str]) -> Option<IntTy> {
+    let mut signed = None;
+    let mut bits = None;
+    let mut longs = 0;
+    for word in words {
+        match *word {
+            "signed" => signed = Some(true),
+            "unsigned" => signed = Some(false),
+            "char" => bits = Some(8),
+            "short" => bits = Some(16),
+            "int" => bits = Some(bits.unwrap_or(32)),
+            "long" => longs += 1,
+            _ => return None,
+        }
+    }
+
+    let bits = match (bits, longs) {
+        (bits, 0) => bits.unwrap_or(32),
+        (None | Some(32), 1 | 2) => 64,
+        _ => return None,
+    };
+    let signed = match signed {
+        Some(signed) => signed,
+        None => bits != 8,
+    };
+    Some(IntTy::new(bits, signed))
+}
@@ -0,0 +1,574 @@
+//! Tokenizes C headers and runs the part of the preprocessor that bindings need: conditionals,
+//! and the expansion of macros defined within the header itself.
+//!
+//! `#include` is ignored, types from the standard headers are known by name, see
+//! [`crate::eval::builtin_int`].
+
+use crate::bindgen::Error;
+use crate::eval::{self, IntTy, Scope, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    Ident(String),
+    Int(IntLit),
+    /// Floating point literals are never part of the bindings.
+    Float,
+    Str(String),
+    Char(u32),
+    Punct(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntLit {
+    pub value: u64,
+    pub unsigned: bool,
+    pub long: bool,
+    pub decimal: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub tok: Tok,
+    pub line: usize,
+}
+
+impl Token {
+    pub fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.tok, Tok::Punct(p) if p == punct)
+    }
+
+    pub fn ident(&self) -> Option<&str> {
+        match &self.tok {
+            Tok::Ident(ident) => Some(ident),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Option<Vec<String>>,
+    body: Vec<Token>,
+    line: usize,
+    /// Predefined to describe the target, rather than defined by the header.
+    builtin: bool,
+}
+
+/// Macros that headers commonly test for, describing a 64-bit Linux target.
+const PREDEFINED: &[(&str, &str)] = &[
+    ("__STDC__", "1"),
+    ("__STDC_VERSION__", "201112L"),
+    ("__linux__", "1"),
+    ("__x86_64__", "1"),
+    ("__LP64__", "1"),
+];
+
+/// The tokens of a header after preprocessing.
+#[derive(Debug)]
+pub struct Preprocessed {
+    pub tokens: Vec<Token>,
+    macros: HashMap<String, Macro>,
+    order: Vec<String>,
+}
+
+impl Preprocessed {
+    /// Object-like macros defined by the header, in order of definition, with their
+    /// expanded bodies.
+    pub fn defines(&self) -> Vec<(&str, usize, Vec<Token>)> {
+        self.order
+            .iter()
+            .filter_map(|name| {
+                let mac = self.macros.get(name)?;
+                if mac.params.is_some() || mac.builtin || mac.body.is_empty() {
+                    return None;
+                }
+                let mut body = Vec::new();
+                expand(&self.macros, &mac.body, &mut vec![name.clone()], &mut body);
+                Some((name.as_str(), mac.line, body))
+            })
+            .collect()
+    }
+}
+
+pub fn preprocess(src: &str) -> Result<Preprocessed, Error> {
+    let mut macros = HashMap::new();
+    for (name, value) in PREDEFINED {
+        let body = tokenize(value, 0)?;
+        macros.insert(
+            name.to_string(),
+            Macro {
+                params: None,
+                body,
+                line: 0,
+                builtin: true,
+            },
+        );
+    }
+
+    let mut pp = Preprocessed {
+        tokens: Vec::new(),
+        macros,
+        order: Vec::new(),
+    };
+    let mut raw = Vec::new();
+    // every conditional records whether its current branch is active, if any branch was, and
+    // the line that it starts on
+    let mut conditions: Vec<(bool, bool, usize)> = Vec::new();
+
+    for (line, text) in logical_lines(&strip_comments(src)) {
+        let active = conditions.iter().all(|(active, ..)| *active);
+        let trimmed = text.trim_start();
+        let Some(directive) = trimmed.strip_prefix('#') else {
+            if active {
+                raw.extend(tokenize(&text, line)?);
+            }
+            continue;
+        };
+
+        let directive = directive.trim_start();
+        let (name, rest) = directive
+            .split_once(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or((directive, ""));
+        match name {
+            "ifdef" | "ifndef" => {
+                let defined = pp.macros.contains_key(rest.trim());
+                let taken = active && (defined == (name == "ifdef"));
+                conditions.push((taken, taken, line));
+            }
+            "if" => {
+                let taken = active && pp.condition(rest, line)?;
+                conditions.push((taken, taken, line));
+            }
+            "elif" => {
+                let parent = conditions.iter().rev().skip(1).all(|(active, ..)| *active);
+                let (_, any, start) = *conditions
+                    .last()
+                    .ok_or_else(|| Error::header(line, "`#elif` without `#if`"))?;
+                let taken = parent && !any && pp.condition(rest, line)?;
+                *conditions.last_mut().unwrap() = (taken, any || taken, start);
+            }
+            "else" => {
+                let parent = conditions.iter().rev().skip(1).all(|(active, ..)| *active);
+                let (_, any, start) = *conditions
+                    .last()
+                    .ok_or_else(|| Error::header(line, "`#else` without `#if`"))?;
+                *conditions.last_mut().unwrap() = (parent && !any, true, start);
+            }
+            "endif" => {
+                conditions
+                    .pop()
+                    .ok_or_else(|| Error::header(line, "`#endif` without `#if`"))?;
+            }
+            _ if !active => {}
+            "define" => pp.define(rest, line)?,
+            "undef" => {
+                pp.macros.remove(rest.trim());
+            }
+            "include" | "pragma" | "line" | "warning" | "" => {}
+            "error" => return Err(Error::header(line, format!("#error {}", rest.trim()))),
+            other => {
+                return Err(Error::header(line, format!("unknown directive `#{other}`")));
+            }
+        }
+    }
+
+    if let Some((_, _, start)) = conditions.last() {
+        return Err(Error::header(*start, "unterminated `#if`"));
+    }
+
+    expand(&pp.macros, &raw, &mut Vec::new(), &mut pp.tokens);
+    Ok(pp)
+}
+
+impl Preprocessed {
+    fn define(&mut self, rest: &str, line: usize) -> Result<(), Error> {
+        let rest = rest.trim_start();
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let (name, rest) = rest.split_at(end);
+        if name.is_empty() {
+            return Err(Error::header(line, "expected a macro name"));
+        }
+
+        // a function-like macro has no whitespace before its parameters
+        let (params, body) = match rest.strip_prefix('(') {
+            Some(rest) => {
+                let (params, body) = rest
+                    .split_once(')')
+                    .ok_or_else(|| Error::header(line, "expected `)`"))?;
+                let params = params
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                (Some(params), body)
+            }
+            None => (None, rest),
+        };
+
+        let mac = Macro {
+            params,
+            body: tokenize(body, line)?,
+            line,
+            builtin: false,
+        };
+        self.macros.insert(name.to_string(), mac);
+        if !self.order.iter().any(|defined| defined == name) {
+            self.order.push(name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Evaluates the condition of an `#if`, where unknown identifiers are `0`.
+    fn condition(&self, expr: &str, line: usize) -> Result<bool, Error> {
+        let tokens = tokenize(expr, line)?;
+        let mut resolved = Vec::with_capacity(tokens.len());
+        let mut iter = tokens.into_iter().peekable();
+        while let Some(token) = iter.next() {
+            if token.ident() != Some("defined") {
+                resolved.push(token);
+                continue;
+            }
+
+            let paren = iter.next_if(|t| t.is_punct("(")).is_some();
+            let name = iter
+                .next()
+                .and_then(|t| t.ident().map(str::to_string))
+                .ok_or_else(|| Error::header(line, "expected a macro name after `defined`"))?;
+            if paren && iter.next_if(|t| t.is_punct(")")).is_none() {
+                return Err(Error::header(line, "expected `)`"));
+            }
+            let defined = self.macros.contains_key(&name);
+            resolved.push(Token {
+                tok: Tok::Int(IntLit {
+                    value: defined as u64,
+                    unsigned: false,
+                    long: false,
+                    decimal: true,
+                }),
+                line,
+            });
+        }
+
+        let mut expanded = Vec::new();
+        expand(&self.macros, &resolved, &mut Vec::new(), &mut expanded);
+        // anything that does not evaluate, such as `__has_include(...)`, is false
+        Ok(eval::eval(&expanded, &Undefined).is_some_and(|v| v.value != 0))
+    }
+}
+
+/// Scope of an `#if`, identifiers that are not macros evaluate to `0`.
+struct Undefined;
+
+impl Scope for Undefined {
+    fn constant(&self, _: &str) -> Option<Value> {
+        Some(Value::new(0, IntTy::LONG))
+    }
+
+    fn int_typedef(&self, _: &str) -> Option<IntTy> {
+        None
+    }
+}
+
+/// Expands the macros within `tokens` into `out`, `hidden` holds the macros that are being
+/// expanded so that they do not recurse.
+fn expand(
+    macros: &HashMap<String, Macro>,
+    tokens: &[Token],
+    hidden: &mut Vec<String>,
+    out: &mut Vec<Token>,
+) {
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+
+        let Some((name, mac)) = token
+            .ident()
+            .filter(|name| !hidden.iter().any(|h| h == name))
+            .and_then(|name| macros.get_key_value(name))
+        else {
+            out.push(token.clone());
+            continue;
+        };
+
+        // stringizing and pasting are not supported, these macros are left as is
+        if mac.body.iter().any(|t| t.is_punct("#") || t.is_punct("##")) {
+            out.push(token.clone());
+            continue;
+        }
+
+        let body = match &mac.params {
+            None => mac.body.clone(),
+            Some(params) => {
+                let Some((args, len)) = tokens
+                    .get(i)
+                    .filter(|t| t.is_punct("("))
+                    .and_then(|_| macro_args(&tokens[i + 1..]))
+                else {
+                    out.push(token.clone());
+                    continue;
+                };
+                i += len + 1;
+
+                let mut body = Vec::with_capacity(mac.body.len());
+                for t in mac.body.iter() {
+                    match t
+                        .ident()
+                        .and_then(|p| params.iter().position(|param| param == p))
+                    {
+                        Some(index) => {
+                            let arg = args.get(index).map(Vec::as_slice).unwrap_or(&[]);
+                            expand(macros, arg, hidden, &mut body);
+                        }
+                        None => body.push(t.clone()),
+                    }
+                }
+                body
+            }
+        };
+
+        let body = body
+            .into_iter()
+            .map(|t| Token {
+                tok: t.tok,
+                line: token.line,
+            })
+            .collect::<Vec<_>>();
+        hidden.push(name.clone());
+        expand(macros, &body, hidden, out);
+        hidden.pop();
+    }
+}
+
+/// Splits the arguments of a macro invocation, `tokens` start after the opening paren. Also
+/// returns the number of tokens consumed, including the closing paren.
+fn macro_args(tokens: &[Token]) -> Option<(Vec<Vec<Token>>, usize)> {
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.tok {
+            Tok::Punct(")") if depth == 0 => {
+                if args.len() == 1 && args[0].is_empty() {
+                    args.clear();
+                }
+                return Some((args, i + 1));
+            }
+            Tok::Punct(",") if depth == 0 => {
+                args.push(Vec::new());
+                continue;
+            }
+            Tok::Punct("(") => depth += 1,
+            Tok::Punct(")") => depth -= 1,
+            _ => {}
+        }
+        args.last_mut().unwrap().push(token.clone());
+    }
+    None
+}
+
+/// Replaces comments with whitespace, preserving newlines.
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                out.push(' ');
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    prev = c;
+                }
+            }
+            '"' | '\'' => {
+                out.push(c);
+                while let Some(next) = chars.next() {
+                    out.push(next);
+                    if next == '\\' {
+                        out.extend(chars.next());
+                    } else if next == c || next == '\n' {
+                        break;
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Joins lines ending in a backslash, each logical line is numbered by its first line.
+fn logical_lines(src: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (i, line) in src.lines().enumerate() {
+        let (start, mut text) = current.take().unwrap_or((i + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                text.push_str(line);
+                text.push(' ');
+                current = Some((start, text));
+            }
+            None => {
+                text.push_str(line);
+                lines.push((start, text));
+            }
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+const PUNCTS: &[&str] = &[
+    "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "##",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "[", "]", "(", ")", "{", "}", ".", ",", ";",
+    ":", "*", "&", "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", "=", "#",
+];
+
+fn tokenize(line: &str, number: usize) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let tok = if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            Tok::Ident(line[start..i].to_string())
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            i += 1;
+            while i < bytes.len() {
+                let c = bytes[i];
+                if matches!(c, b'+' | b'-') && matches!(bytes[i - 1], b'e' | b'E' | b'p' | b'P') {
+                    i += 1;
+                } else if c.is_ascii_alphanumeric() || c == b'_' || c == b'.' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            number_literal(&line[start..i]).ok_or_else(|| {
+                Error::header(number, format!("invalid number `{}`", &line[start..i]))
+            })?
+        } else if c == b'"' || c == b'\'' {
+            i += 1;
+            let mut value = String::new();
+            loop {
+                match bytes.get(i) {
+                    None => return Err(Error::header(number, "unterminated literal")),
+                    Some(b) if *b == c => break,
+                    Some(b'\\') => {
+                        let (escaped, len) = escape(&line[i + 1..])
+                            .ok_or_else(|| Error::header(number, "invalid escape"))?;
+                        value.push(escaped);
+                        i += len + 1;
+                    }
+                    Some(_) => {
+                        let ch = line[i..].chars().next().unwrap();
+                        value.push(ch);
+                        i += ch.len_utf8();
+                    }
+                }
+            }
+            i += 1;
+            if c == b'"' {
+                Tok::Str(value)
+            } else {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Tok::Char(c as u32),
+                    _ => return Err(Error::header(number, "invalid character literal")),
+                }
+            }
+        } else {
+            let punct = PUNCTS
+                .iter()
+                .find(|p| line[i..].starts_with(**p))
+                .ok_or_else(|| {
+                    Error::header(number, format!("unexpected character `{}`", c as char))
+                })?;
+            i += punct.len();
+            Tok::Punct(punct)
+        };
+
+        tokens.push(Token { tok, line: number });
+    }
+    Ok(tokens)
+}
+
+/// Parses the escape sequence after a backslash, along with its length.
+fn escape(s: &str) -> Option<(char, usize)> {
+    let c = s.chars().next()?;
+    Some(match c {
+        'n' => ('\n', 1),
+        't' => ('\t', 1),
+        'r' => ('\r', 1),
+        'a' => ('\x07', 1),
+        'b' => ('\x08', 1),
+        'f' => ('\x0c', 1),
+        'v' => ('\x0b', 1),
+        '\\' | '\'' | '"' | '?' => (c, 1),
+        'x' => {
+            let len = s[1..].chars().take_while(char::is_ascii_hexdigit).count();
+            let value = u32::from_str_radix(&s[1..1 + len], 16).ok()?;
+            (char::from_u32(value)?, len + 1)
+        }
+        '0'..='7' => {
+            let len = s.chars().take(3).take_while(|c| c.is_digit(8)).count();
+            let value = u32::from_str_radix(&s[..len], 8).ok()?;
+            (char::from_u32(value)?, len)
+        }
+        _ => return None,
+    })
+}
+
+fn number_literal(s: &str) -> Option<Tok> {
+    let lower = s.to_ascii_lowercase();
+    let hex = lower.starts_with("0x");
+    if lower.contains('.') || (!hex && lower.contains('e')) || (hex && lower.contains('p')) {
+        return Some(Tok::Float);
+    }
+
+    let digits_end = lower.trim_end_matches(|c| c == 'u' || c == 'l').len();
+    let (digits, suffix) = lower.split_at(digits_end);
+    let (radix, digits) = if let Some(digits) = digits.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = digits.strip_prefix("0b") {
+        (2, digits)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+
+    let (unsigned, long) = match suffix {
+        "" => (false, false),
+        "u" => (true, false),
+        "l" | "ll" => (false, true),
+        "ul" | "ull" | "lu" | "llu" => (true, true),
+        _ => return None,
+    };
+    Some(Tok::Int(IntLit {
+        value: u64::from_str_radix(digits, radix).ok()?,
+        unsigned,
+        long,
+        decimal: radix == 10,
+    }))
+}
@@ -0,0 +1,5 @@
+pub mod bindgen;
+mod decl;
+mod emit;
+mod eval;
+mod lex;
@@ -0,0 +1,59 @@
+use clap::Parser;
+use pebble_bindgen::bindgen::{self, Config};
+use pebblec_parse::lex::io;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Pebble Bindings Generator
+#[derive(Parser, Debug)]
+#[command(about, long_about = None)]
+struct Args {
+    /// path to a C header
+    header: String,
+
+    /// library to link against, defaults to the name of the header
+    #[arg(long)]
+    link: Option<String>,
+
+    /// identifiers to skip within declarations, such as export macros
+    #[arg(long)]
+    ignore: Vec<String>,
+
+    /// path of the generated `.peb` file, printed if omitted
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let link = args.link.unwrap_or_else(|| {
+        Path::new(&args.header)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let config = Config {
+        link,
+        ignore: args.ignore,
+    };
+
+    let bindings = match bindgen::bindgen(&args.header, &config) {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            eprintln!("error: {}: {err}", args.header);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match args.output {
+        Some(output) => {
+            if let Err(err) = io::write(&output, bindings.as_bytes()) {
+                eprintln!("error: could not write `{output}`: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => println!("{bindings}"),
+    }
+    ExitCode::SUCCESS
+}
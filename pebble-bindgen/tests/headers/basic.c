@@ -0,0 +1,91 @@
+#include "basic.h"
+#include <stdarg.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+int basic_counter = 0;
+
+struct BasicHandle {
+    uint32_t flags;
+    struct BasicEntry entries[2];
+};
+
+BasicHandle *basic_open(const char *path, uint32_t flags) {
+    if (path == NULL) {
+        return NULL;
+    }
+
+    BasicHandle *handle = calloc(1, sizeof(BasicHandle));
+    handle->flags = flags;
+    for (int i = 0; i < 2; i++) {
+        struct BasicEntry *entry = &handle->entries[i];
+        entry->id = i + 1;
+        entry->kind = i == 0 ? BASIC_KIND_NUMBER : BASIC_KIND_TEXT;
+        strncpy(entry->name, path, BASIC_MAX_NAME - 1);
+        entry->label = "entry";
+        entry->points[1].x = 1.5f * (i + 1);
+        entry->next = i == 0 ? &handle->entries[1] : NULL;
+        entry->value.number = 100 * (i + 1);
+        entry->len = strlen(path);
+        entry->owned = i == 1;
+    }
+    return handle;
+}
+
+void basic_close(BasicHandle *handle) {
+    free(handle);
+}
+
+BasicStatus basic_status(const BasicHandle *handle) {
+    return handle->flags & BASIC_FLAG_B ? BASIC_RETRY : BASIC_OK;
+}
+
+size_t basic_entries(BasicHandle *handle, struct BasicEntry *entries, size_t len) {
+    size_t count = len < 2 ? len : 2;
+    memcpy(entries, handle->entries, count * sizeof(struct BasicEntry));
+    return count;
+}
+
+void basic_sort(void *base, size_t len, size_t size, BasicCompare compare) {
+    qsort(base, len, size, compare);
+}
+
+void basic_visit(BasicHandle *handle, void (*visit)(const struct BasicEntry *, void *), void *user) {
+    for (int i = 0; i < 2; i++) {
+        visit(&handle->entries[i], user);
+    }
+}
+
+int basic_log(const char *fmt, ...) {
+    va_list args;
+    va_start(args, fmt);
+    int len = vsnprintf(NULL, 0, fmt, args);
+    va_end(args);
+    return len;
+}
+
+BasicValue basic_value(unsigned long long in, BasicPoint at) {
+    BasicValue value;
+    value.f = (float)in + at.x * at.y;
+    return value;
+}
+
+double basic_len(const BasicPoint points[], int count) {
+    double len = 0.0;
+    for (int i = 0; i < count; i++) {
+        len += points[i].x + points[i].y;
+    }
+    return len;
+}
+
+void basic_names(char **names, unsigned int loop) {
+    static char *all[] = {"first", "second", "third"};
+    for (unsigned int i = 0; i < loop && i < 3; i++) {
+        names[i] = all[i];
+    }
+}
+
+_Bool basic_ready(void) {
+    return 1;
+}
@@ -0,0 +1,92 @@
+#ifndef BASIC_H
+#define BASIC_H
+
+#include <stdint.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BASIC_API
+#define BASIC_CALL
+#define BASIC_BIT(n) (1u << (n))
+
+#define BASIC_VERSION 3
+#define BASIC_MAX_NAME 16
+#define BASIC_FLAG_A 0x01
+#define BASIC_FLAG_B BASIC_BIT(4)
+#define BASIC_MASK (BASIC_FLAG_A | BASIC_FLAG_B)
+#define BASIC_BIG 0xFFFFFFFFFFull
+#define BASIC_NEG (-(BASIC_VERSION * 2))
+#define BASIC_BYTE ((uint8_t)0x1ff)
+#define BASIC_NAME "basic" /* not an integer */
+#define BASIC_SCALE 1.5f
+
+typedef uint32_t BasicId;
+typedef struct BasicHandle BasicHandle;
+
+typedef enum {
+    BASIC_OK,
+    BASIC_ERROR = -1,
+    BASIC_RETRY = BASIC_OK + 2,
+} BasicStatus;
+
+enum BasicKind {
+    BASIC_KIND_NONE = 0,
+    BASIC_KIND_NUMBER = 0x10,
+    BASIC_KIND_TEXT,
+};
+
+typedef struct {
+    float x, y;
+} BasicPoint;
+
+struct BasicEntry {
+    BasicId id;
+    enum BasicKind kind;
+    char name[BASIC_MAX_NAME];
+    const char *label;
+    BasicPoint points[2];
+    struct BasicEntry *next;
+    union {
+        int64_t number;
+        double real;
+    } value;
+    struct {
+        uint16_t len;
+        _Bool owned;
+    };
+};
+
+typedef union BasicValue {
+    uint32_t bits;
+    float f;
+    uint8_t bytes[4];
+} BasicValue;
+
+typedef int (*BasicCompare)(const void *a, const void *b);
+
+extern int basic_counter;
+
+static inline int basic_twice(int x) {
+    return x * 2;
+}
+
+BASIC_API BasicHandle *BASIC_CALL basic_open(const char *path, uint32_t flags);
+BASIC_API void BASIC_CALL basic_close(BasicHandle *handle);
+BASIC_API BasicStatus basic_status(const BasicHandle *handle);
+BASIC_API size_t basic_entries(BasicHandle *handle, struct BasicEntry *entries, size_t len);
+BASIC_API void basic_sort(void *base, size_t len, size_t size, BasicCompare compare);
+BASIC_API void basic_visit(BasicHandle *handle, void (*visit)(const struct BasicEntry *, void *), void *user);
+BASIC_API int basic_log(const char *fmt, ...);
+BASIC_API BasicValue basic_value(unsigned long long in, BasicPoint at);
+BASIC_API double basic_len(const BasicPoint points[], int count);
+BASIC_API void basic_names(char **names, unsigned int loop);
+BASIC_API _Bool basic_ready(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
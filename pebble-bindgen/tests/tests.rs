@@ -0,0 +1,126 @@
+use pebble_bindgen::bindgen::{self, Config, Error};
+use pebblec::comp::{CompUnit, Config as CompConfig};
+use pebblec_parse::lex::io;
+
+fn bindings(src: &str) -> Result<String, Error> {
+    bindgen::bindgen_string("test.h", src, &Config::new("test"))
+}
+
+#[test]
+fn basic() {
+    let bindings = bindgen::bindgen("tests/headers/basic.h", &Config::new("basic")).unwrap();
+    assert_eq!(
+        bindings,
+        io::read_string("tests/headers/basic.peb").unwrap()
+    );
+}
+
+/// Builds `tests/headers/basic.c` into a shared library and runs `tests/headers/basic_main.peb`
+/// against the generated bindings.
+#[test]
+fn run_basic() {
+    let lib = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("libbasic.so");
+    let status = std::process::Command::new("cc")
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(&lib)
+        .arg("tests/headers/basic.c")
+        .status()
+        .expect("failed to run `cc`");
+    assert!(status.success());
+
+    let config = Config::new(lib.to_str().unwrap());
+    let bindings = bindgen::bindgen("tests/headers/basic.h", &config).unwrap();
+    let path = "tests/headers/basic_main.peb";
+    let src = io::read_string(path).unwrap() + &bindings;
+    let bytecode = CompUnit::new(CompConfig::default().no_capture(true))
+        .compile_string(path, src)
+        .unwrap();
+    assert_eq!(
+        0,
+        pebblec::interp::InterpInstance::new(&bytecode).run(false)
+    );
+}
+
+#[test]
+fn preprocessor() {
+    let src = "
+        #define FEATURE 2
+        #if FEATURE > 1 && defined(__linux__)
+        #define PICKED 1
+        #elif FEATURE
+        #define PICKED 2
+        #else
+        #error unreachable
+        #endif
+        #ifdef MISSING
+        int missing(void);
+        #endif
+        #define WIDE(x) ((x) * 2L)
+        #define DOUBLED WIDE(FEATURE + \\
+            1)
+        #define FLAG (1 << 31)
+    ";
+    let out = bindings(src).unwrap();
+    assert!(out.contains("PICKED: const i32 = 1;"));
+    assert!(out.contains("DOUBLED: const i64 = 6;"));
+    assert!(out.contains("FLAG: const i32 = -2147483648;"));
+    assert!(out.contains("FEATURE: const i32 = 2;"));
+    assert!(!out.contains("missing"));
+
+    // macros are expanded where they are used, after `A` is undefined
+    let out = bindings("#define A 1\n#define B A\n#define C B\n#undef A").unwrap();
+    assert!(!out.contains("A:") && !out.contains("B:") && !out.contains("C:"));
+
+    assert!(bindings("#if 1\nint f(void);").is_err());
+    assert!(bindings("#endif").is_err());
+    assert!(bindings("#error no").is_err());
+}
+
+#[test]
+fn ignore() {
+    let src = "EXPORT int CALL add(int a, int b);";
+    assert!(bindings(src).is_err());
+
+    let config = Config::new("test").ignore("EXPORT").ignore("CALL");
+    let bindings = bindgen::bindgen_string("test.h", src, &config).unwrap();
+    assert!(bindings.contains("add: (a: i32, b: i32) -> i32;"));
+}
+
+#[test]
+fn skipped() {
+    let src = "
+        #define NAME \"name\"
+        #define SCALE 1.5
+        extern int counter;
+        static inline int twice(int x) { return x * 2; }
+        static int hidden(void);
+        typedef enum { RED, GREEN } Color;
+        Color paint(Color color);
+        struct Node { struct Node *next; int (*visit)(struct Node *node); };
+    ";
+    let out = bindings(src).unwrap();
+    assert!(out.contains("// skipped: `#define NAME`, which is not an integer"));
+    assert!(out.contains("// skipped: `#define SCALE`, which is not an integer"));
+    assert!(out.contains("// skipped: variable `counter`, which cannot be linked"));
+    assert!(out.contains("// skipped: function `twice`, which is static or inline"));
+    assert!(out.contains("// skipped: function `hidden`, which is static or inline"));
+    assert!(out.contains("// skipped: typedef `Color`, written as `u32`"));
+    assert!(out.contains("paint: (color: u32) -> u32;"));
+    assert!(out.contains(
+        "// skipped: type of `Node.next`, which leads back to `Node` and points to bytes"
+    ));
+    assert!(out.contains(
+        "// skipped: type of `Node.visit`, which leads back to `Node` and points to bytes"
+    ));
+}
+
+#[test]
+fn errors() {
+    assert!(bindings("struct S { int a : 3; };").is_err());
+    assert!(bindings("struct S { int len; char data[]; };").is_err());
+    assert!(bindings("Unknown make(void);").is_err());
+    assert!(bindings("int loop(void);").is_err());
+    assert!(bindings("void take(int (*f)(int, ...));").is_err());
+    assert!(bindings("enum E { A = B };").is_err());
+    assert!(bindings("int f(int x[N]);").is_err());
+}
@@ -3,8 +3,9 @@ use pebblec_parse::lex::buffer::{Span, TokenBuffer, TokenId, TokenQuery};
 use pebblec_parse::lex::kind::TokenKind;
 use pebblec_parse::matc::{Bracket, Curly, DelimPair, Paren};
 use pebblec_parse::rules::prelude::{
-    ArrDef, Assign, Attribute, Block, Const, Expr, ExternBlock, ExternFunc, Field, FieldDef, Func,
-    Impl, MethodPath, PType, Param, Path, Stmt, Struct, StructDef, Use,
+    ArrDef, Assign, Attribute, Block, Closure, ClosureBody, Const, Expr, ExternBlock, ExternFunc,
+    Field, FieldDef, Func, Impl, Meta, MethodPath, PType, Param, Pat, Path, Stmt, Struct,
+    StructDef, Use,
 };
 use std::borrow::Borrow;
 use std::ops::Deref;
@@ -46,10 +47,10 @@ impl<'a> Node<'a> {
 
     pub fn token<T: Borrow<TokenId>>(buf: &'a TokenBuffer, token: T) -> Self {
         let t = *token.borrow();
-        if buf.kind(t) == TokenKind::Str {
-            Self::Text(String::leak(format!("\"{}\"", buf.as_str(t))))
-        } else {
-            Self::Text(buf.as_str(t))
+        match buf.kind(t) {
+            TokenKind::Str => Self::Text(String::leak(format!("\"{}\"", buf.as_str(t)))),
+            TokenKind::RawStr => Self::Text(String::leak(format!("r\"{}\"", buf.as_str(t)))),
+            _ => Self::Text(buf.as_str(t)),
         }
     }
 
@@ -288,23 +289,50 @@ fn nodify_params<'a>(
         return None;
     }
 
-    Some(Node::set(arena, BreakCond::Width, {
-        params.iter().map(|p| match p {
-            Param::Slf(t) => {
-                vec![Node::token(buf, t)]
-            }
-            Param::Named {
-                name, colon, ty, ..
-            } => {
-                vec![
-                    Node::token(buf, name),
-                    Node::token(buf, colon),
-                    Node::space(),
-                    nodify_ty(buf, arena, &ty),
-                ]
-            }
-        })
-    }))
+    Some(Node::set(
+        arena,
+        BreakCond::Width,
+        params.iter().map(|p| nodify_param(buf, arena, p)),
+    ))
+}
+
+fn nodify_extern_params<'a>(
+    buf: &'a TokenBuffer,
+    arena: &BlobArena,
+    params: &[Param],
+    variadic: Option<TokenId>,
+) -> Option<Node<'a>> {
+    if params.is_empty() {
+        return None;
+    }
+
+    let mut nodes = params
+        .iter()
+        .map(|p| nodify_param(buf, arena, p))
+        .collect::<Vec<_>>();
+    if let Some(variadic) = variadic {
+        nodes.push(vec![Node::token(buf, &variadic)]);
+    }
+
+    Some(Node::set(arena, BreakCond::Width, nodes.into_iter()))
+}
+
+fn nodify_param<'a>(buf: &'a TokenBuffer, arena: &BlobArena, param: &Param) -> Vec<Node<'a>> {
+    match param {
+        Param::Slf(t) => {
+            vec![Node::token(buf, t)]
+        }
+        Param::Named {
+            name, colon, ty, ..
+        } => {
+            vec![
+                Node::token(buf, name),
+                Node::token(buf, colon),
+                Node::space(),
+                nodify_ty(buf, arena, &ty),
+            ]
+        }
+    }
 }
 
 fn nodify_ty<'a>(buf: &'a TokenBuffer, arena: &BlobArena, ty: &PType) -> Node<'a> {
@@ -313,6 +341,9 @@ fn nodify_ty<'a>(buf: &'a TokenBuffer, arena: &BlobArena, ty: &PType) -> Node<'a
         PType::Ref { inner, .. } => {
             Node::group(arena, &[Node::Text("&"), nodify_ty(buf, arena, inner)])
         }
+        PType::Opt { inner, .. } => {
+            Node::group(arena, &[Node::Text("?"), nodify_ty(buf, arena, inner)])
+        }
         PType::Array { size, inner, .. } => Node::group(
             arena,
             &[
@@ -331,9 +362,83 @@ fn nodify_ty<'a>(buf: &'a TokenBuffer, arena: &BlobArena, ty: &PType) -> Node<'a
                 Node::Text("]"),
             ],
         ),
+        PType::Tuple { elems, .. } => {
+            let elems = elems.iter().map(|ty| nodify_ty(buf, arena, ty));
+            nodify_tuple(arena, elems.collect())
+        }
+        PType::Fn { params, ret, .. } => {
+            let mut nodes = vec![Node::Text("fn(")];
+            for (i, param) in params.iter().enumerate() {
+                if i != 0 {
+                    nodes.push(Node::Text(", "));
+                }
+                nodes.push(nodify_ty(buf, arena, param));
+            }
+            nodes.push(Node::Text(")"));
+            if let Some(ret) = ret {
+                nodes.push(Node::Text(" -> "));
+                nodes.push(nodify_ty(buf, arena, ret));
+            }
+            Node::group(arena, &nodes)
+        }
+        PType::Closure { params, ret, .. } => {
+            let mut nodes = vec![Node::Text("|")];
+            for (i, param) in params.iter().enumerate() {
+                if i != 0 {
+                    nodes.push(Node::Text(", "));
+                }
+                nodes.push(nodify_ty(buf, arena, param));
+            }
+            nodes.push(Node::Text("|"));
+            if let Some(ret) = ret {
+                nodes.push(Node::Text(" -> "));
+                nodes.push(nodify_ty(buf, arena, ret));
+            }
+            Node::group(arena, &nodes)
+        }
     }
 }
 
+fn nodify_pat<'a>(buf: &'a TokenBuffer, arena: &BlobArena, pat: &Pat) -> Node<'a> {
+    match pat {
+        Pat::Ident(t) => Node::token(buf, t),
+        Pat::Tuple { pats, .. } => {
+            let pats = pats.iter().map(|pat| nodify_pat(buf, arena, pat));
+            nodify_tuple(arena, pats.collect())
+        }
+        Pat::Struct { name, fields, .. } => {
+            let mut nodes = vec![Node::token(buf, name), Node::Text(" {")];
+            for (i, field) in fields.iter().enumerate() {
+                nodes.extend([Node::Text(" "), Node::token(buf, &field.name)]);
+                if let Some(pat) = &field.pat {
+                    nodes.extend([Node::Text(": "), nodify_pat(buf, arena, pat)]);
+                }
+                if i != fields.len() - 1 {
+                    nodes.push(Node::Text(","));
+                }
+            }
+            nodes.push(Node::Text(" }"));
+            Node::group(arena, &nodes)
+        }
+    }
+}
+
+/// `(a, b)`, with a trailing comma for the single element `(a,)`.
+fn nodify_tuple<'a>(arena: &BlobArena, elems: Vec<Node<'a>>) -> Node<'a> {
+    let mut nodes = vec![Node::Text("(")];
+    for (i, elem) in elems.iter().enumerate() {
+        if i != 0 {
+            nodes.push(Node::Text(", "));
+        }
+        nodes.push(*elem);
+    }
+    if elems.len() == 1 {
+        nodes.push(Node::Text(","));
+    }
+    nodes.push(Node::Text(")"));
+    Node::group(arena, &nodes)
+}
+
 fn nodify_block<'a>(
     buf: &'a TokenBuffer,
     arena: &BlobArena,
@@ -358,37 +463,7 @@ fn nodify_stmts<'a>(buf: &'a TokenBuffer, arena: &BlobArena, stmts: &[Stmt]) ->
 
     let mut nodes = Vec::with_capacity(stmts.len() * 5);
     for (i, stmt) in stmts.iter().enumerate() {
-        match stmt {
-            Stmt::Let {
-                let_,
-                name,
-                ty,
-                assign,
-                ..
-            } => {
-                check_whitespace(buf, *let_, &mut nodes);
-                nodes.extend([Node::Text("let "), Node::token(buf, name)]);
-
-                if let Some(ty) = ty {
-                    nodes.extend([Node::Text(": "), nodify_ty(buf, arena, ty)]);
-                }
-
-                nodes.extend([
-                    Node::Text(" = "),
-                    nodify_expr(buf, arena, assign),
-                    Node::Text(";"),
-                ]);
-            }
-            Stmt::Semi(expr) => {
-                check_whitespace_span(buf, expr.span(buf), &mut nodes);
-                nodes.extend([nodify_expr(buf, arena, expr), Node::Text(";")])
-            }
-            Stmt::Open(expr) => {
-                check_whitespace_span(buf, expr.span(buf), &mut nodes);
-                nodes.push(nodify_expr(buf, arena, expr))
-            }
-        }
-
+        nodify_stmt(buf, arena, stmt, &mut nodes);
         if i != stmts.len() - 1 {
             nodes.push(Node::Text("\n"));
         }
@@ -397,14 +472,78 @@ fn nodify_stmts<'a>(buf: &'a TokenBuffer, arena: &BlobArena, stmts: &[Stmt]) ->
     Some(Node::Group(arena.alloc_slice(&nodes)))
 }
 
+fn nodify_stmt<'a>(
+    buf: &'a TokenBuffer,
+    arena: &BlobArena,
+    stmt: &Stmt,
+    nodes: &mut Vec<Node<'a>>,
+) {
+    match stmt {
+        Stmt::Let {
+            let_,
+            pat,
+            ty,
+            assign,
+            ..
+        } => {
+            check_whitespace(buf, *let_, nodes);
+            nodes.extend([Node::Text("let "), nodify_pat(buf, arena, pat)]);
+
+            if let Some(ty) = ty {
+                nodes.extend([Node::Text(": "), nodify_ty(buf, arena, ty)]);
+            }
+
+            nodes.extend([
+                Node::Text(" = "),
+                nodify_expr(buf, arena, assign),
+                Node::Text(";"),
+            ]);
+        }
+        Stmt::Semi(expr) => {
+            check_whitespace_span(buf, expr.span(buf), nodes);
+            nodes.extend([nodify_expr(buf, arena, expr), Node::Text(";")])
+        }
+        Stmt::Open(expr) => {
+            check_whitespace_span(buf, expr.span(buf), nodes);
+            nodes.push(nodify_expr(buf, arena, expr))
+        }
+        Stmt::Defer { defer, stmt, .. } => {
+            check_whitespace(buf, *defer, nodes);
+            nodes.push(Node::Text("defer "));
+            nodify_stmt(buf, arena, stmt, nodes);
+        }
+        Stmt::Attributed {
+            attributes, stmt, ..
+        } => {
+            check_whitespace(buf, attributes[0].pound, nodes);
+            nodify_attrs(buf, arena, attributes, nodes);
+            nodify_stmt(buf, arena, stmt, nodes);
+        }
+    }
+}
+
 fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node<'a> {
     match expr {
-        Expr::Break(t)
-        | Expr::Continue(t)
-        | Expr::Ident(t)
-        | Expr::Lit(t)
-        | Expr::Str(t)
-        | Expr::Bool(t) => Node::token(buf, t),
+        Expr::Break { label, value, .. } => {
+            let mut nodes = Vec::with_capacity(3);
+            nodes.push(Node::Text("break"));
+            if let Some(label) = label {
+                nodes.push(Node::space());
+                nodes.push(Node::token(buf, label));
+            }
+            if let Some(value) = value {
+                nodes.push(Node::space());
+                nodes.push(nodify_expr(buf, arena, value));
+            }
+            Node::group(arena, &nodes)
+        }
+        Expr::Continue { label, .. } => match label {
+            Some(label) => Node::group(arena, &[Node::Text("continue "), Node::token(buf, label)]),
+            None => Node::Text("continue"),
+        },
+        Expr::Ident(t) | Expr::Lit(t) | Expr::Str(t) | Expr::Bool(t) | Expr::Null(t) => {
+            Node::token(buf, t)
+        }
         Expr::Bin(_, kind, lhs, rhs) => Node::group(
             arena,
             &[
@@ -425,6 +564,20 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
                 Node::Text(")"),
             ],
         ),
+        Expr::Tuple { exprs, .. } => {
+            if exprs.len() == 1 {
+                nodify_tuple(arena, vec![nodify_expr(buf, arena, &exprs[0])])
+            } else {
+                Node::indent_delimited_with(
+                    buf,
+                    arena,
+                    Paren,
+                    exprs,
+                    BreakCond::Width,
+                    nodify_expr_set,
+                )
+            }
+        }
         Expr::Ret(_, expr) => {
             if let Some(expr) = expr {
                 Node::group(
@@ -504,6 +657,14 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
                 Node::indent_delimited_with(buf, arena, Paren, args, BreakCond::Width, nodify_args),
             ],
         ),
+        Expr::IndirectCall { callee, args, .. } => Node::group(
+            arena,
+            &[
+                nodify_expr(buf, arena, callee),
+                Node::indent_delimited_with(buf, arena, Paren, args, BreakCond::Width, nodify_args),
+            ],
+        ),
+        Expr::Path(path) => nodify_path(buf, arena, path),
         Expr::MethodCall {
             receiver,
             method,
@@ -522,6 +683,7 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
             ],
         ),
         Expr::If {
+            binding,
             condition,
             block,
             otherwise,
@@ -532,6 +694,7 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
                     arena,
                     &[
                         Node::Text("if "),
+                        nodify_binding(buf, arena, binding),
                         nodify_expr(buf, arena, condition),
                         Node::space(),
                         nodify_block(buf, arena, block, BreakCond::MoreThanOne),
@@ -544,6 +707,7 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
                     arena,
                     &[
                         Node::Text("if "),
+                        nodify_binding(buf, arena, binding),
                         nodify_expr(buf, arena, condition),
                         Node::space(),
                         nodify_block(buf, arena, block, BreakCond::MoreThanOne),
@@ -552,6 +716,7 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
             }
         }
         Expr::For {
+            label,
             iter,
             iterable,
             block,
@@ -559,6 +724,7 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
         } => Node::group(
             arena,
             &[
+                nodify_label(buf, arena, label),
                 Node::Text("for "),
                 Node::token(buf, iter),
                 Node::Text(" in "),
@@ -597,19 +763,33 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
                 nodify_ty(buf, arena, ty),
             ],
         ),
-        Expr::Loop { block, .. } => Node::group(
+        Expr::Unsafe { block, .. } => Node::group(
             arena,
             &[
+                Node::Text("unsafe "),
+                nodify_block(buf, arena, block, BreakCond::MoreThanOne),
+            ],
+        ),
+        Expr::Loop { label, block, .. } => Node::group(
+            arena,
+            &[
+                nodify_label(buf, arena, label),
                 Node::Text("loop "),
                 nodify_block(buf, arena, block, BreakCond::Always),
             ],
         ),
         Expr::While {
-            condition, block, ..
+            label,
+            binding,
+            condition,
+            block,
+            ..
         } => Node::group(
             arena,
             &[
+                nodify_label(buf, arena, label),
                 Node::Text("while "),
+                nodify_binding(buf, arena, binding),
                 nodify_expr(buf, arena, condition),
                 Node::space(),
                 nodify_block(buf, arena, block, BreakCond::MoreThanOne),
@@ -628,9 +808,36 @@ fn nodify_expr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, expr: &Expr) -> Node
                 )
             }
         }
+        Expr::Closure(closure) => nodify_closure(buf, arena, closure),
     }
 }
 
+fn nodify_closure<'a>(buf: &'a TokenBuffer, arena: &BlobArena, closure: &Closure) -> Node<'a> {
+    let mut nodes = Vec::new();
+    if closure.moove.is_some() {
+        nodes.push(Node::Text("move "));
+    }
+
+    nodes.push(Node::Text("|"));
+    for (i, param) in closure.params.iter().enumerate() {
+        if i != 0 {
+            nodes.push(Node::Text(", "));
+        }
+        nodes.extend(nodify_param(buf, arena, param));
+    }
+    nodes.push(Node::Text("| "));
+
+    if let Some(ty) = &closure.ty {
+        nodes.extend([Node::Text("-> "), nodify_ty(buf, arena, ty), Node::space()]);
+    }
+
+    nodes.push(match &closure.body {
+        ClosureBody::Expr(expr) => nodify_expr(buf, arena, expr),
+        ClosureBody::Block(block) => nodify_block(buf, arena, block, BreakCond::MoreThanOne),
+    });
+    Node::group(arena, &nodes)
+}
+
 fn nodify_method_receiver<'a>(
     buf: &'a TokenBuffer,
     arena: &BlobArena,
@@ -666,7 +873,11 @@ fn nodify_struct_fields<'a>(
             BreakCond::Always,
             fields.iter().map(|f| {
                 let mut fields = Vec::new();
-                check_whitespace(buf, f.name, &mut fields);
+                match f.attributes.first() {
+                    Some(attr) => check_whitespace(buf, attr.pound, &mut fields),
+                    None => check_whitespace(buf, f.name, &mut fields),
+                }
+                nodify_attrs(buf, arena, &f.attributes, &mut fields);
                 fields.extend([
                     Node::token(buf, f.name),
                     Node::Text(": "),
@@ -703,6 +914,32 @@ fn nodify_struct_field_defs<'a>(
     }
 }
 
+/// `'label: ` in front of a loop, or nothing.
+fn nodify_label<'a>(buf: &'a TokenBuffer, arena: &BlobArena, label: &Option<TokenId>) -> Node<'a> {
+    match label {
+        Some(label) => Node::group(arena, &[Node::token(buf, label), Node::Text(": ")]),
+        None => Node::Text(""),
+    }
+}
+
+fn nodify_binding<'a>(
+    buf: &'a TokenBuffer,
+    arena: &BlobArena,
+    binding: &Option<TokenId>,
+) -> Node<'a> {
+    match binding {
+        Some(binding) => Node::group(
+            arena,
+            &[
+                Node::Text("let "),
+                Node::token(buf, binding),
+                Node::Text(" = "),
+            ],
+        ),
+        None => Node::Text(""),
+    }
+}
+
 fn nodify_expr_set<'a>(
     buf: &'a TokenBuffer,
     arena: &BlobArena,
@@ -736,7 +973,11 @@ pub fn nodify_struct<'a>(buf: &'a TokenBuffer, arena: &BlobArena, strukt: &Struc
         arena,
         &[
             Node::token(buf, strukt.name),
-            Node::Text(": struct "),
+            Node::Text(if strukt.union {
+                ": union "
+            } else {
+                ": struct "
+            }),
             Node::indent_delimited_with(
                 buf,
                 arena,
@@ -765,10 +1006,52 @@ pub fn nodify_const<'a>(buf: &'a TokenBuffer, arena: &BlobArena, konst: &Const)
 }
 
 pub fn nodify_attr<'a>(buf: &'a TokenBuffer, arena: &BlobArena, attr: &Attribute) -> Node<'a> {
-    let mut nodes = vec![Node::Text("#[")];
-    nodes.extend(attr.tokens.iter().map(|t| Node::token(buf, *t)));
-    nodes.push(Node::Text("]"));
-    Node::group(arena, &nodes)
+    Node::group(
+        arena,
+        &[
+            Node::Text("#["),
+            nodify_meta(buf, arena, &attr.meta),
+            Node::Text("]"),
+        ],
+    )
+}
+
+fn nodify_meta<'a>(buf: &'a TokenBuffer, arena: &BlobArena, meta: &Meta) -> Node<'a> {
+    match meta {
+        Meta::Path(path) => nodify_path(buf, arena, path),
+        Meta::NameValue { path, value, .. } => Node::group(
+            arena,
+            &[
+                nodify_path(buf, arena, path),
+                Node::Text(" = "),
+                Node::token(buf, value),
+            ],
+        ),
+        Meta::List { path, items, .. } => {
+            let mut nodes = vec![nodify_path(buf, arena, path), Node::Text("(")];
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    nodes.push(Node::Text(", "));
+                }
+                nodes.push(nodify_meta(buf, arena, item));
+            }
+            nodes.push(Node::Text(")"));
+            Node::group(arena, &nodes)
+        }
+        Meta::Lit(lit) => Node::token(buf, lit),
+    }
+}
+
+/// Attributes of a field or statement, each on its own line.
+fn nodify_attrs<'a>(
+    buf: &'a TokenBuffer,
+    arena: &BlobArena,
+    attrs: &[Attribute],
+    nodes: &mut Vec<Node<'a>>,
+) {
+    for attr in attrs.iter() {
+        nodes.extend([nodify_attr(buf, arena, attr), Node::nl()]);
+    }
 }
 
 pub fn nodify_extern<'a>(
@@ -815,7 +1098,7 @@ fn nodify_extern_funcs<'a>(
                 Paren,
                 &func.params,
                 BreakCond::Width,
-                nodify_params,
+                |buf, arena, params| nodify_extern_params(buf, arena, params, func.variadic),
             ),
         ]);
         if let Some(ty) = &func.ty {
@@ -1,17 +1,255 @@
 #[test]
-fn cast() {
+fn defer() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb",
-        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb")
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/defer.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/defer.peb")
         .unwrap().unwrap()).unwrap()).run(false)
     );
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb",
-        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb")
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/defer.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/defer.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn label() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/label.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/label.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/label.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/label.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn env() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/env.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/env.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/env.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/env.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn fn_ptr() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/fn_ptr.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/fn_ptr.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/fn_ptr.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/fn_ptr.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn repr() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/repr.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/repr.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/repr.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/repr.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn format() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/format.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/format.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/format.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/format.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn map() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/map.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/map.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/map.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/map.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn tuple() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/tuple.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/tuple.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/tuple.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/tuple.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn time() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/time.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/time.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/time.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/time.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn mem() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/mem.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/mem.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/mem.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/mem.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn negative_const() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/negative_const.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/negative_const.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/negative_const.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/negative_const.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn escape() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/escape.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/escape.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/escape.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/escape.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn slice() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn union() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/union.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/union.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/union.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/union.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn vec() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/vec.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/vec.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/vec.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/vec.peb")
         .unwrap().unwrap()).unwrap()).unwrap()).run(false)
     );
 }
@@ -33,6 +271,57 @@ fn methods() {
     );
 }
 #[test]
+fn cast() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/cast.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn optional() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/optional.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/optional.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/optional.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/optional.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn closure() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/closure.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/closure.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/closure.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/closure.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
 fn general() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
@@ -50,19 +339,36 @@ fn general() {
     );
 }
 #[test]
-fn slice() {
+fn number() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb",
-        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb")
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/number.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/number.peb")
         .unwrap().unwrap()).unwrap()).run(false)
     );
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb",
-        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/slice.peb")
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/number.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/number.peb")
+        .unwrap().unwrap()).unwrap()).unwrap()).run(false)
+    );
+}
+#[test]
+fn rand() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/rand.peb",
+        pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/rand.peb")
+        .unwrap().unwrap()).unwrap()).run(false)
+    );
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile_string("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/rand.peb",
+        pebble_fmt::fmt::fmt_string(pebble_fmt::fmt::fmt("/Users/nicolasball/dev/pebble/pebble-fmt/../pebblec/tests/hosted/rand.peb")
         .unwrap().unwrap()).unwrap()).unwrap()).run(false)
     );
 }
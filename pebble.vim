@@ -7,9 +7,10 @@ if exists("b:current_syntax")
 endif
 
 syn keyword pebbleKeyword   struct const extern return if else for while loop break continue let
-syn keyword pebbleKeyword   use impl as in
+syn keyword pebbleKeyword   use impl as in union unsafe
 
 syn keyword pebbleBoolean   true false
+syn keyword pebbleConstant  null
 syn keyword pebbleType      i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 bool str
 
 syn match   pebbleOperator  "self"
@@ -24,6 +25,7 @@ syn match   pebbleOperator  "\v\="
 syn match   pebbleOperator  "\v\!"
 syn match   pebbleOperator  "\v\&"
 syn match   pebbleOperator  "\v\|"
+syn match   pebbleOperator  "\v\?"
 syn match   pebbleOperator  "\v\>"
 syn match   pebbleOperator  "\v\<"
 syn match   pebbleOperator  "\v\^\="
@@ -34,6 +36,7 @@ syn match   pebbleFunction  "\v<\w+\ze\("
 
 syn region  pebbleString    start=/"/ skip=/\\"/ end=/"/
 syn region  pebbleString    start=/'/ skip=/\\'/ end=/'/
+syn match   pebbleLabel     "\v'\h\w*>'@!"
 
 syn match   pebbleNumber    "\v<\d+>"
 syn match   pebbleNumber    "\v<\d+\.\d+>"
@@ -56,6 +59,7 @@ hi def link pebblePathSep   Operator
 hi def link pebbleComment   Comment
 hi def link pebbleFunction  Function
 hi def link pebbleString    String
+hi def link pebbleLabel     Label
 hi def link pebbleNumber    Number
 hi def link pebbleAttribute PreProc
 hi def link pebbleConstant  Number
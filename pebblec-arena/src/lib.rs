@@ -37,6 +37,10 @@ impl BlobArena {
     }
 
     pub fn alloc_str<'a>(&self, str: &str) -> &'a str {
+        if str.is_empty() {
+            return "";
+        }
+
         unsafe { std::mem::transmute(self.alloc_slice(str.as_bytes())) }
     }
 
@@ -62,6 +66,10 @@ impl BlobArena {
     }
 
     pub fn alloc_str_ptr<'a>(&self, str: &str) -> *mut u8 {
+        if str.is_empty() {
+            return std::ptr::NonNull::dangling().as_ptr();
+        }
+
         self.alloc_slice_ptr(str.as_bytes())
     }
 
@@ -85,6 +93,11 @@ impl BlobArena {
         }
     }
 
+    /// Whether `bytes` bytes at `addr` lie within memory reserved by the arena.
+    pub fn contains(&self, addr: usize, bytes: usize) -> bool {
+        self.arena.contains(addr, bytes)
+    }
+
     fn align_grow<T>(&self, layout: Layout) {
         if self.arena.remaining_bytes() < layout.size() {
             self.arena.grow();
@@ -156,6 +169,14 @@ impl<T> Arena<T> {
         end.saturating_sub(curr)
     }
 
+    /// Whether `bytes` bytes at `addr` lie within one of the chunks.
+    pub fn contains(&self, addr: usize, bytes: usize) -> bool {
+        self.chunks.borrow_mut().iter_mut().any(|chunk| {
+            let start = chunk.as_ptr().addr();
+            addr >= start && addr.saturating_add(bytes) <= chunk.end().addr()
+        })
+    }
+
     fn grow(&self) {
         let mut chunks = self.chunks.borrow_mut();
 
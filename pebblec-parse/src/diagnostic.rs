@@ -105,6 +105,15 @@ impl Diag {
         }
     }
 
+    /// The title of the first `RawDiag`.
+    pub fn title(&self) -> &str {
+        match self {
+            Self::Single(raw) => &raw.title,
+            // cannot have an empty bundle
+            Self::Bundle(bundle) => &bundle[0].title,
+        }
+    }
+
     /// Write diagnostics in `self` to stdout.
     #[track_caller]
     pub fn report(self) {
@@ -0,0 +1,179 @@
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+/// Error in an escape sequence, `span` is the byte range within the literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscapeError {
+    pub span: Range<usize>,
+    pub msg: &'static str,
+}
+
+impl EscapeError {
+    fn new(span: Range<usize>, msg: &'static str) -> Self {
+        Self { span, msg }
+    }
+}
+
+/// Decodes the escape sequences in the contents of a string literal.
+///
+/// Supported escapes: `\n`, `\r`, `\t`, `\0`, `\\`, `\'`, `\"`, `\x7f` and `\u{7fff}`.
+pub fn unescape_str(str: &str) -> Result<String, EscapeError> {
+    let mut buf = String::with_capacity(str.len());
+    let mut chars = str.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            buf.push(c);
+            continue;
+        }
+
+        let (val, span) = escape(str, &mut chars, i)?;
+        if val.is_hex && val.val > 0x7f {
+            return Err(EscapeError::new(
+                span,
+                "out of range hex escape, must be at most `\\x7f`",
+            ));
+        }
+        buf.push(char::from_u32(val.val).unwrap());
+    }
+
+    Ok(buf)
+}
+
+/// Decodes the contents of a character literal, e.g. `a` or `\n`, into a byte.
+pub fn unescape_byte(str: &str) -> Result<u8, EscapeError> {
+    let mut chars = str.char_indices().peekable();
+
+    let (val, span) = match chars.next() {
+        None => return Err(EscapeError::new(0..0, "empty character literal")),
+        Some((i, '\\')) => {
+            let (val, span) = escape(str, &mut chars, i)?;
+            (val.val, span)
+        }
+        Some((i, c)) if c.is_ascii() => (c as u32, i..i + 1),
+        Some((i, c)) => {
+            return Err(EscapeError::new(
+                i..i + c.len_utf8(),
+                "non-ASCII character in character literal, use a `\\x` escape",
+            ));
+        }
+    };
+
+    if chars.peek().is_some() {
+        return Err(EscapeError::new(
+            0..str.len(),
+            "character literal may only contain one character",
+        ));
+    }
+
+    u8::try_from(val)
+        .map_err(|_| EscapeError::new(span, "character literal does not fit in a `u8`"))
+}
+
+struct Escaped {
+    val: u32,
+    is_hex: bool,
+}
+
+/// Parses the escape sequence following the `\` at `start`.
+fn escape(
+    str: &str,
+    chars: &mut Peekable<CharIndices>,
+    start: usize,
+) -> Result<(Escaped, Range<usize>), EscapeError> {
+    let Some((i, c)) = chars.next() else {
+        return Err(EscapeError::new(
+            start..start + 1,
+            "expected an escape sequence",
+        ));
+    };
+    let span = |end: usize| start..end;
+
+    let val = match c {
+        'n' => '\n' as u32,
+        'r' => '\r' as u32,
+        't' => '\t' as u32,
+        '0' => 0,
+        '\\' => '\\' as u32,
+        '\'' => '\'' as u32,
+        '"' => '"' as u32,
+        'x' => {
+            let mut val = 0;
+            let mut end = i + 1;
+            for _ in 0..2 {
+                match chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                    Some((j, c)) => {
+                        val = val * 16 + c.to_digit(16).unwrap();
+                        end = j + 1;
+                    }
+                    None => {
+                        return Err(EscapeError::new(
+                            span(end),
+                            "expected two hex digits in `\\x` escape, e.g. `\\x7f`",
+                        ));
+                    }
+                }
+            }
+            return Ok((Escaped { val, is_hex: true }, span(end)));
+        }
+        'u' => {
+            if chars.next_if(|(_, c)| *c == '{').is_none() {
+                return Err(EscapeError::new(
+                    span(i + 1),
+                    "expected `{` in unicode escape, e.g. `\\u{7fff}`",
+                ));
+            }
+
+            let mut val: u32 = 0;
+            let mut digits = 0;
+            let end = loop {
+                match chars.next() {
+                    Some((j, '}')) => break j + 1,
+                    Some((j, c)) if c.is_ascii_hexdigit() => {
+                        digits += 1;
+                        if digits > 6 {
+                            return Err(EscapeError::new(
+                                span(j + 1),
+                                "unicode escape may have at most 6 hex digits",
+                            ));
+                        }
+                        val = val * 16 + c.to_digit(16).unwrap();
+                    }
+                    Some((j, c)) => {
+                        return Err(EscapeError::new(
+                            j..j + c.len_utf8(),
+                            "invalid character in unicode escape",
+                        ));
+                    }
+                    None => {
+                        return Err(EscapeError::new(
+                            span(str.len()),
+                            "unterminated unicode escape, expected `}`",
+                        ));
+                    }
+                }
+            };
+
+            if digits == 0 {
+                return Err(EscapeError::new(span(end), "empty unicode escape"));
+            }
+            if char::from_u32(val).is_none() {
+                return Err(EscapeError::new(
+                    span(end),
+                    "invalid unicode escape, not a valid character",
+                ));
+            }
+
+            return Ok((Escaped { val, is_hex: false }, span(end)));
+        }
+        _ => {
+            return Err(EscapeError::new(
+                span(i + c.len_utf8()),
+                "unknown escape sequence",
+            ));
+        }
+    };
+
+    Ok((Escaped { val, is_hex: false }, span(i + 1)))
+}
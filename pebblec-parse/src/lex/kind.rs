@@ -16,6 +16,7 @@ pub enum TokenKind {
     In,
     True,
     False,
+    Null,
     Break,
     Continue,
     Extern,
@@ -25,6 +26,10 @@ pub enum TokenKind {
     Fn,
     Ret,
     As,
+    Move,
+    Defer,
+    Union,
+    Unsafe,
 
     // delims
     OpenParen,
@@ -40,7 +45,10 @@ pub enum TokenKind {
     Int,
     Float,
     Str,
+    RawStr,
+    Char,
     Ident,
+    Label,
 
     // symbols
     Semi,
@@ -54,11 +62,13 @@ pub enum TokenKind {
     Comma,
     Dot,
     DoubleDot,
+    Ellipsis,
     Pound,
     Ampersand,
     Bang,
     Caret,
     Pipe,
+    Question,
 }
 
 impl TokenKind {
@@ -77,6 +87,7 @@ impl TokenKind {
             Self::In => "in",
             Self::True => "true",
             Self::False => "false",
+            Self::Null => "null",
             Self::Break => "break",
             Self::Continue => "continue",
             Self::Extern => "extern",
@@ -86,6 +97,10 @@ impl TokenKind {
             Self::Fn => "fn",
             Self::Ret => "return",
             Self::As => "as",
+            Self::Move => "move",
+            Self::Defer => "defer",
+            Self::Union => "union",
+            Self::Unsafe => "unsafe",
 
             // delims
             Self::OpenParen => "(",
@@ -101,7 +116,10 @@ impl TokenKind {
             Self::Int => "integer",
             Self::Float => "float",
             Self::Str => "string",
+            Self::RawStr => "raw string",
+            Self::Char => "character",
             Self::Ident => "identifier",
+            Self::Label => "label",
 
             // symbols
             Self::Semi => ";",
@@ -115,11 +133,13 @@ impl TokenKind {
             Self::Comma => ",",
             Self::Dot => ".",
             Self::DoubleDot => "..",
+            Self::Ellipsis => "...",
             Self::Pound => "#",
             Self::Ampersand => "&",
             Self::Bang => "!",
             Self::Caret => "^",
             Self::Pipe => "|",
+            Self::Question => "?",
         }
     }
 }
@@ -1,18 +1,21 @@
+use crate::diagnostic::Diag;
+use annotate_snippets::Level;
 use buffer::{Span, Token, TokenBuffer};
 use kind::TokenKind;
 use source::Source;
-use winnow::ascii::float;
-use winnow::combinator::{delimited, opt, preceded};
+use std::borrow::Cow;
+use std::ops::Range;
+use winnow::combinator::{delimited, not, opt, peek, preceded, terminated};
 use winnow::error::{ContextError, ErrMode};
-use winnow::stream::Stream;
-use winnow::token::any;
-use winnow::{
-    LocatingSlice, ModalResult, Parser, combinator::alt, stream::AsChar, token::take_while,
-};
+use winnow::stream::{Location, Stream};
+use winnow::token::{any, one_of};
+use winnow::{LocatingSlice, ModalResult, Parser, combinator::alt, token::take_while};
 
 pub mod buffer;
+pub mod escape;
 pub mod io;
 pub mod kind;
+pub mod number;
 pub mod source;
 
 pub struct Lexer {
@@ -24,66 +27,245 @@ impl Lexer {
         Self { source }
     }
 
-    pub fn lex<'a>(self) -> ModalResult<TokenBuffer> {
+    /// Tokenize the source.
+    ///
+    /// Lexing continues past invalid tokens so that every error in the source is reported
+    /// together in the returned [`Diag`].
+    pub fn lex(self) -> Result<TokenBuffer, Diag> {
         let mut tokens = Vec::new();
+        let mut errors = Vec::new();
         let mut input = LocatingSlice::new(self.source.source.as_str());
 
-        while !input.is_empty() {
-            take_while(.., |c| c == ' ' || c == '\n').parse_next(&mut input)?;
+        loop {
+            if let Err(err) = comment(&mut input) {
+                errors.push(err);
+            }
             if input.is_empty() {
                 break;
             }
 
-            match any_token(&mut input) {
-                Some(token) => tokens.push(token?),
-                None => {}
+            let token = match tokens.last().map(|t: &Token| t.kind) {
+                Some(TokenKind::Dot) => alt((tuple_index, any_token)).parse_next(&mut input),
+                // `'a` is an unterminated character literal unless it names a loop
+                Some(TokenKind::Break | TokenKind::Continue) => {
+                    alt((label, any_token)).parse_next(&mut input)
+                }
+                _ => alt((label_def, any_token)).parse_next(&mut input),
+            };
+
+            match token {
+                Ok(token) => {
+                    if let Err(err) = validate_literal(&self.source.source, token) {
+                        errors.push(err);
+                    }
+                    tokens.push(token);
+                }
+                Err(_) => errors.push(recover(&mut input)),
             }
         }
 
-        Ok(TokenBuffer::new(tokens, self.source))
+        let buf = TokenBuffer::new(tokens, self.source);
+        if errors.is_empty() {
+            Ok(buf)
+        } else {
+            let source = buf.source();
+            Err(Diag::bundle(errors.into_iter().map(|err| {
+                let span = Span::from_range(err.span).with_source(source.id as u32);
+                Diag::new(Level::Error, source.clone(), span, err.msg, Vec::new())
+            })))
+        }
+    }
+}
+
+/// Error encountered by the [`Lexer`], `span` is the byte range within the source.
+#[derive(Debug)]
+struct LexError {
+    span: Range<usize>,
+    msg: Cow<'static, str>,
+}
+
+impl LexError {
+    fn new(span: Range<usize>, msg: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            span,
+            msg: msg.into(),
+        }
+    }
+}
+
+/// Reports the invalid token at the start of `input` and skips past it.
+fn recover(input: &mut LocatingSlice<&str>) -> LexError {
+    let start = input.location();
+
+    if input.starts_with('"') || input.starts_with("r\"") {
+        let quote = if input.starts_with('r') { 2 } else { 1 };
+        // the rest of the source is within the string
+        _ = input.next_slice(input.len());
+        return LexError::new(start..start + quote, "unterminated double quote string");
+    }
+
+    if input.starts_with('\'') || input.starts_with("b'") {
+        let len = input.find('\n').unwrap_or(input.len());
+        _ = input.next_slice(len);
+        return LexError::new(start..start + len, "unterminated character literal");
     }
+
+    let c = input.next_token().unwrap();
+    LexError::new(
+        start..start + c.len_utf8(),
+        format!("unknown character `{}`", c.escape_debug()),
+    )
 }
 
-fn comment<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<()> {
+/// Checks the escape sequences of string and character literals, and the digits and suffix of
+/// numeric literals.
+fn validate_literal(source: &str, token: Token) -> Result<(), LexError> {
+    let range = token.span.range();
+    if matches!(token.kind, TokenKind::Int | TokenKind::Float) {
+        return number::parse(&source[range.clone()])
+            .map(|_| ())
+            .map_err(|err| {
+                LexError::new(
+                    range.start + err.span.start..range.start + err.span.end,
+                    err.msg,
+                )
+            });
+    }
+
+    let result = match token.kind {
+        TokenKind::Str => escape::unescape_str(&source[range.clone()])
+            .map(|_| ())
+            .map_err(|err| (range.start, err)),
+        TokenKind::Char => {
+            let (offset, inner) = char_lit_inner(&source[range.clone()]);
+            escape::unescape_byte(inner)
+                .map(|_| ())
+                .map_err(|err| (range.start + offset, err))
+        }
+        _ => Ok(()),
+    };
+
+    result.map_err(|(offset, err)| {
+        let end = if err.span.is_empty() {
+            range.end
+        } else {
+            offset + err.span.end
+        };
+        LexError::new(offset + err.span.start..end, err.msg)
+    })
+}
+
+/// Strips the `b` prefix and quotes of a character literal, returning the offset of the contents.
+pub fn char_lit_inner(lit: &str) -> (usize, &str) {
+    let offset = if lit.starts_with('b') { 2 } else { 1 };
+    (offset, &lit[offset..lit.len() - 1])
+}
+
+/// Skips whitespace and comments.
+///
+/// An unterminated block comment consumes the rest of `input`.
+fn comment(input: &mut LocatingSlice<&str>) -> Result<(), LexError> {
     loop {
-        take_while(.., |c| c == ' ' || c == '\n').parse_next(input)?;
-        match alt(("//", "/*")).parse_next(input)? {
-            "//" => {
-                while input.peek_token().is_some_and(|(_, c)| c != '\n') {
-                    _ = input.next_token();
-                }
-            }
-            "/*" => {
-                while input.peek_slice(2).1 != "*/" {
-                    _ = input.next_token();
+        let _: ModalResult<_> = take_while(.., char::is_whitespace).parse_next(input);
+
+        if input.starts_with("//") {
+            let len = input.find('\n').unwrap_or(input.len());
+            _ = input.next_slice(len);
+        } else if input.starts_with("/*") {
+            let start = input.location();
+            match input.find("*/") {
+                Some(end) => _ = input.next_slice(end + 2),
+                None => {
+                    _ = input.next_slice(input.len());
+                    return Err(LexError::new(
+                        start..start + 2,
+                        "unterminated block comment, expected `*/`",
+                    ));
                 }
-                _ = (input.next_token(), input.next_token());
             }
-            _ => unreachable!(),
+        } else {
+            return Ok(());
         }
     }
 }
 
-fn any_token<'a>(input: &mut LocatingSlice<&'a str>) -> Option<ModalResult<Token>> {
-    _ = comment(input);
-    if input.is_empty() {
-        return None;
-    }
-
-    let token = alt((str_lit, symbols, delim, int_lit, keyword_ident)).parse_next(input);
-    Some(token)
+fn any_token(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    alt((
+        str_lit,
+        raw_str_lit,
+        char_lit,
+        symbols,
+        delim,
+        int_lit,
+        keyword_ident,
+    ))
+    .parse_next(input)
 }
 
 fn str_lit<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
-    let ((_, inner_span), _span) = delimited("\"", take_while(.., |c| c != '\"').with_span(), "\"")
+    let ((_, inner_span), _span) = delimited("\"", escaped_until('"').with_span(), "\"")
         .with_span()
         .parse_next(input)?;
     // TODO: include the `"`
     Ok(Token::new(TokenKind::Str, Span::from_range(inner_span)))
 }
 
+fn raw_str_lit(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    let (_, inner_span) =
+        preceded("r\"", take_while(.., |c| c != '\"').with_span()).parse_next(input)?;
+    "\"".parse_next(input)?;
+    Ok(Token::new(TokenKind::RawStr, Span::from_range(inner_span)))
+}
+
+/// Character literal, `'a'` or `b'a'`, the span includes the prefix and quotes.
+fn char_lit(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    let (_, span) = (opt('b'), delimited('\'', escaped_until('\''), '\''))
+        .with_span()
+        .parse_next(input)?;
+    Ok(Token::new(TokenKind::Char, Span::from_range(span)))
+}
+
+/// Loop label, `'outer`, the span includes the quote.
+fn label(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    let (_, span) = (
+        '\'',
+        one_of(|c: char| c.is_alphabetic() || c == '_'),
+        take_while(.., |c: char| c.is_alphanumeric() || c == '_'),
+        not('\''),
+    )
+        .with_span()
+        .parse_next(input)?;
+    Ok(Token::new(TokenKind::Label, Span::from_range(span)))
+}
+
+/// Loop label followed by the `:` of its definition, `'outer: loop { ... }`.
+fn label_def(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    terminated(label, peek((take_while(.., char::is_whitespace), ':'))).parse_next(input)
+}
+
+/// Takes everything up to an unescaped `end` on the same line for characters.
+fn escaped_until<'a>(end: char) -> impl Parser<LocatingSlice<&'a str>, &'a str, ContextError> {
+    move |input: &mut LocatingSlice<&'a str>| {
+        let start = input.checkpoint();
+        let mut len = 0;
+        let mut escaped = false;
+        for c in input.chars() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == end || (end == '\'' && c == '\n') {
+                break;
+            }
+            len += c.len_utf8();
+        }
+        input.reset(&start);
+        Ok(input.next_slice(len))
+    }
+}
+
 const SYMBOL_TABLE: [TokenKind; 256] = symbol_table();
-pub const SYMBOLS: [(char, TokenKind); 14] = [
+pub const SYMBOLS: [(char, TokenKind); 15] = [
     (';', TokenKind::Semi),
     (':', TokenKind::Colon),
     ('=', TokenKind::Equals),
@@ -98,6 +280,7 @@ pub const SYMBOLS: [(char, TokenKind); 14] = [
     ('!', TokenKind::Bang),
     ('^', TokenKind::Caret),
     ('|', TokenKind::Pipe),
+    ('?', TokenKind::Question),
 ];
 const DUMMY_SYM: TokenKind = TokenKind::Ident;
 
@@ -126,6 +309,13 @@ fn symbols<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
         if let Some((_, span)) = opt('.'.with_span()).parse_next(input)? {
             if input.peek_token().is_some_and(|(_, t)| t == '.') {
                 _ = input.next_token();
+                if input.peek_token().is_some_and(|(_, t)| t == '.') {
+                    _ = input.next_token();
+                    return Ok(Token::new(
+                        TokenKind::Ellipsis,
+                        Span::from_range(span.start..span.end + 2),
+                    ));
+                }
                 return Ok(Token::new(
                     TokenKind::DoubleDot,
                     Span::from_range(span.start..span.end + 1),
@@ -178,40 +368,29 @@ fn delim<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
     }
 }
 
-fn int_lit<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
-    let other = *input;
-    let (mut kind, span) = alt((
-        preceded("0x", take_while(1.., AsChar::is_hex_digit)).map(|_| TokenKind::Int),
-        preceded("0b", take_while(1.., AsChar::is_hex_digit)).map(|_| TokenKind::Int),
-        float.map(|_: f64| TokenKind::Float),
-    ))
-    .with_span()
-    .parse_next(input)?;
-
-    if matches!(kind, TokenKind::Float) {
-        let slice = other.get(..span.len());
-
-        // TODO: get rid of winnow and parse manually, this is annoying
-        if slice == Some("infinity") || slice == Some("nan") {
-            kind = TokenKind::Ident;
-        } else {
-            if slice.is_some_and(|s| s.parse::<i64>().is_ok()) {
-                return Ok(Token::new(TokenKind::Int, Span::from_range(span)));
-            } else if slice.is_some_and(|s| s.ends_with('.'))
-                && input.peek_token().is_some_and(|(_, t)| t == '.')
-            {
-                *input = other;
-                take_while(1.., AsChar::is_hex_digit).parse_next(input)?;
+/// Numeric literal, the grammar is described in [`number`].
+fn int_lit(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    let Some((len, float)) = number::scan(input) else {
+        return ModalResult::Err(ErrMode::Backtrack(ContextError::new()));
+    };
 
-                return Ok(Token::new(
-                    TokenKind::Int,
-                    Span::from_range(span.start..span.end - 1),
-                ));
-            }
-        }
-    }
+    let start = input.location();
+    _ = input.next_slice(len);
+    let kind = if float {
+        TokenKind::Float
+    } else {
+        TokenKind::Int
+    };
+    Ok(Token::new(kind, Span::from_range(start..start + len)))
+}
 
-    Ok(Token::new(kind, Span::from_range(span)))
+/// Tuple index following a `.`, e.g. the `0` and `1` in `t.0.1`, which would otherwise be lexed
+/// as the float `0.1`.
+fn tuple_index(input: &mut LocatingSlice<&str>) -> ModalResult<Token> {
+    let (_, span) = take_while(1.., |c: char| c.is_ascii_digit())
+        .with_span()
+        .parse_next(input)?;
+    Ok(Token::new(TokenKind::Int, Span::from_range(span)))
 }
 
 // This is 4% slower than linear search with fxhash
@@ -241,18 +420,9 @@ fn int_lit<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
 //];
 
 fn keyword_ident<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
-    let (result, span) = take_while(1.., |c: char| {
-        !c.is_whitespace()
-            && c != '.'
-            && SYMBOL_TABLE
-                .get(c as usize)
-                .is_some_and(|s| *s == DUMMY_SYM)
-            && DELIM_TABLE
-                .get(c as usize)
-                .is_some_and(|d| *d == DUMMY_DELIM)
-    })
-    .with_span()
-    .parse_next(input)?;
+    let (result, span) = take_while(1.., |c: char| c.is_alphanumeric() || c == '_')
+        .with_span()
+        .parse_next(input)?;
 
     let token = match result {
         "let" => TokenKind::Let,
@@ -260,6 +430,7 @@ fn keyword_ident<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
         "else" => TokenKind::Else,
         "true" => TokenKind::True,
         "false" => TokenKind::False,
+        "null" => TokenKind::Null,
         "for" => TokenKind::For,
         "in" => TokenKind::In,
         "fn" => TokenKind::Fn,
@@ -268,7 +439,11 @@ fn keyword_ident<'a>(input: &mut LocatingSlice<&'a str>) -> ModalResult<Token> {
         "continue" => TokenKind::Continue,
         "break" => TokenKind::Break,
         "as" => TokenKind::As,
+        "move" => TokenKind::Move,
+        "defer" => TokenKind::Defer,
         "struct" => TokenKind::Struct,
+        "union" => TokenKind::Union,
+        "unsafe" => TokenKind::Unsafe,
         "impl" => TokenKind::Impl,
         "enum" => TokenKind::Enum,
         "loop" => TokenKind::Loop,
@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Numeric literal, e.g. `255`, `0xff_u8`, `1_000_000` or `1.5e3f32`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number<'a> {
+    pub val: NumberVal,
+    /// Type suffix, one of `u8`, `u16`, `u32`, `u64`, `i8`, `i16`, `i32`, `i64`, `f32` or `f64`.
+    pub suffix: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberVal {
+    Int(u64),
+    Float(f64),
+}
+
+/// Error in a numeric literal, `span` is the byte range within the literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberError {
+    pub span: Range<usize>,
+    pub msg: Cow<'static, str>,
+}
+
+impl NumberError {
+    fn new(span: Range<usize>, msg: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            span,
+            msg: msg.into(),
+        }
+    }
+}
+
+const INT_SUFFIXES: [&str; 8] = ["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"];
+const FLOAT_SUFFIXES: [&str; 2] = ["f32", "f64"];
+
+/// Returns the length of the numeric literal at the start of `str`, and whether it is a float.
+///
+/// The literal is not validated, see [`parse`].
+pub fn scan(str: &str) -> Option<(usize, bool)> {
+    let bytes = str.as_bytes();
+    if !bytes.first().is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let alnum = |i: usize| {
+        bytes[i..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+            .count()
+    };
+
+    if radix(str).is_some() {
+        return Some((2 + alnum(2), false));
+    }
+
+    let digits = |i: usize| {
+        bytes[i..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'_')
+            .count()
+    };
+
+    let mut len = digits(0);
+    let mut float = false;
+
+    // `1.5` and `1.`, but not `1..2` or `1.method()`
+    if bytes.get(len) == Some(&b'.')
+        && !bytes
+            .get(len + 1)
+            .is_some_and(|b| *b == b'.' || b.is_ascii_alphabetic() || *b == b'_')
+    {
+        float = true;
+        len += 1 + digits(len + 1);
+    }
+
+    if matches!(bytes.get(len), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+        if bytes.get(len + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            float = true;
+            len += 1 + sign + digits(len + 1 + sign);
+        }
+    }
+
+    let suffix = alnum(len);
+    if FLOAT_SUFFIXES.contains(&&str[len..len + suffix]) {
+        float = true;
+    }
+
+    Some((len + suffix, float))
+}
+
+fn radix(str: &str) -> Option<(u32, &'static str)> {
+    match str.get(..2) {
+        Some("0x") => Some((16, "hexadecimal")),
+        Some("0o") => Some((8, "octal")),
+        Some("0b") => Some((2, "binary")),
+        _ => None,
+    }
+}
+
+/// Parses a numeric literal produced by [`scan`].
+pub fn parse(str: &str) -> Result<Number<'_>, NumberError> {
+    if let Some((radix, name)) = radix(str) {
+        let digits_len = str[2..]
+            .bytes()
+            .take_while(|b| {
+                if radix == 16 {
+                    b.is_ascii_hexdigit() || *b == b'_'
+                } else {
+                    b.is_ascii_digit() || *b == b'_'
+                }
+            })
+            .count();
+        let digits = &str[2..2 + digits_len];
+        let suffix = suffix(str, 2 + digits_len)?;
+
+        if digits.bytes().all(|b| b == b'_') {
+            return Err(NumberError::new(
+                0..str.len(),
+                format!("expected digits in {} literal", name),
+            ));
+        }
+        if let Some(i) = digits
+            .bytes()
+            .position(|b| b != b'_' && !(b as char).is_digit(radix))
+        {
+            return Err(NumberError::new(
+                2 + i..2 + i + 1,
+                format!("invalid digit for a {} literal", name),
+            ));
+        }
+        if suffix.is_some_and(|s| FLOAT_SUFFIXES.contains(&s)) {
+            return Err(NumberError::new(
+                2 + digits_len..str.len(),
+                format!("{} float literals are not supported", name),
+            ));
+        }
+
+        let digits = digits.replace('_', "");
+        let val = u64::from_str_radix(&digits, radix)
+            .map_err(|_| NumberError::new(0..str.len(), "integer literal is too large"))?;
+        return Ok(Number {
+            val: NumberVal::Int(val),
+            suffix,
+        });
+    }
+
+    let len = str
+        .bytes()
+        .take_while(|b| !b.is_ascii_alphabetic() || *b == b'e' || *b == b'E')
+        .count();
+    // an exponent without digits is part of the suffix
+    let len = match str[..len].rfind(['e', 'E']) {
+        Some(e) if !str[e + 1..len].bytes().any(|b| b.is_ascii_digit()) => e,
+        _ => len,
+    };
+    let number = &str[..len];
+    let suffix = suffix(str, len)?;
+
+    let is_float = number.contains(['.', 'e', 'E']);
+    let digits = number.replace('_', "");
+    if is_float || suffix.is_some_and(|s| FLOAT_SUFFIXES.contains(&s)) {
+        if suffix.is_some_and(|s| INT_SUFFIXES.contains(&s)) {
+            return Err(NumberError::new(
+                len..str.len(),
+                "integer suffix on a float literal",
+            ));
+        }
+
+        let val = digits
+            .parse()
+            .map_err(|_| NumberError::new(0..str.len(), "invalid float literal"))?;
+        return Ok(Number {
+            val: NumberVal::Float(val),
+            suffix,
+        });
+    }
+
+    let val = digits
+        .parse()
+        .map_err(|_| NumberError::new(0..str.len(), "integer literal is too large"))?;
+    Ok(Number {
+        val: NumberVal::Int(val),
+        suffix,
+    })
+}
+
+fn suffix(str: &str, start: usize) -> Result<Option<&str>, NumberError> {
+    let suffix = str[start..].trim_start_matches('_');
+    if suffix.is_empty() {
+        return Ok(None);
+    }
+
+    if INT_SUFFIXES.contains(&suffix) || FLOAT_SUFFIXES.contains(&suffix) {
+        Ok(Some(suffix))
+    } else {
+        Err(NumberError::new(
+            str.len() - suffix.len()..str.len(),
+            format!("invalid suffix `{}` for number literal", suffix),
+        ))
+    }
+}
@@ -26,7 +26,7 @@ pub enum SourceError {
     InvalidExtension(String),
     #[error("could not open `{file}`: {io}")]
     Io { file: String, io: std::io::Error },
-    #[error("encountered unparsable symbol in `{0}`")]
+    #[error("failed to lex `{0}`")]
     Lex(String),
     #[error("failed to parse `{0}`")]
     Parse(String),
@@ -61,7 +61,8 @@ impl SourceMap {
             Ok(buf) => {
                 tokens.insert(buf.source_id(), buf);
             }
-            Err(_) => {
+            Err(diag) => {
+                diag.report();
                 return Err(SourceError::Lex(origin));
             }
         }
@@ -89,7 +90,8 @@ impl SourceMap {
             Ok(buf) => {
                 tokens.insert(buf.source_id(), buf);
             }
-            Err(_) => {
+            Err(diag) => {
+                diag.report();
                 return Err(SourceError::Lex(origin));
             }
         }
@@ -237,7 +239,13 @@ impl SourceMap {
                 }
                 Ok(source) => {
                     let src = source.origin.to_string_lossy().to_string();
-                    let buffer = Lexer::new(source).lex().unwrap();
+                    let buffer = match Lexer::new(source).lex() {
+                        Ok(buffer) => buffer,
+                        Err(diag) => {
+                            diag.report();
+                            return Err(SourceError::Lex(src));
+                        }
+                    };
                     let mut err = false;
                     match crate::parse(&buffer) {
                         Ok(items) => new_items.extend(items),
@@ -38,7 +38,7 @@ impl AssignKind {
         match self {
             Self::Equals => "=",
 
-            Self::Mul => "+=",
+            Self::Mul => "*=",
             Self::Div => "/=",
             Self::Rem => "%=",
 
@@ -202,16 +202,18 @@ pub fn parse<'a>(buffer: &'a TokenBuffer) -> Result<Vec<Item>, Diag> {
                 }
             }
 
-            (Some(TokenKind::Ident), Some(TokenKind::Colon), Some(TokenKind::Struct)) => {
-                match rules::prelude::StructRule::parse(&mut stream) {
-                    Err(diag) => {
-                        diags.push(diag);
-                    }
-                    Ok(s) => {
-                        items.push(ItemKind::Struct(s));
-                    }
+            (
+                Some(TokenKind::Ident),
+                Some(TokenKind::Colon),
+                Some(TokenKind::Struct | TokenKind::Union),
+            ) => match rules::prelude::StructRule::parse(&mut stream) {
+                Err(diag) => {
+                    diags.push(diag);
                 }
-            }
+                Ok(s) => {
+                    items.push(ItemKind::Struct(s));
+                }
+            },
 
             (Some(TokenKind::Ident), Some(TokenKind::Colon), Some(TokenKind::Const)) => {
                 match rules::prelude::ConstRule::parse(&mut stream) {
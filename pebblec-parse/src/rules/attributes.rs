@@ -1,14 +1,205 @@
+use super::expr::Path;
 use super::{PErr, ParserRule, RResult};
-use crate::lex::buffer::{Span, TokenId, TokenQuery};
-use crate::matc::Bracket;
+use crate::diagnostic::{Diag, Msg};
+use crate::lex::buffer::{Buffer, Span, TokenBuffer, TokenId, TokenQuery};
+use crate::lex::kind::*;
+use crate::lex::number::{self, Number, NumberVal};
+use crate::matc::{Bracket, Paren};
 use crate::stream::TokenStream;
+use annotate_snippets::Level;
 
-// TODO: parse these suckers
+/// `#[<meta>]`, applies to the item, field or statement that follows.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attribute {
     pub span: Span,
     pub pound: TokenId,
-    pub tokens: Vec<TokenId>,
+    pub meta: Meta,
+}
+
+/// Contents of an [`Attribute`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Meta {
+    /// `intrinsic`
+    Path(Path),
+    /// `link = "SDL3"`
+    NameValue {
+        span: Span,
+        path: Path,
+        value: TokenId,
+    },
+    /// `link("SDL3")`, `repr(C, align(8))`
+    List {
+        span: Span,
+        path: Path,
+        items: Vec<Meta>,
+    },
+    /// `"SDL3"`, `8`, only found within a [`Meta::List`].
+    Lit(TokenId),
+}
+
+impl Meta {
+    pub fn span(&self, token_buffer: &TokenBuffer) -> Span {
+        match self {
+            Self::Path(path) => path.span,
+            Self::NameValue { span, .. } | Self::List { span, .. } => *span,
+            Self::Lit(lit) => token_buffer.span(*lit),
+        }
+    }
+
+    /// Path that names the meta, `None` for literals.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) | Self::NameValue { path, .. } | Self::List { path, .. } => Some(path),
+            Self::Lit(_) => None,
+        }
+    }
+}
+
+/// Item, field or statement that an [`Attribute`] is applied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrTarget {
+    Func,
+    Extern,
+    Struct,
+    Field,
+    Const,
+    Impl,
+    Stmt,
+}
+
+impl AttrTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Func => "a function",
+            Self::Extern => "an extern block",
+            Self::Struct => "a struct",
+            Self::Field => "a field",
+            Self::Const => "a const",
+            Self::Impl => "an impl",
+            Self::Stmt => "a statement",
+        }
+    }
+}
+
+/// Attributes understood by the compiler, along with what they may be applied to.
+pub const KNOWN_ATTRIBUTES: [(&str, &[AttrTarget]); 4] = [
+    ("intrinsic", &[AttrTarget::Func]),
+    ("link", &[AttrTarget::Extern]),
+    ("repr", &[AttrTarget::Struct]),
+    ("align", &[AttrTarget::Struct]),
+];
+
+impl Attribute {
+    /// The path of the attribute joined with `::`, e.g. `repr` in `#[repr(C)]`.
+    pub fn name<'a>(&self, stream: &TokenStream<'a>) -> String {
+        self.path()
+            .segments
+            .iter()
+            .map(|s| stream.as_str(s))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    pub fn path(&self) -> &Path {
+        self.meta.path().expect("attributes start with a path")
+    }
+
+    /// Reports attributes that are unknown or do not apply to `target`.
+    pub fn validate<'a>(&self, stream: &TokenStream<'a>, target: AttrTarget) -> Result<(), Diag> {
+        let name = self.name(stream);
+        match KNOWN_ATTRIBUTES.iter().find(|(known, _)| *known == name) {
+            Some((_, targets)) if targets.contains(&target) => Ok(()),
+            Some(_) => Err(stream.report_error(
+                format!("`{}` cannot be applied to {}", name, target.as_str()),
+                self.span,
+            )),
+            None => {
+                let known = KNOWN_ATTRIBUTES
+                    .iter()
+                    .map(|(known, _)| format!("`{}`", known))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Err(stream
+                    .report_error(format!("unknown attribute `{}`", name), self.span)
+                    .msg(Msg::new(
+                        Level::Help,
+                        stream.token_buffer().source(),
+                        self.path().span,
+                        format!("known attributes are {}", known),
+                    )))
+            }
+        }
+    }
+
+    /// Expects an attribute without arguments, `#[intrinsic]`.
+    pub fn word<'a>(&self, stream: &TokenStream<'a>) -> Result<(), Diag> {
+        match &self.meta {
+            Meta::Path(_) => Ok(()),
+            _ => Err(stream.report_error(
+                format!("`{}` does not take arguments", self.name(stream)),
+                self.span,
+            )),
+        }
+    }
+
+    /// Expects a single string argument, `#[link("SDL3")]` or `#[link = "SDL3"]`.
+    pub fn str_arg<'a>(&self, stream: &TokenStream<'a>) -> Result<TokenId, Diag> {
+        match &self.meta {
+            Meta::NameValue { value, .. } if stream.kind(value) == TokenKind::Str => Ok(*value),
+            Meta::List { items, .. } => match items.as_slice() {
+                [Meta::Lit(lit)] if stream.kind(lit) == TokenKind::Str => Ok(*lit),
+                _ => Err(self.expected_str(stream)),
+            },
+            _ => Err(self.expected_str(stream)),
+        }
+    }
+
+    /// Expects a single integer argument, `#[align(16)]` or `#[align = 16]`.
+    pub fn int_arg<'a>(&self, stream: &TokenStream<'a>) -> Result<(TokenId, u64), Diag> {
+        let lit = match &self.meta {
+            Meta::NameValue { value, .. } if stream.kind(value) == TokenKind::Int => *value,
+            Meta::List { items, .. } => match items.as_slice() {
+                [Meta::Lit(lit)] if stream.kind(lit) == TokenKind::Int => *lit,
+                _ => return Err(self.expected_int(stream)),
+            },
+            _ => return Err(self.expected_int(stream)),
+        };
+
+        match number::parse(stream.as_str(lit)) {
+            Ok(Number {
+                val: NumberVal::Int(val),
+                suffix: None,
+            }) => Ok((lit, val)),
+            _ => Err(self.expected_int(stream)),
+        }
+    }
+
+    /// Expects a list of arguments, `#[repr(C, packed)]`.
+    pub fn list_args<'a>(&self, stream: &TokenStream<'a>) -> Result<&[Meta], Diag> {
+        match &self.meta {
+            Meta::List { items, .. } if !items.is_empty() => Ok(items),
+            _ => {
+                let name = self.name(stream);
+                Err(stream.report_error(format!("expected `#[{}(...)]`", name), self.span))
+            }
+        }
+    }
+
+    fn expected_int<'a>(&self, stream: &TokenStream<'a>) -> Diag {
+        let name = self.name(stream);
+        stream.report_error(
+            format!("expected `#[{}(<int>)]` or `#[{} = <int>]`", name, name),
+            self.span,
+        )
+    }
+
+    fn expected_str<'a>(&self, stream: &TokenStream<'a>) -> Diag {
+        let name = self.name(stream);
+        stream.report_error(
+            format!("expected `#[{}(\"...\")]` or `#[{} = \"...\"]`", name, name),
+            self.span,
+        )
+    }
 }
 
 pub struct AttributeRule;
@@ -17,31 +208,154 @@ impl<'a, 's> ParserRule<'a> for AttributeRule {
     type Output = Attribute;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        if !stream.match_peek::<Pound>() {
+            return Err(stream.recover("expected `#`"));
+        }
         let pound = stream.expect();
-        let _bracket = stream.expect();
+        if !stream.match_peek::<OpenBracket>() {
+            return Err(stream.fail("expected `[`"));
+        }
+        stream.eat();
+
         let offset = stream.find_matched_delim_offset::<Bracket>();
         let mut slice = stream.slice(offset);
         stream.eat_n(offset);
-        let _bracket = stream.expect();
-
-        let tokens = slice.drain();
-        match tokens.len() {
-            0 => {
-                return Err(PErr::Fail(stream.report_error(
-                    "expected atleast one argument in attribute",
-                    stream.span(stream.prev()),
-                )));
+        if !stream.match_peek::<CloseBracket>() {
+            return Err(PErr::Fail(
+                stream.report_error("unclosed attribute", stream.span(pound)),
+            ));
+        }
+        let close = stream.expect();
+
+        if slice.is_empty() {
+            return Err(PErr::Fail(
+                stream.report_error("expected an attribute", stream.span(close)),
+            ));
+        }
+        if !slice.match_peek::<Ident>() {
+            return Err(slice.fail("expected an identifier"));
+        }
+
+        let meta = MetaRule::parse(&mut slice).map_err(PErr::fail)?;
+        if !slice.is_empty() {
+            return Err(slice.fail("expected `]`"));
+        }
+
+        Ok(Attribute {
+            span: Span::from_spans(stream.span(pound), stream.span(close)),
+            pound,
+            meta,
+        })
+    }
+}
+
+/// Any number of attributes, `#[a] #[b(c)]`.
+pub struct AttributesRule;
+
+impl<'a> ParserRule<'a> for AttributesRule {
+    type Output = Vec<Attribute>;
+
+    fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        let mut attributes = Vec::new();
+        while stream.match_peek::<Pound>() {
+            attributes.push(AttributeRule::parse(stream)?);
+        }
+        Ok(attributes)
+    }
+}
+
+/// `<path>`, `<path> = <lit>`, `<path>(<meta>, ...)` or `<lit>`
+pub struct MetaRule;
+
+impl<'a> ParserRule<'a> for MetaRule {
+    type Output = Meta;
+
+    fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        if is_lit(stream) {
+            return Ok(Meta::Lit(stream.expect()));
+        }
+        if !stream.match_peek::<Ident>() {
+            return Err(stream.fail("expected an identifier or literal"));
+        }
+
+        let path = meta_path(stream)?;
+        if stream.match_peek::<Equals>() {
+            stream.eat();
+            if !is_lit(stream) {
+                return Err(stream.fail("expected a literal"));
+            }
+            let value = stream.expect();
+
+            Ok(Meta::NameValue {
+                span: Span::from_spans(path.span, stream.span(value)),
+                path,
+                value,
+            })
+        } else if stream.match_peek::<OpenParen>() {
+            stream.eat();
+            let offset = stream.find_matched_delim_offset::<Paren>();
+            let mut slice = stream.slice(offset);
+            stream.eat_n(offset);
+            if !stream.match_peek::<CloseParen>() {
+                return Err(stream.fail("expected `)`"));
+            }
+            let close = stream.expect();
+
+            let mut items = Vec::new();
+            while !slice.is_empty() {
+                items.push(MetaRule::parse(&mut slice)?);
+                if !slice.is_empty() {
+                    if !slice.match_peek::<Comma>() {
+                        return Err(slice.fail("expected `,`"));
+                    }
+                    slice.eat();
+                }
             }
-            1 => Ok(Attribute {
-                pound,
-                span: Span::from_spans(stream.span(pound), stream.span(tokens[0])),
-                tokens,
-            }),
-            _ => Ok(Attribute {
-                pound,
-                span: Span::from_spans(stream.span(pound), stream.span(*tokens.last().unwrap())),
-                tokens,
-            }),
+
+            Ok(Meta::List {
+                span: Span::from_spans(path.span, stream.span(close)),
+                path,
+                items,
+            })
+        } else {
+            Ok(Meta::Path(path))
+        }
+    }
+}
+
+fn is_lit(stream: &TokenStream) -> bool {
+    matches!(
+        stream.peek_kind(),
+        Some(
+            TokenKind::Str
+                | TokenKind::Int
+                | TokenKind::Float
+                | TokenKind::Char
+                | TokenKind::True
+                | TokenKind::False
+        )
+    )
+}
+
+/// `<ident>[::<ident>]*`
+fn meta_path(stream: &mut TokenStream) -> RResult<Path> {
+    let first = stream.expect();
+    let mut span = stream.span(first);
+    let mut segments = vec![first];
+
+    while stream.match_peek::<Colon>()
+        && stream
+            .peekn(1)
+            .is_some_and(|t| stream.kind(t) == TokenKind::Colon)
+    {
+        stream.eat_n(2);
+        if !stream.match_peek::<Ident>() {
+            return Err(stream.fail("expected an identifier"));
         }
+        let segment = stream.expect();
+        span = Span::from_spans(span, stream.span(segment));
+        segments.push(segment);
     }
+
+    Ok(Path { span, segments })
 }
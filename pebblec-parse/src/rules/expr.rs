@@ -1,7 +1,7 @@
 use super::arr::{ArrDef, ArrDefRule};
-use super::block::Block;
-use super::func::ArgsRule;
-use super::stmt::CntrlFlowRule;
+use super::block::{Block, BlockRules};
+use super::func::{ArgsRule, Param};
+use super::stmt::{CntrlFlowRule, LoopRule};
 use super::strukt::StructDef;
 use super::types::{PType, TypeRule};
 use super::{ParserRule, RResult};
@@ -17,14 +17,28 @@ use std::marker::PhantomData;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expr {
-    Break(TokenId),
-    Continue(TokenId),
+    /// `break ['label] [<expr>]`, a value is only allowed for `loop`.
+    Break {
+        span: Span,
+        label: Option<TokenId>,
+        value: Option<Box<Expr>>,
+    },
+    /// `continue ['label]`
+    Continue {
+        span: Span,
+        label: Option<TokenId>,
+    },
     Ident(TokenId),
     Lit(TokenId),
     Str(TokenId),
     Bool(TokenId),
+    Null(TokenId),
     Bin(Span, BinOpKind, Box<Expr>, Box<Expr>),
     Paren(Box<Expr>),
+    Tuple {
+        span: Span,
+        exprs: Vec<Expr>,
+    },
     Ret(Span, Option<Box<Expr>>),
     Assign(Assign),
     StructDef(StructDef),
@@ -45,20 +59,31 @@ pub enum Expr {
         func: TokenId,
         args: Vec<Expr>,
     },
+    /// Call through a function pointer that is not named by an identifier, e.g. `(s.f)(x)`.
+    IndirectCall {
+        span: Span,
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// `Type::method` without a call, used to take the address of a method.
+    Path(Path),
     MethodCall {
         span: Span,
         receiver: MethodPath,
         method: TokenId,
         args: Vec<Expr>,
     },
+    /// `if [let <ident> =] <expr> <block> [else <block>]`, the `let` unwraps an optional.
     If {
         span: Span,
+        binding: Option<TokenId>,
         condition: Box<Expr>,
         block: Block,
         otherwise: Option<Block>,
     },
     For {
         span: Span,
+        label: Option<TokenId>,
         iter: TokenId,
         iterable: Box<Expr>,
         block: Block,
@@ -76,28 +101,41 @@ pub enum Expr {
     },
     Loop {
         span: Span,
+        label: Option<TokenId>,
         luup: TokenId,
         block: Block,
     },
+    /// `['label:] while [let <ident> =] <expr> <block>`, the `let` unwraps an optional.
     While {
         span: Span,
+        label: Option<TokenId>,
         wile: TokenId,
+        binding: Option<TokenId>,
         condition: Box<Expr>,
         block: Block,
     },
     Unary(Span, TokenId, UOpKind, Box<Expr>),
+    Closure(Closure),
+    /// `unsafe <block>`, evaluates to the end of the block.
+    Unsafe {
+        span: Span,
+        unsaf: TokenId,
+        block: Block,
+    },
 }
 
 impl Expr {
     pub fn span(&self, token_buffer: &TokenBuffer) -> Span {
         match self {
-            Self::Break(t) => token_buffer.span(*t),
-            Self::Continue(t) => token_buffer.span(*t),
+            Self::Break { span, .. } => *span,
+            Self::Continue { span, .. } => *span,
             Self::Ident(t) => token_buffer.span(*t),
             Self::Lit(t) => token_buffer.span(*t),
             Self::Str(t) => token_buffer.span(*t),
             Self::Bool(t) => token_buffer.span(*t),
+            Self::Null(t) => token_buffer.span(*t),
             Self::Paren(inner) => inner.span(token_buffer),
+            Self::Tuple { span, .. } => *span,
             Self::Bin(span, _, _, _) => *span,
             Self::Ret(span, _) => *span,
             Self::Assign(assign) => assign.span,
@@ -110,6 +148,8 @@ impl Expr {
             Self::Access { span, .. } => *span,
             Self::IndexOf { span, .. } => *span,
             Self::Call { span, .. } => *span,
+            Self::IndirectCall { span, .. } => *span,
+            Self::Path(path) => path.span,
             Self::If { span, .. } => *span,
             Self::For { span, .. } => *span,
             Self::Range { span, .. } => *span,
@@ -118,10 +158,113 @@ impl Expr {
             Self::Cast { span, .. } => *span,
             Self::Unary(span, _, _, _) => *span,
             Self::MethodCall { span, .. } => *span,
+            Self::Closure(closure) => closure.span,
+            Self::Unsafe { span, .. } => *span,
         }
     }
 }
 
+/// `[move] |[<ident>: <type>,]*| [-> <type>] <body>`
+///
+/// Captured locals are borrowed unless the closure is marked `move`, in which case they are
+/// copied into the closure. A block body is required when the return type is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Closure {
+    pub span: Span,
+    pub moove: Option<TokenId>,
+    pub params: Vec<Param>,
+    pub ty: Option<PType>,
+    pub body: ClosureBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClosureBody {
+    Expr(Box<Expr>),
+    Block(Block),
+}
+
+pub struct ClosureRule;
+
+impl<'a> ParserRule<'a> for ClosureRule {
+    type Output = Closure;
+
+    fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        let start = match stream.peek() {
+            Some(t) if matches!(stream.kind(t), TokenKind::Pipe | TokenKind::Move) => t,
+            _ => return Err(stream.recover("expected closure")),
+        };
+
+        let moove = stream.match_peek::<Move>().then(|| stream.expect());
+        if !stream.match_peek::<Pipe>() {
+            return Err(stream.fail("expected `|`"));
+        }
+        stream.expect();
+
+        let mut params = Vec::new();
+        loop {
+            if stream.match_peek::<Pipe>() {
+                stream.expect();
+                break;
+            }
+
+            if !stream.match_peek::<Ident>() {
+                return Err(stream.fail("expected closure parameter"));
+            }
+            let name = stream.expect();
+            if !stream.match_peek::<Colon>() {
+                return Err(PErr::Fail(stream.report_error(
+                    "closure parameters require a type",
+                    stream.span(name),
+                )));
+            }
+            let colon = stream.expect();
+            let ty = TypeRule::parse(stream).map_err(PErr::fail)?;
+            params.push(Param::Named {
+                span: Span::from_spans(stream.span(name), ty.span()),
+                name,
+                colon,
+                ty,
+            });
+
+            if stream.match_peek::<Comma>() {
+                stream.expect();
+            } else if !stream.match_peek::<Pipe>() {
+                return Err(stream.fail("expected `,` or `|`"));
+            }
+        }
+
+        let ty = if stream.match_peek::<Hyphen>() {
+            let (_, _, ty) =
+                <(Next<Hyphen>, Next<CloseAngle>, TypeRule)>::parse(stream).map_err(PErr::fail)?;
+            if !stream.match_peek::<OpenCurly>() {
+                return Err(stream.fail("expected a block after the closure return type"));
+            }
+            Some(ty)
+        } else {
+            None
+        };
+
+        let body = if stream.match_peek::<OpenCurly>() {
+            ClosureBody::Block(BlockRules::parse(stream).map_err(PErr::fail)?)
+        } else {
+            ClosureBody::Expr(Box::new(ExprRule::parse(stream).map_err(PErr::fail)?))
+        };
+
+        let end = match &body {
+            ClosureBody::Block(block) => block.span,
+            ClosureBody::Expr(expr) => expr.span(stream.token_buffer()),
+        };
+
+        Ok(Closure {
+            span: Span::from_spans(stream.span(start), end),
+            moove,
+            params,
+            ty,
+            body,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MethodPath {
     Field(Box<Expr>),
@@ -345,6 +488,11 @@ impl<'a, 's> ParserRule<'a> for TermRule {
             }
         }
 
+        // the closure body extends as far as possible, so nothing can follow it
+        if matches!(stream.peek_kind(), Some(TokenKind::Pipe | TokenKind::Move)) {
+            return Ok(Expr::Closure(ClosureRule::parse(stream)?));
+        }
+
         let peek = stream.peek_kind();
         let term = match peek {
             Some(TokenKind::Ident) => match stream.peekn(1).map(|t| stream.kind(t)) {
@@ -358,7 +506,26 @@ impl<'a, 's> ParserRule<'a> for TermRule {
                     })
                 }
                 Some(TokenKind::Colon) => {
-                    let mut path = PathRules::<OpenParen>::parse(stream)?;
+                    let first = stream.expect();
+                    let mut segments = vec![first];
+                    while stream.match_peek::<Colon>() {
+                        let (_, _, segment) =
+                            <(Next<Colon>, Next<Colon>, Next<Ident>)>::parse(stream)?;
+                        segments.push(segment);
+                    }
+                    let mut path = Path {
+                        span: Span::from_spans(
+                            stream.span(first),
+                            stream.span(*segments.last().unwrap()),
+                        ),
+                        segments,
+                    };
+
+                    // `Type::method` without arguments names the method
+                    if !stream.match_peek::<OpenParen>() {
+                        return Ok(Expr::Path(path));
+                    }
+
                     let (args_span, args) = ArgsRule::parse(stream)?;
                     let span = Span::from_spans(path.span, args_span);
                     let method = path.segments.pop().unwrap();
@@ -377,9 +544,12 @@ impl<'a, 's> ParserRule<'a> for TermRule {
             },
             Some(TokenKind::If) => Ok(CntrlFlowRule::parse(stream)?),
             Some(TokenKind::Slf) => Ok(Expr::Ident(stream.expect())),
-            Some(TokenKind::Float) | Some(TokenKind::Int) => Ok(Expr::Lit(stream.expect())),
+            Some(TokenKind::Float) | Some(TokenKind::Int) | Some(TokenKind::Char) => {
+                Ok(Expr::Lit(stream.expect()))
+            }
             Some(TokenKind::True) | Some(TokenKind::False) => Ok(Expr::Bool(stream.expect())),
-            Some(TokenKind::Str) => Ok(Expr::Str(stream.expect())),
+            Some(TokenKind::Null) => Ok(Expr::Null(stream.expect())),
+            Some(TokenKind::Str | TokenKind::RawStr) => Ok(Expr::Str(stream.expect())),
             Some(TokenKind::Hyphen) | Some(TokenKind::Bang) | Some(TokenKind::Ampersand) => Ok({
                 let t = stream.expect();
                 let expr = Box::new(TermRule::parse(stream)?);
@@ -399,8 +569,43 @@ impl<'a, 's> ParserRule<'a> for TermRule {
                 )
             }),
             Some(TokenKind::OpenBracket) => Ok(Expr::Array(ArrDefRule::parse(stream)?)),
-            Some(TokenKind::Break) => Ok(Expr::Break(stream.expect())),
-            Some(TokenKind::Continue) => Ok(Expr::Continue(stream.expect())),
+            Some(TokenKind::Loop | TokenKind::Label) => Ok(LoopRule::parse(stream)?),
+            Some(TokenKind::Unsafe) => {
+                let unsaf = stream.expect();
+                let block = BlockRules::parse(stream).map_err(PErr::fail)?;
+                Ok(Expr::Unsafe {
+                    span: Span::from_spans(stream.span(unsaf), block.span),
+                    unsaf,
+                    block,
+                })
+            }
+            Some(TokenKind::Break) => {
+                let brk = stream.expect();
+                let label = stream.match_peek::<Label>().then(|| stream.expect());
+                let value = match stream.peek_kind() {
+                    None | Some(TokenKind::Semi | TokenKind::CloseCurly) => None,
+                    Some(_) => Some(Box::new(ExprRule::parse(stream).map_err(PErr::fail)?)),
+                };
+
+                let end = match (&value, label) {
+                    (Some(value), _) => value.span(stream.token_buffer()),
+                    (None, Some(label)) => stream.span(label),
+                    (None, None) => stream.span(brk),
+                };
+                Ok(Expr::Break {
+                    span: Span::from_spans(stream.span(brk), end),
+                    label,
+                    value,
+                })
+            }
+            Some(TokenKind::Continue) => {
+                let cont = stream.expect();
+                let label = stream.match_peek::<Label>().then(|| stream.expect());
+                Ok(Expr::Continue {
+                    span: Span::from_spans(stream.span(cont), stream.span(label.unwrap_or(cont))),
+                    label,
+                })
+            }
             Some(TokenKind::OpenParen) => {
                 let open = stream.expect();
                 let offset = stream.find_matched_delim_offset::<Paren>();
@@ -414,6 +619,17 @@ impl<'a, 's> ParserRule<'a> for TermRule {
                         ))
                     })?;
 
+                // `(a, b)` and `(a,)` are tuples
+                let mut exprs = Vec::new();
+                let mut trailing_comma = false;
+                while slice.match_peek::<Comma>() {
+                    slice.expect();
+                    trailing_comma = slice.is_empty();
+                    if !trailing_comma {
+                        exprs.push(ExprRule::parse(&mut slice).map_err(PErr::fail)?);
+                    }
+                }
+
                 if stream.is_empty() {
                     return Err(PErr::Fail(
                         stream.report_error("mismatched delimiter", stream.span(open)),
@@ -421,8 +637,16 @@ impl<'a, 's> ParserRule<'a> for TermRule {
                 }
 
                 assert!(stream.match_peek::<CloseParen>());
-                stream.expect();
-                Ok(Expr::Paren(Box::new(inner)))
+                let close = stream.expect();
+                if exprs.is_empty() && !trailing_comma {
+                    Ok(Expr::Paren(Box::new(inner)))
+                } else {
+                    exprs.insert(0, inner);
+                    Ok(Expr::Tuple {
+                        span: Span::from_spans(stream.span(open), stream.span(close)),
+                        exprs,
+                    })
+                }
             }
             _ => Err(PErr::Recover(stream.report_error(
                 "expected term",
@@ -464,16 +688,16 @@ impl<'a, 's> ParserRule<'a> for TermRule {
             } else if stream.match_peek::<Dot>() {
                 let dot = stream.expect();
 
-                if !stream.match_peek::<Ident>() {
+                if !stream.match_peek::<Ident>() && !stream.match_peek::<Int>() {
                     return Err(PErr::Fail(stream.report_error(
-                        "invalid access: expected identifier after `.`",
+                        "invalid access: expected identifier or tuple index after `.`",
                         stream.span(dot),
                     )));
                 }
 
                 let field = stream.expect();
 
-                if stream.match_peek::<OpenParen>() {
+                if stream.kind(field) == TokenKind::Ident && stream.match_peek::<OpenParen>() {
                     let (span, args) = ArgsRule::parse(stream).map_err(PErr::fail)?;
                     term_result = Expr::MethodCall {
                         span: Span::from_spans(stream.span(field), span),
@@ -488,6 +712,22 @@ impl<'a, 's> ParserRule<'a> for TermRule {
                         field,
                     };
                 }
+            } else if stream.match_peek::<OpenParen>()
+                && matches!(
+                    term_result,
+                    Expr::Paren(_)
+                        | Expr::Access { .. }
+                        | Expr::IndexOf { .. }
+                        | Expr::Call { .. }
+                        | Expr::IndirectCall { .. }
+                )
+            {
+                let (args_span, args) = ArgsRule::parse(stream).map_err(PErr::fail)?;
+                term_result = Expr::IndirectCall {
+                    span: Span::from_spans(start_span, args_span),
+                    callee: Box::new(term_result),
+                    args,
+                };
             } else if stream.match_peek::<OpenBracket>() {
                 let open_bracket = stream.expect();
                 let index_expr = ExprRule::parse(stream)?;
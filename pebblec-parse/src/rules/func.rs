@@ -26,21 +26,14 @@ impl Func {
         stream: &TokenStream<'a>,
         attr: &Attribute,
     ) -> Result<(), Diag> {
-        assert!(
-            attr.tokens.len() == 1,
-            "add more attribute parsing: {:#?}",
-            attr.tokens
-                .iter()
-                .map(|t| stream.as_str(t))
-                .collect::<Vec<_>>()
-        );
-
-        match stream.as_str(attr.tokens[0]) {
+        attr.validate(stream, AttrTarget::Func)?;
+        match attr.name(stream).as_str() {
             "intrinsic" => {
+                attr.word(stream)?;
                 self.attributes.push(Attr::Intrinsic);
                 Ok(())
             }
-            str => Err(stream.report_error(format!("invalid attribute `{}`", str), attr.span)),
+            _ => unreachable!(),
         }
     }
 }
@@ -64,21 +57,16 @@ impl ExternBlock {
         stream: &TokenStream<'a>,
         attr: &Attribute,
     ) -> Result<(), Diag> {
-        assert!(attr.tokens.len() == 4, "add more attribute parsing");
-
-        if attr
-            .tokens
-            .first()
-            .map(|t| stream.as_str(*t) == "link")
-            .is_some_and(|b| b)
-        {
-            for func in self.funcs.iter_mut() {
-                func.link = Some(*attr.tokens.get(2).unwrap());
+        attr.validate(stream, AttrTarget::Extern)?;
+        match attr.name(stream).as_str() {
+            "link" => {
+                let link = attr.str_arg(stream)?;
+                for func in self.funcs.iter_mut() {
+                    func.link = Some(link);
+                }
+                Ok(())
             }
-
-            Ok(())
-        } else {
-            Err(stream.report_error("invalid attributes for external function", attr.span))
+            _ => unreachable!(),
         }
     }
 }
@@ -89,6 +77,7 @@ pub struct ExternFunc {
     pub name: TokenId,
     pub ty: Option<PType>,
     pub params: Vec<Param>,
+    pub variadic: Option<TokenId>,
     pub convention: TokenId,
     pub link: Option<TokenId>,
 }
@@ -191,7 +180,7 @@ impl<'a, 's> ParserRule<'a> for ExternFnRule {
                 Spanned<(
                     Next<Ident>,
                     Next<Colon>,
-                    ParamsRule,
+                    ExternParamsRule,
                     XNor<(Next<Hyphen>, Next<CloseAngle>, TypeRule)>,
                     Next<Semi>,
                 )>,
@@ -209,10 +198,11 @@ impl<'a, 's> ParserRule<'a> for ExternFnRule {
                 .into_iter()
                 .map(|spanned| {
                     let span = spanned.span();
-                    let (name, _, params, ty, _) = spanned.into_inner();
+                    let (name, _, (params, variadic), ty, _) = spanned.into_inner();
                     ExternFunc {
                         ty: ty.map(|(_, _, t)| t),
                         params,
+                        variadic,
                         span,
                         name,
                         convention,
@@ -231,68 +221,109 @@ impl<'a, 's> ParserRule<'a> for ParamsRule {
     type Output = Vec<Param>;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
-        let str = *stream;
-        let _open = match Next::<OpenParen>::parse(stream) {
-            Err(err) => {
-                *stream = str;
-                return Err(err.fail());
-            }
-            Ok(_) => {}
-        };
+        params(stream, false).map(|(params, _)| params)
+    }
+}
 
-        if !stream.match_peek::<CloseParen>() {
-            let index = stream.find_matched_delim_offset::<Paren>();
-            let mut slice = stream.slice(index);
-            stream.eat_n(index);
-            let _close = stream.expect();
+/// Parameters of an external function, which may end in a variadic `...`.
+#[derive(Debug, Default)]
+pub struct ExternParamsRule;
 
-            let mut params = Vec::new();
-            loop {
-                if slice.is_empty() {
-                    break;
-                }
+impl<'a> ParserRule<'a> for ExternParamsRule {
+    type Output = (Vec<Param>, Option<TokenId>);
 
-                match slice.peek_kind() {
-                    Some(TokenKind::Slf) => {
-                        let _self = slice.expect();
-                        let comma = Opt::<Next<Comma>>::parse(&mut slice)?;
-                        params.push((comma, Param::Slf(_self)));
+    fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        params(stream, true)
+    }
+}
+
+fn params<'a>(
+    stream: &mut TokenStream<'a>,
+    allow_variadic: bool,
+) -> RResult<(Vec<Param>, Option<TokenId>)> {
+    let str = *stream;
+    let _open = match Next::<OpenParen>::parse(stream) {
+        Err(err) => {
+            *stream = str;
+            return Err(err.fail());
+        }
+        Ok(_) => {}
+    };
+
+    if !stream.match_peek::<CloseParen>() {
+        let index = stream.find_matched_delim_offset::<Paren>();
+        let mut slice = stream.slice(index);
+        stream.eat_n(index);
+        let _close = stream.expect();
+
+        let mut params = Vec::new();
+        let mut variadic = None;
+        loop {
+            if slice.is_empty() {
+                break;
+            }
+
+            match slice.peek_kind() {
+                Some(TokenKind::Slf) => {
+                    let _self = slice.expect();
+                    let comma = Opt::<Next<Comma>>::parse(&mut slice)?;
+                    params.push((comma, Param::Slf(_self)));
+                }
+                Some(TokenKind::Ellipsis) => {
+                    if !allow_variadic {
+                        return Err(slice
+                            .fail("variadic parameters are only allowed in external functions"));
                     }
-                    _ => {
-                        let (name, colon, ty, comma) =
-                            <(Next<Ident>, Next<Colon>, TypeRule, Opt<Next<Comma>>)>::parse(
-                                &mut slice,
-                            )?;
-                        params.push((
-                            comma,
-                            Param::Named {
-                                span: Span::from_spans(stream.span(name), ty.span()),
-                                name,
-                                colon,
-                                ty,
-                            },
-                        ));
+                    if params.is_empty() {
+                        return Err(slice.fail("variadic functions require a fixed parameter"));
+                    }
+                    if params.last().is_some_and(|(comma, _)| comma.is_none()) {
+                        return Err(slice.fail("expected comma before `...`"));
+                    }
+
+                    variadic = Some(slice.expect());
+                    _ = Opt::<Next<Comma>>::parse(&mut slice)?;
+                    if !slice.is_empty() {
+                        return Err(slice.fail("`...` must be the last parameter"));
                     }
                 }
+                _ => {
+                    let (name, colon, ty, comma) =
+                        <(Next<Ident>, Next<Colon>, TypeRule, Opt<Next<Comma>>)>::parse(
+                            &mut slice,
+                        )?;
+                    params.push((
+                        comma,
+                        Param::Named {
+                            span: Span::from_spans(stream.span(name), ty.span()),
+                            name,
+                            colon,
+                            ty,
+                        },
+                    ));
+                }
             }
+        }
 
-            if params.len() > 1 {
-                for (i, (comma, _param)) in params.iter().enumerate() {
-                    if i < params.len() - 1 {
-                        if comma.is_none() {
-                            return Err(PErr::Fail(stream.error(format!(
-                                "expected comma after parameter, before next parameter"
-                            ))));
-                        }
+        if params.len() > 1 {
+            for (i, (comma, _param)) in params.iter().enumerate() {
+                if i < params.len() - 1 {
+                    if comma.is_none() {
+                        return Err(PErr::Fail(stream.error(format!(
+                            "expected comma after parameter, before next parameter"
+                        ))));
                     }
                 }
             }
-
-            Ok(params.into_iter().map(|(_, param)| param).collect())
-        } else {
-            let _close = stream.expect();
-            Ok(Vec::new())
         }
+
+        Ok((
+            params.into_iter().map(|(_, param)| param).collect(),
+            variadic,
+        ))
+    } else {
+        let _close = stream.expect();
+        Ok((Vec::new(), None))
     }
 }
 
@@ -10,12 +10,35 @@ pub enum Stmt {
     Let {
         span: Span,
         let_: TokenId,
-        name: TokenId,
+        pat: Pat,
         ty: Option<PType>,
         assign: Expr,
     },
     Semi(Expr),
     Open(Expr),
+    /// `defer <stmt>`, runs `stmt` when the enclosing block exits.
+    Defer {
+        span: Span,
+        defer: TokenId,
+        stmt: Box<Stmt>,
+    },
+    /// `#[<attr>] <stmt>`
+    Attributed {
+        span: Span,
+        attributes: Vec<Attribute>,
+        stmt: Box<Stmt>,
+    },
+}
+
+impl Stmt {
+    pub fn span(&self, token_buffer: &TokenBuffer) -> Span {
+        match self {
+            Self::Let { span, .. } | Self::Defer { span, .. } | Self::Attributed { span, .. } => {
+                *span
+            }
+            Self::Semi(expr) | Self::Open(expr) => expr.span(token_buffer),
+        }
+    }
 }
 
 pub struct StmtRule;
@@ -24,6 +47,33 @@ impl<'a, 's> ParserRule<'a> for StmtRule {
     type Output = Stmt;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        if stream.match_peek::<Pound>() {
+            let attributes = AttributesRule::parse(stream).map_err(PErr::fail)?;
+            let stmt = StmtRule::parse(stream).map_err(PErr::fail)?;
+            return Ok(Stmt::Attributed {
+                span: Span::from_spans(attributes[0].span, stmt.span(stream.token_buffer())),
+                attributes,
+                stmt: Box::new(stmt),
+            });
+        }
+
+        if stream.match_peek::<kind::Defer>() {
+            let defer = stream.expect();
+            let stmt = StmtRule::parse(stream).map_err(PErr::fail)?;
+            if matches!(stmt, Stmt::Let { .. } | Stmt::Defer { .. }) {
+                return Err(PErr::Fail(stream.report_error(
+                    "expected an expression or assignment after `defer`",
+                    stmt.span(stream.token_buffer()),
+                )));
+            }
+
+            return Ok(Stmt::Defer {
+                span: Span::from_spans(stream.span(defer), stmt.span(stream.token_buffer())),
+                defer,
+                stmt: Box::new(stmt),
+            });
+        }
+
         let chk = *stream;
         match LetRule::parse(stream) {
             Ok(let_) => Ok(let_),
@@ -65,7 +115,7 @@ impl<'a, 's> ParserRule<'a> for LetRule {
             return Err(PErr::Recover(stream.error("expected `let`")));
         }
 
-        let (let_, name) = <(Next<Let>, Next<Ident>)>::parse(stream).map_err(PErr::fail)?;
+        let (let_, pat) = <(Next<Let>, PatRule)>::parse(stream).map_err(PErr::fail)?;
         let ty = if Opt::<Next<Colon>>::parse(stream)?.is_some() {
             Some(TypeRule::parse(stream).map_err(PErr::fail)?)
         } else {
@@ -81,11 +131,120 @@ impl<'a, 's> ParserRule<'a> for LetRule {
             let_,
             ty,
             assign: expr,
-            name,
+            pat,
         })
     }
 }
 
+/// Binding of a `let`, e.g. `x`, `(a, (b, c))` or `Point { x, y: (a, b) }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pat {
+    Ident(TokenId),
+    Tuple {
+        span: Span,
+        pats: Vec<Pat>,
+    },
+    Struct {
+        span: Span,
+        name: TokenId,
+        fields: Vec<FieldPat>,
+    },
+}
+
+impl Pat {
+    pub fn span(&self, token_buffer: &TokenBuffer) -> Span {
+        match self {
+            Self::Ident(t) => token_buffer.span(*t),
+            Self::Tuple { span, .. } => *span,
+            Self::Struct { span, .. } => *span,
+        }
+    }
+}
+
+/// `<ident>[: <pat>]`, the shorthand `x` binds the field to `x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPat {
+    pub span: Span,
+    pub name: TokenId,
+    pub pat: Option<Pat>,
+}
+
+pub struct PatRule;
+
+impl<'a, 's> ParserRule<'a> for PatRule {
+    type Output = Pat;
+
+    fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
+        match stream.peek_kind() {
+            Some(TokenKind::Ident)
+                if stream.peekn(1).map(|t| stream.kind(t)) == Some(TokenKind::OpenCurly) =>
+            {
+                let name = stream.expect();
+                let _open = stream.expect();
+
+                let mut fields = Vec::new();
+                while !stream.match_peek::<CloseCurly>() {
+                    let field = Next::<Ident>::parse(stream).map_err(PErr::fail)?;
+                    let pat = if Opt::<Next<Colon>>::parse(stream)?.is_some() {
+                        Some(PatRule::parse(stream).map_err(PErr::fail)?)
+                    } else {
+                        None
+                    };
+                    let span = match &pat {
+                        Some(pat) => {
+                            Span::from_spans(stream.span(field), pat.span(stream.token_buffer()))
+                        }
+                        None => stream.span(field),
+                    };
+                    fields.push(FieldPat {
+                        span,
+                        name: field,
+                        pat,
+                    });
+
+                    if Opt::<Next<Comma>>::parse(stream)?.is_none()
+                        && !stream.match_peek::<CloseCurly>()
+                    {
+                        return Err(stream.fail("expected `,` after field"));
+                    }
+                }
+                let close = stream.expect();
+
+                Ok(Pat::Struct {
+                    span: Span::from_spans(stream.span(name), stream.span(close)),
+                    name,
+                    fields,
+                })
+            }
+            Some(TokenKind::Ident) => Ok(Pat::Ident(stream.expect())),
+            Some(TokenKind::OpenParen) => {
+                let open = stream.expect();
+
+                let mut pats = Vec::new();
+                let mut trailing_comma = false;
+                while !stream.match_peek::<CloseParen>() {
+                    pats.push(PatRule::parse(stream).map_err(PErr::fail)?);
+                    trailing_comma = Opt::<Next<Comma>>::parse(stream)?.is_some();
+                    if !trailing_comma && !stream.match_peek::<CloseParen>() {
+                        return Err(stream.fail("expected `,` or `)`"));
+                    }
+                }
+                let close = stream.expect();
+
+                if pats.len() == 1 && !trailing_comma {
+                    return Ok(pats.pop().unwrap());
+                }
+
+                Ok(Pat::Tuple {
+                    span: Span::from_spans(stream.span(open), stream.span(close)),
+                    pats,
+                })
+            }
+            _ => Err(stream.fail("expected identifier or pattern")),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct AssignRule;
 
@@ -257,8 +416,9 @@ impl<'a, 's> ParserRule<'a> for CntrlFlowRule {
             return Err(PErr::Recover(stream.error("expected `if`")));
         }
 
-        let (iff, expr, block, otherwise) = <(
-            Next<If>,
+        let iff = stream.expect();
+        let binding = let_binding(stream)?;
+        let (expr, block, otherwise) = <(
             ToFirstOpenCurlyExprButSubjectToChangeInOtherWordsPleaseFixMe,
             BlockRules,
             Opt<(Next<Else>, BlockRules)>,
@@ -274,6 +434,7 @@ impl<'a, 's> ParserRule<'a> for CntrlFlowRule {
         Ok(if let Some((_else, otherwise)) = otherwise {
             Expr::If {
                 span,
+                binding,
                 condition: Box::new(expr),
                 block,
                 otherwise: Some(otherwise),
@@ -281,6 +442,7 @@ impl<'a, 's> ParserRule<'a> for CntrlFlowRule {
         } else {
             Expr::If {
                 span,
+                binding,
                 condition: Box::new(expr),
                 block,
                 otherwise: None,
@@ -289,6 +451,17 @@ impl<'a, 's> ParserRule<'a> for CntrlFlowRule {
     }
 }
 
+/// Parses the `let <ident> =` of an `if let` or `while let`.
+fn let_binding(stream: &mut TokenStream) -> RResult<Option<TokenId>> {
+    if !stream.match_peek::<Let>() {
+        return Ok(None);
+    }
+
+    let (_let, ident, _equals) =
+        <(Next<Let>, Next<Ident>, Next<Equals>)>::parse(stream).map_err(PErr::fail)?;
+    Ok(Some(ident))
+}
+
 pub struct ToFirstOpenCurlyExprButSubjectToChangeInOtherWordsPleaseFixMe;
 
 impl<'a, 's> ParserRule<'a> for ToFirstOpenCurlyExprButSubjectToChangeInOtherWordsPleaseFixMe {
@@ -312,21 +485,59 @@ impl<'a, 's> ParserRule<'a> for ToFirstOpenCurlyExprButSubjectToChangeInOtherWor
     }
 }
 
+/// Parses the `'label:` in front of a loop that begins with `T`.
+fn loop_label<T: MatchTokenKind>(stream: &mut TokenStream) -> RResult<Option<TokenId>> {
+    let (label, offset) = if stream.match_peek::<Label>()
+        && stream
+            .peekn(1)
+            .is_some_and(|t| stream.kind(t) == TokenKind::Colon)
+    {
+        (true, 2)
+    } else {
+        (false, 0)
+    };
+
+    if !stream
+        .peekn(offset)
+        .is_some_and(|t| T::matches(Some(stream.kind(t))))
+    {
+        return Err(PErr::Recover(stream.error(T::expect())));
+    }
+
+    if label {
+        let label = stream.expect();
+        stream.eat();
+        Ok(Some(label))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Span of a loop starting at its label, if any.
+fn loop_span(stream: &TokenStream, label: Option<TokenId>, span: Span) -> Span {
+    match label {
+        Some(label) => Span::from_spans(stream.span(label), span),
+        None => span,
+    }
+}
+
 pub struct LoopRule;
 
 impl<'a, 's> ParserRule<'a> for LoopRule {
     type Output = Expr;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
-        if !stream.match_peek::<Loop>() {
-            return Err(PErr::Recover(stream.error("expected `loop`")));
-        }
-
+        let label = loop_label::<Loop>(stream)?;
         let spanned = Spanned::<(Next<Loop>, BlockRules)>::parse(stream).map_err(PErr::fail)?;
-        let span = spanned.span();
+        let span = loop_span(stream, label, spanned.span());
         let (luup, block) = spanned.into_inner();
 
-        Ok(Expr::Loop { span, luup, block })
+        Ok(Expr::Loop {
+            span,
+            label,
+            luup,
+            block,
+        })
     }
 }
 
@@ -336,22 +547,25 @@ impl<'a, 's> ParserRule<'a> for WhileRule {
     type Output = Expr;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
-        if !stream.match_peek::<kind::While>() {
-            return Err(PErr::Recover(stream.error("expected `while`")));
-        }
-
-        let spanned = Spanned::<(
-            Next<kind::While>,
+        let label = loop_label::<kind::While>(stream)?;
+        let wile = stream.expect();
+        let binding = let_binding(stream)?;
+        let (condition, block) = <(
             ToFirstOpenCurlyExprButSubjectToChangeInOtherWordsPleaseFixMe,
             BlockRules,
         )>::parse(stream)
         .map_err(PErr::fail)?;
-        let span = spanned.span();
-        let (wile, condition, block) = spanned.into_inner();
+        let span = loop_span(
+            stream,
+            label,
+            Span::from_spans(stream.span(wile), block.span),
+        );
 
         Ok(Expr::While {
             span,
+            label,
             wile,
+            binding,
             condition: Box::new(condition),
             block,
         })
@@ -364,10 +578,7 @@ impl<'a, 's> ParserRule<'a> for ForRule {
     type Output = Expr;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
-        if !stream.match_peek::<For>() {
-            return Err(PErr::Recover(stream.error("expected `for`")));
-        }
-
+        let label = loop_label::<For>(stream)?;
         let spanned = Spanned::<(
             Next<For>,
             Next<Ident>,
@@ -376,10 +587,11 @@ impl<'a, 's> ParserRule<'a> for ForRule {
             BlockRules,
         )>::parse(stream)
         .map_err(PErr::fail)?;
-        let span = spanned.span();
+        let span = loop_span(stream, label, spanned.span());
         let (_four, iter, _inn, iterable, block) = spanned.into_inner();
         Ok(Expr::For {
             span,
+            label,
             iter,
             iterable: Box::new(iterable),
             block,
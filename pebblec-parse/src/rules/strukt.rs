@@ -1,10 +1,12 @@
+use super::attributes::{AttrTarget, Attribute, AttributesRule, Meta};
 use super::expr::{Expr, ExprRule};
 use super::types::{PType, TypeRule};
 use super::{Next, PErr, ParserRule, RResult};
 use crate::combinator::opt::Opt;
 use crate::combinator::spanned::Spanned;
+use crate::diagnostic::Diag;
+use crate::lex::buffer::*;
 use crate::lex::kind::*;
-use crate::lex::{buffer::*, kind};
 use crate::matc::Curly;
 use crate::stream::TokenStream;
 
@@ -13,11 +15,75 @@ pub struct Struct {
     pub span: Span,
     pub name: TokenId,
     pub fields: Vec<Field>,
+    pub repr: Repr,
+    /// Declared with `union`, every field is stored at offset 0.
+    pub union: bool,
+}
+
+impl Struct {
+    pub fn parse_attr<'a>(
+        &mut self,
+        stream: &TokenStream<'a>,
+        attr: &Attribute,
+    ) -> Result<(), Diag> {
+        attr.validate(stream, AttrTarget::Struct)?;
+        match attr.name(stream).as_str() {
+            "repr" => {
+                for item in attr.list_args(stream)? {
+                    match item {
+                        Meta::Path(path) if path.segments.len() == 1 => {
+                            match stream.as_str(path.segments[0]) {
+                                "C" => {}
+                                "packed" => self.repr.packed = true,
+                                _ => return Err(unknown_repr(stream, item)),
+                            }
+                        }
+                        _ => return Err(unknown_repr(stream, item)),
+                    }
+                }
+            }
+            "align" => {
+                let (lit, align) = attr.int_arg(stream)?;
+                if !align.is_power_of_two() {
+                    return Err(
+                        stream.report_error("alignment must be a power of two", stream.span(lit))
+                    );
+                }
+                self.repr.align = Some(align as usize);
+            }
+            _ => unreachable!(),
+        }
+
+        if self.repr.packed && self.repr.align.is_some() {
+            return Err(stream.report_error("packed structs cannot be over-aligned", attr.span));
+        }
+        Ok(())
+    }
+}
+
+fn unknown_repr<'a>(stream: &TokenStream<'a>, item: &Meta) -> Diag {
+    stream.report_error(
+        "unknown representation, expected `C` or `packed`",
+        item.span(stream.token_buffer()),
+    )
+}
+
+/// Layout of a [`Struct`], set with `#[repr(C)]`, `#[repr(packed)]` and `#[align(<int>)]`.
+///
+/// Fields are always laid out in declaration order with C padding rules, so `#[repr(C)]` only
+/// states that intent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Repr {
+    /// Fields are byte aligned, without any padding.
+    pub packed: bool,
+    /// Minimum alignment of the struct.
+    pub align: Option<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Field {
     pub span: Span,
+    pub attributes: Vec<Attribute>,
     pub name: TokenId,
     pub ty: PType,
 }
@@ -29,13 +95,21 @@ impl<'a, 's> ParserRule<'a> for StructRule {
     type Output = Struct;
 
     fn parse(stream: &mut TokenStream<'a>) -> RResult<Self::Output> {
-        let (name, _, _) =
-            <(Next<Ident>, Next<Colon>, Next<kind::Struct>) as ParserRule>::parse(stream)?;
+        let (name, _) = <(Next<Ident>, Next<Colon>) as ParserRule>::parse(stream)?;
+        let union = match stream.peek_kind() {
+            Some(TokenKind::Struct) => false,
+            Some(TokenKind::Union) => true,
+            _ => return Err(stream.recover("expected `struct` or `union`")),
+        };
+        stream.eat();
+
         let (block_span, fields) = StructBlockRule::parse(stream).map_err(PErr::fail)?;
         Ok(Struct {
             name,
             fields,
             span: Span::from_spans(stream.span(name), block_span),
+            repr: Repr::default(),
+            union,
         })
     }
 }
@@ -63,8 +137,8 @@ impl<'a, 's> ParserRule<'a> for StructBlockRule {
     }
 }
 
-/// `<ident>: <ty>[,]`
-///               ^ ^ optional on last field
+/// `[#[<attr>]]* <ident>: <ty>[,]`
+///                            ^ ^ optional on last field
 #[derive(Debug, Default)]
 pub struct StructFieldDecl;
 
@@ -75,6 +149,7 @@ impl<'a, 's> ParserRule<'a> for StructFieldDecl {
         let mut fields = Vec::new();
 
         while !stream.match_peek::<CloseCurly>() {
+            let attributes = AttributesRule::parse(stream)?;
             let field =
                 Spanned::<(Next<Ident>, Next<Colon>, TypeRule, Opt<Next<Comma>>)>::parse(stream)?;
             let span = field.span();
@@ -84,7 +159,12 @@ impl<'a, 's> ParserRule<'a> for StructFieldDecl {
                 return Err(PErr::Fail(stream.error("expected `,` after field")));
             }
 
-            fields.push(Field { span, name, ty });
+            fields.push(Field {
+                span,
+                attributes,
+                name,
+                ty,
+            });
         }
 
         Ok(fields)
@@ -23,6 +23,28 @@ pub enum PType {
         span: Span,
         inner: Box<PType>,
     },
+    /// `(i32, f32)`, `()` is the unit type.
+    Tuple {
+        span: Span,
+        elems: Vec<PType>,
+    },
+    /// `fn(i32, f32) -> bool`, the return type is unit when omitted.
+    Fn {
+        span: Span,
+        params: Vec<PType>,
+        ret: Option<Box<PType>>,
+    },
+    /// `|i32, f32| -> bool`, the return type is unit when omitted.
+    Closure {
+        span: Span,
+        params: Vec<PType>,
+        ret: Option<Box<PType>>,
+    },
+    /// `?&Window`, a reference or function pointer that may be `null`.
+    Opt {
+        span: Span,
+        inner: Box<PType>,
+    },
 }
 
 impl PType {
@@ -32,13 +54,22 @@ impl PType {
             Self::Ref { span, .. } => *span,
             Self::Array { span, .. } => *span,
             Self::Slice { span, .. } => *span,
+            Self::Tuple { span, .. } => *span,
+            Self::Fn { span, .. } => *span,
+            Self::Closure { span, .. } => *span,
+            Self::Opt { span, .. } => *span,
         }
     }
 
     pub fn peel_refs(&self) -> &PType {
         match self {
-            Self::Ref { inner, .. } => inner.peel_refs(),
-            Self::Simple(_, _) | Self::Slice { .. } | Self::Array { .. } => self,
+            Self::Ref { inner, .. } | Self::Opt { inner, .. } => inner.peel_refs(),
+            Self::Simple(_, _)
+            | Self::Slice { .. }
+            | Self::Array { .. }
+            | Self::Tuple { .. }
+            | Self::Fn { .. }
+            | Self::Closure { .. } => self,
         }
     }
 }
@@ -50,7 +81,15 @@ impl<'a, 's> ParserRule<'a> for TypeRule {
     type Output = PType;
 
     fn parse(stream: &mut TokenStream<'a>) -> super::RResult<Self::Output> {
-        Alt::<(SimpleType, RefType, ArrayType)>::parse(stream).map_err(|diag| {
+        Alt::<(
+            SimpleType,
+            RefType,
+            ArrayType,
+            TupleType,
+            FnType,
+            Alt<(ClosureType, OptType)>,
+        )>::parse(stream)
+        .map_err(|diag| {
             if diag.recoverable() {
                 stream.fail("expected type")
             } else {
@@ -158,3 +197,144 @@ impl<'a, 's> ParserRule<'a> for ArrayType {
         }
     }
 }
+
+/// `([<type>,]*)`
+///
+/// A single type without a trailing comma, `(<type>)`, is the type itself.
+#[derive(Debug, Default)]
+pub struct TupleType;
+
+impl<'a, 's> ParserRule<'a> for TupleType {
+    type Output = PType;
+
+    fn parse(stream: &mut TokenStream<'a>) -> super::RResult<Self::Output> {
+        if !stream.match_peek::<OpenParen>() {
+            return Err(stream.recover("expected `(`"));
+        }
+
+        let open = stream.expect();
+        let mut elems = Vec::new();
+        let mut trailing_comma = false;
+        while !stream.match_peek::<CloseParen>() {
+            elems.push(TypeRule::parse(stream).map_err(PErr::fail)?);
+            trailing_comma = stream.match_peek::<Comma>();
+            if trailing_comma {
+                stream.expect();
+            } else if !stream.match_peek::<CloseParen>() {
+                return Err(stream.fail("expected `,` or `)`"));
+            }
+        }
+        let close = stream.expect();
+
+        if elems.len() == 1 && !trailing_comma {
+            return Ok(elems.pop().unwrap());
+        }
+
+        Ok(PType::Tuple {
+            span: Span::from_spans(stream.span(open), stream.span(close)),
+            elems,
+        })
+    }
+}
+
+/// `fn([<type>,]*) [-> <type>]`
+#[derive(Debug, Default)]
+pub struct FnType;
+
+impl<'a, 's> ParserRule<'a> for FnType {
+    type Output = PType;
+
+    fn parse(stream: &mut TokenStream<'a>) -> super::RResult<Self::Output> {
+        if !stream.match_peek::<Fn>() {
+            return Err(stream.recover("expected `fn`"));
+        }
+
+        let fn_ = stream.expect();
+        if !stream.match_peek::<OpenParen>() {
+            return Err(stream.fail("expected `(`"));
+        }
+
+        stream.expect();
+        let mut params = Vec::new();
+        while !stream.match_peek::<CloseParen>() {
+            params.push(TypeRule::parse(stream).map_err(PErr::fail)?);
+            if stream.match_peek::<Comma>() {
+                stream.expect();
+            } else if !stream.match_peek::<CloseParen>() {
+                return Err(stream.fail("expected `,` or `)`"));
+            }
+        }
+        let close = stream.expect();
+        let mut span = Span::from_spans(stream.span(fn_), stream.span(close));
+
+        let ret = if stream.match_peek::<Hyphen>() {
+            let (_, _, ret) =
+                <(Next<Hyphen>, Next<CloseAngle>, TypeRule)>::parse(stream).map_err(PErr::fail)?;
+            span = Span::from_spans(span, ret.span());
+            Some(Box::new(ret))
+        } else {
+            None
+        };
+
+        Ok(PType::Fn { span, params, ret })
+    }
+}
+
+/// `|[<type>,]*| [-> <type>]`
+#[derive(Debug, Default)]
+pub struct ClosureType;
+
+impl<'a> ParserRule<'a> for ClosureType {
+    type Output = PType;
+
+    fn parse(stream: &mut TokenStream<'a>) -> super::RResult<Self::Output> {
+        if !stream.match_peek::<Pipe>() {
+            return Err(stream.recover("expected `|`"));
+        }
+
+        let open = stream.expect();
+        let mut params = Vec::new();
+        while !stream.match_peek::<Pipe>() {
+            params.push(TypeRule::parse(stream).map_err(PErr::fail)?);
+            if stream.match_peek::<Comma>() {
+                stream.expect();
+            } else if !stream.match_peek::<Pipe>() {
+                return Err(stream.fail("expected `,` or `|`"));
+            }
+        }
+        let close = stream.expect();
+        let mut span = Span::from_spans(stream.span(open), stream.span(close));
+
+        let ret = if stream.match_peek::<Hyphen>() {
+            let (_, _, ret) =
+                <(Next<Hyphen>, Next<CloseAngle>, TypeRule)>::parse(stream).map_err(PErr::fail)?;
+            span = Span::from_spans(span, ret.span());
+            Some(Box::new(ret))
+        } else {
+            None
+        };
+
+        Ok(PType::Closure { span, params, ret })
+    }
+}
+
+/// `?<type>`
+#[derive(Debug, Default)]
+pub struct OptType;
+
+impl<'a> ParserRule<'a> for OptType {
+    type Output = PType;
+
+    fn parse(stream: &mut TokenStream<'a>) -> super::RResult<Self::Output> {
+        if !stream.match_peek::<Question>() {
+            return Err(stream.recover("expected `?`"));
+        }
+
+        let question = stream.expect();
+        let inner = TypeRule::parse(stream).map_err(PErr::fail)?;
+        Ok(PType::Opt {
+            span: Span::from_spans(stream.span(question), inner.span()),
+            inner: Box::new(inner),
+        })
+    }
+}
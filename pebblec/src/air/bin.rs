@@ -1,6 +1,6 @@
 use super::OffsetVar;
 use super::ctx::AirCtx;
-use crate::air::{Air, Bits, ConstData, Reg, assign_expr, extract_var_from_expr};
+use crate::air::{Air, Bits, ConstData, Reg, assign_expr, extract_var_from_expr, field_offset};
 use crate::ir::lit::LitKind;
 use crate::ir::ty::{Sign, Ty, TyKind, Width};
 use crate::ir::*;
@@ -97,6 +97,9 @@ pub fn assign_bin_op(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, bin: &BinOp) {
             }
 
             let ty = match (bin.lhs.infer(ctx), bin.rhs.infer(ctx)) {
+                // `null` is compared as the optional on the other side
+                (InferTy::Ty(Ty::NULL), InferTy::Ty(ty))
+                | (InferTy::Ty(ty), InferTy::Ty(Ty::NULL)) => ty,
                 (InferTy::Ty(lhs), InferTy::Ty(rhs)) => {
                     assert_eq!(lhs, rhs);
                     lhs
@@ -120,10 +123,7 @@ pub fn assign_bin_op(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, bin: &BinOp) {
                 TyKind::Float(float) => (float.width(), Sign::I),
                 TyKind::Bool => (Width::BOOL, Sign::U),
                 TyKind::Ref(TyKind::Str) => unreachable!(),
-                TyKind::Ref(_) => {
-                    // TODO: NULL
-                    (Width::PTR, Sign::U)
-                }
+                TyKind::Ref(_) | TyKind::Opt(_) => (Width::PTR, Sign::U),
                 ty => unreachable!("{ty:#?}"),
             };
 
@@ -166,39 +166,17 @@ pub fn assign_bin_op(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, bin: &BinOp) {
 }
 
 pub fn aquire_accessor_field(ctx: &mut AirCtx, access: &Access) -> (OffsetVar, Ty) {
-    let ty = access.lhs.infer_abs(ctx).unwrap();
+    let mut ty = access.lhs.infer_abs(ctx).unwrap();
     let var = extract_var_from_expr(ctx, ty, access.lhs);
 
-    let id = ty.expect_struct();
-    let mut strukt = ctx.tys.strukt(id);
-
     let mut offset = 0;
-    for (i, acc) in access.accessors.iter().rev().enumerate() {
-        let ty = strukt.field_ty(acc.sym);
-        if i == access.accessors.len() - 1 {
-            let field_offset = strukt.field_offset(&ctx.tys, acc.sym);
-            return (var.add((offset + field_offset) as usize), ty);
-        }
-
-        match ty.0 {
-            TyKind::Struct(id) => {
-                let field_offset = strukt.field_offset(&ctx.tys, acc.sym);
-                offset += field_offset;
-                strukt = ctx.tys.strukt(*id);
-            }
-            TyKind::Array(_, _)
-            | TyKind::Slice(_)
-            | TyKind::Ref(_)
-            | TyKind::Str
-            | TyKind::Int(_)
-            | TyKind::Float(_)
-            | TyKind::Bool
-            | TyKind::Unit => {
-                panic!("cannot access field on {ty:?}")
-            }
-        }
+    for acc in access.accessors.iter().rev() {
+        let (field_offset, field_ty) = field_offset(ctx, ty, acc);
+        offset += field_offset;
+        ty = field_ty;
     }
-    unreachable!()
+
+    (var.add(offset as usize), ty)
 }
 
 fn assign_logical_and(ctx: &mut AirCtx, lhs: &Expr, rhs: &Expr, dst: OffsetVar) {
@@ -9,6 +9,7 @@ use crate::ir::ty::{Ty, TyKind, TypeKey, Width};
 use crate::ir::{self, *};
 use indexmap::IndexMap;
 use pebblec_arena::BlobArena;
+use pebblec_parse::lex::buffer::Span;
 use pebblec_parse::sym::{Ident, Symbol};
 use std::ops::Deref;
 
@@ -20,6 +21,10 @@ pub struct AirCtx<'a, 'ctx> {
     pub air_sigs: IndexMap<Symbol, &'a AirSig<'a>>,
     pub impl_air_sigs: IndexMap<(Ty, Symbol), &'a AirSig<'a>>,
     pub storage: BlobArena,
+    /// Functions generated for closures while lowering the bodies of other functions.
+    pub lowered_closures: Vec<AirFunc<'a>>,
+    /// Number of symbol tables holding globals, visible from every function.
+    pub global_tables: usize,
 
     ctx: &'ctx Ctx<'ctx>,
     var_index: usize,
@@ -27,6 +32,7 @@ pub struct AirCtx<'a, 'ctx> {
     ty_map: IndexMap<Var, Ty>,
     bss: Bss,
     func: Option<FuncHash>,
+    closure_air_sigs: IndexMap<Span, &'a AirSig<'a>>,
     instr_builder: InstrBuilder<'a, 'ctx>,
 }
 
@@ -63,7 +69,9 @@ fn sig_to_air_sig<'a>(storage: &BlobArena, sig: &Sig) -> &'a AirSig<'a> {
             sig::Linkage::External { link } => AirLinkage::External {
                 link: storage.alloc_str(link),
             },
+            sig::Linkage::Host => AirLinkage::Host,
         },
+        variadic: sig.variadic,
     })
 }
 
@@ -93,7 +101,9 @@ fn method_sig_to_air_sig<'a>(storage: &BlobArena, ty: Ty, sig: &Sig) -> &'a AirS
             sig::Linkage::External { link } => AirLinkage::External {
                 link: storage.alloc_str(link),
             },
+            sig::Linkage::Host => AirLinkage::Host,
         },
+        variadic: sig.variadic,
     })
 }
 
@@ -133,6 +143,9 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
             ty_map: IndexMap::default(),
             bss: Bss::default(),
             func: None,
+            lowered_closures: Vec::new(),
+            global_tables: 0,
+            closure_air_sigs: IndexMap::default(),
             instr_builder: InstrBuilder::Const(Vec::new()),
             storage,
         }
@@ -165,12 +178,69 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
 
     #[track_caller]
     pub fn finish_func(&mut self) -> AirFunc<'a> {
+        let air_sig = self.air_sig(self.active_sig());
         match &mut self.instr_builder {
-            InstrBuilder::Func(b) => b.build(&self.air_sigs, &self.impl_air_sigs),
+            InstrBuilder::Func(b) => {
+                let params = b
+                    .func
+                    .sig
+                    .params
+                    .iter()
+                    .map(|param| match param {
+                        Param::Named { ident, .. } | Param::Slf(ident) => self.func_args[ident],
+                    })
+                    .collect();
+                b.build(air_sig, params)
+            }
             InstrBuilder::Const(_) => panic!("called `finish_func` with const builder"),
         }
     }
 
+    /// Lowers `func`, the body of a closure, with `f` while the active function is being built.
+    ///
+    /// Only the globals remain in scope, the closure reaches its captures through its environment.
+    pub fn in_closure_func(
+        &mut self,
+        func: &'ctx ir::Func<'ctx>,
+        f: impl FnOnce(&mut Self),
+    ) -> AirFunc<'a> {
+        let prev_func = self.func;
+        let prev_builder =
+            std::mem::replace(&mut self.instr_builder, InstrBuilder::Const(Vec::new()));
+        let tables = self.tables.split_off(self.global_tables);
+
+        self.in_var_scope(|ctx| {
+            ctx.start_func(func);
+            f(ctx);
+        });
+        let air_func = self.finish_func();
+
+        self.tables.extend(tables);
+        self.instr_builder = prev_builder;
+        self.func = prev_func;
+        air_func
+    }
+
+    /// The closure function generated for `closure` in [`ir::Ctx::closure_funcs`].
+    pub fn closure_func(&self, closure: &ir::Closure) -> &'ctx ir::Func<'ctx> {
+        self.ctx
+            .closure_funcs
+            .get(&closure.span)
+            .expect("closure was not typed")
+    }
+
+    /// Returns the signature of the closure function `func`, and whether it was already lowered.
+    pub fn closure_air_sig(&mut self, func: &ir::Func) -> (&'a AirSig<'a>, bool) {
+        match self.closure_air_sigs.get(&func.sig.span) {
+            Some(air_sig) => (*air_sig, true),
+            None => {
+                let air_sig = sig_to_air_sig(&self.storage, func.sig);
+                self.closure_air_sigs.insert(func.sig.span, air_sig);
+                (air_sig, false)
+            }
+        }
+    }
+
     #[track_caller]
     pub fn finish_const(&mut self) -> Vec<Air<'a>> {
         match &mut self.instr_builder {
@@ -206,31 +276,48 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
         builder.active
     }
 
-    pub fn loop_ctx(&self) -> Option<LoopCtx> {
+    /// Finds the enclosing loop at `span`, which `break` and `continue` target.
+    #[track_caller]
+    pub fn loop_ctx(&self, span: Span) -> LoopCtx {
         let builder = self.expect_func_builder();
-        builder.loop_ctx
+        *builder
+            .loops
+            .iter()
+            .rev()
+            .find(|l| l.span == span)
+            .expect("loop not entered")
     }
 
     pub fn break_block(&self) -> Option<BlockId> {
         let builder = self.expect_func_builder();
-        builder.loop_ctx.map(|l| l.breac)
+        builder.loops.last().map(|l| l.breac)
     }
 
     pub fn loop_start(&self) -> Option<BlockId> {
         let builder = self.expect_func_builder();
-        builder.loop_ctx.map(|l| l.start)
+        builder.loops.last().map(|l| l.start)
     }
 
+    /// Enters the loop at `span`, a `break` with a value assigns it to `dst`.
     #[track_caller]
-    pub fn in_loop(&mut self, breac: BlockId, f: impl FnOnce(&mut Self, BlockId)) -> BlockId {
+    pub fn in_loop(
+        &mut self,
+        span: Span,
+        breac: BlockId,
+        dst: Option<(OffsetVar, Ty)>,
+        f: impl FnOnce(&mut Self, BlockId),
+    ) -> BlockId {
         self.in_scope(|ctx, inner| {
-            let prev_loop_ctx = ctx.loop_ctx();
-            ctx.set_loop_ctx(Some(LoopCtx {
+            let defers = ctx.expect_func_builder().defers.len();
+            ctx.expect_func_builder_mut().loops.push(LoopCtx {
+                span,
                 breac,
                 start: inner,
-            }));
+                defers,
+                dst,
+            });
             f(ctx, inner);
-            ctx.set_loop_ctx(prev_loop_ctx);
+            ctx.expect_func_builder_mut().loops.pop();
         })
     }
 
@@ -261,17 +348,43 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
         result
     }
 
+    pub fn push_defers(&mut self) {
+        self.expect_func_builder_mut().defers.push(Vec::new());
+    }
+
+    #[track_caller]
+    pub fn pop_defers(&mut self) -> Vec<&'ctx Stmt<'ctx>> {
+        self.expect_func_builder_mut()
+            .defers
+            .pop()
+            .expect("no block to defer in")
+    }
+
+    /// Defers the statement of `defer` to the exit of the innermost block.
+    #[track_caller]
+    pub fn defer(&mut self, defer: &Defer) {
+        let stmt = self.ctx.defers[&defer.span];
+        self.expect_func_builder_mut()
+            .defers
+            .last_mut()
+            .expect("no block to defer in")
+            .push(stmt);
+    }
+
+    pub fn split_defers(&mut self, depth: usize) -> Vec<Vec<&'ctx Stmt<'ctx>>> {
+        self.expect_func_builder_mut().defers.split_off(depth)
+    }
+
+    pub fn restore_defers(&mut self, defers: Vec<Vec<&'ctx Stmt<'ctx>>>) {
+        self.expect_func_builder_mut().defers.extend(defers);
+    }
+
     pub fn set_active_block(&mut self, block: BlockId) {
         let builder = self.expect_func_builder_mut();
         assert!(block.0 < builder.instrs.len());
         builder.active = block;
     }
 
-    pub fn set_loop_ctx(&mut self, loop_ctx: Option<LoopCtx>) {
-        let builder = self.expect_func_builder_mut();
-        builder.loop_ctx = loop_ctx;
-    }
-
     #[track_caller]
     pub fn new_var_registered(&mut self, ident: &Ident, ty: Ty) -> Var {
         let var = self.anon_var(ty);
@@ -308,7 +421,7 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
     #[track_caller]
     pub fn anon_var(&mut self, ty: Ty) -> Var {
         let var = self.anon_var_no_salloc(ty);
-        self.ins(Air::SAlloc(var, ty.size(&self.tys)));
+        self.ins(Air::SAlloc(var, self.tys.ty_layout(ty)));
         var
     }
 
@@ -340,7 +453,13 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
                             None
                         }
                     }
-                    Param::Slf(ident) => self.func_args.get(ident).copied(),
+                    Param::Slf(slf) => {
+                        if slf.sym == ident {
+                            self.func_args.get(slf).copied()
+                        } else {
+                            None
+                        }
+                    }
                 })
             })
             .expect("invalid var ident")
@@ -392,8 +511,13 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LoopCtx {
+    pub span: Span,
     pub start: BlockId,
     pub breac: BlockId,
+    /// Number of blocks with deferred statements enclosing the loop.
+    pub defers: usize,
+    /// Destination of the values that break out of a `loop`.
+    pub dst: Option<(OffsetVar, Ty)>,
 }
 
 pub const RET_REG: Reg = Reg::A;
@@ -423,6 +547,22 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
         self.ins(Air::Call(air_sig, args));
     }
 
+    pub fn fn_addr(&mut self, reg: Reg, sig: &Sig) {
+        let air_sig = self.air_sig(sig);
+        self.ins(Air::FnAddr(reg, air_sig));
+    }
+
+    #[track_caller]
+    fn air_sig(&self, sig: &Sig) -> &'a AirSig<'a> {
+        match sig.method_self {
+            Some(ty) => self.impl_air_sigs[&(ty, sig.ident)],
+            None => match self.air_sigs.get(&sig.ident) {
+                Some(air_sig) => air_sig,
+                None => self.closure_air_sigs[&sig.span],
+            },
+        }
+    }
+
     pub fn ret_var(&mut self, var: OffsetVar, ty: Ty) {
         match ty.0 {
             TyKind::Bool => self.ret_ivar(var, Width::BOOL),
@@ -430,9 +570,11 @@ impl<'a, 'ctx> AirCtx<'a, 'ctx> {
             TyKind::Float(ty) => self.ret_ivar(var, ty.width()),
             TyKind::Array(_, _)
             | TyKind::Slice(_)
-            | TyKind::Ref(TyKind::Str)
-            | TyKind::Struct(_) => self.ret_ptr(var),
-            TyKind::Ref(_) => self.ret_ivar(var, Width::PTR),
+            | TyKind::Ref(TyKind::Str | TyKind::Slice(_))
+            | TyKind::Struct(_)
+            | TyKind::Tuple(_)
+            | TyKind::Closure(_, _) => self.ret_ptr(var),
+            TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_) => self.ret_ivar(var, Width::PTR),
             ty @ TyKind::Unit | ty @ TyKind::Str => panic!("cannot return {:?}", ty),
         }
     }
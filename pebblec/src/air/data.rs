@@ -9,12 +9,15 @@ pub struct Bss {
 
 impl Bss {
     pub fn str_lit(&mut self, str: &str) -> (BssEntry, usize) {
-        let raw = String::from_utf8(str.as_bytes().to_vec()).unwrap();
-        let fmt = raw.replace("\\n", "\n").replace("\\0", "\0");
-        let data = self.data.alloc_str_ptr(&fmt);
-        let len = fmt.len();
+        let data = self.data.alloc_str_ptr(str);
+        let len = str.len();
         (BssEntry::str_lit(data), len)
     }
+
+    /// Whether `bytes` bytes at `addr` belong to the bss.
+    pub fn contains(&self, addr: usize, bytes: usize) -> bool {
+        self.data.contains(addr, bytes)
+    }
 }
 
 #[derive(Debug, Clone, Hash)]
@@ -2,7 +2,7 @@ use self::data::Bss;
 use crate::ir::ctx::CtxFmt;
 use crate::ir::lit::{Lit, LitKind};
 use crate::ir::mem::Layout;
-use crate::ir::sig::{Param, Sig};
+use crate::ir::sig::{Param, Sig, is_print};
 use crate::ir::strukt::StructDef;
 use crate::ir::ty::store::TyStore;
 use crate::ir::ty::{FloatTy, IntTy, Sign, Ty, TyKind, Width};
@@ -13,7 +13,7 @@ use data::BssEntry;
 use indexmap::IndexMap;
 use pebblec_arena::BlobArena;
 use pebblec_parse::rules::prelude::Attr;
-use pebblec_parse::sym::Symbol;
+use pebblec_parse::sym::Ident;
 use pebblec_parse::{AssignKind, UOpKind};
 use std::collections::HashMap;
 use std::ops::Range;
@@ -31,13 +31,23 @@ pub enum Air<'a> {
     Ret,
 
     Call(&'a AirSig<'a>, Args),
+    /// Call the function whose address, see [`Air::FnAddr`], is stored in `callee`.
+    ///
+    /// `args` are not the callee's parameters, so the callee's parameters are pointed at them.
+    CallIndirect {
+        callee: OffsetVar,
+        args: Args,
+    },
+    /// Load the address of a function into `Reg`.
+    FnAddr(Reg, &'a AirSig<'a>),
 
     /// Swap the A and B registers.
     SwapReg,
     MovIVar(Reg, OffsetVar, Width),
     MovIConst(Reg, ConstData),
 
-    SAlloc(Var, usize),
+    /// Allocate a `Var` on the stack with the given layout.
+    SAlloc(Var, Layout),
 
     /// Load address of `Var` into `Reg`.
     Addr(Reg, OffsetVar),
@@ -133,12 +143,52 @@ pub enum Air<'a> {
 
     FSqrt(FloatTy),
 
+    /// Allocate [`Reg::A`] bytes aligned to [`Reg::B`] on the heap, storing the address in
+    /// [`Reg::A`].
+    Alloc,
+    /// Resize the heap allocation at [`Reg::A`] to [`Reg::B`] bytes, storing the new address in
+    /// [`Reg::A`].
+    Realloc,
+    /// Free the heap allocation at [`Reg::A`].
+    Free,
+
+    /// Store the address of the command-line arguments, a `&[&str]`, in [`Reg::A`].
+    Args,
+    /// Look up the environment variable named by the `&str` at [`Reg::A`], storing the address of
+    /// its `&str` value in [`Reg::A`], or 0 if it is not set.
+    EnvVar,
+    /// Store the address of the current directory, a `&str`, in [`Reg::A`].
+    CurrentDir,
+    /// Perform a file or stdin operation of `core::io` with `args`, the params of the enclosing
+    /// intrinsic, storing its status in [`Reg::A`].
+    Io(IoOp, Args),
+    /// Store the nanoseconds measured by the monotonic clock in [`Reg::A`].
+    Now,
+    /// Store the nanoseconds since the Unix epoch in [`Reg::A`].
+    UnixTime,
+    /// Block for the number of nanoseconds in [`Reg::A`].
+    Sleep,
+    /// Store a seed for `core::rand` in [`Reg::A`].
+    RandSeed,
+
     /// Exit with code stored in [`Reg::A`].
     Exit,
     /// The address of `fmt` should be loaded into [`Reg::A`].
     PrintCStr,
 }
 
+/// Operations of [`Air::Io`], see `core/io.peb` for their params.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoOp {
+    Open,
+    Close,
+    Read,
+    Write,
+    ReadLine,
+    ReadAll,
+    Metadata,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Prim {
     UInt,
@@ -306,12 +356,14 @@ pub enum ConstData {
 #[derive(Debug, PartialEq)]
 pub struct AirFunc<'a> {
     pub sig: &'a AirSig<'a>,
+    /// Variables that hold the arguments, in parameter order.
+    pub params: Vec<Var>,
     instrs: Vec<Air<'a>>,
     blocks: IndexMap<BlockId, Range<usize>>,
 }
 
 impl<'a> AirFunc<'a> {
-    pub fn new(sig: &'a AirSig<'a>, blocks: Vec<Vec<Air<'a>>>) -> Self {
+    pub fn new(sig: &'a AirSig<'a>, params: Vec<Var>, blocks: Vec<Vec<Air<'a>>>) -> Self {
         let mut instrs = Vec::new();
         let mut ranges = IndexMap::with_capacity(blocks.len());
         for (hash, block_instrs) in blocks.into_iter().enumerate() {
@@ -324,6 +376,7 @@ impl<'a> AirFunc<'a> {
         Self {
             blocks: ranges,
             sig,
+            params,
             instrs,
         }
     }
@@ -351,12 +404,14 @@ pub struct AirSig<'a> {
     pub ty: Ty,
     pub params: &'a [Ty],
     pub linkage: AirLinkage<'a>,
+    pub variadic: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AirLinkage<'a> {
     Local,
     External { link: &'a str },
+    Host,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -367,7 +422,10 @@ pub struct AirFuncBuilder<'a, 'ctx> {
     pub func: &'ctx Func<'ctx>,
     instrs: Vec<Vec<Air<'a>>>,
     active: BlockId,
-    loop_ctx: Option<LoopCtx>,
+    /// Loops enclosing the active block, innermost last.
+    loops: Vec<LoopCtx>,
+    /// Statements deferred in each of the enclosing blocks, innermost last.
+    defers: Vec<Vec<&'ctx Stmt<'ctx>>>,
 }
 
 impl<'a, 'ctx> AirFuncBuilder<'a, 'ctx> {
@@ -375,7 +433,8 @@ impl<'a, 'ctx> AirFuncBuilder<'a, 'ctx> {
         Self {
             instrs: vec![Vec::new()],
             active: BlockId(0),
-            loop_ctx: None,
+            loops: Vec::new(),
+            defers: Vec::new(),
             func,
         }
     }
@@ -397,19 +456,9 @@ impl<'a, 'ctx> AirFuncBuilder<'a, 'ctx> {
     }
 
     #[track_caller]
-    pub fn build(
-        &mut self,
-        sigs: &IndexMap<Symbol, &'a AirSig<'a>>,
-        impl_sigs: &IndexMap<(Ty, Symbol), &'a AirSig<'a>>,
-    ) -> AirFunc<'a> {
+    pub fn build(&mut self, air_sig: &'a AirSig<'a>, params: Vec<Var>) -> AirFunc<'a> {
         assert!(!self.instrs.is_empty());
-        let sig = self.func.sig;
-        let air_sig = match sig.method_self {
-            Some(ty) => impl_sigs.get(&(ty, sig.ident)).unwrap(),
-            None => sigs.get(&sig.ident).unwrap(),
-        };
-
-        AirFunc::new(air_sig, std::mem::take(&mut self.instrs))
+        AirFunc::new(air_sig, params, std::mem::take(&mut self.instrs))
     }
 }
 
@@ -483,9 +532,10 @@ impl IntTy {
 impl Expr<'_> {
     pub fn infer(&self, ctx: &mut AirCtx) -> InferTy {
         match self {
-            Self::Lit(lit) => match lit.kind {
-                LitKind::Int(_) => InferTy::Int,
-                LitKind::Float(_) => InferTy::Float,
+            Self::Lit(lit) => match (lit.ty, lit.kind) {
+                (Some(ty), _) => InferTy::Ty(ty),
+                (None, LitKind::Int(_)) => InferTy::Int,
+                (None, LitKind::Float(_)) => InferTy::Float,
             },
             Self::Ident(ident) => {
                 // instead of `var_ty` with the ident, we use this to avoid grabbing the most
@@ -503,6 +553,9 @@ impl Expr<'_> {
             }
             Self::Access(access) => InferTy::Ty(aquire_access_ty(ctx, access)),
             Self::Call(call) => InferTy::Ty(call.sig.ty),
+            Self::IndirectCall(call) => InferTy::Ty(indirect_call_fn_ty(ctx, call).1),
+            Self::Fn(func) => InferTy::Ty(func.ty),
+            Self::Closure(closure) => InferTy::Ty(ctx.closure_tys[&closure.span]),
             Self::Str(_) => InferTy::Ty(Ty::STR_LIT),
             Self::Bin(bin) => {
                 let lhs = bin.lhs.infer(ctx);
@@ -516,6 +569,7 @@ impl Expr<'_> {
                 }
             }
             Self::Bool(_) => InferTy::Ty(Ty::BOOL),
+            Self::Null(_) => InferTy::Ty(Ty::NULL),
             Self::IndexOf(index) => match index.array.infer(ctx) {
                 InferTy::Ty(arr_ty) => match arr_ty.0 {
                     TyKind::Array(_, inner) => InferTy::Ty(Ty(*inner)),
@@ -543,12 +597,28 @@ impl Expr<'_> {
             },
             Self::MethodCall(call) => InferTy::Ty(call.expect_sig(ctx).ty),
             Self::Struct(def) => InferTy::Ty(def.ty),
+            Self::Tuple(tuple) => {
+                let elems = tuple
+                    .exprs
+                    .iter()
+                    .map(|expr| match expr.infer(ctx) {
+                        InferTy::Ty(ty) => ty,
+                        InferTy::Int => Ty::ISIZE,
+                        InferTy::Float => Ty::FSIZE,
+                    })
+                    .collect::<Vec<_>>();
+                InferTy::Ty(ctx.tys.intern_tuple(&elems))
+            }
             Self::Block(block) => block
                 .end
                 .map(|e| e.infer(ctx))
                 .unwrap_or_else(|| InferTy::Ty(Ty::UNIT)),
             Self::If(if_) => if_.block.infer(ctx),
-            Self::Loop(_) | Self::For(_) => InferTy::Ty(Ty::UNIT),
+            Self::Loop(loop_) => match loop_.value_ident() {
+                Some(ident) => InferTy::Ty(ctx.var_ty(&ident)),
+                None => InferTy::Ty(Ty::UNIT),
+            },
+            Self::For(_) => InferTy::Ty(Ty::UNIT),
             Self::Break(_) | Self::Continue(_) => unreachable!(),
             // TODO: there is array and range left, but they are never called? Why not?
             ty => todo!("infer: {ty:?}"),
@@ -564,45 +634,30 @@ impl Expr<'_> {
 }
 
 fn aquire_access_ty(ctx: &mut AirCtx, access: &Access) -> Ty {
-    let ty = access.lhs.infer_abs(ctx).unwrap();
-    let id = match ty.0 {
-        TyKind::Struct(id) => id,
-        TyKind::Array(_, _)
-        | TyKind::Slice(_)
-        | TyKind::Int(_)
-        | TyKind::Unit
-        | TyKind::Bool
-        | TyKind::Ref(_)
-        | TyKind::Str
-        | TyKind::Float(_) => {
-            unreachable!()
-        }
-    };
-    let mut strukt = ctx.tys.strukt(*id);
+    let mut ty = access.lhs.infer_abs(ctx).unwrap();
+    for acc in access.accessors.iter().rev() {
+        ty = field_offset(ctx, ty, acc).1;
+    }
+    ty
+}
 
-    for (i, acc) in access.accessors.iter().rev().enumerate() {
-        let ty = strukt.field_ty(acc.sym);
-        if i == access.accessors.len() - 1 {
-            return ty;
+/// Byte offset and type of the field `acc` of `ty`, which is either a struct or a tuple.
+#[track_caller]
+pub fn field_offset(ctx: &AirCtx, ty: Ty, acc: &Ident) -> (i32, Ty) {
+    match ty.0 {
+        TyKind::Struct(id) => {
+            let strukt = ctx.tys.strukt(*id);
+            (
+                strukt.field_offset(&ctx.tys, acc.sym),
+                strukt.field_ty(acc.sym),
+            )
         }
-
-        match ty.0 {
-            TyKind::Struct(id) => {
-                strukt = ctx.tys.strukt(*id);
-            }
-            TyKind::Array(_, _)
-            | TyKind::Slice(_)
-            | TyKind::Int(_)
-            | TyKind::Unit
-            | TyKind::Bool
-            | TyKind::Ref(_)
-            | TyKind::Str
-            | TyKind::Float(_) => {
-                unreachable!()
-            }
+        TyKind::Tuple(elems) => {
+            let (index, elem) = ty.tuple_elem(acc.sym).expect("invalid tuple index");
+            (ctx.tys.tuple_offset(elems, index), elem)
         }
+        _ => panic!("cannot access field on {ty:?}"),
     }
-    unreachable!()
 }
 
 pub fn lower_const<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, konst: &Const) -> Vec<Air<'a>> {
@@ -616,9 +671,12 @@ pub fn lower_const<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, konst: &Const) -> Vec<A
 pub struct ByteCode<'a> {
     pub bss: Bss,
     pub tys: TyStore,
+    /// Signatures of functions declared in `extern` blocks, including host functions.
     pub extern_sigs: HashMap<&'a str, &'a AirSig<'a>>,
     pub funcs: Vec<AirFunc<'a>>,
     pub consts: Vec<Air<'a>>,
+    /// Vars that hold the value of each const after `consts` are evaluated.
+    pub globals: HashMap<&'a str, (Var, Ty)>,
     _storage: BlobArena,
 }
 
@@ -627,20 +685,34 @@ pub fn lower<'a, 'ctx>(mut ir: Ir<'ctx>) -> ByteCode<'a> {
     let mut air_ctx = AirCtx::new(&ir.ctx, ir.key, tys);
     let consts = ir
         .const_eval_order
-        .into_iter()
-        .flat_map(|id| lower_const(&mut air_ctx, ir.ctx.get_const(id).unwrap()))
+        .iter()
+        .flat_map(|id| lower_const(&mut air_ctx, ir.ctx.get_const(*id).unwrap()))
         .collect::<Vec<_>>();
-    let funcs = ir
+    let globals = ir
+        .const_eval_order
+        .iter()
+        .map(|id| {
+            let konst = ir.ctx.get_const(*id).unwrap();
+            let var = air_ctx.expect_var(konst.name.sym);
+            (
+                air_ctx.storage.alloc_str(konst.name.as_str()),
+                (var, konst.ty),
+            )
+        })
+        .collect();
+    air_ctx.global_tables = air_ctx.tables.len();
+    let mut funcs = ir
         .ctx
         .funcs
         .iter()
         .map(|func| lower_func(&mut air_ctx, func))
         .collect::<Vec<_>>();
+    funcs.append(&mut air_ctx.lowered_closures);
     let extern_sigs = air_ctx
         .air_sigs
         .iter()
         .filter_map(|(ident, sig)| {
-            matches!(sig.linkage, AirLinkage::External { .. })
+            (sig.linkage != AirLinkage::Local)
                 .then_some((air_ctx.storage.alloc_str(ident.as_str()), *sig))
         })
         .collect();
@@ -653,6 +725,7 @@ pub fn lower<'a, 'ctx>(mut ir: Ir<'ctx>) -> ByteCode<'a> {
         extern_sigs,
         funcs,
         consts,
+        globals,
         _storage: storage,
     }
 }
@@ -686,10 +759,33 @@ pub fn lower_intrinsic<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -
         init_params(ctx, func);
         match func.sig.ident.as_str() {
             "exit" => exit(ctx, func),
-            "print" => print(ctx, func),
-            "println" => println(ctx, func),
+            "print" | "eprint" => print(ctx, func),
+            "println" | "eprintln" => println(ctx, func),
             "str_from_raw_parts" => str_from_raw_parts(ctx, func),
             "sqrt_f32" => sqrt_f32(ctx, func),
+            "alloc" => alloc(ctx, func),
+            "realloc" => realloc(ctx, func),
+            "free" => free(ctx, func),
+            "args" => args(ctx, func),
+            "var" => var(ctx, func),
+            "current_dir" => current_dir(ctx, func),
+            "io_open" => io(ctx, func, IoOp::Open),
+            "io_close" => io(ctx, func, IoOp::Close),
+            "io_read" => io(ctx, func, IoOp::Read),
+            "io_write" => io(ctx, func, IoOp::Write),
+            "io_read_line" => io(ctx, func, IoOp::ReadLine),
+            "io_read_all" => io(ctx, func, IoOp::ReadAll),
+            "io_metadata" => io(ctx, func, IoOp::Metadata),
+            "now" => now(ctx, func),
+            "unix_time" => unix_time(ctx, func),
+            "sleep_nanos" => sleep(ctx, func),
+            "rand_seed" => rand_seed(ctx, func),
+            // evaluated during lowering, see `ir::type_query`
+            "size_of" | "align_of" => {
+                ctx.start_func(func);
+                ctx.ins(Air::Ret);
+                ctx.finish_func()
+            }
             i => unimplemented!("intrinsic: {i}"),
         }
     })
@@ -768,6 +864,126 @@ pub fn sqrt_f32<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFu
     ctx.finish_func()
 }
 
+pub fn alloc<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    let Param::Named { ident: bytes, .. } = func.sig.params[0] else {
+        unreachable!()
+    };
+    let Param::Named { ident: align, .. } = func.sig.params[1] else {
+        unreachable!()
+    };
+
+    let bytes = OffsetVar::zero(ctx.expect_var(bytes.sym));
+    let align = OffsetVar::zero(ctx.expect_var(align.sym));
+    ctx.ins_set([
+        Air::MovIVar(Reg::A, bytes, Width::SIZE),
+        Air::MovIVar(Reg::B, align, Width::SIZE),
+        Air::Alloc,
+        Air::Ret,
+    ]);
+    ctx.finish_func()
+}
+
+pub fn realloc<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    let Param::Named { ident: ptr, .. } = func.sig.params[0] else {
+        unreachable!()
+    };
+    let Param::Named { ident: bytes, .. } = func.sig.params[1] else {
+        unreachable!()
+    };
+
+    let ptr = OffsetVar::zero(ctx.expect_var(ptr.sym));
+    let bytes = OffsetVar::zero(ctx.expect_var(bytes.sym));
+    ctx.ins_set([
+        Air::MovIVar(Reg::A, ptr, Width::PTR),
+        Air::MovIVar(Reg::B, bytes, Width::SIZE),
+        Air::Realloc,
+        Air::Ret,
+    ]);
+    ctx.finish_func()
+}
+
+pub fn free<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    let Param::Named { ident: ptr, .. } = func.sig.params[0] else {
+        unreachable!()
+    };
+
+    let ptr = OffsetVar::zero(ctx.expect_var(ptr.sym));
+    ctx.ins_set([Air::MovIVar(Reg::A, ptr, Width::PTR), Air::Free, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn args<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    ctx.ins_set([Air::Args, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn var<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    let Param::Named { ident: name, .. } = func.sig.params[0] else {
+        unreachable!()
+    };
+
+    let name = OffsetVar::zero(ctx.expect_var(name.sym));
+    ctx.ins_set([Air::Addr(Reg::A, name), Air::EnvVar, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn current_dir<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    ctx.ins_set([Air::CurrentDir, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn io<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func, op: IoOp) -> AirFunc<'a> {
+    ctx.start_func(func);
+    let vars = func
+        .sig
+        .params
+        .iter()
+        .map(|param| {
+            let Param::Named { ident, ty, .. } = param else {
+                unreachable!()
+            };
+            (*ty, ctx.expect_var(ident.sym))
+        })
+        .collect();
+    ctx.ins_set([Air::Io(op, Args { vars }), Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn now<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    ctx.ins_set([Air::Now, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn unix_time<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    ctx.ins_set([Air::UnixTime, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn sleep<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    let Param::Named { ident: nanos, .. } = func.sig.params[0] else {
+        unreachable!()
+    };
+
+    let nanos = OffsetVar::zero(ctx.expect_var(nanos.sym));
+    ctx.ins_set([Air::MovIVar(Reg::A, nanos, Width::W64), Air::Sleep, Air::Ret]);
+    ctx.finish_func()
+}
+
+pub fn rand_seed<'a, 'ctx>(ctx: &mut AirCtx<'a, 'ctx>, func: &'ctx Func) -> AirFunc<'a> {
+    ctx.start_func(func);
+    ctx.ins_set([Air::RandSeed, Air::Ret]);
+    ctx.finish_func()
+}
+
 fn init_params(ctx: &mut AirCtx, func: &Func) {
     for param in func.sig.params.iter() {
         match &param {
@@ -784,7 +1000,119 @@ fn init_params(ctx: &mut AirCtx, func: &Func) {
     }
 }
 
+/// Builds the environment of `closure` in the active frame and writes the closure to `dst`.
+///
+/// The function of the closure is lowered the first time it is defined.
+fn define_closure(ctx: &mut AirCtx, dst: OffsetVar, closure: &Closure) {
+    let captures = closure
+        .captures
+        .iter()
+        .map(|ident| {
+            let var = ctx.expect_var(ident.sym);
+            (var, ctx.expect_var_ty(var))
+        })
+        .collect::<Vec<_>>();
+    let slots = captures
+        .iter()
+        .map(|(_, ty)| if closure.moves { *ty } else { Ty::PTR })
+        .collect::<Vec<_>>();
+    let env_ty = ctx.tys.intern_tuple(&slots);
+    let TyKind::Tuple(elems) = env_ty.0 else {
+        unreachable!()
+    };
+
+    let env = ctx.anon_var(env_ty);
+    for (i, (var, ty)) in captures.iter().enumerate() {
+        let slot = OffsetVar::new(env, ctx.tys.tuple_offset(elems, i) as usize);
+        if closure.moves {
+            assign_var_other(ctx, slot, OffsetVar::zero(*var), *ty);
+        } else {
+            ctx.ins_set([
+                Air::Addr(Reg::A, OffsetVar::zero(*var)),
+                Air::PushIReg {
+                    dst: slot,
+                    width: Width::PTR,
+                    src: Reg::A,
+                },
+            ]);
+        }
+    }
+
+    let func = ctx.closure_func(closure);
+    let (air_sig, lowered) = ctx.closure_air_sig(func);
+    if !lowered {
+        let tys = captures.iter().map(|(_, ty)| *ty).collect::<Vec<_>>();
+        let air_func = ctx.in_closure_func(func, |ctx| {
+            lower_closure_body(ctx, closure, func, env_ty, &tys)
+        });
+        ctx.lowered_closures.push(air_func);
+    }
+
+    ctx.ins_set([
+        Air::Addr(Reg::A, OffsetVar::zero(env)),
+        Air::PushIReg {
+            dst,
+            width: Width::PTR,
+            src: Reg::A,
+        },
+        Air::FnAddr(Reg::A, air_sig),
+        Air::PushIReg {
+            dst: dst.add(Width::PTR),
+            width: Width::PTR,
+            src: Reg::A,
+        },
+    ]);
+}
+
+fn lower_closure_body(
+    ctx: &mut AirCtx,
+    closure: &Closure,
+    func: &Func,
+    env_ty: Ty,
+    captures: &[Ty],
+) {
+    init_params(ctx, func);
+
+    let env_param = ctx.expect_var(closure_env(closure).sym);
+    let env = OffsetVar::zero(ctx.anon_var_no_salloc(env_ty));
+    ctx.ins_set([
+        Air::MovIVar(Reg::A, OffsetVar::zero(env_param), Width::PTR),
+        Air::Deref {
+            dst: env,
+            addr: Reg::A,
+        },
+    ]);
+
+    let TyKind::Tuple(elems) = env_ty.0 else {
+        unreachable!()
+    };
+    for (i, (ident, ty)) in closure.captures.iter().zip(captures).enumerate() {
+        let slot = env.add(ctx.tys.tuple_offset(elems, i) as usize);
+        if closure.moves {
+            ctx.ins(Air::Addr(Reg::A, slot));
+        } else {
+            ctx.ins(Air::MovIVar(Reg::A, slot, Width::PTR));
+        }
+        let var = ctx.anon_var_no_salloc(*ty);
+        ctx.ins(Air::Deref {
+            dst: OffsetVar::zero(var),
+            addr: Reg::A,
+        });
+        ctx.register_var(ident, var);
+    }
+
+    if func.sig.ty.is_unit() {
+        air_block(ctx, &func.block);
+        ctx.ins(Air::Ret);
+    } else {
+        let dst = OffsetVar::zero(ctx.anon_var(func.sig.ty));
+        assign_air_block(ctx, dst, func.sig.ty, &func.block);
+        ctx.ret_var(dst, func.sig.ty);
+    }
+}
+
 fn air_block(ctx: &mut AirCtx, block: &Block) {
+    ctx.push_defers();
     block_stmts(ctx, block.stmts);
     match block.end {
         Some(end) => {
@@ -793,9 +1121,11 @@ fn air_block(ctx: &mut AirCtx, block: &Block) {
         }
         None => {}
     }
+    pop_defers(ctx);
 }
 
 fn assign_air_block(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, block: &Block) {
+    ctx.push_defers();
     block_stmts(ctx, block.stmts);
     if let Some(end) = &block.end {
         assign_expr(ctx, dst, ty, end);
@@ -804,26 +1134,55 @@ fn assign_air_block(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, block: &Block) {
         //println!("{block:?}");
         //assert!(ty == ctx.tys.unit());
     }
+    pop_defers(ctx);
+}
+
+/// Runs the statements deferred in the innermost block as it exits, in reverse order.
+fn pop_defers(ctx: &mut AirCtx) {
+    for stmt in ctx.pop_defers().iter().rev() {
+        air_stmt(ctx, stmt);
+    }
+}
+
+/// Runs the statements deferred in the blocks that are `depth` or more deep before control flow
+/// leaves them, innermost first.
+///
+/// The blocks remain open afterwards, but their deferred statements are not visible while they
+/// run, so that control flow within a deferred statement cannot run it again.
+fn unwind_defers(ctx: &mut AirCtx, depth: usize) {
+    let defers = ctx.split_defers(depth);
+    for stmt in defers.iter().rev().flat_map(|defers| defers.iter().rev()) {
+        air_stmt(ctx, stmt);
+    }
+    ctx.restore_defers(defers);
 }
 
 fn block_stmts(ctx: &mut AirCtx, stmts: &[Stmt]) {
     for stmt in stmts.iter() {
-        match stmt {
-            Stmt::Semi(stmt) => match stmt {
-                SemiStmt::Let(let_) => air_let_stmt(ctx, let_),
-                SemiStmt::Assign(assign) => air_assign_stmt(ctx, assign),
-                SemiStmt::Ret(ret) => match &ret.expr {
-                    Some(expr) => {
-                        air_return(ctx, ctx.active_sig().ty, &expr);
-                    }
-                    None => ctx.ins(Air::Ret),
-                },
-                SemiStmt::Expr(expr) => {
-                    eval_expr(ctx, expr);
+        air_stmt(ctx, stmt);
+    }
+}
+
+fn air_stmt(ctx: &mut AirCtx, stmt: &Stmt) {
+    match stmt {
+        Stmt::Semi(stmt) => match stmt {
+            SemiStmt::Let(let_) => air_let_stmt(ctx, let_),
+            SemiStmt::Assign(assign) => air_assign_stmt(ctx, assign),
+            SemiStmt::Ret(ret) => match &ret.expr {
+                Some(expr) => {
+                    air_return(ctx, ctx.active_sig().ty, &expr);
+                }
+                None => {
+                    unwind_defers(ctx, 0);
+                    ctx.ins(Air::Ret);
                 }
             },
-            Stmt::Open(expr) => eval_expr(ctx, expr),
-        }
+            SemiStmt::Expr(expr) => {
+                eval_expr(ctx, expr);
+            }
+            SemiStmt::Defer(defer) => ctx.defer(defer),
+        },
+        Stmt::Open(expr) => eval_expr(ctx, expr),
     }
 }
 
@@ -836,6 +1195,83 @@ fn air_let_stmt(ctx: &mut AirCtx, stmt: &Let) {
             // defer registering so that something in stmt.rhs isn't shadowed
             ctx.register_var(ident, dst);
         }
+        target => {
+            let mut bindings = Vec::new();
+            let_bindings(ctx, target, &stmt.rhs, &mut bindings);
+            for (ident, var) in bindings.iter() {
+                ctx.register_var(ident, *var);
+            }
+        }
+    }
+}
+
+/// Evaluates `expr` into a new variable for each identifier bound by `target`.
+fn let_bindings(
+    ctx: &mut AirCtx,
+    target: &LetTarget,
+    expr: &Expr,
+    bindings: &mut Vec<(Ident, Var)>,
+) {
+    match (target, expr) {
+        (LetTarget::Ident(ident), _) => {
+            let ty = ctx.var_ty(ident);
+            let dst = ctx.anon_var(ty);
+            assign_expr(ctx, OffsetVar::zero(dst), ty, expr);
+            bindings.push((*ident, dst));
+        }
+        (LetTarget::Tuple { targets, .. }, Expr::Tuple(tuple))
+            if targets.len() == tuple.exprs.len() =>
+        {
+            for (target, expr) in targets.iter().zip(tuple.exprs.iter()) {
+                let_bindings(ctx, target, expr, bindings);
+            }
+        }
+        _ => {
+            let ty = expr.infer_abs(ctx).unwrap();
+            let src = extract_var_from_expr(ctx, ty, expr);
+            destructure(ctx, target, src, ty, bindings);
+        }
+    }
+}
+
+/// Copies the fields of `src` into a new variable for each identifier bound by `target`.
+fn destructure(
+    ctx: &mut AirCtx,
+    target: &LetTarget,
+    src: OffsetVar,
+    ty: Ty,
+    bindings: &mut Vec<(Ident, Var)>,
+) {
+    match target {
+        LetTarget::Ident(ident) => {
+            let dst = ctx.anon_var(ty);
+            assign_var_other(ctx, OffsetVar::zero(dst), src, ty);
+            bindings.push((*ident, dst));
+        }
+        LetTarget::Tuple { targets, .. } => {
+            let TyKind::Tuple(elems) = ty.0 else {
+                unreachable!()
+            };
+
+            for (i, target) in targets.iter().enumerate() {
+                let offset = ctx.tys.tuple_offset(elems, i);
+                destructure(
+                    ctx,
+                    target,
+                    src.add(offset as usize),
+                    Ty(elems[i]),
+                    bindings,
+                );
+            }
+        }
+        LetTarget::Struct { id, fields, .. } => {
+            for (name, target) in fields.iter() {
+                let strukt = ctx.tys.strukt(*id);
+                let offset = strukt.field_offset(&ctx.tys, name.sym);
+                let field_ty = strukt.field_ty(name.sym);
+                destructure(ctx, target, src.add(offset as usize), field_ty, bindings);
+            }
+        }
     }
 }
 
@@ -895,7 +1331,16 @@ fn load_addr_index_of(ctx: &mut AirCtx, index: &IndexOf, dst: Reg) -> Ty {
 }
 
 fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
+    // a pointer that is known to be valid is stored in an optional as is
+    if let Some(inner) = ty.0.opt_inner()
+        && !matches!(expr, Expr::Null(_) | Expr::Break(_) | Expr::Continue(_))
+        && expr.infer_abs(ctx) == Some(inner)
+    {
+        return assign_expr(ctx, dst, inner, expr);
+    }
+
     match &expr {
+        Expr::Closure(closure) => define_closure(ctx, dst, closure),
         Expr::IndexOf(index) => {
             load_addr_index_of(ctx, index, Reg::A);
 
@@ -961,6 +1406,10 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 ConstData::Bits(if bool.val { Bits::TRUE } else { Bits::FALSE }),
             ));
         }
+        Expr::Null(_) => {
+            assert!(ty.is_opt());
+            ctx.ins(Air::PushIConst(dst, ConstData::Bits(Bits::from_u64(0))));
+        }
         Expr::Str(str) => {
             assert_eq!(ty, Ty::STR_LIT);
             let (entry, len) = ctx.str_lit(str.val);
@@ -982,6 +1431,17 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
             assert_eq!(ty, ctx.tys.struct_ty_id(def.id));
             define_struct(ctx, def, dst);
         }
+        Expr::Tuple(tuple) => {
+            let TyKind::Tuple(elems) = ty.0 else {
+                unreachable!()
+            };
+            assert_eq!(elems.len(), tuple.exprs.len());
+
+            for (i, expr) in tuple.exprs.iter().enumerate() {
+                let offset = ctx.tys.tuple_offset(elems, i);
+                assign_expr(ctx, dst.add(offset as usize), Ty(elems[i]), expr);
+            }
+        }
         Expr::Call(call) => {
             assert_eq!(ty, call.sig.ty);
 
@@ -993,6 +1453,22 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 extract_return_from_a(ctx, dst, ty);
             }
         }
+        Expr::IndirectCall(call) => {
+            assert_eq!(ty, indirect_call_fn_ty(ctx, call).1);
+            indirect_call(ctx, call);
+            if !ty.is_unit() {
+                extract_return_from_a(ctx, dst, ty);
+            }
+        }
+        Expr::Fn(func) => {
+            assert_eq!(ty, func.ty);
+            ctx.fn_addr(Reg::A, func.sig);
+            ctx.ins(Air::PushIReg {
+                dst,
+                width: Width::PTR,
+                src: Reg::A,
+            });
+        }
         Expr::MethodCall(call) => {
             let sig = call.expect_sig(ctx);
             assert_eq!(ty, sig.ty);
@@ -1043,7 +1519,7 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 },
                 TyKind::Float(float) => (Prim::Float, float.width()),
                 TyKind::Bool => (Prim::Bool, Width::BOOL),
-                TyKind::Ref(_) => {
+                TyKind::Ref(_) | TyKind::Opt(_) => {
                     //assert!(matches!(ty, Ty::PTR));
                     (Prim::UInt, Width::PTR)
                 }
@@ -1058,7 +1534,7 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 TyKind::Float(float) => (Prim::Float, float.width()),
                 TyKind::Bool => (Prim::Bool, Width::BOOL),
                 TyKind::Ref(TyKind::Str) => unreachable!(),
-                TyKind::Ref(_) => {
+                TyKind::Ref(_) | TyKind::Opt(_) => {
                     assert_eq!(width, Width::PTR);
                     assert_eq!(from, Prim::UInt);
                     (Prim::UInt, Width::PTR)
@@ -1108,13 +1584,17 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 let ref_ty = ctx.tys.intern_kind(TyKind::Ref(ty.0));
                 let ptr_var = extract_var_from_expr(ctx, ref_ty, unary.inner);
                 let addr_reg = Reg::A;
+                // `dst` may already point somewhere, e.g. `dst* = src*`, so copy the value
+                // instead of pointing `dst` at the source
+                let src = OffsetVar::zero(ctx.anon_var_no_salloc(ty));
                 ctx.ins_set([
                     Air::MovIVar(addr_reg, ptr_var, Width::PTR),
                     Air::Deref {
-                        dst,
+                        dst: src,
                         addr: addr_reg,
                     },
                 ]);
+                assign_var_other(ctx, dst, src, ty);
             }
             UOpKind::Not => {
                 let (width, mask) = match ty.0 {
@@ -1136,6 +1616,21 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 ]);
             }
             UOpKind::Neg => {
+                // negate the literal directly so that the minimum value, e.g. `-128i8`, does
+                // not overflow
+                if let TyKind::Int(int_ty) = ty.0
+                    && let Expr::Lit(Lit {
+                        kind: LitKind::Int(int),
+                        ..
+                    }) = unary.inner
+                {
+                    ctx.ins(Air::PushIConst(
+                        dst,
+                        ConstData::Bits(Bits::from_width(int.wrapping_neg(), int_ty.width())),
+                    ));
+                    return;
+                }
+
                 let result = OffsetVar::zero(ctx.anon_var(ty));
                 assign_expr(ctx, result, ty, unary.inner);
 
@@ -1179,13 +1674,13 @@ fn assign_expr(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty, expr: &Expr) {
                 }
             }
         },
-        Expr::Block(_)
-        | Expr::Continue(_)
-        | Expr::Break(_)
-        | Expr::Range(_)
-        | Expr::For(_)
-        | Expr::Loop(_)
-        | Expr::While(_) => unreachable!(),
+        Expr::Loop(loop_) => air_loop(ctx, loop_, Some((dst, ty))),
+        Expr::Block(block) => ctx.in_var_scope(|ctx| {
+            ctx.push_pop_sp(|ctx| assign_air_block(ctx, dst, ty, block));
+        }),
+        Expr::Continue(_) | Expr::Break(_) | Expr::Range(_) | Expr::For(_) | Expr::While(_) => {
+            unreachable!()
+        }
     }
 }
 
@@ -1220,7 +1715,7 @@ fn extract_return_from_a(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty) {
                 src: Reg::A,
             });
         }
-        TyKind::Ref(TyKind::Str) | TyKind::Slice(_) => {
+        TyKind::Ref(TyKind::Str | TyKind::Slice(_)) | TyKind::Closure(_, _) => {
             ctx.ins_set([
                 Air::Addr(Reg::B, dst),
                 Air::MemCpy {
@@ -1234,7 +1729,7 @@ fn extract_return_from_a(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty) {
                 },
             ]);
         }
-        TyKind::Ref(_) => {
+        TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_) => {
             ctx.ins(Air::PushIReg {
                 dst,
                 width: Width::PTR,
@@ -1256,8 +1751,8 @@ fn extract_return_from_a(ctx: &mut AirCtx, dst: OffsetVar, ty: Ty) {
                 },
             ]);
         }
-        TyKind::Struct(s) => {
-            let bytes = ctx.tys.struct_layout(*s).size;
+        TyKind::Struct(_) | TyKind::Tuple(_) => {
+            let bytes = ty.size(&ctx.tys);
             ctx.ins_set([
                 Air::Addr(Reg::B, dst),
                 Air::MemCpy {
@@ -1355,7 +1850,7 @@ fn extract_var_from_expr(ctx: &mut AirCtx, ty: Ty, expr: &Expr) -> OffsetVar {
 
             var
         }
-        Expr::Break(_) | Expr::Continue(_) | Expr::Loop(_) | Expr::For(_) => unreachable!(),
+        Expr::Break(_) | Expr::Continue(_) | Expr::For(_) => unreachable!(),
         _ => {
             let dst = OffsetVar::zero(ctx.anon_var(ty));
             assign_expr(ctx, dst, ty, expr);
@@ -1380,7 +1875,7 @@ fn assign_var_other(ctx: &mut AirCtx, dst: OffsetVar, other: OffsetVar, ty: Ty)
                 src: other,
             }]);
         }
-        TyKind::Ref(TyKind::Str) => {
+        TyKind::Ref(TyKind::Str | TyKind::Slice(_)) | TyKind::Closure(_, _) => {
             ctx.ins_set([
                 Air::PushIVar {
                     dst,
@@ -1394,15 +1889,15 @@ fn assign_var_other(ctx: &mut AirCtx, dst: OffsetVar, other: OffsetVar, ty: Ty)
                 },
             ]);
         }
-        TyKind::Ref(_) => {
+        TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_) => {
             ctx.ins(Air::PushIVar {
                 dst,
                 width: Width::PTR,
                 src: other,
             });
         }
-        TyKind::Struct(id) => {
-            let bytes = ctx.tys.struct_layout(*id).size;
+        TyKind::Struct(_) | TyKind::Tuple(_) => {
+            let bytes = ty.size(&ctx.tys);
             ctx.ins_set([
                 Air::Addr(Reg::B, dst),
                 Air::Addr(Reg::A, other),
@@ -1463,18 +1958,20 @@ fn eval_or_assign_if(ctx: &mut AirCtx, if_: &If, dst: Option<(OffsetVar, Ty)>) {
         let condition = OffsetVar::zero(ctx.anon_var(Ty::BOOL));
 
         ctx.push_pop_sp(|ctx| {
-            assign_expr(ctx, condition, Ty::BOOL, if_.condition);
-            ctx.ins(Air::MovIVar(Reg::A, condition, Width::BOOL));
+            let binding = binding_var(ctx, if_.binding);
+            air_condition(ctx, condition, if_.condition, binding);
 
             match (if_.block, if_.otherwise) {
                 (Expr::Block(then), Some(Expr::Block(otherwise))) => {
                     let exit = ctx.new_block();
                     let then = ctx.in_scope(|ctx, _| {
-                        if let Some((var, ty)) = dst {
-                            assign_air_block(ctx, var, ty, then);
-                        } else {
-                            air_block(ctx, then);
-                        }
+                        in_binding_scope(ctx, binding, |ctx| {
+                            if let Some((var, ty)) = dst {
+                                assign_air_block(ctx, var, ty, then);
+                            } else {
+                                air_block(ctx, then);
+                            }
+                        });
                         ctx.ins(Air::Jmp(exit));
                     });
                     let otherwise = ctx.in_scope(|ctx, _| {
@@ -1496,7 +1993,7 @@ fn eval_or_assign_if(ctx: &mut AirCtx, if_: &If, dst: Option<(OffsetVar, Ty)>) {
                     let otherwise = ctx.new_block();
                     let then = ctx.in_scope(|ctx, _| {
                         assert!(dst.is_none());
-                        air_block(ctx, then);
+                        in_binding_scope(ctx, binding, |ctx| air_block(ctx, then));
                         ctx.ins(Air::Jmp(otherwise));
                     });
                     ctx.ins(Air::IfElse {
@@ -1512,6 +2009,70 @@ fn eval_or_assign_if(ctx: &mut AirCtx, if_: &If, dst: Option<(OffsetVar, Ty)>) {
     });
 }
 
+/// The variable bound by `if let` or `while let`, which holds the unwrapped pointer.
+fn binding_var(ctx: &mut AirCtx, binding: Option<Ident>) -> Option<(Ident, OffsetVar)> {
+    binding.map(|ident| {
+        let ty = ctx.var_ty(&ident);
+        (ident, OffsetVar::zero(ctx.anon_var(ty)))
+    })
+}
+
+/// Evaluates the condition of an `if` or `while` into `condition` and `Reg::A`.
+///
+/// With a `binding`, `expr` is an optional that is stored in the binding's variable, and the
+/// condition is whether it is not `null`.
+fn air_condition(
+    ctx: &mut AirCtx,
+    condition: OffsetVar,
+    expr: &Expr,
+    binding: Option<(Ident, OffsetVar)>,
+) {
+    match binding {
+        Some((_, var)) => {
+            let ty = expr.infer_abs(ctx).unwrap();
+            assert!(ty.is_opt());
+            assign_expr(ctx, var, ty, expr);
+            Ne::new(Width::PTR, Sign::U).visit_leaf(ctx, condition, var, 0u64);
+        }
+        None => assign_expr(ctx, condition, Ty::BOOL, expr),
+    }
+    ctx.ins(Air::MovIVar(Reg::A, condition, Width::BOOL));
+}
+
+/// Runs `f` with the `binding` of an `if let` or `while let` in scope, which is only bound in
+/// the block that runs when the optional is not `null`.
+fn in_binding_scope(
+    ctx: &mut AirCtx,
+    binding: Option<(Ident, OffsetVar)>,
+    f: impl FnOnce(&mut AirCtx),
+) {
+    ctx.in_var_scope(|ctx| {
+        if let Some((ident, var)) = binding {
+            ctx.register_var(&ident, var.var);
+        }
+        f(ctx)
+    });
+}
+
+/// Lowers `loop_`, a `break` with a value assigns it to `dst`.
+fn air_loop(ctx: &mut AirCtx, loop_: &Loop, dst: Option<(OffsetVar, Ty)>) {
+    ctx.in_var_scope(|ctx| {
+        let sp = OffsetVar::zero(ctx.anon_var(Ty::USIZE));
+        ctx.ins(Air::ReadSP(sp));
+
+        let exit = ctx.new_block();
+        let loop_block = ctx.in_loop(loop_.span, exit, dst, |ctx, loop_block| {
+            air_block(ctx, &loop_.block);
+            ctx.ins(Air::WriteSP(sp));
+            ctx.ins(Air::Jmp(loop_block));
+        });
+        ctx.ins(Air::Jmp(loop_block));
+        ctx.set_active_block(exit);
+
+        ctx.ins(Air::WriteSP(sp));
+    });
+}
+
 // TODO: perhaps unify eval and assign entirely? Creating unnecessary anon_var for expressions that
 // cannot return a type does incur overhead in the bytecode, but like, not a lot?
 fn eval_expr(ctx: &mut AirCtx, expr: &Expr) {
@@ -1519,37 +2080,24 @@ fn eval_expr(ctx: &mut AirCtx, expr: &Expr) {
         Expr::If(if_) => {
             eval_if(ctx, if_);
         }
-        Expr::Loop(loop_) => {
-            ctx.in_var_scope(|ctx| {
-                let sp = OffsetVar::zero(ctx.anon_var(Ty::USIZE));
-                ctx.ins(Air::ReadSP(sp));
-
-                let exit = ctx.new_block();
-                let loop_block = ctx.in_loop(exit, |ctx, loop_block| {
-                    air_block(ctx, &loop_.block);
-                    ctx.ins(Air::WriteSP(sp));
-                    ctx.ins(Air::Jmp(loop_block));
-                });
-                ctx.ins(Air::Jmp(loop_block));
-                ctx.set_active_block(exit);
-
-                ctx.ins(Air::WriteSP(sp));
-            });
-        }
+        Expr::Loop(loop_) => air_loop(ctx, loop_, None),
+        Expr::Block(block) => ctx.in_var_scope(|ctx| {
+            ctx.push_pop_sp(|ctx| air_block(ctx, block));
+        }),
         Expr::While(while_) => {
             ctx.in_var_scope(|ctx| {
                 let condition = OffsetVar::zero(ctx.anon_var(Ty::BOOL));
 
                 ctx.push_pop_sp(|ctx| {
+                    let binding = binding_var(ctx, while_.binding);
                     let exit = ctx.new_block();
-                    let loop_block = ctx.in_loop(exit, |ctx, loop_block| {
+                    let loop_block = ctx.in_loop(while_.span, exit, None, |ctx, loop_block| {
                         let post_condition = ctx.in_scope(|ctx, _| {
-                            air_block(ctx, &while_.block);
+                            in_binding_scope(ctx, binding, |ctx| air_block(ctx, &while_.block));
                             ctx.ins(Air::Jmp(loop_block));
                         });
 
-                        assign_expr(ctx, condition, Ty::BOOL, while_.condition);
-                        ctx.ins(Air::MovIVar(Reg::A, condition, Width::BOOL));
+                        air_condition(ctx, condition, while_.condition, binding);
                         ctx.ins(Air::IfElse {
                             condition: Reg::A,
                             then: post_condition,
@@ -1601,32 +2149,33 @@ fn eval_expr(ctx: &mut AirCtx, expr: &Expr) {
                             ));
 
                             let exit = ctx.new_block();
-                            let loop_block = ctx.in_loop(exit, |ctx, loop_block| {
-                                let post_condition = ctx.in_scope(|ctx, _| {
-                                    // reset iter to anon_it
-                                    assign_var_other(ctx, it, anon_it, ty);
+                            let loop_block =
+                                ctx.in_loop(for_.span, exit, None, |ctx, loop_block| {
+                                    let post_condition = ctx.in_scope(|ctx, _| {
+                                        // reset iter to anon_it
+                                        assign_var_other(ctx, it, anon_it, ty);
 
-                                    // perform user code
-                                    air_block(ctx, &for_.block);
+                                        // perform user code
+                                        air_block(ctx, &for_.block);
 
-                                    ctx.ins(Air::Jmp(loop_block));
-                                });
+                                        ctx.ins(Air::Jmp(loop_block));
+                                    });
 
-                                // add assign anon_it
-                                add!(ctx, width, sign, anon_it, anon_it, add_factor);
-                                ctx.ins(Air::PushIConst(
-                                    add_factor,
-                                    ConstData::Bits(Bits::from_u64(1)),
-                                ));
-
-                                // break if end is met
-                                ge!(ctx, width, sign, continue_, anon_it, end);
-                                ctx.ins(Air::IfElse {
-                                    condition: Reg::A,
-                                    then: exit,
-                                    otherwise: post_condition,
+                                    // add assign anon_it
+                                    add!(ctx, width, sign, anon_it, anon_it, add_factor);
+                                    ctx.ins(Air::PushIConst(
+                                        add_factor,
+                                        ConstData::Bits(Bits::from_u64(1)),
+                                    ));
+
+                                    // break if end is met
+                                    ge!(ctx, width, sign, continue_, anon_it, end);
+                                    ctx.ins(Air::IfElse {
+                                        condition: Reg::A,
+                                        then: exit,
+                                        otherwise: post_condition,
+                                    });
                                 });
-                            });
                             ctx.ins(Air::Jmp(loop_block));
                             ctx.set_active_block(exit);
                         });
@@ -1708,34 +2257,35 @@ fn eval_expr(ctx: &mut AirCtx, expr: &Expr) {
                             ));
 
                             let exit = ctx.new_block();
-                            let loop_block = ctx.in_loop(exit, |ctx, loop_block| {
-                                let post_condition = ctx.in_scope(|ctx, _| {
-                                    assign_var_other(ctx, it, arr_ptr, iter_ty);
+                            let loop_block =
+                                ctx.in_loop(for_.span, exit, None, |ctx, loop_block| {
+                                    let post_condition = ctx.in_scope(|ctx, _| {
+                                        assign_var_other(ctx, it, arr_ptr, iter_ty);
 
-                                    air_block(ctx, &for_.block);
+                                        air_block(ctx, &for_.block);
 
-                                    ctx.ins(Air::Jmp(loop_block));
-                                });
+                                        ctx.ins(Air::Jmp(loop_block));
+                                    });
+
+                                    add!(ctx, Width::SIZE, Sign::U, it_count, it_count, add_factor);
+                                    ctx.ins(Air::PushIConst(
+                                        add_factor,
+                                        ConstData::Bits(Bits::from_u64(1)),
+                                    ));
+                                    add!(ctx, Width::SIZE, Sign::U, arr_ptr, arr_ptr, elem_offset);
+                                    ctx.ins(Air::PushIConst(
+                                        elem_offset,
+                                        ConstData::Bits(Bits::from_u64(elem_size as u64)),
+                                    ));
 
-                                add!(ctx, Width::SIZE, Sign::U, it_count, it_count, add_factor);
-                                ctx.ins(Air::PushIConst(
-                                    add_factor,
-                                    ConstData::Bits(Bits::from_u64(1)),
-                                ));
-                                add!(ctx, Width::SIZE, Sign::U, arr_ptr, arr_ptr, elem_offset);
-                                ctx.ins(Air::PushIConst(
-                                    elem_offset,
-                                    ConstData::Bits(Bits::from_u64(elem_size as u64)),
-                                ));
-
-                                // break if end is met
-                                ge!(ctx, Width::SIZE, Sign::U, continue_, it_count, len);
-                                ctx.ins(Air::IfElse {
-                                    condition: Reg::A,
-                                    then: exit,
-                                    otherwise: post_condition,
+                                    // break if end is met
+                                    ge!(ctx, Width::SIZE, Sign::U, continue_, it_count, len);
+                                    ctx.ins(Air::IfElse {
+                                        condition: Reg::A,
+                                        then: exit,
+                                        otherwise: post_condition,
+                                    });
                                 });
-                            });
                             ctx.ins(Air::Jmp(loop_block));
                             ctx.set_active_block(exit);
                         });
@@ -1743,13 +2293,21 @@ fn eval_expr(ctx: &mut AirCtx, expr: &Expr) {
                 }
             }
         }
-        Expr::Break(_) => {
-            let break_block = ctx.break_block().unwrap();
-            ctx.ins(Air::Jmp(break_block));
+        Expr::Break(brk) => {
+            let loop_ctx = ctx.loop_ctx(brk.target);
+            if let Some(value) = brk.value {
+                match loop_ctx.dst {
+                    Some((dst, ty)) => assign_expr(ctx, dst, ty, value),
+                    None => eval_expr(ctx, value),
+                }
+            }
+            unwind_defers(ctx, loop_ctx.defers);
+            ctx.ins(Air::Jmp(loop_ctx.breac));
         }
-        Expr::Continue(_) => {
-            let loop_start = ctx.loop_start().unwrap();
-            ctx.ins(Air::Jmp(loop_start));
+        Expr::Continue(cont) => {
+            let loop_ctx = ctx.loop_ctx(cont.target);
+            unwind_defers(ctx, loop_ctx.defers);
+            ctx.ins(Air::Jmp(loop_ctx.start));
         }
         _ => {
             let ty = expr.infer_abs(ctx).unwrap();
@@ -1759,19 +2317,65 @@ fn eval_expr(ctx: &mut AirCtx, expr: &Expr) {
     }
 }
 
+fn indirect_call_fn_ty(ctx: &mut AirCtx, call: &IndirectCall) -> (&'static [&'static TyKind], Ty) {
+    match call.callee.infer_abs(ctx).unwrap().0 {
+        TyKind::Fn(params, ret) | TyKind::Closure(params, ret) => (params, Ty(ret)),
+        ty => panic!("invalid callee type: {ty:?}"),
+    }
+}
+
+/// Leaves the return value in [`Reg::A`], see [`extract_return_from_a`].
+fn indirect_call(ctx: &mut AirCtx, call: &IndirectCall) {
+    let (params, _) = indirect_call_fn_ty(ctx, call);
+    assert_eq!(params.len(), call.args.len());
+
+    ctx.push_pop_sp(|ctx| {
+        let fn_ty = call.callee.infer_abs(ctx).unwrap();
+        let mut callee = extract_var_from_expr(ctx, fn_ty, call.callee);
+
+        let mut args = Args {
+            vars: Vec::with_capacity(params.len() + 1),
+        };
+        // a closure is a pointer to its environment followed by the function, which takes
+        // the environment as its first argument
+        if fn_ty.is_closure() {
+            let env = OffsetVar::zero(ctx.anon_var(Ty::PTR));
+            ctx.ins(Air::PushIVar {
+                dst: env,
+                width: Width::PTR,
+                src: callee,
+            });
+            args.vars.push((Ty::PTR, env.var));
+            callee = callee.add(Width::PTR);
+        }
+        for (expr, param) in call.args.iter().zip(params.iter()) {
+            let ty = Ty(param);
+            let var = OffsetVar::zero(ctx.anon_var(ty));
+            assign_expr(ctx, var, ty, expr);
+            args.vars.push((ty, var.var));
+        }
+
+        ctx.ins(Air::CallIndirect { callee, args });
+    });
+}
+
 fn generate_args(ctx: &mut AirCtx, sig: &Sig, args: &[Expr]) -> Args {
     let ident = sig.ident.as_str();
-    if ident == "print" || ident == "println" {
+    if is_print(ident) {
         return print_generate_args(ctx, args);
     }
 
-    assert_eq!(args.len(), sig.params.len());
+    if sig.variadic {
+        assert!(args.len() >= sig.params.len());
+    } else {
+        assert_eq!(args.len(), sig.params.len());
+    }
     if args.is_empty() {
         return Args::default();
     }
 
     let mut call_args = Args {
-        vars: Vec::with_capacity(sig.params.len()),
+        vars: Vec::with_capacity(args.len()),
     };
 
     for (expr, param) in args.iter().zip(sig.params.iter()) {
@@ -1781,12 +2385,33 @@ fn generate_args(ctx: &mut AirCtx, sig: &Sig, args: &[Expr]) -> Args {
         };
 
         let var = ctx.func_arg_var(ident, ty);
-        ctx.ins(Air::SAlloc(var, ty.size(&ctx.tys)));
+        ctx.ins(Air::SAlloc(var, ctx.tys.ty_layout(ty)));
         let the_fn_param = OffsetVar::zero(var);
         assign_expr(ctx, the_fn_param, ty, expr);
         call_args.vars.push((ty, the_fn_param.var));
     }
 
+    for expr in args.iter().skip(sig.params.len()) {
+        let (ty, promoted) = match expr.infer(ctx) {
+            InferTy::Int => (Ty::ISIZE, Ty::ISIZE),
+            InferTy::Float => (Ty::F64, Ty::F64),
+            InferTy::Ty(ty) => (ty, ty.0.default_promotion().unwrap()),
+        };
+
+        let the_fn_param = OffsetVar::zero(ctx.anon_var(promoted));
+        if ty == promoted {
+            assign_expr(ctx, the_fn_param, ty, expr);
+        } else {
+            let cast = Expr::Cast(Cast {
+                span: expr.span(),
+                lhs: expr,
+                ty: promoted,
+            });
+            assign_expr(ctx, the_fn_param, promoted, &cast);
+        }
+        call_args.vars.push((promoted, the_fn_param.var));
+    }
+
     call_args
 }
 
@@ -1826,7 +2451,7 @@ fn generate_method_args(ctx: &mut AirCtx, sig: &Sig, callee: &Expr, args: &[Expr
         match &param {
             Param::Named { ident, ty, .. } => {
                 let var = ctx.func_arg_var(*ident, *ty);
-                ctx.ins(Air::SAlloc(var, ty.size(&ctx.tys)));
+                ctx.ins(Air::SAlloc(var, ctx.tys.ty_layout(*ty)));
                 let the_fn_param = OffsetVar::zero(var);
                 assign_expr(ctx, the_fn_param, *ty, expr);
                 call_args.vars.push((*ty, the_fn_param.var));
@@ -1837,7 +2462,7 @@ fn generate_method_args(ctx: &mut AirCtx, sig: &Sig, callee: &Expr, args: &[Expr
                 let expr_ty = expr.infer_abs(ctx).unwrap();
                 let expr_var = extract_var_from_expr(ctx, expr_ty, expr);
                 ctx.ins_set([
-                    Air::SAlloc(var, self_ty.size(&ctx.tys)),
+                    Air::SAlloc(var, ctx.tys.ty_layout(self_ty)),
                     Air::Addr(Reg::A, expr_var),
                     Air::PushIReg {
                         dst: OffsetVar::zero(var),
@@ -1988,7 +2613,11 @@ fn assign_lit(ctx: &mut AirCtx, lit: &Lit, var: OffsetVar, ty: Ty) {
         | TyKind::Bool
         | TyKind::Unit
         | TyKind::Str
-        | TyKind::Struct(_) => {
+        | TyKind::Struct(_)
+        | TyKind::Tuple(_)
+        | TyKind::Fn(_, _)
+        | TyKind::Closure(_, _)
+        | TyKind::Opt(_) => {
             panic!("cannot assign lit to {ty:?}")
         }
     }
@@ -1998,6 +2627,7 @@ fn assign_lit(ctx: &mut AirCtx, lit: &Lit, var: OffsetVar, ty: Ty) {
 fn air_return(ctx: &mut AirCtx, ty: Ty, end: &Expr) {
     let dst = OffsetVar::zero(ctx.anon_var(ty));
     assign_expr(ctx, dst, ty, end);
+    unwind_defers(ctx, 0);
     ctx.ret_var(dst, ty);
 
     //match end {
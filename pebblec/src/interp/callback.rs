@@ -0,0 +1,214 @@
+use super::ctx::InterpCtx;
+use super::{InstrResult, execute};
+use crate::air::AirFunc;
+use crate::ir::ty::store::TyStore;
+use crate::ir::ty::{FloatTy, Sign, TyKind};
+use libffi::low::ffi_cif;
+use libffi::middle::{Cif, Closure, Type};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::panic::AssertUnwindSafe;
+use std::thread::ThreadId;
+
+/// A Pebble function exposed to C as a function pointer.
+///
+/// Calling the pointer re-enters the interpreter with a new frame for `func`, so the pointer is
+/// only valid while the interpreter is blocked on the external call that received it. Calls
+/// outside of that window, or from another thread, abort.
+pub struct Callback<'a> {
+    // dropped before `data`, which it points to
+    closure: Closure<'a>,
+    data: Box<CallbackData<'a>>,
+}
+
+type Libs<'a> = HashMap<&'a str, libloading::Library>;
+
+struct CallbackData<'a> {
+    func: &'a AirFunc<'a>,
+    air_funcs: &'a [AirFunc<'a>],
+    log: bool,
+    thread: ThreadId,
+    // the interpreter is only borrowed for the duration of an external call, so these are set
+    // before every call that passes this callback and cleared by `Bound` once it returns
+    ctx: Cell<*mut InterpCtx<'a>>,
+    libs: Cell<*const Libs<'a>>,
+}
+
+impl<'a> Callback<'a> {
+    pub fn new(
+        tys: &TyStore,
+        func: &'a AirFunc<'a>,
+        air_funcs: &'a [AirFunc<'a>],
+        log: bool,
+    ) -> Self {
+        let data = Box::new(CallbackData {
+            func,
+            air_funcs,
+            log,
+            thread: std::thread::current().id(),
+            ctx: Cell::new(std::ptr::null_mut()),
+            libs: Cell::new(std::ptr::null()),
+        });
+
+        let params = func.sig.params.iter().map(|ty| ty.libffi_type(tys));
+        let ret = if func.sig.ty.is_unit() {
+            Type::void()
+        } else {
+            func.sig.ty.libffi_type(tys)
+        };
+
+        // `data` is boxed and outlives `closure`, see the field order of `Callback`
+        let userdata = unsafe { &*(data.as_ref() as *const CallbackData<'a>) };
+        let closure = Closure::new(Cif::new(params, ret), callback, userdata);
+        Self { closure, data }
+    }
+
+    /// Binds the callback to the interpreter of the current external call and returns its code
+    /// pointer. The binding is undone when `bound` is dropped.
+    pub fn bind(
+        &self,
+        ctx: *mut InterpCtx<'a>,
+        libs: &HashMap<&str, libloading::Library>,
+        bound: &mut Bound<'a>,
+    ) -> *const c_void {
+        let data = self.data.as_ref();
+        bound.0.push((data, data.ctx.get(), data.libs.get()));
+        data.ctx.set(ctx);
+        data.libs
+            .set(libs as *const HashMap<&str, libloading::Library> as *const _);
+        *self.closure.code_ptr() as *const c_void
+    }
+}
+
+/// The callbacks bound for an external call.
+///
+/// Dropping restores the bindings from before the call, so that a nested call which passes the
+/// same callback does not unbind it for the outer one.
+#[derive(Default)]
+pub struct Bound<'a>(Vec<(*const CallbackData<'a>, *mut InterpCtx<'a>, *const Libs<'a>)>);
+
+impl Drop for Bound<'_> {
+    fn drop(&mut self) {
+        for (data, ctx, libs) in self.0.drain(..).rev() {
+            // the data is boxed and never dropped before the interpreter
+            let data = unsafe { &*data };
+            data.ctx.set(ctx);
+            data.libs.set(libs);
+        }
+    }
+}
+
+unsafe extern "C" fn callback(
+    _cif: &ffi_cif,
+    result: &mut u64,
+    args: *const *const c_void,
+    data: &CallbackData,
+) {
+    let sig = data.func.sig;
+    if std::thread::current().id() != data.thread {
+        eprintln!(
+            "error: `{}` was called from a thread other than the interpreter's",
+            sig.ident
+        );
+        std::process::abort();
+    }
+    if data.ctx.get().is_null() {
+        eprintln!(
+            "error: `{}` was called after the external call that received it returned",
+            sig.ident
+        );
+        std::process::abort();
+    }
+    let ctx = unsafe { &mut *data.ctx.get() };
+    let libs = unsafe { &*data.libs.get() };
+
+    let position = ctx.position();
+    let sp = ctx.stack.sp();
+    let depth = ctx.frames.len();
+
+    // copy the arguments out of C's frame, the interpreter can only address its own stack
+    let mut addrs = Vec::with_capacity(sig.params.len());
+    for (i, ty) in sig.params.iter().enumerate() {
+        let bytes = ty.size(ctx.tys);
+        let addr = match ctx.stack.anon_alloc(bytes) {
+            Ok(addr) => addr,
+            Err(overflow) => {
+                ctx.report_stack_overflow(overflow);
+                std::process::exit(1);
+            }
+        };
+        unsafe {
+            std::ptr::copy_nonoverlapping(*args.add(i) as *const u8, addr as *mut u8, bytes);
+        }
+        addrs.push(addr);
+    }
+
+    ctx.start_func(data.func);
+    for (param, addr) in data.func.params.iter().zip(addrs) {
+        ctx.stack.point(*param, addr);
+    }
+
+    let (air_funcs, log) = (data.air_funcs, data.log);
+    // the callback's frame is popped by its `Ret`
+    while ctx.frames.len() > depth {
+        let mut unwind_ctx = AssertUnwindSafe(&mut *ctx);
+        let libs = AssertUnwindSafe(libs);
+        match std::panic::catch_unwind(move || execute(&mut unwind_ctx, air_funcs, &libs, log)) {
+            Ok(InstrResult::Ok) => ctx.incr_instr(),
+            Ok(InstrResult::Continue) => {}
+            Ok(InstrResult::Break) => unreachable!(),
+            Ok(InstrResult::StackOverflow(overflow)) => {
+                ctx.report_stack_overflow(overflow);
+                std::process::exit(1);
+            }
+            Ok(InstrResult::Err(err)) => {
+                eprintln!("error: {err}");
+                ctx.report_backtrace();
+                std::process::exit(1);
+            }
+            // unwinding into C is undefined
+            Err(_) => {
+                ctx.report_backtrace();
+                std::process::abort();
+            }
+        }
+    }
+
+    let ret = ctx.a.r();
+    match sig.ty.0 {
+        TyKind::Unit => {}
+        // integers narrower than a register are widened, see `ffi_arg`
+        TyKind::Int(int) => {
+            let shift = 64 - int.size() * 8;
+            *result = match int.sign() {
+                Sign::I => (((ret << shift) as i64) >> shift) as u64,
+                Sign::U => (ret << shift) >> shift,
+            };
+        }
+        TyKind::Bool => *result = ret & 1,
+        TyKind::Float(FloatTy::F32) => unsafe {
+            *(result as *mut u64 as *mut u32) = ret as u32;
+        },
+        // returned by pointer, see `AirCtx::ret_var`
+        ty @ (TyKind::Ref(TyKind::Str)
+        | TyKind::Array(_, _)
+        | TyKind::Slice(_)
+        | TyKind::Struct(_)
+        | TyKind::Tuple(_)
+        | TyKind::Closure(_, _)) => unsafe {
+            std::ptr::copy_nonoverlapping(
+                ret as *const u8,
+                result as *mut u64 as *mut u8,
+                ty.size(ctx.tys),
+            );
+        },
+        TyKind::Float(FloatTy::F64) | TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_) => {
+            *result = ret
+        }
+        TyKind::Str => unreachable!(),
+    }
+
+    ctx.restore_position(position);
+    *ctx.stack.sp_mut() = sp;
+}
@@ -1,9 +1,17 @@
-use super::InstrResult;
-use super::stack::Stack;
+use super::callback::Callback;
+use super::env::Env;
+use super::heap::Heap;
+use super::host::HostFunc;
+use super::io::Files;
+use super::rand::Seeds;
+use super::stack::{Stack, StackOverflow};
+use super::time::Clock;
+use super::{InstrResult, InterpError};
 use crate::air::data::Bss;
-use crate::air::{Air, AirFunc, BlockId, Reg};
+use crate::air::{Air, AirFunc, AirSig, BlockId, Reg};
 use crate::ir::ty::store::TyStore;
 use crate::ir::ty::{FloatTy, Width};
+use std::collections::HashMap;
 use std::slice;
 
 #[derive(Default)]
@@ -32,10 +40,22 @@ impl<'a> Frame<'a> {
     }
 }
 
+/// The next instruction to execute, see [`InterpCtx::position`].
+pub struct Position<'a> {
+    func_block: Option<(&'a AirFunc<'a>, BlockId)>,
+    instrs: slice::Iter<'a, Air<'a>>,
+    instr: usize,
+}
+
 pub struct InterpCtx<'a> {
     // garauntee that bss will be in memory for raw pointer access
-    _bss: &'a Bss,
+    bss: &'a Bss,
     pub stack: Stack,
+    pub heap: Heap,
+    pub env: Env,
+    pub files: Files,
+    pub clock: Clock,
+    pub seeds: Seeds,
     pub tys: &'a TyStore,
     pub frames: Vec<Frame<'a>>,
 
@@ -45,6 +65,16 @@ pub struct InterpCtx<'a> {
 
     pub a: BitsReg,
     pub b: BitsReg,
+
+    /// Functions keyed by the address of their signature, which is the value of a function
+    /// pointer, see [`Air::FnAddr`].
+    funcs: HashMap<*const AirSig<'a>, &'a AirFunc<'a>>,
+    /// Pebble functions that have been passed to external functions, keyed by their signature.
+    pub callbacks: HashMap<*const AirSig<'a>, Callback<'a>>,
+    /// Rust closures registered for the functions of `extern("host")` blocks.
+    pub host: HashMap<String, HostFunc>,
+    /// Set once a C library is loaded, see [`InterpCtx::check_addr`].
+    pub foreign: bool,
 }
 
 macro_rules! debug_op {
@@ -74,20 +104,63 @@ macro_rules! debug_flop {
 }
 
 impl<'a> InterpCtx<'a> {
-    pub fn new(tys: &'a TyStore, bss: &'a Bss) -> Self {
+    pub fn new(
+        tys: &'a TyStore,
+        bss: &'a Bss,
+        funcs: &'a [AirFunc<'a>],
+        stack_size: usize,
+    ) -> Self {
         Self {
             tys,
-            _bss: bss,
-            stack: Stack::default(),
+            bss,
+            stack: Stack::new(stack_size),
+            heap: Heap::default(),
+            env: Env::default(),
+            files: Files::default(),
+            clock: Clock::default(),
+            seeds: Seeds::default(),
             frames: Vec::new(),
             func_block: None,
             instrs: [].iter(),
             instr: 0,
             a: BitsReg::default(),
             b: BitsReg::default(),
+            funcs: funcs
+                .iter()
+                .map(|func| (func.sig as *const AirSig, func))
+                .collect(),
+            callbacks: HashMap::default(),
+            host: HashMap::default(),
+            foreign: false,
         }
     }
 
+    /// Checks that `bytes` bytes at `addr` belong to the stack, a live heap allocation or the
+    /// bss.
+    ///
+    /// Memory handed out by a C library cannot be told apart from a stray pointer, so once a
+    /// library is loaded, any other address that is not null is trusted.
+    pub fn check_addr(&self, addr: usize, bytes: usize) -> Result<(), InterpError> {
+        if bytes == 0
+            || self.stack.contains(addr, bytes)
+            || self.heap.contains(addr, bytes)
+            || self.bss.contains(addr, bytes)
+            || (self.foreign && addr != 0)
+        {
+            Ok(())
+        } else {
+            Err(InterpError::InvalidAddress { addr, bytes })
+        }
+    }
+
+    /// The function that the function pointer `addr` refers to.
+    pub fn fn_ptr(&self, addr: u64) -> Result<&'a AirFunc<'a>, InterpError> {
+        self.funcs
+            .get(&(addr as *const AirSig))
+            .copied()
+            .ok_or(InterpError::InvalidFnPtr(addr))
+    }
+
     pub fn next(&mut self) -> Option<&'a Air<'a>> {
         self.instrs.next()
     }
@@ -96,6 +169,22 @@ impl<'a> InterpCtx<'a> {
         self.instr += 1;
     }
 
+    /// Saves the current position so that a nested call, such as a [`Callback`], can return
+    /// to it.
+    pub fn position(&self) -> Position<'a> {
+        Position {
+            func_block: self.func_block,
+            instrs: self.instrs.clone(),
+            instr: self.instr,
+        }
+    }
+
+    pub fn restore_position(&mut self, position: Position<'a>) {
+        self.func_block = position.func_block;
+        self.instrs = position.instrs;
+        self.instr = position.instr;
+    }
+
     pub fn consts(&mut self, consts: &'a [Air<'a>]) {
         self.instrs = consts.iter();
     }
@@ -113,6 +202,20 @@ impl<'a> InterpCtx<'a> {
         self.instr = 0;
     }
 
+    /// Starts `func` without a caller, so that returning from it ends execution.
+    pub fn start_entry(&mut self, func: &'a AirFunc<'a>) {
+        self.func_block = None;
+        self.start_func(func);
+    }
+
+    /// Drops the frames above `depth`, such as those of a call that panicked.
+    pub fn unwind_to(&mut self, depth: usize) {
+        while self.frames.len() > depth {
+            self.frames.pop();
+            self.stack.restore_vars();
+        }
+    }
+
     #[track_caller]
     pub fn start_block(&mut self, block: BlockId) {
         match self.func_block {
@@ -130,6 +233,7 @@ impl<'a> InterpCtx<'a> {
     pub fn pop_frame(&mut self) -> InstrResult {
         match self.frames.pop() {
             Some(Frame { func, instr, block }) => {
+                self.stack.restore_vars();
                 self.instr = instr;
                 self.func_block = Some((func, block));
                 self.instrs = func.block(block).iter();
@@ -139,21 +243,13 @@ impl<'a> InterpCtx<'a> {
 
                 InstrResult::Continue
             }
-            None => {
-                match self.func_block {
-                    Some((f, _)) => {
-                        if f.sig.ident == "main" {
-                            return InstrResult::Break;
-                        }
-                    }
-                    None => {}
-                }
-                panic!("no return");
-            }
+            // returned from the entry function
+            None => InstrResult::Break,
         }
     }
 
     pub fn push_frame(&mut self, frame: Frame<'a>) {
+        self.stack.save_vars();
         self.frames.push(frame);
     }
 
@@ -171,16 +267,45 @@ impl<'a> InterpCtx<'a> {
         }
     }
 
+    /// Identifier of the function currently being executed.
+    pub fn func_ident(&self) -> Option<&'a str> {
+        self.func_block.map(|(f, _)| f.sig.ident)
+    }
+
+    pub fn report_stack_overflow(&self, overflow: StackOverflow) {
+        eprintln!(
+            "error: stack overflow in function `{}`: requested {} bytes, {} bytes remaining of {}",
+            self.func_ident().unwrap_or("<const>"),
+            overflow.requested,
+            overflow.remaining,
+            self.stack.bytes(),
+        );
+        self.report_backtrace();
+    }
+
     pub fn report_backtrace(&self) {
-        println!("Backtrace:");
-        for (i, func) in self
+        eprintln!("Backtrace:");
+
+        // collapse recursive calls so that deep recursion doesn't flood the output
+        let mut frames = self
             .frames
             .iter()
             .rev()
             .map(|f| f.func.sig.ident)
-            .enumerate()
-        {
-            println!("    {i}: {func}");
+            .peekable();
+        let mut i = 0;
+        while let Some(func) = frames.next() {
+            let mut repeated = 0;
+            while frames.next_if_eq(&func).is_some() {
+                repeated += 1;
+            }
+
+            if repeated > 0 {
+                eprintln!("    {i}: {func} (repeated {repeated} more times)");
+            } else {
+                eprintln!("    {i}: {func}");
+            }
+            i += repeated + 1;
         }
     }
 
@@ -265,6 +390,25 @@ impl<'a> InterpCtx<'a> {
             Air::PrintCStr => {
                 println!(" | print_c_str @ `{}`", self.a.r());
             }
+            Air::Alloc => {
+                println!(" | alloc {} bytes @ align {}", self.a.r(), self.b.r());
+            }
+            Air::Realloc => {
+                println!(" | realloc Addr({:#x}) @ {} bytes", self.a.r(), self.b.r());
+            }
+            Air::Free => {
+                println!(" | free Addr({:#x})", self.a.r());
+            }
+            Air::Args | Air::CurrentDir | Air::Now | Air::UnixTime | Air::RandSeed => {}
+            Air::Io(op, _) => {
+                println!(" | io {op:?}");
+            }
+            Air::EnvVar => {
+                println!(" | env var @ Addr({:#x})", self.a.r());
+            }
+            Air::Sleep => {
+                println!(" | sleep {} ns", self.a.r());
+            }
             Air::IfElse {
                 condition,
                 then,
@@ -280,9 +424,9 @@ impl<'a> InterpCtx<'a> {
                     otherwise
                 );
             }
-            Air::SAlloc(var, bytes) => {
+            Air::SAlloc(var, layout) => {
                 println!(" | Addr({:?}) <- {:#x}", var, self.stack.sp());
-                println!(" | SP({:?}) += {}", self.stack.sp(), bytes);
+                println!(" | SP({:?}) += {}", self.stack.sp(), layout.size);
             }
             Air::MemCpy { dst, src, bytes } => {
                 println!(
@@ -341,6 +485,19 @@ impl<'a> InterpCtx<'a> {
                         .collect::<Vec<_>>(),
                 );
             }
+            Air::CallIndirect { callee, args } => {
+                println!(
+                    " | call proc [{:#x}({:?})]",
+                    self.stack.read_var::<u64>(*callee),
+                    args.vars
+                        .iter()
+                        .map(|(ty, var)| (format!("{ty:?}"), var))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            Air::FnAddr(reg, sig) => {
+                println!(" | {reg:?} <- &{}", sig.ident);
+            }
 
             Air::MulAB(_, _) => debug_op!(self, *),
             Air::DivAB(_, _) => debug_op!(self, /),
@@ -0,0 +1,88 @@
+use super::heap::Heap;
+use crate::ir::ty::{TyKind, Width};
+use std::collections::HashMap;
+
+/// Command-line arguments and environment of the program, read through `core::env`.
+///
+/// Strings are copied into the [`Heap`] the first time they are requested and shared by every
+/// later request, so the addresses handed to the program stay valid until the `Heap` is dropped.
+#[derive(Debug, Default)]
+pub struct Env {
+    args: Vec<String>,
+    args_addr: Option<usize>,
+    vars: HashMap<String, usize>,
+    current_dir: Option<usize>,
+}
+
+impl Env {
+    pub fn new(args: Vec<String>) -> Self {
+        Self {
+            args,
+            ..Default::default()
+        }
+    }
+
+    /// Address of the `&[&str]` that holds the arguments.
+    pub fn args(&mut self, heap: &mut Heap) -> usize {
+        if let Some(addr) = self.args_addr {
+            return addr;
+        }
+
+        let strs = heap.alloc(self.args.len() * TyKind::FAT_PTR_SIZE, 8);
+        for (i, arg) in self.args.iter().enumerate() {
+            let str = alloc_str(heap, arg);
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    str as *const u8,
+                    (strs + i * TyKind::FAT_PTR_SIZE) as *mut u8,
+                    TyKind::FAT_PTR_SIZE,
+                )
+            };
+        }
+        let addr = alloc_fat_ptr(heap, strs, self.args.len());
+        self.args_addr = Some(addr);
+        addr
+    }
+
+    /// Address of the `&str` value of the environment variable `name`, or 0 if it is not set.
+    pub fn var(&mut self, heap: &mut Heap, name: &str) -> usize {
+        if let Some(addr) = self.vars.get(name) {
+            return *addr;
+        }
+
+        match std::env::var_os(name) {
+            Some(value) => {
+                let addr = alloc_str(heap, &value.to_string_lossy());
+                self.vars.insert(name.to_string(), addr);
+                addr
+            }
+            None => 0,
+        }
+    }
+
+    /// Address of the `&str` path of the current directory, which is empty if it cannot be read.
+    pub fn current_dir(&mut self, heap: &mut Heap) -> usize {
+        *self.current_dir.get_or_insert_with(|| {
+            let dir = std::env::current_dir()
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            alloc_str(heap, &dir)
+        })
+    }
+}
+
+/// Copies `str` into the heap, returning the address of a `&str` that points to it.
+fn alloc_str(heap: &mut Heap, str: &str) -> usize {
+    let bytes = heap.alloc(str.len(), 1);
+    unsafe { std::ptr::copy_nonoverlapping(str.as_ptr(), bytes as *mut u8, str.len()) };
+    alloc_fat_ptr(heap, bytes, str.len())
+}
+
+fn alloc_fat_ptr(heap: &mut Heap, ptr: usize, len: usize) -> usize {
+    let addr = heap.alloc(TyKind::FAT_PTR_SIZE, 8);
+    unsafe {
+        (addr as *mut u64).write(ptr as u64);
+        ((addr + Width::PTR.bytes()) as *mut u64).write(len as u64);
+    }
+    addr
+}
@@ -0,0 +1,264 @@
+use super::InterpError;
+use super::ctx::InterpCtx;
+use crate::ir::format::{Align, Format, Piece, Spec, SpecKind};
+use crate::ir::ty::{FloatTy, Sign, Ty, TyKind, Width};
+use core::str;
+use std::fmt::{Display, LowerExp};
+
+/// Renders the format string `fmt` with `args`, the type and address of each argument.
+///
+/// `resolve` only checks literal format strings, so `fmt` is checked again here. Also fails if a
+/// string or slice points to memory that does not belong to the program.
+pub fn format(ctx: &InterpCtx, fmt: &str, args: &[(Ty, usize)]) -> Result<String, InterpError> {
+    let invalid = |msg: String| InterpError::Format {
+        fmt: fmt.to_string(),
+        msg,
+    };
+    let format = Format::parse(fmt).map_err(|err| invalid(err.msg))?;
+    let specs = format.specs().count();
+    if specs != args.len() {
+        return Err(invalid(format!(
+            "expected {specs} arguments, got {}",
+            args.len()
+        )));
+    }
+
+    let mut buf = String::new();
+    let mut args = args.iter();
+    for piece in format.pieces.iter() {
+        match piece {
+            Piece::Lit(str) => buf.push_str(str),
+            Piece::Arg(spec) => {
+                let (ty, addr) = args.next().unwrap();
+                write_arg(ctx, &mut buf, *ty, *addr, spec)?;
+            }
+        }
+    }
+
+    Ok(buf)
+}
+
+fn write_arg(
+    ctx: &InterpCtx,
+    buf: &mut String,
+    ty: Ty,
+    addr: usize,
+    spec: &Spec,
+) -> Result<(), InterpError> {
+    let (prefix, body) = match (spec.kind, ty.0) {
+        (SpecKind::Debug, _)
+        | (
+            _,
+            TyKind::Struct(_)
+            | TyKind::Tuple(_)
+            | TyKind::Array(_, _)
+            | TyKind::Ref(TyKind::Slice(_)),
+        ) => {
+            let mut body = String::new();
+            write_debug(ctx, &mut body, ty, addr)?;
+            (String::new(), body)
+        }
+        (_, TyKind::Int(int)) => {
+            let bits = read(ctx, addr, int.width());
+            int_parts(spec, bits, int.width(), int.sign())
+        }
+        (_, TyKind::Float(FloatTy::F32)) => {
+            let val = f32::from_bits(read(ctx, addr, Width::W32) as u32);
+            float_parts(spec, val.is_sign_negative() && !val.is_nan(), val.abs())
+        }
+        (_, TyKind::Float(FloatTy::F64)) => {
+            let val = f64::from_bits(read(ctx, addr, Width::W64));
+            float_parts(spec, val.is_sign_negative() && !val.is_nan(), val.abs())
+        }
+        (_, TyKind::Bool) => (
+            String::new(),
+            (read(ctx, addr, Width::BOOL) == 1).to_string(),
+        ),
+        (_, TyKind::Ref(TyKind::Str)) => {
+            let str = read_str(ctx, addr)?;
+            let body = match spec.precision {
+                Some(precision) => str.chars().take(precision).collect(),
+                None => str.to_string(),
+            };
+            (String::new(), body)
+        }
+        (SpecKind::Display, TyKind::Opt(_)) => match read(ctx, addr, Width::PTR) {
+            0 => (String::new(), "null".to_string()),
+            ptr => (String::new(), format!("{:#x}", ptr)),
+        },
+        (SpecKind::Display, TyKind::Ref(_) | TyKind::Fn(_, _)) => {
+            (String::new(), format!("{:#x}", read(ctx, addr, Width::PTR)))
+        }
+        (_, TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_)) => {
+            int_parts(spec, read(ctx, addr, Width::PTR), Width::PTR, Sign::U)
+        }
+        (_, TyKind::Str | TyKind::Slice(_) | TyKind::Closure(_, _) | TyKind::Unit) => {
+            unreachable!()
+        }
+    };
+
+    let numeric = matches!(ty.0, TyKind::Int(_) | TyKind::Float(_));
+    pad(buf, spec, numeric, &prefix, &body);
+    Ok(())
+}
+
+/// Splits an integer into its sign or radix prefix and its digits.
+fn int_parts(spec: &Spec, bits: u64, width: Width, sign: Sign) -> (String, String) {
+    let radix_prefix = |prefix: &str| {
+        if spec.alternate {
+            prefix.to_string()
+        } else {
+            String::new()
+        }
+    };
+
+    match spec.kind {
+        SpecKind::LowerHex => (radix_prefix("0x"), format!("{:x}", bits)),
+        SpecKind::UpperHex => (radix_prefix("0x"), format!("{:X}", bits)),
+        SpecKind::Binary => (radix_prefix("0b"), format!("{:b}", bits)),
+        SpecKind::Octal => (radix_prefix("0o"), format!("{:o}", bits)),
+        _ => {
+            let (negative, digits) = match sign {
+                Sign::U => (false, bits.to_string()),
+                Sign::I => {
+                    let shift = 64 - width.bytes() * 8;
+                    let val = ((bits << shift) as i64) >> shift;
+                    (val < 0, val.unsigned_abs().to_string())
+                }
+            };
+            (sign_prefix(spec, negative), digits)
+        }
+    }
+}
+
+fn float_parts<F: Display + LowerExp>(spec: &Spec, negative: bool, abs: F) -> (String, String) {
+    let body = match (spec.kind, spec.precision) {
+        (SpecKind::Exp, Some(precision)) => format!("{:.*e}", precision, abs),
+        (SpecKind::Exp, None) => format!("{:e}", abs),
+        (_, Some(precision)) => format!("{:.*}", precision, abs),
+        (_, None) => abs.to_string(),
+    };
+    (sign_prefix(spec, negative), body)
+}
+
+fn sign_prefix(spec: &Spec, negative: bool) -> String {
+    if negative {
+        "-".to_string()
+    } else if spec.sign {
+        "+".to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn pad(buf: &mut String, spec: &Spec, numeric: bool, prefix: &str, body: &str) {
+    let len = prefix.chars().count() + body.chars().count();
+    let padding = spec.width.unwrap_or(0).saturating_sub(len);
+
+    if spec.zero && numeric {
+        // zeros go between the sign and the digits
+        buf.push_str(prefix);
+        buf.extend(std::iter::repeat_n('0', padding));
+        buf.push_str(body);
+        return;
+    }
+
+    let align = spec
+        .align
+        .unwrap_or(if numeric { Align::Right } else { Align::Left });
+    let (before, after) = match align {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+
+    buf.extend(std::iter::repeat_n(spec.fill, before));
+    buf.push_str(prefix);
+    buf.push_str(body);
+    buf.extend(std::iter::repeat_n(spec.fill, after));
+}
+
+fn write_debug(ctx: &InterpCtx, buf: &mut String, ty: Ty, addr: usize) -> Result<(), InterpError> {
+    match ty.0 {
+        TyKind::Ref(TyKind::Str) => buf.push_str(&format!("{:?}", read_str(ctx, addr)?)),
+        TyKind::Struct(id) => {
+            let strukt = ctx.tys.strukt(*id);
+            let fields = ctx.tys.fields(*id);
+
+            buf.push_str(strukt.name.as_str());
+            // the active field is not tracked, and reading the others may follow invalid pointers
+            if strukt.union {
+                buf.push_str(" { .. }");
+                return Ok(());
+            }
+            buf.push_str(" {");
+            for (i, field) in strukt.fields.iter().enumerate() {
+                if i > 0 {
+                    buf.push(',');
+                }
+                let (_, offset) = fields.fields[&field.name.sym];
+                buf.push(' ');
+                buf.push_str(field.name.as_str());
+                buf.push_str(": ");
+                write_debug(ctx, buf, field.ty, addr + offset as usize)?;
+            }
+            if !strukt.fields.is_empty() {
+                buf.push(' ');
+            }
+            buf.push('}');
+        }
+        TyKind::Tuple(elems) => {
+            buf.push('(');
+            for (i, elem) in elems.iter().enumerate() {
+                if i > 0 {
+                    buf.push_str(", ");
+                }
+                let offset = ctx.tys.tuple_offset(elems, i) as usize;
+                write_debug(ctx, buf, Ty(elem), addr + offset)?;
+            }
+            if elems.len() == 1 {
+                buf.push(',');
+            }
+            buf.push(')');
+        }
+        TyKind::Array(len, inner) => write_list(ctx, buf, Ty(inner), addr, *len)?,
+        TyKind::Ref(TyKind::Slice(inner)) => {
+            let ptr = read(ctx, addr, Width::PTR) as usize;
+            let len = read(ctx, addr + Width::PTR.bytes(), Width::SIZE) as usize;
+            ctx.check_addr(ptr, len.saturating_mul(Ty(inner).size(ctx.tys)))?;
+            write_list(ctx, buf, Ty(inner), ptr, len)?;
+        }
+        _ => write_arg(ctx, buf, ty, addr, &Spec::default())?,
+    }
+    Ok(())
+}
+
+fn write_list(
+    ctx: &InterpCtx,
+    buf: &mut String,
+    elem: Ty,
+    addr: usize,
+    len: usize,
+) -> Result<(), InterpError> {
+    let size = elem.size(ctx.tys);
+    buf.push('[');
+    for i in 0..len {
+        if i > 0 {
+            buf.push_str(", ");
+        }
+        write_debug(ctx, buf, elem, addr + i * size)?;
+    }
+    buf.push(']');
+    Ok(())
+}
+
+fn read(ctx: &InterpCtx, addr: usize, width: Width) -> u64 {
+    ctx.stack.read_some_bits_with_addr(addr, width).to_u64()
+}
+
+fn read_str<'a>(ctx: &InterpCtx, addr: usize) -> Result<&'a str, InterpError> {
+    let ptr = read(ctx, addr, Width::PTR) as usize;
+    let len = read(ctx, addr + Width::PTR.bytes(), Width::SIZE) as usize;
+    ctx.check_addr(ptr, len)?;
+    Ok(unsafe { str::from_raw_parts(ptr as *const u8, len) })
+}
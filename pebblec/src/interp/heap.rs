@@ -0,0 +1,79 @@
+use super::InterpError;
+use std::alloc::Layout;
+use std::collections::BTreeMap;
+
+/// Memory requested by a program through the `alloc` family of intrinsics.
+///
+/// Allocations that are never freed are released when the `Heap` is dropped.
+#[derive(Debug, Default)]
+pub struct Heap {
+    allocs: BTreeMap<usize, Layout>,
+}
+
+impl Heap {
+    /// Allocates memory for the interpreter itself, whose layouts are always valid.
+    #[track_caller]
+    pub fn alloc(&mut self, bytes: usize, align: usize) -> usize {
+        self.try_alloc(bytes, align)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_alloc(&mut self, bytes: usize, align: usize) -> Result<usize, InterpError> {
+        let layout = Layout::from_size_align(bytes.max(1), align)
+            .map_err(|_| InterpError::Alloc { bytes, align })?;
+
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        if ptr.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+
+        self.allocs.insert(ptr.addr(), layout);
+        Ok(ptr.addr())
+    }
+
+    pub fn realloc(&mut self, addr: usize, bytes: usize) -> Result<usize, InterpError> {
+        let layout = *self.allocs.get(&addr).ok_or(InterpError::Realloc(addr))?;
+        let new_layout = Layout::from_size_align(bytes.max(1), layout.align()).map_err(|_| {
+            InterpError::Alloc {
+                bytes,
+                align: layout.align(),
+            }
+        })?;
+
+        let ptr = unsafe { std::alloc::realloc(addr as *mut u8, layout, new_layout.size()) };
+        if ptr.is_null() {
+            std::alloc::handle_alloc_error(new_layout);
+        }
+
+        self.allocs.remove(&addr);
+        self.allocs.insert(ptr.addr(), new_layout);
+        Ok(ptr.addr())
+    }
+
+    /// Freeing a null pointer does nothing.
+    pub fn free(&mut self, addr: usize) -> Result<(), InterpError> {
+        if addr == 0 {
+            return Ok(());
+        }
+
+        let layout = self.allocs.remove(&addr).ok_or(InterpError::Free(addr))?;
+        unsafe { std::alloc::dealloc(addr as *mut u8, layout) };
+        Ok(())
+    }
+
+    /// Whether `bytes` bytes at `addr` lie within a live allocation.
+    pub fn contains(&self, addr: usize, bytes: usize) -> bool {
+        self.allocs
+            .range(..=addr)
+            .next_back()
+            .is_some_and(|(start, layout)| addr.saturating_add(bytes) <= start + layout.size())
+    }
+}
+
+impl Drop for Heap {
+    fn drop(&mut self) {
+        for (addr, layout) in std::mem::take(&mut self.allocs) {
+            unsafe { std::alloc::dealloc(addr as *mut u8, layout) };
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use super::value::{HostType, Value};
+use crate::air::AirSig;
+use crate::ir::ty::TyKind;
+
+/// A Rust closure that Pebble calls through an `extern("host")` block.
+pub struct HostFunc {
+    params: Vec<fn(&TyKind) -> bool>,
+    ret: fn(&TyKind) -> bool,
+    func: Box<dyn FnMut(Vec<Value>) -> Value>,
+}
+
+impl HostFunc {
+    /// Whether the closure accepts the parameters of `sig` and returns its type.
+    pub fn matches(&self, sig: &AirSig) -> bool {
+        self.params.len() == sig.params.len()
+            && self
+                .params
+                .iter()
+                .zip(sig.params.iter())
+                .all(|(param, ty)| param(ty.0))
+            && (self.ret)(sig.ty.0)
+    }
+
+    /// `args` must match the parameters of the closure, see [`HostFunc::matches`].
+    pub fn call(&mut self, args: Vec<Value>) -> Value {
+        (self.func)(args)
+    }
+}
+
+/// Rust closures that can be registered as host functions, with parameters and a return type
+/// that implement [`HostType`].
+pub trait IntoHostFunc<Args> {
+    fn into_host_func(self) -> HostFunc;
+}
+
+macro_rules! impl_into_host_func {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoHostFunc<($($arg,)*)> for F
+        where
+            F: FnMut($($arg),*) -> R + 'static,
+            R: HostType,
+            $($arg: HostType,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_host_func(mut self) -> HostFunc {
+                HostFunc {
+                    params: vec![$($arg::matches),*],
+                    ret: R::matches,
+                    func: Box::new(move |args| {
+                        let mut args = args.into_iter();
+                        $(
+                            let Ok($arg) = $arg::try_from(args.next().unwrap()) else {
+                                unreachable!("host function called with mismatched arguments");
+                            };
+                        )*
+                        self($($arg),*).into()
+                    }),
+                }
+            }
+        }
+    };
+}
+
+impl_into_host_func!();
+impl_into_host_func!(A1);
+impl_into_host_func!(A1, A2);
+impl_into_host_func!(A1, A2, A3);
+impl_into_host_func!(A1, A2, A3, A4);
+impl_into_host_func!(A1, A2, A3, A4, A5);
+impl_into_host_func!(A1, A2, A3, A4, A5, A6);
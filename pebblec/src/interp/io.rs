@@ -0,0 +1,311 @@
+use super::InterpError;
+use super::ctx::InterpCtx;
+use super::value::Value;
+use crate::air::{Args, IoOp, OffsetVar};
+use crate::ir::ty::{TyKind, Width};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::time::UNIX_EPOCH;
+
+// Modes of `io_open`, mirrored by the `FILE_*` consts of `core::io`.
+const READ: u32 = 1;
+const WRITE: u32 = 2;
+const APPEND: u32 = 4;
+const CREATE: u32 = 8;
+const TRUNCATE: u32 = 16;
+
+const STDIN: u64 = 0;
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+// Handle of `File::none`, mirrored by `FILE_NONE` of `core::io`.
+const NONE: u64 = u64::MAX;
+
+/// Result of an [`Air::Io`] operation, mirrored by the `IO_*` consts of `core::io`.
+///
+/// [`Air::Io`]: crate::air::Air::Io
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Status {
+    Ok = 0,
+    NotFound = 1,
+    PermissionDenied = 2,
+    AlreadyExists = 3,
+    InvalidInput = 4,
+    InvalidData = 5,
+    Eof = 6,
+    Other = 7,
+}
+
+impl From<io::Error> for Status {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::AlreadyExists => Self::AlreadyExists,
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidFilename => Self::InvalidInput,
+            io::ErrorKind::InvalidData => Self::InvalidData,
+            io::ErrorKind::UnexpectedEof => Self::Eof,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Files opened by the program through `core::io`.
+///
+/// Handles 0, 1 and 2 refer to stdin, stdout and stderr, and cannot be closed. Handle
+/// `u64::MAX` is the placeholder of `File::none` and is never given to a file.
+#[derive(Debug)]
+pub struct Files {
+    files: HashMap<u64, BufReader<File>>,
+    next: u64,
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self {
+            files: HashMap::default(),
+            next: STDERR + 1,
+        }
+    }
+}
+
+impl Files {
+    pub fn open(&mut self, path: &str, mode: u32) -> Result<u64, Status> {
+        if mode & (READ | WRITE | APPEND) == 0 {
+            return Err(Status::InvalidInput);
+        }
+
+        let file = OpenOptions::new()
+            .read(mode & READ != 0)
+            .write(mode & WRITE != 0)
+            .append(mode & APPEND != 0)
+            .create(mode & CREATE != 0)
+            .truncate(mode & TRUNCATE != 0)
+            .open(path)?;
+        let handle = self.next;
+        if handle == NONE {
+            return Err(Status::Other);
+        }
+        self.next += 1;
+        self.files.insert(handle, BufReader::new(file));
+        Ok(handle)
+    }
+
+    pub fn close(&mut self, handle: u64) -> Result<(), Status> {
+        self.files
+            .remove(&handle)
+            .map(|_| ())
+            .ok_or(Status::InvalidInput)
+    }
+
+    pub fn read(&mut self, handle: u64, buf: &mut [u8]) -> Result<usize, Status> {
+        Ok(match handle {
+            STDIN => io::stdin().read(buf)?,
+            _ => self.file(handle)?.read(buf)?,
+        })
+    }
+
+    pub fn write(&mut self, handle: u64, bytes: &[u8]) -> Result<(), Status> {
+        match handle {
+            STDIN => return Err(Status::InvalidInput),
+            STDOUT => {
+                let mut stdout = io::stdout();
+                stdout.write_all(bytes)?;
+                stdout.flush()?;
+            }
+            STDERR => io::stderr().write_all(bytes)?,
+            _ => {
+                let file = self.file(handle)?;
+                // write at the position the program has read up to, not the end of the buffer
+                if !file.buffer().is_empty() {
+                    let position = file.stream_position()?;
+                    file.seek(SeekFrom::Start(position))?;
+                }
+                file.get_mut().write_all(bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the next line without its line ending, or `None` after the last line.
+    pub fn read_line(&mut self, handle: u64) -> Result<Option<String>, Status> {
+        let mut line = String::new();
+        let read = match handle {
+            STDIN => io::stdin().read_line(&mut line)?,
+            _ => self.file(handle)?.read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    pub fn read_all(&mut self, handle: u64) -> Result<String, Status> {
+        let mut contents = String::new();
+        match handle {
+            STDIN => io::stdin().read_to_string(&mut contents)?,
+            _ => self.file(handle)?.read_to_string(&mut contents)?,
+        };
+        Ok(contents)
+    }
+
+    fn file(&mut self, handle: u64) -> Result<&mut BufReader<File>, Status> {
+        self.files.get_mut(&handle).ok_or(Status::InvalidInput)
+    }
+}
+
+/// Executes `op` with `args`, the params declared for it in `core/io.peb`.
+///
+/// The pointers among `args` are checked before any file is touched.
+pub fn execute(ctx: &mut InterpCtx, op: IoOp, args: &Args) -> Result<Status, InterpError> {
+    let arg = |i: usize| OffsetVar::zero(args.vars[i].1);
+    let ptr = |ctx: &InterpCtx, i: usize| ctx.stack.read_var::<u64>(arg(i)) as usize;
+    match op {
+        IoOp::Open => {
+            check_str(ctx, arg(0))?;
+            ctx.check_addr(ptr(ctx, 2), Width::W64.bytes())?;
+        }
+        IoOp::Close => {}
+        IoOp::Read => {
+            ctx.check_addr(ptr(ctx, 1), ctx.stack.read_var::<u64>(arg(2)) as usize)?;
+            ctx.check_addr(ptr(ctx, 3), Width::W64.bytes())?;
+        }
+        IoOp::Write => check_str(ctx, arg(1))?,
+        IoOp::ReadLine | IoOp::ReadAll => ctx.check_addr(ptr(ctx, 1), TyKind::FAT_PTR_SIZE)?,
+        IoOp::Metadata => {
+            check_str(ctx, arg(0))?;
+            let TyKind::Ref(meta) = args.vars[1].0.0 else {
+                unreachable!("invalid `io_metadata` signature");
+            };
+            ctx.check_addr(ptr(ctx, 1), meta.size(ctx.tys))?;
+        }
+    }
+
+    let result = match op {
+        // (path: &str, mode: u32, handle: &u64)
+        IoOp::Open => path(ctx, arg(0)).and_then(|path| {
+            let mode = ctx.stack.read_var::<u32>(arg(1));
+            let handle = ctx.files.open(path, mode)?;
+            unsafe { write_u64(ctx.stack.read_var::<u64>(arg(2)), handle) };
+            Ok(())
+        }),
+        // (handle: u64)
+        IoOp::Close => ctx.files.close(ctx.stack.read_var::<u64>(arg(0))),
+        // (handle: u64, buf: &u8, len: u64, read: &u64)
+        IoOp::Read => {
+            let handle = ctx.stack.read_var::<u64>(arg(0));
+            let ptr = ctx.stack.read_var::<u64>(arg(1));
+            let len = ctx.stack.read_var::<u64>(arg(2));
+            let buf = unsafe { std::slice::from_raw_parts_mut(ptr as *mut u8, len as usize) };
+            ctx.files
+                .read(handle, buf)
+                .map(|read| unsafe { write_u64(ctx.stack.read_var::<u64>(arg(3)), read as u64) })
+        }
+        // (handle: u64, bytes: &str)
+        IoOp::Write => {
+            let handle = ctx.stack.read_var::<u64>(arg(0));
+            let bytes = str_bytes(ctx, arg(1));
+            ctx.files.write(handle, bytes)
+        }
+        // (handle: u64, line: &&str)
+        IoOp::ReadLine => {
+            let handle = ctx.stack.read_var::<u64>(arg(0));
+            match ctx.files.read_line(handle) {
+                Ok(Some(line)) => {
+                    write_str(ctx, ctx.stack.read_var::<u64>(arg(1)), &line);
+                    Ok(())
+                }
+                Ok(None) => Err(Status::Eof),
+                Err(status) => Err(status),
+            }
+        }
+        // (handle: u64, contents: &&str)
+        IoOp::ReadAll => {
+            let handle = ctx.stack.read_var::<u64>(arg(0));
+            ctx.files.read_all(handle).map(|contents| {
+                write_str(ctx, ctx.stack.read_var::<u64>(arg(1)), &contents);
+            })
+        }
+        // (path: &str, meta: &Metadata)
+        IoOp::Metadata => path(ctx, arg(0)).and_then(|path| {
+            let meta = std::fs::metadata(path)?;
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_secs());
+            let TyKind::Ref(ty @ TyKind::Struct(id)) = args.vars[1].0.0 else {
+                unreachable!("invalid `io_metadata` signature");
+            };
+            let field = |name: &str| match name {
+                "size" => Value::U64(meta.len()),
+                "is_dir" => Value::Bool(meta.is_dir()),
+                "readonly" => Value::Bool(meta.permissions().readonly()),
+                "modified" => Value::U64(modified),
+                field => unreachable!("unknown `Metadata` field `{field}`"),
+            };
+            let strukt = ctx.tys.strukt(*id);
+            let value = Value::Struct {
+                name: strukt.name.as_str().to_string(),
+                fields: strukt
+                    .fields
+                    .iter()
+                    .map(|f| (f.name.as_str().to_string(), field(f.name.as_str())))
+                    .collect(),
+            };
+            let addr = ctx.stack.read_var::<u64>(arg(1)) as usize;
+            let written = unsafe {
+                value.write(ty, ctx.tys, addr, &mut |_| -> Result<usize, Infallible> {
+                    unreachable!("`Metadata` does not contain strings")
+                })
+            };
+            let Ok(()) = written;
+            Ok(())
+        }),
+    };
+
+    Ok(match result {
+        Ok(()) => Status::Ok,
+        Err(status) => status,
+    })
+}
+
+fn check_str(ctx: &InterpCtx, var: OffsetVar) -> Result<(), InterpError> {
+    let ptr = ctx.stack.read_var::<u64>(var);
+    let len = ctx.stack.read_var::<u64>(var.add(Width::PTR));
+    ctx.check_addr(ptr as usize, len as usize)
+}
+
+fn str_bytes<'a>(ctx: &InterpCtx, var: OffsetVar) -> &'a [u8] {
+    let ptr = ctx.stack.read_var::<u64>(var);
+    let len = ctx.stack.read_var::<u64>(var.add(Width::PTR));
+    unsafe { std::slice::from_raw_parts(ptr as *const u8, len as usize) }
+}
+
+fn path<'a>(ctx: &InterpCtx, var: OffsetVar) -> Result<&'a str, Status> {
+    str::from_utf8(str_bytes(ctx, var)).map_err(|_| Status::InvalidInput)
+}
+
+/// Copies `str` into the heap and writes a `&str` that points to it to `addr`. The program
+/// releases it with `free`.
+fn write_str(ctx: &mut InterpCtx, addr: u64, str: &str) {
+    let bytes = ctx.heap.alloc(str.len(), 1);
+    unsafe {
+        std::ptr::copy_nonoverlapping(str.as_ptr(), bytes as *mut u8, str.len());
+        write_u64(addr, bytes as u64);
+        write_u64(addr + Width::PTR.bytes() as u64, str.len() as u64);
+    }
+}
+
+unsafe fn write_u64(addr: u64, val: u64) {
+    unsafe { (addr as *mut u64).write_unaligned(val) };
+}
@@ -1,38 +1,404 @@
+use self::callback::{Bound, Callback};
 use self::ctx::InterpCtx;
+use self::env::Env;
+use self::rand::Seeds;
+use self::stack::StackOverflow;
+use self::time::Clock;
 use crate::air::{
     Air, AirFunc, AirLinkage, AirSig, Bits, ByteCode, ConstData, IntKind, OffsetVar, Prim,
 };
+use crate::ir::sig::is_print;
 use crate::ir::ty::store::TyStore;
 use crate::ir::ty::{FloatTy, Sign, Ty, TyKind, Width};
 use core::str;
 use libffi::low::CodePtr;
 use libffi::middle::{Arg, Cif, Type};
 use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::ffi::{c_char, c_void};
 use std::ops::{Add, Div, Mul, Rem, Shl, Shr, Sub};
 use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+use thiserror::Error;
 
+pub use self::host::{HostFunc, IntoHostFunc};
+pub use self::value::{HostType, Value};
+
+mod callback;
 mod ctx;
+mod env;
+mod format;
+mod heap;
+mod host;
+mod io;
+mod rand;
 mod stack;
+mod time;
+mod value;
+
+/// Size of the interpreter stack in bytes when none is provided.
+pub const DEFAULT_STACK_SIZE: usize = 8_000_000;
 
+/// Executes [`ByteCode`], either from `main` with [`InterpInstance::run`] or by embedding it in a
+/// Rust application.
+///
+/// The consts are evaluated before the first function runs, and the state of the interpreter,
+/// such as the value of the consts, persists between calls.
 pub struct InterpInstance<'a> {
     bytecode: &'a ByteCode<'a>,
+    ctx: InterpCtx<'a>,
+    libs: HashMap<&'a str, libloading::Library>,
+    initialized: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum InterpError {
+    #[error("host function `{0}` is declared but not registered")]
+    UnknownHost(String),
+    #[error("host function `{name}` does not match its declaration `{sig}`")]
+    HostSignature { name: String, sig: String },
+    #[error("no function named `{0}`")]
+    UnknownFunction(String),
+    #[error("`{func}` takes {expected} arguments, found {found}")]
+    Arguments {
+        func: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("argument {index} of `{func}` expected `{expected}`, found `{found}`")]
+    ArgumentType {
+        func: String,
+        index: usize,
+        expected: String,
+        found: String,
+    },
+    #[error("no const named `{0}`")]
+    UnknownGlobal(String),
+    #[error("`{name}` has type `{expected}`, found `{found}`")]
+    GlobalType {
+        name: String,
+        expected: String,
+        found: String,
+    },
+    #[error("values of type `{0}` cannot be passed between Rust and Pebble")]
+    Unsupported(String),
+    #[error("stack overflow")]
+    StackOverflow,
+    #[error("panicked in `{0}`")]
+    Panicked(String),
+    #[error("invalid memory: {addr:#x} is not allocated")]
+    InvalidAddress { addr: usize, bytes: usize },
+    #[error("invalid allocation: {bytes} bytes with an alignment of {align}")]
+    Alloc { bytes: usize, align: usize },
+    #[error("invalid realloc: {0:#x} is not a heap allocation")]
+    Realloc(usize),
+    #[error("invalid free: {0:#x} is not a heap allocation")]
+    Free(usize),
+    #[error("invalid function pointer: {0:#x}")]
+    InvalidFnPtr(u64),
+    #[error("invalid format string {fmt:?}: {msg}")]
+    Format { fmt: String, msg: String },
 }
 
 impl<'a> InterpInstance<'a> {
     pub fn new(bytecode: &'a ByteCode<'a>) -> Self {
-        Self { bytecode }
+        Self::with_stack_size(bytecode, DEFAULT_STACK_SIZE)
+    }
+
+    /// Creates an instance whose stack holds `bytes` bytes. Exceeding it aborts execution with a
+    /// stack overflow.
+    pub fn with_stack_size(bytecode: &'a ByteCode<'a>, bytes: usize) -> Self {
+        Self {
+            bytecode,
+            ctx: InterpCtx::new(&bytecode.tys, &bytecode.bss, &bytecode.funcs, bytes),
+            libs: HashMap::default(),
+            initialized: false,
+        }
+    }
+
+    /// Arguments passed to `main: (args: &[&str])` and returned by `core::env::args`.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.ctx.env = Env::new(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Replaces the clock of `core::time` with a fake one that starts at 0 and only moves with
+    /// [`InterpInstance::advance_clock`] or when the program sleeps, which returns immediately.
+    pub fn fake_clock(mut self) -> Self {
+        self.ctx.clock = Clock::fake();
+        self
+    }
+
+    /// Moves the fake clock forward by `duration`. Has no effect unless
+    /// [`InterpInstance::fake_clock`] is used.
+    pub fn advance_clock(&mut self, duration: Duration) {
+        self.ctx.clock.advance(duration);
     }
 
-    pub fn run(&self, log: bool) -> i32 {
-        let libs = load_libraries(self.bytecode.extern_sigs.values().copied());
-        let main = self
+    /// Pins the seed of `core::rand`, so that every run of the program produces the same numbers.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.ctx.seeds = Seeds::new(seed);
+        self
+    }
+
+    /// Registers `func` for the host function `name`, which Pebble declares within an
+    /// `extern("host")` block. The parameters and return type of `func` must match the
+    /// declaration.
+    pub fn host<Args>(mut self, name: impl Into<String>, func: impl IntoHostFunc<Args>) -> Self {
+        self.ctx.host.insert(name.into(), func.into_host_func());
+        self
+    }
+
+    pub fn run(&mut self, log: bool) -> i32 {
+        let main = self.init().and_then(|()| {
+            self.bytecode
+                .funcs
+                .iter()
+                .find(|f| f.sig.ident == "main")
+                .ok_or_else(|| InterpError::UnknownFunction("main".to_string()))
+        });
+        let main = match main {
+            Ok(main) => main,
+            // already reported
+            Err(InterpError::StackOverflow | InterpError::Panicked(_)) => return 1,
+            Err(err) => {
+                eprintln!("error: {err}");
+                return 1;
+            }
+        };
+        // `main: (args: &[&str])` receives a copy of the slice returned by `core::env::args`
+        let args = match main.params.first() {
+            Some(param) => {
+                let addr = match self.ctx.stack.anon_alloc(TyKind::FAT_PTR_SIZE) {
+                    Ok(addr) => addr,
+                    Err(overflow) => {
+                        self.ctx.report_stack_overflow(overflow);
+                        return 1;
+                    }
+                };
+                let args = self.ctx.env.args(&mut self.ctx.heap);
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        args as *const u8,
+                        addr as *mut u8,
+                        TyKind::FAT_PTR_SIZE,
+                    )
+                };
+                Some((*param, addr))
+            }
+            None => None,
+        };
+        self.ctx.start_entry(main);
+        if let Some((param, addr)) = args {
+            self.ctx.stack.point(param, addr);
+        }
+        match self.execute(log) {
+            Ok(()) => self.ctx.a.r() as i32,
+            Err(_) => 1,
+        }
+    }
+
+    /// Calls the function `name` with `args` and returns its result.
+    ///
+    /// Arguments and results are copied, so a `&str` argument is only valid during the call and
+    /// a returned `&str` must not point into the stack frame of `name`.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, InterpError> {
+        self.init()?;
+
+        let tys = &self.bytecode.tys;
+        let func = self
             .bytecode
             .funcs
             .iter()
-            .find(|f| f.sig.ident == "main")
-            .unwrap();
-        entry(main, self.bytecode, &libs, log)
+            .find(|f| f.sig.ident == name)
+            .ok_or_else(|| InterpError::UnknownFunction(name.to_string()))?;
+        let sig = func.sig;
+        if args.len() != sig.params.len() {
+            return Err(InterpError::Arguments {
+                func: name.to_string(),
+                expected: sig.params.len(),
+                found: args.len(),
+            });
+        }
+        for (index, (arg, ty)) in args.iter().zip(sig.params.iter()).enumerate() {
+            if !arg.matches(ty.0, tys) {
+                return Err(InterpError::ArgumentType {
+                    func: name.to_string(),
+                    index,
+                    expected: ty.to_string_with(tys),
+                    found: arg.ty_string(tys),
+                });
+            }
+        }
+        if !Value::supports(sig.ty.0, tys) {
+            return Err(InterpError::Unsupported(sig.ty.to_string_with(tys)));
+        }
+
+        let position = self.ctx.position();
+        let sp = self.ctx.stack.sp();
+        let result = self.call_func(func, args);
+        self.ctx.unwind_to(0);
+        self.ctx.restore_position(position);
+        *self.ctx.stack.sp_mut() = sp;
+        result
+    }
+
+    fn call_func(&mut self, func: &'a AirFunc<'a>, args: &[Value]) -> Result<Value, InterpError> {
+        let mut addrs = Vec::with_capacity(args.len());
+        for (arg, ty) in args.iter().zip(func.sig.params.iter()) {
+            let layout = self.ctx.tys.ty_layout(*ty);
+            let tys = self.ctx.tys;
+            let stack = &mut self.ctx.stack;
+            let written = stack
+                .anon_alloc_aligned(layout.size, layout.alignment)
+                .and_then(|addr| unsafe {
+                    arg.write(ty.0, tys, addr, &mut |bytes| stack.anon_alloc(bytes))?;
+                    Ok(addr)
+                });
+            let addr = written.map_err(|overflow| {
+                self.ctx.report_stack_overflow(overflow);
+                InterpError::StackOverflow
+            })?;
+            addrs.push(addr);
+        }
+
+        self.ctx.start_entry(func);
+        for (param, addr) in func.params.iter().zip(addrs) {
+            self.ctx.stack.point(*param, addr);
+        }
+        self.execute(false)?;
+
+        let result = self.ctx.a.r();
+        Ok(match Value::from_bits(func.sig.ty.0, result) {
+            Some(value) => value,
+            // composite results are returned by address
+            None => unsafe { Value::read(func.sig.ty.0, self.ctx.tys, result as usize) }.unwrap(),
+        })
+    }
+
+    /// Reads the value of the const `name`.
+    pub fn global(&mut self, name: &str) -> Result<Value, InterpError> {
+        self.init()?;
+        let (addr, ty) = self.global_addr(name)?;
+        Ok(unsafe { Value::read(ty.0, self.ctx.tys, addr) }.unwrap())
+    }
+
+    /// Assigns `value` to the const `name`, which must have the same type.
+    ///
+    /// Strings are copied into the heap of the interpreter, where they live as long as the
+    /// instance.
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) -> Result<(), InterpError> {
+        self.init()?;
+        let value = value.into();
+        let (addr, ty) = self.global_addr(name)?;
+        let tys = self.ctx.tys;
+        if !value.matches(ty.0, tys) {
+            return Err(InterpError::GlobalType {
+                name: name.to_string(),
+                expected: ty.to_string_with(tys),
+                found: value.ty_string(tys),
+            });
+        }
+        let heap = &mut self.ctx.heap;
+        let written = unsafe {
+            value.write(ty.0, tys, addr, &mut |bytes| {
+                Ok::<_, Infallible>(heap.alloc(bytes, 1))
+            })
+        };
+        let Ok(()) = written;
+        Ok(())
+    }
+
+    fn global_addr(&self, name: &str) -> Result<(usize, Ty), InterpError> {
+        let (var, ty) = self
+            .bytecode
+            .globals
+            .get(name)
+            .ok_or_else(|| InterpError::UnknownGlobal(name.to_string()))?;
+        if !Value::supports(ty.0, self.ctx.tys) {
+            return Err(InterpError::Unsupported(ty.to_string_with(self.ctx.tys)));
+        }
+        Ok((self.ctx.stack.var_addr(OffsetVar::zero(*var)), *ty))
+    }
+
+    /// Checks the host functions, loads the external libraries and evaluates the consts.
+    fn init(&mut self) -> Result<(), InterpError> {
+        if self.initialized {
+            return Ok(());
+        }
+
+        for sig in self.bytecode.extern_sigs.values() {
+            if sig.linkage != AirLinkage::Host {
+                continue;
+            }
+            match self.ctx.host.get(sig.ident) {
+                Some(func) if func.matches(sig) => {}
+                Some(_) => return Err(host_signature(sig, &self.bytecode.tys)),
+                None => return Err(InterpError::UnknownHost(sig.ident.to_string())),
+            }
+        }
+
+        self.libs = load_libraries(self.bytecode.extern_sigs.values().copied());
+        self.ctx.foreign = !self.libs.is_empty();
+        self.ctx.consts(&self.bytecode.consts);
+        self.execute(false)?;
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Executes instructions until the entry function returns, or until the consts are
+    /// evaluated. Stack overflows, panics and mismatched host results are reported before they
+    /// are returned.
+    fn execute(&mut self, log: bool) -> Result<(), InterpError> {
+        let funcs = &self.bytecode.funcs;
+        loop {
+            let libs = AssertUnwindSafe(&self.libs);
+            let mut unwind_ctx = AssertUnwindSafe(&mut self.ctx);
+            match std::panic::catch_unwind(move || execute(&mut unwind_ctx, funcs, &libs, log)) {
+                Ok(result) => match result {
+                    InstrResult::Break => return Ok(()),
+                    InstrResult::Continue => continue,
+                    InstrResult::Ok => {
+                        self.ctx.incr_instr();
+                    }
+                    InstrResult::StackOverflow(overflow) => {
+                        self.ctx.report_stack_overflow(overflow);
+                        self.ctx.a.w(1);
+                        return Err(InterpError::StackOverflow);
+                    }
+                    InstrResult::Err(err) => {
+                        eprintln!("error: {err}");
+                        self.ctx.report_backtrace();
+                        self.ctx.a.w(1);
+                        return Err(err);
+                    }
+                },
+                Err(_) => {
+                    self.ctx.report_backtrace();
+                    self.ctx.a.w(1);
+                    let func = self.ctx.func_ident().unwrap_or("<const>");
+                    return Err(InterpError::Panicked(func.to_string()));
+                }
+            }
+        }
+    }
+}
+
+fn host_signature(sig: &AirSig, tys: &TyStore) -> InterpError {
+    let params = sig
+        .params
+        .iter()
+        .map(|ty| ty.to_string_with(tys))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let ret = if sig.ty.is_unit() {
+        String::new()
+    } else {
+        format!(" -> {}", sig.ty.to_string_with(tys))
+    };
+    InterpError::HostSignature {
+        name: sig.ident.to_string(),
+        sig: format!("({params}){ret}"),
     }
 }
 
@@ -61,55 +427,6 @@ fn load_libraries<'a>(
         .collect()
 }
 
-fn entry(
-    main: &AirFunc,
-    bytecode: &ByteCode,
-    libs: &HashMap<&str, libloading::Library>,
-    log: bool,
-) -> i32 {
-    let mut ctx = InterpCtx::new(&bytecode.tys, &bytecode.bss);
-
-    ctx.consts(&bytecode.consts);
-
-    loop {
-        let bytecode = AssertUnwindSafe(bytecode);
-        let mut unwind_ctx = AssertUnwindSafe(&mut ctx);
-        match std::panic::catch_unwind(move || execute(&mut unwind_ctx, &bytecode.funcs, libs, log))
-        {
-            Ok(result) => match result {
-                InstrResult::Break => break,
-                InstrResult::Continue => continue,
-                InstrResult::Ok => {
-                    ctx.incr_instr();
-                }
-            },
-            Err(_) => todo!("more information required"),
-        }
-    }
-
-    ctx.start_func(main);
-    loop {
-        let bytecode = AssertUnwindSafe(bytecode);
-        let mut unwind_ctx = AssertUnwindSafe(&mut ctx);
-        match std::panic::catch_unwind(move || execute(&mut unwind_ctx, &bytecode.funcs, libs, log))
-        {
-            Ok(result) => match result {
-                InstrResult::Break => break,
-                InstrResult::Continue => continue,
-                InstrResult::Ok => {
-                    ctx.incr_instr();
-                }
-            },
-            Err(_) => {
-                ctx.report_backtrace();
-                ctx.a.w(1);
-                break;
-            }
-        }
-    }
-    ctx.a.r() as i32
-}
-
 macro_rules! float_op {
     ($ctx:expr, $width:expr, $op:tt) => {
         match $width {
@@ -314,10 +631,22 @@ macro_rules! bit_op {
     };
 }
 
+/// Unwraps `result`, or returns its error from [`execute`].
+macro_rules! instr_try {
+    ($result:expr) => {
+        match $result {
+            Ok(val) => val,
+            Err(err) => return InstrResult::Err(err),
+        }
+    };
+}
+
 enum InstrResult {
     Break,
     Continue,
     Ok,
+    StackOverflow(StackOverflow),
+    Err(InterpError),
 }
 
 fn execute<'a>(
@@ -346,118 +675,51 @@ fn execute<'a>(
                         .unwrap_or_else(|| panic!("invalid func"));
                     ctx.start_func(func);
 
-                    if sig.ident == "print" || sig.ident == "println" {
+                    if is_print(sig.ident) {
                         let (ty, fmt) = args.vars.first().unwrap();
                         assert_eq!(*ty, Ty::STR_LIT);
                         let fmt = OffsetVar::zero(*fmt);
                         let addr = ctx.stack.read_var::<u64>(fmt);
                         let len = ctx.stack.read_var::<u64>(fmt.add(Width::W64));
+                        instr_try!(ctx.check_addr(addr as usize, len as usize));
                         let str = unsafe { str::from_raw_parts(addr as *const u8, len as usize) };
 
-                        enum Entry {
-                            Char(char),
-                            Arg,
-                        }
+                        let args = args
+                            .vars
+                            .iter()
+                            .skip(1)
+                            .map(|(ty, var)| (*ty, ctx.stack.var_addr(OffsetVar::zero(*var))))
+                            .collect::<Vec<_>>();
 
-                        let mut args = args.vars.iter();
-                        let _fmt = args.next();
-                        let mut buf = String::with_capacity(str.len());
-                        for entry in str
-                            .chars()
-                            .map(|c| if c == '%' { Entry::Arg } else { Entry::Char(c) })
-                        {
-                            match entry {
-                                Entry::Char(c) => buf.push(c),
-                                Entry::Arg => {
-                                    print!("{}", buf);
-                                    buf.clear();
-                                    match args.next() {
-                                        Some((ty, var)) => {
-                                            let var = OffsetVar::zero(*var);
-                                            match ty.0 {
-                                                TyKind::Int(ty) => match ty.kind() {
-                                                    IntKind::U8 => {
-                                                        print!("{}", ctx.stack.read_var::<u8>(var))
-                                                    }
-                                                    IntKind::U16 => {
-                                                        print!("{}", ctx.stack.read_var::<u16>(var))
-                                                    }
-                                                    IntKind::U32 => {
-                                                        print!("{}", ctx.stack.read_var::<u32>(var))
-                                                    }
-                                                    IntKind::U64 => {
-                                                        print!("{}", ctx.stack.read_var::<u64>(var))
-                                                    }
-                                                    IntKind::I8 => {
-                                                        print!("{}", ctx.stack.read_var::<i8>(var))
-                                                    }
-                                                    IntKind::I16 => {
-                                                        print!("{}", ctx.stack.read_var::<i16>(var))
-                                                    }
-                                                    IntKind::I32 => {
-                                                        print!("{}", ctx.stack.read_var::<i32>(var))
-                                                    }
-                                                    IntKind::I64 => {
-                                                        print!("{}", ctx.stack.read_var::<i64>(var))
-                                                    }
-                                                },
-                                                TyKind::Float(float) => match float {
-                                                    FloatTy::F32 => {
-                                                        print!("{}", ctx.stack.read_var::<f32>(var))
-                                                    }
-                                                    FloatTy::F64 => {
-                                                        print!("{}", ctx.stack.read_var::<f64>(var))
-                                                    }
-                                                },
-                                                TyKind::Bool => {
-                                                    print!("{}", ctx.stack.read_var::<u8>(var) == 1)
-                                                }
-                                                TyKind::Ref(TyKind::Str) => {
-                                                    let ptr = ctx
-                                                        .stack
-                                                        .read_some_bits(var, Width::SIZE)
-                                                        .to_u64();
-                                                    let len = ctx
-                                                        .stack
-                                                        .read_some_bits(
-                                                            var.add(Width::PTR),
-                                                            Width::SIZE,
-                                                        )
-                                                        .to_u64();
-                                                    print!("{}", unsafe {
-                                                        str::from_raw_parts(
-                                                            ptr as *const u8,
-                                                            len as usize,
-                                                        )
-                                                    })
-                                                }
-                                                TyKind::Ref(_) => {
-                                                    print!("{:#x}", ctx.stack.read_var::<u64>(var))
-                                                }
-                                                ty => unimplemented!("print arg: {ty:?}"),
-                                            }
-                                        }
-                                        None => panic!("expected more args in print"),
-                                    }
-                                }
-                            }
-                        }
-
-                        assert_eq!(12.4, f32::from_le_bytes((12.4f32).to_le_bytes()));
-
-                        if args.next().is_some() {
-                            panic!("too many args in printf");
-                        }
-
-                        print!("{}", buf);
-                        if sig.ident == "println" {
-                            println!();
+                        let str = instr_try!(format::format(ctx, str, &args));
+                        match sig.ident {
+                            "print" => print!("{str}"),
+                            "println" => println!("{str}"),
+                            "eprint" => eprint!("{str}"),
+                            _ => eprintln!("{str}"),
                         }
-                        buf.clear();
                     }
 
                     return InstrResult::Continue;
                 }
+                AirLinkage::Host => {
+                    let args = args
+                        .vars
+                        .iter()
+                        .map(|(ty, var)| {
+                            let addr = ctx.stack.var_addr(OffsetVar::zero(*var));
+                            unsafe { Value::read(ty.0, ctx.tys, addr) }
+                                .expect("host functions are checked by `InterpInstance::init`")
+                        })
+                        .collect();
+                    let func = ctx.host.get_mut(sig.ident).unwrap();
+                    let result = func.call(args);
+                    // a closure returning `Value` is only checked against the declaration here
+                    if !result.matches(sig.ty.0, ctx.tys) {
+                        return InstrResult::Err(host_signature(sig, ctx.tys));
+                    }
+                    ctx.a.w(result.to_bits().unwrap());
+                }
                 AirLinkage::External { link } => {
                     // TODO: build these once
 
@@ -465,8 +727,9 @@ fn execute<'a>(
                     let func: libloading::Symbol<*mut c_void> =
                         unsafe { lib.get(sig.ident.as_bytes()).unwrap() };
 
-                    let mut params = Vec::with_capacity(sig.params.len());
-                    for param in sig.params.iter() {
+                    // variadic arguments are typed by the call, see `TyKind::default_promotion`
+                    let mut params = Vec::with_capacity(args.vars.len());
+                    for (param, _) in args.vars.iter() {
                         params.push(param.libffi_type(ctx.tys))
                     }
 
@@ -476,6 +739,21 @@ fn execute<'a>(
                         sig.ty.libffi_type(ctx.tys)
                     };
                     let cif = Cif::new(params.into_iter(), ty);
+                    if sig.variadic {
+                        // `Cif` has no variadic constructor, so the prepared cif is redone in place
+                        unsafe {
+                            let raw = cif.as_raw_ptr();
+                            libffi::low::prep_cif_var(
+                                raw,
+                                (*raw).abi,
+                                sig.params.len(),
+                                args.vars.len(),
+                                (*raw).rtype,
+                                (*raw).arg_types,
+                            )
+                            .expect("invalid variadic call");
+                        }
+                    }
 
                     let size = if sig.ty == Ty::UNIT {
                         0
@@ -483,11 +761,41 @@ fn execute<'a>(
                         sig.ty.size(&ctx.tys)
                     };
 
+                    // Pebble functions are passed as closures that re-enter the interpreter
+                    let ctx_ptr = &raw mut *ctx;
+                    let tys = ctx.tys;
+                    let mut bound = Bound::default();
+                    let callbacks = args
+                        .vars
+                        .iter()
+                        .map(|(ty, v)| match ty.0 {
+                            TyKind::Fn(_, _) | TyKind::Opt(TyKind::Fn(_, _)) => {
+                                let sig = ctx.stack.read_var::<u64>(OffsetVar::zero(*v));
+                                // `null` is passed through as is
+                                if sig == 0 {
+                                    return Ok(None);
+                                }
+                                let func = ctx.fn_ptr(sig)?;
+                                let callback = ctx
+                                    .callbacks
+                                    .entry(func.sig)
+                                    .or_insert_with(|| Callback::new(tys, func, air_funcs, log));
+                                Ok(Some(callback.bind(ctx_ptr, libs, &mut bound)))
+                            }
+                            _ => Ok(None),
+                        })
+                        .collect::<Result<Vec<_>, InterpError>>();
+                    let callbacks = instr_try!(callbacks);
+
                     let args = args
                         .vars
                         .iter()
-                        .map(|(_, v)| {
-                            Arg::new(unsafe { &*ctx.stack.var_ptr_mut(OffsetVar::zero(*v)) })
+                        .zip(callbacks.iter())
+                        .map(|((_, v), callback)| match callback {
+                            Some(code) => Arg::new(code),
+                            None => {
+                                Arg::new(unsafe { &*ctx.stack.var_ptr_mut(OffsetVar::zero(*v)) })
+                            }
                         })
                         .collect::<Vec<_>>();
 
@@ -495,38 +803,80 @@ fn execute<'a>(
                         match size {
                             0 => cif.call::<()>(CodePtr(func.into_raw().as_raw_ptr()), &args),
                             1 => {
-                                let result =
-                                    cif.call::<u8>(CodePtr(func.into_raw().as_raw_ptr()), &args);
+                                // libffi writes a full register for small return values
+                                let result = cif
+                                    .call::<u64>(CodePtr(func.into_raw().as_raw_ptr()), &args)
+                                    as u8;
                                 match sig.ty.0 {
                                     TyKind::Bool => {
                                         ctx.a.w(result as u64);
                                     }
                                     TyKind::Ref(TyKind::Str) => todo!(),
-                                    TyKind::Ref(_) => ctx.a.w(result as u64),
+                                    TyKind::Ref(_) | TyKind::Int(_) | TyKind::Float(_) => {
+                                        ctx.a.w(result as u64)
+                                    }
+                                    TyKind::Struct(_) | TyKind::Tuple(_) => {
+                                        let addr = match ctx.stack.anon_alloc(1) {
+                                            Ok(addr) => addr,
+                                            Err(overflow) => {
+                                                return InstrResult::StackOverflow(overflow);
+                                            }
+                                        };
+                                        ctx.stack.write_bits(Bits::from_u8(result), addr);
+                                        ctx.a.w(addr as u64);
+                                    }
                                     _ => todo!(),
                                 }
                             }
                             2 => {
-                                let result =
-                                    cif.call::<u16>(CodePtr(func.into_raw().as_raw_ptr()), &args);
+                                // libffi writes a full register for small return values
+                                let result = cif
+                                    .call::<u64>(CodePtr(func.into_raw().as_raw_ptr()), &args)
+                                    as u16;
                                 match sig.ty.0 {
                                     TyKind::Bool => {
                                         ctx.a.w(result as u64);
                                     }
                                     TyKind::Ref(TyKind::Str) => todo!(),
-                                    TyKind::Ref(_) => ctx.a.w(result as u64),
+                                    TyKind::Ref(_) | TyKind::Int(_) | TyKind::Float(_) => {
+                                        ctx.a.w(result as u64)
+                                    }
+                                    TyKind::Struct(_) | TyKind::Tuple(_) => {
+                                        let addr = match ctx.stack.anon_alloc(2) {
+                                            Ok(addr) => addr,
+                                            Err(overflow) => {
+                                                return InstrResult::StackOverflow(overflow);
+                                            }
+                                        };
+                                        ctx.stack.write_bits(Bits::from_u16(result), addr);
+                                        ctx.a.w(addr as u64);
+                                    }
                                     _ => todo!(),
                                 }
                             }
                             4 => {
-                                let result =
-                                    cif.call::<u32>(CodePtr(func.into_raw().as_raw_ptr()), &args);
+                                // libffi writes a full register for small return values
+                                let result = cif
+                                    .call::<u64>(CodePtr(func.into_raw().as_raw_ptr()), &args)
+                                    as u32;
                                 match sig.ty.0 {
                                     TyKind::Bool => {
                                         ctx.a.w(result as u64);
                                     }
                                     TyKind::Ref(TyKind::Str) => todo!(),
-                                    TyKind::Ref(_) => ctx.a.w(result as u64),
+                                    TyKind::Ref(_) | TyKind::Int(_) | TyKind::Float(_) => {
+                                        ctx.a.w(result as u64)
+                                    }
+                                    TyKind::Struct(_) | TyKind::Tuple(_) => {
+                                        let addr = match ctx.stack.anon_alloc(4) {
+                                            Ok(addr) => addr,
+                                            Err(overflow) => {
+                                                return InstrResult::StackOverflow(overflow);
+                                            }
+                                        };
+                                        ctx.stack.write_bits(Bits::from_u32(result), addr);
+                                        ctx.a.w(addr as u64);
+                                    }
                                     _ => todo!(),
                                 }
                             }
@@ -539,10 +889,18 @@ fn execute<'a>(
                                         ctx.a.w(result);
                                     }
                                     TyKind::Ref(TyKind::Str) => todo!(),
-                                    TyKind::Ref(_) => ctx.a.w(result),
-                                    TyKind::Struct(id) => {
-                                        let bytes = ctx.tys.struct_layout(*id).size;
-                                        let addr = ctx.stack.anon_alloc(bytes);
+                                    TyKind::Ref(_)
+                                    | TyKind::Opt(_)
+                                    | TyKind::Int(_)
+                                    | TyKind::Float(_) => ctx.a.w(result),
+                                    TyKind::Struct(_) | TyKind::Tuple(_) => {
+                                        let bytes = sig.ty.size(ctx.tys);
+                                        let addr = match ctx.stack.anon_alloc(bytes) {
+                                            Ok(addr) => addr,
+                                            Err(overflow) => {
+                                                return InstrResult::StackOverflow(overflow);
+                                            }
+                                        };
                                         ctx.stack.write_bits(Bits::from_u64(result), addr as usize);
                                         ctx.a.w(addr as u64);
                                     }
@@ -555,6 +913,23 @@ fn execute<'a>(
                 }
             }
         }
+        Air::CallIndirect { callee, args } => {
+            let func = instr_try!(ctx.fn_ptr(ctx.stack.read_var::<u64>(*callee)));
+            assert_eq!(func.params.len(), args.vars.len());
+
+            let addrs = args
+                .vars
+                .iter()
+                .map(|(_, arg)| ctx.stack.var_addr(OffsetVar::zero(*arg)))
+                .collect::<Vec<_>>();
+            // point the parameters within the callee's frame so that the caller's are restored
+            // on return
+            ctx.start_func(func);
+            for (param, addr) in func.params.iter().zip(addrs) {
+                ctx.stack.point(*param, addr);
+            }
+            return InstrResult::Continue;
+        }
 
         Air::IfElse {
             condition,
@@ -573,8 +948,10 @@ fn execute<'a>(
             return InstrResult::Continue;
         }
 
-        Air::SAlloc(var, bytes) => {
-            ctx.stack.alloc(*var, *bytes);
+        Air::SAlloc(var, layout) => {
+            if let Err(overflow) = ctx.stack.alloc(*var, *layout) {
+                return InstrResult::StackOverflow(overflow);
+            }
         }
 
         Air::ReadSP(var) => {
@@ -589,9 +966,14 @@ fn execute<'a>(
             let addr = ctx.stack.var_addr(*var) as u64;
             ctx.w(*reg, addr);
         }
+        Air::FnAddr(reg, sig) => {
+            ctx.w(*reg, *sig as *const AirSig as u64);
+        }
         Air::MemCpy { dst, src, bytes } => {
             let dst = ctx.r(*dst) as usize;
             let src = ctx.r(*src) as usize;
+            instr_try!(ctx.check_addr(dst, *bytes));
+            instr_try!(ctx.check_addr(src, *bytes));
             unsafe { ctx.stack.memcpy(dst, src, *bytes) };
         }
 
@@ -614,6 +996,7 @@ fn execute<'a>(
 
         Air::Read { dst, addr, width } => {
             let addr = ctx.r(*addr);
+            instr_try!(ctx.check_addr(addr as usize, width.bytes()));
             let bits = ctx
                 .stack
                 .read_some_bits_with_addr(addr as usize, *width)
@@ -623,11 +1006,14 @@ fn execute<'a>(
         Air::Write { addr, data, width } => {
             let addr = ctx.r(*addr);
             let data_bits = ctx.r(*data);
+            instr_try!(ctx.check_addr(addr as usize, width.bytes()));
             ctx.stack
                 .write_bits(Bits::from_width(data_bits, *width), addr as usize);
         }
         Air::Deref { dst, addr } => {
             let addr = ctx.r(*addr);
+            // the size of the pointee is not known here, so at least its first byte is checked
+            instr_try!(ctx.check_addr(addr as usize, 1));
             ctx.stack.point(dst.var, dst.offset + addr as usize);
         }
 
@@ -712,7 +1098,21 @@ fn execute<'a>(
                     Prim::Float => unreachable!(),
                 },
                 Prim::Float => match to {
-                    Prim::Float => {}
+                    Prim::Float => match (from_width, to_width) {
+                        (Width::W32, Width::W64) => {
+                            let f = f32::from_le_bytes(
+                                bytes[..std::mem::size_of::<f32>()].try_into().unwrap(),
+                            );
+                            ctx.a.w((f as f64).to_bits());
+                        }
+                        (Width::W64, Width::W32) => {
+                            let f = f64::from_le_bytes(
+                                bytes[..std::mem::size_of::<f64>()].try_into().unwrap(),
+                            );
+                            ctx.a.w((f as f32).to_bits() as u64);
+                        }
+                        _ => {}
+                    },
                     Prim::UInt => match from_width {
                         Width::W32 => {
                             ctx.a.w(f32::from_le_bytes(
@@ -748,42 +1148,9 @@ fn execute<'a>(
                     Prim::Bool => unreachable!(),
                 },
                 Prim::UInt => match to {
-                    Prim::UInt => {}
+                    // the register is already zero extended
+                    Prim::UInt | Prim::Int => {}
                     Prim::Bool => unreachable!(),
-                    Prim::Int => match from_width {
-                        Width::W8 => {
-                            unsafe {
-                                ctx.a.w(std::mem::transmute(u8::from_le_bytes(
-                                    bytes[..std::mem::size_of::<u8>()].try_into().unwrap(),
-                                ) as i8
-                                    as i64))
-                            };
-                        }
-                        Width::W16 => {
-                            unsafe {
-                                ctx.a.w(std::mem::transmute(u16::from_le_bytes(
-                                    bytes[..std::mem::size_of::<u16>()].try_into().unwrap(),
-                                ) as i16
-                                    as i64))
-                            };
-                        }
-                        Width::W32 => {
-                            unsafe {
-                                ctx.a.w(std::mem::transmute(u32::from_le_bytes(
-                                    bytes[..std::mem::size_of::<u32>()].try_into().unwrap(),
-                                ) as i32
-                                    as i64))
-                            };
-                        }
-                        Width::W64 => {
-                            unsafe {
-                                ctx.a.w(std::mem::transmute(u64::from_le_bytes(
-                                    bytes[..std::mem::size_of::<u64>()].try_into().unwrap(),
-                                )
-                                    as i64))
-                            };
-                        }
-                    },
                     Prim::Float => match from_width {
                         Width::W32 => {
                             let f = u32::from_le_bytes(
@@ -819,7 +1186,24 @@ fn execute<'a>(
                     },
                 },
                 Prim::Int => match to {
-                    Prim::Int => {}
+                    Prim::Int => match from_width {
+                        Width::W8 => {
+                            ctx.a.w(i8::from_le_bytes(
+                                bytes[..std::mem::size_of::<i8>()].try_into().unwrap(),
+                            ) as i64 as u64);
+                        }
+                        Width::W16 => {
+                            ctx.a.w(i16::from_le_bytes(
+                                bytes[..std::mem::size_of::<i16>()].try_into().unwrap(),
+                            ) as i64 as u64);
+                        }
+                        Width::W32 => {
+                            ctx.a.w(i32::from_le_bytes(
+                                bytes[..std::mem::size_of::<i32>()].try_into().unwrap(),
+                            ) as i64 as u64);
+                        }
+                        Width::W64 => {}
+                    },
                     Prim::Bool => unreachable!(),
                     Prim::UInt => match from_width {
                         Width::W8 => {
@@ -880,13 +1264,66 @@ fn execute<'a>(
             }
         }
 
+        Air::Alloc => {
+            let addr = instr_try!(ctx.heap.try_alloc(ctx.a.r() as usize, ctx.b.r() as usize));
+            ctx.a.w(addr as u64);
+        }
+        Air::Realloc => {
+            let addr = instr_try!(ctx.heap.realloc(ctx.a.r() as usize, ctx.b.r() as usize));
+            ctx.a.w(addr as u64);
+        }
+        Air::Free => {
+            instr_try!(ctx.heap.free(ctx.a.r() as usize));
+        }
+
+        Air::Args => {
+            let addr = ctx.env.args(&mut ctx.heap);
+            ctx.a.w(addr as u64);
+        }
+        Air::EnvVar => {
+            let name = ctx.a.r() as usize;
+            instr_try!(ctx.check_addr(name, TyKind::FAT_PTR_SIZE));
+            let (ptr, len) = unsafe {
+                let ptr = (name as *const u64).read();
+                let len = ((name + Width::PTR.bytes()) as *const u64).read();
+                (ptr as usize, len as usize)
+            };
+            instr_try!(ctx.check_addr(ptr, len));
+            let name = unsafe { str::from_raw_parts(ptr as *const u8, len) };
+            let addr = ctx.env.var(&mut ctx.heap, name);
+            ctx.a.w(addr as u64);
+        }
+        Air::CurrentDir => {
+            let addr = ctx.env.current_dir(&mut ctx.heap);
+            ctx.a.w(addr as u64);
+        }
+        Air::Io(op, args) => {
+            let status = instr_try!(io::execute(ctx, *op, args));
+            ctx.a.w(status as u64);
+        }
+        Air::Now => {
+            ctx.a.w(ctx.clock.now());
+        }
+        Air::UnixTime => {
+            ctx.a.w(ctx.clock.unix_time());
+        }
+        Air::Sleep => {
+            ctx.clock.sleep(Duration::from_nanos(ctx.a.r()));
+        }
+        Air::RandSeed => {
+            ctx.a.w(ctx.seeds.next());
+        }
+
         Air::Exit => {
             return InstrResult::Break;
         }
-        Air::PrintCStr => unsafe {
-            libc::printf(ctx.a.r() as *const c_char);
-            libc::printf("\n\0".as_ptr() as *const c_char);
-        },
+        Air::PrintCStr => {
+            instr_try!(ctx.check_addr(ctx.a.r() as usize, 1));
+            unsafe {
+                libc::printf(ctx.a.r() as *const c_char);
+                libc::printf("\n\0".as_ptr() as *const c_char);
+            }
+        }
     }
 
     InstrResult::Ok
@@ -912,11 +1349,64 @@ impl TyKind {
             TyKind::Str => Type::pointer(),
             TyKind::Bool => Type::u8(),
             TyKind::Ref(TyKind::Str) => Type::structure([Type::u64(), Type::pointer()]),
-            TyKind::Ref(_) => Type::pointer(),
+            TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_) => Type::pointer(),
+            TyKind::Closure(_, _) => Type::structure([Type::pointer(), Type::pointer()]),
+            // extern signatures reject packed and over-aligned structs, see `TyStore::non_c_struct`,
+            // so libffi computes the same layout as the `TyStore`
             TyKind::Struct(id) => {
-                Type::structure(tys.strukt(*id).fields.iter().map(|f| f.ty.libffi_type(tys)))
+                let strukt = tys.strukt(*id);
+                if strukt.union {
+                    // libffi has no unions, so a union is described as units of its alignment.
+                    // Following the x86-64 System V eightbyte rules, a unit that overlaps an
+                    // integer in any member is an integer, otherwise it only holds floats.
+                    let layout = tys.struct_layout(*id);
+                    let mut ints = vec![false; layout.size];
+                    self.mark_ints(tys, 0, &mut ints);
+                    Type::structure(ints.chunks(layout.alignment).map(|unit| {
+                        match (layout.alignment, unit.contains(&true)) {
+                            (1, _) => Type::u8(),
+                            (2, _) => Type::u16(),
+                            (4, true) => Type::u32(),
+                            (4, false) => Type::f32(),
+                            (_, true) => Type::u64(),
+                            (_, false) => Type::f64(),
+                        }
+                    }))
+                } else {
+                    Type::structure(strukt.fields.iter().map(|f| f.ty.libffi_type(tys)))
+                }
             }
+            TyKind::Array(len, inner) => {
+                Type::structure(std::iter::repeat_n(inner.libffi_type(tys), *len))
+            }
+            TyKind::Tuple(elems) => Type::structure(elems.iter().map(|e| e.libffi_type(tys))),
             ty => todo!("{ty:?}"),
         }
     }
+
+    /// Marks the bytes of `ints`, starting at `offset`, that hold an integer or pointer in `self`.
+    fn mark_ints(&self, tys: &TyStore, offset: usize, ints: &mut [bool]) {
+        match self {
+            TyKind::Float(_) | TyKind::Unit => {}
+            TyKind::Struct(id) => {
+                let fields = tys.fields(*id);
+                for field in tys.strukt(*id).fields.iter() {
+                    let field_offset = fields.fields.get(&field.name.sym).unwrap().1 as usize;
+                    field.ty.0.mark_ints(tys, offset + field_offset, ints);
+                }
+            }
+            TyKind::Array(len, inner) => {
+                let size = inner.size(tys);
+                for i in 0..*len {
+                    inner.mark_ints(tys, offset + i * size, ints);
+                }
+            }
+            TyKind::Tuple(elems) => {
+                for (i, elem) in elems.iter().enumerate() {
+                    elem.mark_ints(tys, offset + tys.tuple_offset(elems, i) as usize, ints);
+                }
+            }
+            ty => ints[offset..offset + ty.size(tys)].fill(true),
+        }
+    }
 }
@@ -0,0 +1,35 @@
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seeds handed to `core::rand`.
+///
+/// Each seed is drawn from a splitmix64 sequence, so a pinned seed makes every `Rng::new` in the
+/// program reproducible while keeping the generators distinct.
+#[derive(Debug)]
+pub struct Seeds {
+    state: u64,
+}
+
+impl Default for Seeds {
+    fn default() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        if let Ok(time) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u128(time.as_nanos());
+        }
+        Self::new(hasher.finish())
+    }
+}
+
+impl Seeds {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
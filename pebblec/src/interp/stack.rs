@@ -1,25 +1,42 @@
 use crate::air::{Bits, OffsetVar, Var};
+use crate::ir::mem::Layout;
 use crate::ir::ty::Width;
 use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct Stack {
     vars: HashMap<Var, usize>,
+    /// Previous addresses of the vars reassigned by each active call, restored when the call
+    /// returns so that recursive calls do not clobber their caller's vars.
+    saved: Vec<HashMap<Var, usize>>,
     stack: Vec<u64>,
     sp: usize,
 }
 
 impl Default for Stack {
     fn default() -> Self {
+        Self::new(super::DEFAULT_STACK_SIZE)
+    }
+}
+
+/// An allocation would have moved the stack pointer past the end of the [`Stack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackOverflow {
+    pub requested: usize,
+    pub remaining: usize,
+}
+
+impl Stack {
+    /// Allocates a stack of at least `bytes` bytes, rounded up to a whole word.
+    pub fn new(bytes: usize) -> Self {
         Self {
             vars: HashMap::default(),
-            stack: vec![0; 1_000_000],
+            saved: Vec::new(),
+            stack: vec![0; bytes.div_ceil(8)],
             sp: 0,
         }
     }
-}
 
-impl Stack {
     pub fn len(&self) -> usize {
         self.stack.len()
     }
@@ -28,20 +45,55 @@ impl Stack {
         self.len() * 8
     }
 
-    pub fn anon_alloc(&mut self, bytes: usize) -> usize {
-        let sp = self.sp;
-        self.sp += bytes;
-        self.sp = (self.sp + 7) & !7;
-        self.start_addr() + sp
+    pub fn anon_alloc(&mut self, bytes: usize) -> Result<usize, StackOverflow> {
+        self.anon_alloc_aligned(bytes, 8)
     }
 
-    pub fn alloc(&mut self, var: Var, bytes: usize) {
-        let addr = self.anon_alloc(bytes);
-        self.vars.insert(var, addr);
+    /// Allocations are at least 8 byte aligned.
+    pub fn anon_alloc_aligned(
+        &mut self,
+        bytes: usize,
+        alignment: usize,
+    ) -> Result<usize, StackOverflow> {
+        let addr = (self.start_addr() + self.sp).next_multiple_of(alignment.max(8));
+        let sp = addr - self.start_addr();
+        let new_sp = (sp + bytes + 7) & !7;
+        if new_sp > self.bytes() {
+            return Err(StackOverflow {
+                requested: bytes,
+                remaining: self.bytes() - self.sp,
+            });
+        }
+
+        self.sp = new_sp;
+        Ok(addr)
+    }
+
+    pub fn alloc(&mut self, var: Var, layout: Layout) -> Result<(), StackOverflow> {
+        let addr = self.anon_alloc_aligned(layout.size, layout.alignment)?;
+        self.set_var(var, addr);
+        Ok(())
     }
 
     pub fn point(&mut self, var: Var, addr: usize) {
-        self.vars.insert(var, addr);
+        self.set_var(var, addr);
+    }
+
+    fn set_var(&mut self, var: Var, addr: usize) {
+        if let Some(prev) = self.vars.insert(var, addr)
+            && let Some(saved) = self.saved.last_mut()
+        {
+            saved.entry(var).or_insert(prev);
+        }
+    }
+
+    pub fn save_vars(&mut self) {
+        self.saved.push(HashMap::default());
+    }
+
+    pub fn restore_vars(&mut self) {
+        let saved = self.saved.pop().expect("no saved vars");
+        self.vars.extend(saved);
     }
 
     pub fn sp(&self) -> usize {
@@ -129,17 +181,25 @@ impl Stack {
         }
     }
 
+    /// Vars only point outside of the stack after an `Air::Deref`, which checks the address with
+    /// `InterpCtx::check_addr`.
     //#[track_caller]
     fn read<I: Read>(&self, addr: usize) -> I {
+        if addr < self.start_addr() || addr >= self.start_addr() + self.bytes() {
+            // memory outside of the stack, e.g. the heap or bss
+            assert!(addr != 0, "invalid memory: read from null pointer");
+            return unsafe {
+                I::read(
+                    std::slice::from_raw_parts(addr as *const u8, std::mem::size_of::<I>()),
+                    0,
+                )
+            };
+        }
+
         assert!(
-            addr <= self.end_addr(),
+            self.contains(addr, std::mem::size_of::<I>()),
             "invalid stack memory: read from {addr:#x}, stack ends at {:#x}",
-            self.end_addr()
-        );
-        assert!(
-            addr >= self.start_addr(),
-            "invalid stack memory: read from {addr:#x}, stack starts at {:#x}",
-            self.start_addr()
+            self.start_addr() + self.bytes()
         );
 
         let index = addr - self.start_addr();
@@ -151,27 +211,28 @@ impl Stack {
         }
     }
 
+    /// See [`Stack::read`].
     fn write<I: WriteBits>(&mut self, bits: I, addr: usize) {
-        assert!(
-            addr <= self.end_addr(),
-            "invalid stack memory: write to {addr:#x}, stack ends at {:#x}",
-            self.end_addr()
-        );
-        assert!(
-            addr >= self.start_addr(),
-            "invalid stack memory: write to {addr:#x}, stack start at {:#x}",
-            self.start_addr()
-        );
+        if addr >= self.start_addr() && addr < self.start_addr() + self.bytes() {
+            assert!(
+                self.contains(addr, std::mem::size_of::<I>()),
+                "invalid stack memory: write to {addr:#x}, stack ends at {:#x}",
+                self.start_addr() + self.bytes()
+            );
+        } else {
+            assert!(addr != 0, "invalid memory: write to null pointer");
+        }
 
         bits.write_bits(addr as *mut u8);
     }
 
-    fn start_addr(&self) -> usize {
-        self.stack.as_ptr().addr()
+    /// Whether `bytes` bytes at `addr` lie within the memory reserved for the stack.
+    pub fn contains(&self, addr: usize, bytes: usize) -> bool {
+        addr >= self.start_addr() && addr.saturating_add(bytes) <= self.start_addr() + self.bytes()
     }
 
-    fn end_addr(&self) -> usize {
-        self.start_addr() + self.sp().min(self.start_addr() + 8_000_000)
+    fn start_addr(&self) -> usize {
+        self.stack.as_ptr().addr()
     }
 }
 
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Clock read by `core::time`.
+///
+/// A fake clock starts at the Unix epoch and only moves when [`Clock::advance`] is called or the
+/// program sleeps, which returns immediately, so tests can control the time the program sees.
+#[derive(Debug)]
+pub enum Clock {
+    System { start: Instant },
+    Fake { elapsed: Duration },
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::System {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock {
+    pub fn fake() -> Self {
+        Self::Fake {
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Nanoseconds since the clock was created.
+    pub fn now(&self) -> u64 {
+        match self {
+            Self::System { start } => nanos(start.elapsed()),
+            Self::Fake { elapsed } => nanos(*elapsed),
+        }
+    }
+
+    /// Nanoseconds since the Unix epoch, or 0 if the system clock is set before it.
+    pub fn unix_time(&self) -> u64 {
+        match self {
+            Self::System { .. } => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, nanos),
+            Self::Fake { elapsed } => nanos(*elapsed),
+        }
+    }
+
+    pub fn sleep(&mut self, duration: Duration) {
+        match self {
+            Self::System { .. } => std::thread::sleep(duration),
+            Self::Fake { .. } => self.advance(duration),
+        }
+    }
+
+    /// Moves a fake clock forward by `duration`. Has no effect on the system clock.
+    pub fn advance(&mut self, duration: Duration) {
+        if let Self::Fake { elapsed } = self {
+            *elapsed = elapsed.saturating_add(duration);
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
@@ -0,0 +1,330 @@
+use crate::ir::ty::store::TyStore;
+use crate::ir::ty::{FloatTy, IntTy, Sign, TyKind, Width};
+
+/// A Pebble value passed between Rust and the interpreter, see [`InterpInstance`].
+///
+/// [`InterpInstance`]: super::InterpInstance
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    /// A `&str`, copied into and out of the interpreter.
+    Str(String),
+    /// A struct named `name`, with every field of the declaration in any order.
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+}
+
+impl Value {
+    /// The Pebble type of scalar values, which fit in a register.
+    pub fn scalar_kind(&self) -> Option<TyKind> {
+        Some(match self {
+            Self::Unit => TyKind::Unit,
+            Self::Bool(_) => TyKind::Bool,
+            Self::I8(_) => TyKind::Int(IntTy::new_8(Sign::I)),
+            Self::I16(_) => TyKind::Int(IntTy::new_16(Sign::I)),
+            Self::I32(_) => TyKind::Int(IntTy::new_32(Sign::I)),
+            Self::I64(_) => TyKind::Int(IntTy::new_64(Sign::I)),
+            Self::U8(_) => TyKind::Int(IntTy::new_8(Sign::U)),
+            Self::U16(_) => TyKind::Int(IntTy::new_16(Sign::U)),
+            Self::U32(_) => TyKind::Int(IntTy::new_32(Sign::U)),
+            Self::U64(_) => TyKind::Int(IntTy::new_64(Sign::U)),
+            Self::F32(_) => TyKind::Float(FloatTy::F32),
+            Self::F64(_) => TyKind::Float(FloatTy::F64),
+            Self::Str(_) | Self::Struct { .. } => return None,
+        })
+    }
+
+    /// Whether the value can be stored as a `ty`.
+    pub fn matches(&self, ty: &TyKind, tys: &TyStore) -> bool {
+        match (self, ty) {
+            (Self::Str(_), TyKind::Ref(TyKind::Str)) => true,
+            (Self::Struct { name, fields }, TyKind::Struct(id)) => {
+                let strukt = tys.strukt(*id);
+                // every declared field is given exactly once, so none is left uninitialized
+                !strukt.union
+                    && strukt.name.as_str() == name
+                    && strukt.fields.len() == fields.len()
+                    && strukt.fields.iter().all(|f| {
+                        let mut given = fields.iter().filter(|(field, _)| f.name.as_str() == field);
+                        given
+                            .next()
+                            .is_some_and(|(_, value)| value.matches(f.ty.0, tys))
+                            && given.next().is_none()
+                    })
+            }
+            (value, ty) => value.scalar_kind().is_some_and(|kind| kind == *ty),
+        }
+    }
+
+    /// Describes the type of the value for diagnostics.
+    pub fn ty_string(&self, tys: &TyStore) -> String {
+        match self {
+            Self::Str(_) => "&str".to_string(),
+            Self::Struct { name, .. } => name.clone(),
+            value => value.scalar_kind().unwrap().to_string_with(tys),
+        }
+    }
+
+    /// Whether values of type `ty` fit in a register, see [`Value::from_bits`].
+    pub fn is_scalar(ty: &TyKind) -> bool {
+        matches!(
+            ty,
+            TyKind::Unit | TyKind::Bool | TyKind::Int(_) | TyKind::Float(_)
+        )
+    }
+
+    /// Whether values of type `ty` can be represented as a [`Value`]. Unions are not supported
+    /// because the active field is not tracked.
+    pub fn supports(ty: &TyKind, tys: &TyStore) -> bool {
+        match ty {
+            TyKind::Ref(TyKind::Str) => true,
+            TyKind::Struct(id) => {
+                let strukt = tys.strukt(*id);
+                !strukt.union && strukt.fields.iter().all(|f| Self::supports(f.ty.0, tys))
+            }
+            ty => Self::is_scalar(ty),
+        }
+    }
+
+    /// Decodes a scalar of type `ty` from the bits of a register, as returned by a function.
+    pub fn from_bits(ty: &TyKind, bits: u64) -> Option<Self> {
+        Some(match ty {
+            TyKind::Unit => Self::Unit,
+            TyKind::Bool => Self::Bool(bits & 1 == 1),
+            TyKind::Int(int) => match (int.sign(), int.size()) {
+                (Sign::I, 1) => Self::I8(bits as i8),
+                (Sign::I, 2) => Self::I16(bits as i16),
+                (Sign::I, 4) => Self::I32(bits as i32),
+                (Sign::I, _) => Self::I64(bits as i64),
+                (Sign::U, 1) => Self::U8(bits as u8),
+                (Sign::U, 2) => Self::U16(bits as u16),
+                (Sign::U, 4) => Self::U32(bits as u32),
+                (Sign::U, _) => Self::U64(bits),
+            },
+            TyKind::Float(FloatTy::F32) => Self::F32(f32::from_bits(bits as u32)),
+            TyKind::Float(FloatTy::F64) => Self::F64(f64::from_bits(bits)),
+            _ => return None,
+        })
+    }
+
+    /// The bits of a scalar within a register, zero extended.
+    pub fn to_bits(&self) -> Option<u64> {
+        Some(match self {
+            Self::Unit => 0,
+            Self::Bool(b) => *b as u64,
+            Self::I8(i) => *i as u8 as u64,
+            Self::I16(i) => *i as u16 as u64,
+            Self::I32(i) => *i as u32 as u64,
+            Self::I64(i) => *i as u64,
+            Self::U8(u) => *u as u64,
+            Self::U16(u) => *u as u64,
+            Self::U32(u) => *u as u64,
+            Self::U64(u) => *u,
+            Self::F32(f) => f.to_bits() as u64,
+            Self::F64(f) => f.to_bits(),
+            Self::Str(_) | Self::Struct { .. } => return None,
+        })
+    }
+
+    /// Reads a value of type `ty` from memory.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to a valid value of type `ty`.
+    pub unsafe fn read(ty: &TyKind, tys: &TyStore, addr: usize) -> Option<Self> {
+        match ty {
+            TyKind::Ref(TyKind::Str) => {
+                let ptr = unsafe { read_bits(addr, Width::PTR.bytes()) };
+                let len = unsafe { read_bits(addr + Width::PTR.bytes(), Width::SIZE.bytes()) };
+                let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len as usize) };
+                Some(Self::Str(String::from_utf8_lossy(bytes).into_owned()))
+            }
+            TyKind::Struct(id) => {
+                let strukt = tys.strukt(*id);
+                if strukt.union {
+                    return None;
+                }
+                let offsets = tys.fields(*id);
+                let fields = strukt
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let (_, offset) = offsets.fields[&field.name.sym];
+                        let value = unsafe { Self::read(field.ty.0, tys, addr + offset as usize)? };
+                        Some((field.name.as_str().to_string(), value))
+                    })
+                    .collect::<Option<_>>()?;
+                Some(Self::Struct {
+                    name: strukt.name.as_str().to_string(),
+                    fields,
+                })
+            }
+            ty => {
+                let size = match ty {
+                    TyKind::Unit => 0,
+                    TyKind::Bool => 1,
+                    TyKind::Int(int) => int.size(),
+                    TyKind::Float(float) => float.size(),
+                    _ => return None,
+                };
+                Self::from_bits(ty, unsafe { read_bits(addr, size) })
+            }
+        }
+    }
+
+    /// Writes the value to memory as a `ty`. The bytes of strings are copied into memory returned
+    /// by `alloc_str`, which must outlive every use of the value within the interpreter.
+    ///
+    /// # Safety
+    ///
+    /// The value must match `ty`, see [`Value::matches`], and `addr` must be valid for writes of
+    /// the size of `ty`.
+    pub unsafe fn write<E>(
+        &self,
+        ty: &TyKind,
+        tys: &TyStore,
+        addr: usize,
+        alloc_str: &mut impl FnMut(usize) -> Result<usize, E>,
+    ) -> Result<(), E> {
+        match self {
+            Self::Str(str) => {
+                let ptr = alloc_str(str.len())?;
+                unsafe {
+                    std::ptr::copy_nonoverlapping(str.as_ptr(), ptr as *mut u8, str.len());
+                    write_bits(addr, ptr as u64, Width::PTR.bytes());
+                    write_bits(
+                        addr + Width::PTR.bytes(),
+                        str.len() as u64,
+                        Width::SIZE.bytes(),
+                    );
+                }
+            }
+            Self::Struct { fields, .. } => {
+                let TyKind::Struct(id) = ty else {
+                    unreachable!("struct value written as `{}`", ty.to_string_with(tys));
+                };
+                let offsets = tys.fields(*id);
+                for (field, value) in fields.iter() {
+                    let (ty, offset) = offsets
+                        .fields
+                        .iter()
+                        .find(|(name, _)| name.as_str() == field)
+                        .map(|(_, (ty, offset))| (*ty, *offset))
+                        .unwrap();
+                    unsafe { value.write(ty.0, tys, addr + offset as usize, alloc_str)? };
+                }
+            }
+            value => {
+                let size = ty.size(tys);
+                unsafe { write_bits(addr, value.to_bits().unwrap(), size) };
+            }
+        }
+        Ok(())
+    }
+}
+
+unsafe fn read_bits(addr: usize, size: usize) -> u64 {
+    let mut bytes = [0; 8];
+    unsafe { std::ptr::copy_nonoverlapping(addr as *const u8, bytes.as_mut_ptr(), size) };
+    u64::from_le_bytes(bytes)
+}
+
+unsafe fn write_bits(addr: usize, bits: u64, size: usize) {
+    let bytes = bits.to_le_bytes();
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), addr as *mut u8, size) };
+}
+
+/// Rust types that are passed to and returned from host functions, see
+/// [`InterpInstance::host`].
+///
+/// [`InterpInstance::host`]: super::InterpInstance::host
+pub trait HostType: TryFrom<Value> + Into<Value> {
+    /// Whether the Pebble type `ty` converts into this type.
+    fn matches(ty: &TyKind) -> bool;
+}
+
+/// Accepts any scalar, see [`Value::is_scalar`].
+impl HostType for Value {
+    fn matches(ty: &TyKind) -> bool {
+        Value::is_scalar(ty)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Self::Unit
+    }
+}
+
+impl TryFrom<Value> for () {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Unit => Ok(()),
+            value => Err(value),
+        }
+    }
+}
+
+impl HostType for () {
+    fn matches(ty: &TyKind) -> bool {
+        ty.is_unit()
+    }
+}
+
+macro_rules! impl_value {
+    ($($ty:ty => $variant:ident,)*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value)
+                }
+            }
+
+            impl TryFrom<Value> for $ty {
+                type Error = Value;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    match value {
+                        Value::$variant(value) => Ok(value),
+                        value => Err(value),
+                    }
+                }
+            }
+
+            impl HostType for $ty {
+                fn matches(ty: &TyKind) -> bool {
+                    Value::from(<$ty>::default()).scalar_kind().as_ref() == Some(ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_value!(
+    bool => Bool,
+    i8 => I8,
+    i16 => I16,
+    i32 => I32,
+    i64 => I64,
+    u8 => U8,
+    u16 => U16,
+    u32 => U32,
+    u64 => U64,
+    f32 => F32,
+    f64 => F64,
+);
@@ -1,6 +1,6 @@
 use super::sig::Sig;
 use super::ty::{store::TyStore, *};
-use super::{Const, Func};
+use super::{Closure, Const, Func, Stmt};
 use indexmap::IndexMap;
 use pebblec_arena::BlobArena;
 use pebblec_parse::annotate_snippets::Level;
@@ -10,7 +10,7 @@ use pebblec_parse::lex::kind::TokenKind;
 use pebblec_parse::lex::source::SourceMap;
 use pebblec_parse::sym::{Ident, Symbol};
 use std::borrow::{Borrow, Cow};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct Ctx<'a> {
@@ -21,6 +21,41 @@ pub struct Ctx<'a> {
     pub funcs: Vec<Func<'a>>,
     pub sigs: IndexMap<Symbol, &'a Sig<'a>>,
     pub impl_sigs: IndexMap<(Ty, Symbol), &'a Sig<'a>>,
+    /// Functions declared with `#[intrinsic]`, known before any body is lowered.
+    pub intrinsics: HashSet<Symbol>,
+    /// Every closure expression, in the order they are lowered.
+    pub closures: Vec<&'a Closure<'a>>,
+    /// Type of each closure, keyed by the span of the closure expression.
+    pub closure_tys: HashMap<Span, Ty>,
+    /// Functions that the closures are lowered into, keyed by the span of the closure expression.
+    pub closure_funcs: IndexMap<Span, Func<'a>>,
+    /// Set while lowering the body of a closure without a return type, where `return` is not
+    /// allowed.
+    pub inferred_closure_ret: bool,
+    /// Statements deferred to the exit of their block, retrieved by `air` when the block exits.
+    pub defers: HashMap<Span, &'a Stmt<'a>>,
+    /// Loops enclosing the expression being lowered, innermost last.
+    pub loops: Vec<LoopScope>,
+    /// Number of enclosing loops when lowering a deferred statement. Control flow cannot leave
+    /// a deferred statement, so `return` is rejected here, and `break` and `continue` unless
+    /// they target a loop within it.
+    pub defer_loops: Option<usize>,
+    /// Set while lowering the contents of an `unsafe` block, where union fields are accessible.
+    pub in_unsafe: bool,
+    /// Parameters and bindings in scope of the expression being lowered, which shadow the
+    /// functions of the same name.
+    pub locals: Vec<Symbol>,
+}
+
+/// Loop that `break` and `continue` may target, by its label or as the innermost loop.
+#[derive(Debug, Clone, Copy)]
+pub struct LoopScope {
+    pub span: Span,
+    pub label: Option<Symbol>,
+    /// Only `loop` can `break` with a value.
+    pub is_loop: bool,
+    pub value_break: Option<Span>,
+    pub unit_break: Option<Span>,
 }
 
 // TODO: move into deterministic test?
@@ -34,6 +69,9 @@ impl PartialEq for Ctx<'_> {
             && self.funcs == other.funcs
             && self.sigs == other.sigs
             && self.impl_sigs == other.impl_sigs
+            && self.closures == other.closures
+            && self.closure_tys == other.closure_tys
+            && self.closure_funcs == other.closure_funcs
     }
 }
 
@@ -47,6 +85,16 @@ impl<'a> Ctx<'a> {
             funcs: Vec::default(),
             sigs: IndexMap::default(),
             impl_sigs: IndexMap::default(),
+            intrinsics: HashSet::default(),
+            closures: Vec::default(),
+            closure_tys: HashMap::default(),
+            closure_funcs: IndexMap::default(),
+            inferred_closure_ret: false,
+            defers: HashMap::default(),
+            loops: Vec::new(),
+            defer_loops: None,
+            in_unsafe: false,
+            locals: Vec::new(),
         }
     }
 
@@ -191,6 +239,25 @@ impl<'a> Ctx<'a> {
         )
     }
 
+    #[track_caller]
+    pub fn out_of_range(&self, span: Span, ty: Ty) -> Diag {
+        match ty.0 {
+            TyKind::Int(int) => self.report_error(
+                span,
+                format!(
+                    "literal out of range for `{}`, the range is `{}..={}`",
+                    int.as_str(),
+                    int.min(),
+                    int.max()
+                ),
+            ),
+            _ => self.report_error(
+                span,
+                format!("literal out of range for `{}`", ty.to_string(self)),
+            ),
+        }
+    }
+
     #[track_caller]
     pub fn undeclared<U: SpannedCtxFmt>(&self, u: U) -> Diag {
         let (span, str) = u.spanned_ctx_fmt(self);
@@ -0,0 +1,304 @@
+use super::InferTy;
+use super::ty::TyKind;
+use std::ops::Range;
+
+/// Parsed format string of `print` and `println`.
+///
+/// Arguments are substituted with `{}` or `{:spec}`, where `spec` is
+/// `[[fill]align][+][#][0][width][.precision][type]`:
+///     align: `<`, `^` or `>`
+///     type: `?` (debug), `x`, `X`, `b`, `o` or `e`
+///
+/// `%` is equivalent to `{}`. `{{`, `}}` and `%%` escape the literal characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format<'a> {
+    pub pieces: Vec<Piece<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece<'a> {
+    Lit(&'a str),
+    Arg(Spec),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub sign: bool,
+    pub alternate: bool,
+    pub zero: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+    pub kind: SpecKind,
+}
+
+impl Default for Spec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            sign: false,
+            alternate: false,
+            zero: false,
+            width: None,
+            precision: None,
+            kind: SpecKind::Display,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecKind {
+    Display,
+    Debug,
+    LowerHex,
+    UpperHex,
+    Binary,
+    Octal,
+    Exp,
+}
+
+impl SpecKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Display => "{}",
+            Self::Debug => "{:?}",
+            Self::LowerHex => "{:x}",
+            Self::UpperHex => "{:X}",
+            Self::Binary => "{:b}",
+            Self::Octal => "{:o}",
+            Self::Exp => "{:e}",
+        }
+    }
+}
+
+/// Error in a format string, `span` is the byte range within the string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub span: Range<usize>,
+    pub msg: String,
+}
+
+impl FormatError {
+    fn new(span: Range<usize>, msg: impl Into<String>) -> Self {
+        Self {
+            span,
+            msg: msg.into(),
+        }
+    }
+}
+
+impl<'a> Format<'a> {
+    pub fn parse(str: &'a str) -> Result<Self, FormatError> {
+        let mut pieces = Vec::new();
+        let mut lit_start = 0;
+        let mut chars = str.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' | '}' | '%' if chars.peek().is_some_and(|(_, next)| *next == c) => {
+                    // keep the first char of the escape
+                    pieces.push(Piece::Lit(&str[lit_start..i + 1]));
+                    chars.next();
+                    lit_start = i + 2;
+                }
+                '%' => {
+                    pieces.push(Piece::Lit(&str[lit_start..i]));
+                    pieces.push(Piece::Arg(Spec::default()));
+                    lit_start = i + 1;
+                }
+                '{' => {
+                    let Some(end) = str[i..].find('}').map(|end| i + end) else {
+                        return Err(FormatError::new(i..str.len(), "unterminated `{`"));
+                    };
+
+                    pieces.push(Piece::Lit(&str[lit_start..i]));
+                    pieces.push(Piece::Arg(parse_spec(&str[i + 1..end], i + 1)?));
+                    while chars.next_if(|(j, _)| *j <= end).is_some() {}
+                    lit_start = end + 1;
+                }
+                '}' => {
+                    return Err(FormatError::new(
+                        i..i + 1,
+                        "unmatched `}`, use `}}` to print a `}`",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        pieces.push(Piece::Lit(&str[lit_start..]));
+        pieces.retain(|piece| !matches!(piece, Piece::Lit("")));
+        Ok(Self { pieces })
+    }
+
+    pub fn specs(&self) -> impl Iterator<Item = &Spec> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Arg(spec) => Some(spec),
+            Piece::Lit(_) => None,
+        })
+    }
+}
+
+/// `offset` is the position of `spec` in the format string, used for error spans.
+fn parse_spec(spec: &str, offset: usize) -> Result<Spec, FormatError> {
+    let mut result = Spec::default();
+    if spec.is_empty() {
+        return Ok(result);
+    }
+
+    let Some(spec) = spec.strip_prefix(':') else {
+        return Err(FormatError::new(
+            offset..offset + spec.len(),
+            "expected `:` before the format spec, positional and named arguments are not supported",
+        ));
+    };
+    let offset = offset + 1;
+    let err = |msg: &str| FormatError::new(offset..offset + spec.len(), msg);
+
+    let chars = spec.chars().collect::<Vec<_>>();
+    let mut i = 0;
+
+    let align = |c: char| match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    };
+    if let Some(a) = chars.get(1).and_then(|c| align(*c)) {
+        result.fill = chars[0];
+        result.align = Some(a);
+        i = 2;
+    } else if let Some(a) = chars.first().and_then(|c| align(*c)) {
+        result.align = Some(a);
+        i = 1;
+    }
+
+    if chars.get(i) == Some(&'+') {
+        result.sign = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'#') {
+        result.alternate = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        result.zero = true;
+        i += 1;
+    }
+
+    let number = |i: &mut usize| -> Option<usize> {
+        let start = *i;
+        while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+            *i += 1;
+        }
+        (start != *i).then(|| {
+            chars[start..*i]
+                .iter()
+                .collect::<String>()
+                .parse()
+                .unwrap_or(usize::MAX)
+        })
+    };
+
+    result.width = number(&mut i);
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        result.precision =
+            Some(number(&mut i).ok_or_else(|| err("expected a precision after `.`"))?);
+    }
+
+    result.kind = match chars.get(i..) {
+        Some([]) => SpecKind::Display,
+        Some(['?']) => SpecKind::Debug,
+        Some(['x']) => SpecKind::LowerHex,
+        Some(['X']) => SpecKind::UpperHex,
+        Some(['b']) => SpecKind::Binary,
+        Some(['o']) => SpecKind::Octal,
+        Some(['e']) => SpecKind::Exp,
+        _ => return Err(err(&format!("invalid format spec `{}`", spec))),
+    };
+
+    Ok(result)
+}
+
+/// Category of an argument's type, used to check it against its [`Spec`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Ptr,
+    /// Structs, tuples, arrays and slices, which only have a debug representation.
+    Aggregate,
+    Unit,
+}
+
+impl ArgKind {
+    pub fn from_infer(infer: InferTy) -> Self {
+        match infer {
+            InferTy::Int => Self::Int,
+            InferTy::Float => Self::Float,
+            InferTy::Ty(ty) => match ty.0 {
+                TyKind::Int(_) => Self::Int,
+                TyKind::Float(_) => Self::Float,
+                TyKind::Bool => Self::Bool,
+                TyKind::Ref(TyKind::Str) => Self::Str,
+                TyKind::Ref(TyKind::Slice(_)) => Self::Aggregate,
+                TyKind::Ref(_) | TyKind::Fn(_, _) | TyKind::Opt(_) => Self::Ptr,
+                TyKind::Struct(_) | TyKind::Tuple(_) | TyKind::Array(_, _) | TyKind::Slice(_) => {
+                    Self::Aggregate
+                }
+                TyKind::Str | TyKind::Closure(_, _) | TyKind::Unit => Self::Unit,
+            },
+        }
+    }
+}
+
+impl Spec {
+    /// Returns why an argument of `kind` cannot be formatted with `self`, phrased to follow the
+    /// argument's type, e.g. "cannot be formatted with `{:x}`".
+    pub fn check(&self, kind: ArgKind) -> Result<(), String> {
+        let numeric = matches!(kind, ArgKind::Int | ArgKind::Float);
+
+        match (self.kind, kind) {
+            (_, ArgKind::Unit) => return Err("cannot be formatted".to_string()),
+            (SpecKind::Debug, _) => {}
+            (SpecKind::Display, ArgKind::Aggregate) => {
+                return Err("cannot be formatted with `{}`, use `{:?}`".to_string());
+            }
+            (SpecKind::Display, _) => {}
+            (
+                SpecKind::LowerHex | SpecKind::UpperHex | SpecKind::Binary | SpecKind::Octal,
+                ArgKind::Int | ArgKind::Ptr,
+            ) => {}
+            (SpecKind::Exp, ArgKind::Float) => {}
+            (kind, _) => {
+                return Err(format!("cannot be formatted with `{}`", kind.as_str()));
+            }
+        }
+
+        if self.precision.is_some() && !matches!(kind, ArgKind::Float | ArgKind::Str) {
+            return Err(
+                "cannot be formatted with a precision, only floats and strings can".to_string(),
+            );
+        }
+
+        if (self.sign || self.zero) && !numeric {
+            return Err(
+                "cannot be formatted with the `+` or `0` flags, only numbers can".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use super::ty::{FloatTy, Ty, TyKind};
 use pebblec_parse::lex::buffer::Span;
 use std::hash::Hash;
 
@@ -5,6 +6,8 @@ use std::hash::Hash;
 pub struct Lit<'a> {
     pub span: Span,
     pub kind: &'a LitKind,
+    /// Type of the literal if it is not inferred, e.g. a character literal is a `u8`.
+    pub ty: Option<Ty>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -21,3 +24,23 @@ impl Hash for LitKind {
         }
     }
 }
+
+impl LitKind {
+    /// Whether the literal, negated if `negative`, is representable as a value of `ty`.
+    pub fn fits(&self, ty: Ty, negative: bool) -> bool {
+        match (self, ty.0) {
+            (Self::Int(val), TyKind::Int(int)) => {
+                let val = if negative {
+                    -(*val as i128)
+                } else {
+                    *val as i128
+                };
+                int.min() <= val && val <= int.max()
+            }
+            (Self::Float(val), TyKind::Float(FloatTy::F32)) => {
+                !val.is_finite() || (*val as f32).is_finite()
+            }
+            _ => true,
+        }
+    }
+}
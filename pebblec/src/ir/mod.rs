@@ -4,16 +4,18 @@ use self::sem::sem_analysis_pre_typing;
 use self::sig::Linkage;
 use self::ty::{FloatTy, IntTy, Sign, Ty, TyKind};
 use crate::comp::CompErr;
-use crate::ir::ctx::Ctx;
+use crate::ir::ctx::{Ctx, LoopScope};
 use crate::ir::lit::Lit;
 use indexmap::IndexMap;
 use pebblec_parse::diagnostic::{Diag, Msg};
 use pebblec_parse::lex::buffer::TokenId;
 use pebblec_parse::lex::buffer::{Span, TokenQuery};
 use pebblec_parse::lex::kind::TokenKind;
+use pebblec_parse::lex::number::{self, Number, NumberVal};
 use pebblec_parse::lex::source::SourceMap;
+use pebblec_parse::lex::{char_lit_inner, escape};
 use pebblec_parse::rules::prelude::PType;
-use pebblec_parse::rules::prelude::{self as rules, Attr};
+use pebblec_parse::rules::prelude::{self as rules, Attr, AttrTarget};
 use pebblec_parse::sym::{Ident, Symbol};
 use pebblec_parse::{AssignKind, ItemKind, UOpKind};
 use pebblec_parse::{BinOpKind, Item};
@@ -23,10 +25,11 @@ use sig::Param;
 use sig::Sig;
 use std::collections::HashSet;
 use std::hash::Hash;
-use strukt::{Field, FieldDef, Struct, StructDef};
+use strukt::{Field, FieldDef, Struct, StructDef, StructId};
 use ty::TypeKey;
 
 pub mod ctx;
+pub mod format;
 pub mod lit;
 pub mod mem;
 pub mod resolve;
@@ -56,21 +59,37 @@ pub fn lower<'a>(mut source_map: SourceMap) -> Result<Ir<'a>, CompErr> {
 
 pub fn lower_items<'a>(mut ctx: Ctx<'a>, mut items: Vec<Item>) -> Result<Ir<'a>, Diag> {
     let mut attrs = Vec::new();
+    let mut errors = Vec::new();
     for item in items.iter_mut() {
+        let stream = ctx.source_map.buffer(item.source).stream();
         match &mut item.kind {
             ItemKind::Attr(attr) => attrs.push(attr),
             ItemKind::Func(func) => {
                 for attr in attrs.drain(..) {
-                    _ = func
-                        .parse_attr(&ctx.source_map.buffer(item.source).stream(), attr)
-                        .map_err(Diag::report);
+                    errors.extend(func.parse_attr(&stream, attr).err());
                 }
             }
             ItemKind::Extern(exturn) => {
                 for attr in attrs.drain(..) {
-                    _ = exturn
-                        .parse_attr(&ctx.source_map.buffer(item.source).stream(), attr)
-                        .map_err(Diag::report);
+                    errors.extend(exturn.parse_attr(&stream, attr).err());
+                }
+            }
+            ItemKind::Struct(strukt) => {
+                for attr in attrs.drain(..) {
+                    errors.extend(strukt.parse_attr(&stream, attr).err());
+                }
+                for attr in strukt.fields.iter().flat_map(|f| f.attributes.iter()) {
+                    errors.extend(attr.validate(&stream, AttrTarget::Field).err());
+                }
+            }
+            ItemKind::Const(_) => {
+                for attr in attrs.drain(..) {
+                    errors.extend(attr.validate(&stream, AttrTarget::Const).err());
+                }
+            }
+            ItemKind::Impl(_) => {
+                for attr in attrs.drain(..) {
+                    errors.extend(attr.validate(&stream, AttrTarget::Impl).err());
                 }
             }
             _ => {
@@ -80,6 +99,9 @@ pub fn lower_items<'a>(mut ctx: Ctx<'a>, mut items: Vec<Item>) -> Result<Ir<'a>,
             }
         }
     }
+    if !errors.is_empty() {
+        return Err(Diag::bundle(errors));
+    }
 
     // TODO: check for duplicate struct definitions
     let structs = items
@@ -103,6 +125,15 @@ pub fn lower_items<'a>(mut ctx: Ctx<'a>, mut items: Vec<Item>) -> Result<Ir<'a>,
             .map(|f| func_sig(&mut ctx, None, f)),
     )?;
     ctx.store_sigs(sigs)?;
+    ctx.intrinsics = items
+        .iter()
+        .filter_map(|i| match &i.kind {
+            ItemKind::Func(func) if func.attributes.contains(&Attr::Intrinsic) => {
+                Some(Symbol::intern(ctx.as_str(func.name)))
+            }
+            _ => None,
+        })
+        .collect();
 
     let impls = lower_set(
         items
@@ -175,6 +206,7 @@ pub fn lower_items<'a>(mut ctx: Ctx<'a>, mut items: Vec<Item>) -> Result<Ir<'a>,
     sem_analysis_pre_typing(&ctx)?;
     let key = resolve_types(&mut ctx)?;
     sem_analysis(&ctx, &key)?;
+    closure_funcs(&mut ctx);
 
     Ok(Ir {
         ctx,
@@ -225,30 +257,36 @@ fn add_structs_recur<'a, 'ctx>(
     });
 
     for field in rules_strukt.fields.iter() {
-        let mut ty = field.ty.peel_refs();
+        let mut tys = vec![field.ty.peel_refs()];
 
         // TODO: introduce indirection detection so that structs can have references to themselves
-        loop {
+        while let Some(ty) = tys.pop() {
             match ty {
                 rules::PType::Simple(_, id) => {
                     if !ctx.tys.is_builtin(ctx.as_str(id).as_ref()) {
-                        if let Some(strukt) = retrieve_struct(&field.ty, ctx, structs) {
+                        if let Some(strukt) = retrieve_struct(ty, ctx, structs) {
                             add_structs_recur(ctx, structs, defined, processing, strukt)?;
                         } else {
                             errors.push(ctx.report_error(field.ty.span(), "undefined type"));
                         }
                     }
-
-                    break;
                 }
                 rules::PType::Array { inner, .. } => {
-                    ty = &*inner;
+                    tys.push(&*inner);
                 }
                 rules::PType::Slice { inner, .. } => {
-                    ty = &*inner;
+                    tys.push(&*inner);
                 }
-                rules::PType::Ref { inner, .. } => {
-                    ty = &*inner;
+                rules::PType::Ref { inner, .. } | rules::PType::Opt { inner, .. } => {
+                    tys.push(&*inner);
+                }
+                rules::PType::Tuple { elems, .. } => {
+                    tys.extend(elems.iter());
+                }
+                rules::PType::Fn { params, ret, .. }
+                | rules::PType::Closure { params, ret, .. } => {
+                    tys.extend(params.iter());
+                    tys.extend(ret.as_deref());
                 }
             }
         }
@@ -314,13 +352,15 @@ fn report_struct_cycle<'a, 'ctx>(
             title.push_str(", ");
         }
 
-        if let Some(field) = curr.strukt.fields.iter().find(|f| {
-            Symbol::intern(ctx.as_str(match f.ty {
-                rules::PType::Simple(_, id) => id,
-                rules::PType::Array { .. }
-                | rules::PType::Slice { .. }
-                | rules::PType::Ref { .. } => unreachable!(),
-            })) == next.sym
+        if let Some(field) = curr.strukt.fields.iter().find(|f| match f.ty {
+            rules::PType::Simple(_, id) => Symbol::intern(ctx.as_str(id)) == next.sym,
+            rules::PType::Array { .. }
+            | rules::PType::Slice { .. }
+            | rules::PType::Ref { .. }
+            | rules::PType::Tuple { .. }
+            | rules::PType::Fn { .. }
+            | rules::PType::Closure { .. }
+            | rules::PType::Opt { .. } => false,
         }) {
             msgs.push(Msg::error_span(&ctx.source_map, ctx.span(curr.strukt.name)));
             msgs.push(Msg::note_span(&ctx.source_map, field.span));
@@ -345,8 +385,10 @@ pub fn retrieve_struct<'a, 'ctx>(
             structs.get(&ident).map(|s| *s)
         }
         PType::Ref { inner, .. } => retrieve_struct(inner, ctx, structs),
+        PType::Opt { inner, .. } => retrieve_struct(inner, ctx, structs),
         PType::Array { inner, .. } => retrieve_struct(inner, ctx, structs),
         PType::Slice { inner, .. } => retrieve_struct(inner, ctx, structs),
+        PType::Tuple { .. } | PType::Fn { .. } | PType::Closure { .. } => None,
     }
 }
 
@@ -429,7 +471,7 @@ fn process_expr<'a, 'ctx>(
     expr: &rules::Expr,
 ) -> Result<(), Diag> {
     match expr {
-        rules::Expr::Lit(_) => {
+        rules::Expr::Lit(_) | rules::Expr::Str(_) | rules::Expr::Bool(_) => {
             evaluation_order.push(name_of_const);
         }
         rules::Expr::Ident(other) => {
@@ -463,6 +505,18 @@ fn process_expr<'a, 'ctx>(
                 rhs,
             )?;
         }
+        rules::Expr::Unary(_, _, _, inner) => {
+            process_expr(
+                ctx,
+                consts,
+                defined,
+                processing,
+                rules_const,
+                evaluation_order,
+                name_of_const,
+                inner,
+            )?;
+        }
         _ => unimplemented!(),
     }
 
@@ -552,15 +606,27 @@ pub struct Const<'a> {
 
 fn konst<'a>(ctx: &mut Ctx<'a>, konst: &rules::Const) -> Result<Const<'a>, Diag> {
     let expr = pexpr(ctx, &konst.expr)?;
+    let (span, ty) = ptype(ctx, &konst.ty)?;
+    escaping_closure(ctx, span, ty, "stored in constants")?;
     Ok(Const {
         span: konst.span,
         name: ctx.token_ident(konst.name),
-        ty: ptype(ctx, &konst.ty)?.1,
+        ty,
         expr: ctx.intern(expr),
     })
 }
 
 fn strukt<'a>(ctx: &mut Ctx<'a>, strukt: &rules::Struct) -> Result<Struct, Diag> {
+    if strukt.union && strukt.fields.is_empty() {
+        return Err(ctx.report_error(
+            ctx.span(strukt.name),
+            format!(
+                "union `{}` must have at least one field",
+                ctx.as_str(strukt.name)
+            ),
+        ));
+    }
+
     let mut field_names = Vec::with_capacity(strukt.fields.len());
 
     for field in strukt.fields.iter() {
@@ -579,6 +645,8 @@ fn strukt<'a>(ctx: &mut Ctx<'a>, strukt: &rules::Struct) -> Result<Struct, Diag>
     Ok(Struct {
         span: strukt.span,
         name: ctx.token_ident(strukt.name),
+        repr: strukt.repr,
+        union: strukt.union,
         fields: strukt
             .fields
             .iter()
@@ -588,13 +656,30 @@ fn strukt<'a>(ctx: &mut Ctx<'a>, strukt: &rules::Struct) -> Result<Struct, Diag>
 }
 
 fn field<'a>(ctx: &mut Ctx<'a>, field: &rules::Field) -> Result<Field, Diag> {
+    let (span, ty) = ptype(ctx, &field.ty)?;
+    escaping_closure(ctx, span, ty, "stored in struct fields")?;
     Ok(Field {
         span: field.span,
         name: ctx.token_ident(field.name),
-        ty: ptype(ctx, &field.ty)?.1,
+        ty,
     })
 }
 
+/// Rejects a closure stored by value within `ty`, which would outlive the stack frame that holds
+/// its environment.
+fn escaping_closure(ctx: &Ctx, span: Span, ty: Ty, place: &str) -> Result<(), Diag> {
+    if ty.holds_closure() {
+        return Err(ctx
+            .report_error(span, format!("closures cannot be {place}"))
+            .msg(Msg::note(
+                &ctx.source_map,
+                span,
+                "a closure's environment lives in the stack frame that created it",
+            )));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Func<'a> {
     pub name_span: Span,
@@ -627,12 +712,14 @@ fn func_sig<'a>(
 ) -> Result<Sig<'a>, Diag> {
     let params = params(ctx, &func.params)?;
 
-    let ty = func
-        .ty
-        .as_ref()
-        .map(|t| ptype(ctx, &t).map(|t| t.1))
-        .transpose()?
-        .unwrap_or(Ty::UNIT);
+    let ty = match &func.ty {
+        Some(ty) => {
+            let (span, ty) = ptype(ctx, ty)?;
+            escaping_closure(ctx, span, ty, "returned from functions")?;
+            ty
+        }
+        None => Ty::UNIT,
+    };
 
     // TODO: cascading `Self`?
     //// check for `Self` type
@@ -664,46 +751,104 @@ fn func_sig<'a>(
         method_self,
         ty,
         linkage: Linkage::Local,
+        variadic: false,
     })
 }
 
 fn extern_sig<'a>(ctx: &mut Ctx<'a>, func: &rules::ExternFunc) -> Result<Sig<'a>, Diag> {
     let params = params(ctx, &func.params)?;
 
-    match ctx.as_str(func.convention).as_ref() {
-        "C" => {}
+    let host = match ctx.as_str(func.convention).as_ref() {
+        "C" => false,
+        "host" => true,
         c => {
             return Err(ctx.report_error(
                 func.convention,
                 format!("Unknown calling convention `{}`", c),
             ));
         }
-    }
+    };
 
-    let Some(link) = func.link else {
-        return Err(ctx.report_error(
-            func.name,
-            format!(
-                "Unknown linkage for `{}`, specify with the `link(\"<path>\")` attribute",
-                ctx.as_str(func.name),
-            ),
-        ));
+    let linkage = if host {
+        if let Some(variadic) = func.variadic {
+            return Err(ctx.report_error(variadic, "host functions cannot be variadic"));
+        }
+        Linkage::Host
+    } else {
+        let Some(link) = func.link else {
+            return Err(ctx.report_error(
+                func.name,
+                format!(
+                    "Unknown linkage for `{}`, specify with the `link(\"<path>\")` attribute",
+                    ctx.as_str(func.name),
+                ),
+            ));
+        };
+        Linkage::External {
+            link: ctx.intern_str(ctx.as_str(link).as_ref()),
+        }
     };
 
+    let ty = func.ty.as_ref().map(|t| ptype(ctx, t)).transpose()?;
+    if let Some((span, ty)) = ty {
+        escaping_closure(ctx, span, ty, "returned from functions")?;
+    }
+    let by_value = params
+        .iter()
+        .filter_map(|p| match p {
+            Param::Named { span, ty, .. } => Some((*span, *ty)),
+            Param::Slf(_) => None,
+        })
+        .chain(ty);
+    for (span, ty) in by_value {
+        if let Some(strukt) = ctx.tys.non_c_struct(ty.0) {
+            let strukt = ctx.tys.strukt(strukt);
+            let repr = if strukt.repr.packed {
+                "packed"
+            } else {
+                "over-aligned"
+            };
+            return Err(ctx
+                .report_error(
+                    span,
+                    format!(
+                        "`{}` does not have a C layout and cannot be passed to extern functions by value",
+                        strukt.name.as_str()
+                    ),
+                )
+                .msg(Msg::note(
+                    &ctx.source_map,
+                    strukt.name.span,
+                    format!("`{}` is {}, pass it by reference instead", strukt.name.as_str(), repr),
+                )));
+        }
+
+        if !host && let Some(strukt) = ctx.tys.struct_with_fn(ty.0) {
+            let strukt = ctx.tys.strukt(strukt);
+            return Err(ctx
+                .report_error(
+                    span,
+                    format!(
+                        "`{}` stores a function pointer and cannot be passed to or returned from C functions",
+                        strukt.name.as_str()
+                    ),
+                )
+                .msg(Msg::note(
+                    &ctx.source_map,
+                    strukt.name.span,
+                    "Pebble functions can only be passed to C directly as parameters",
+                )));
+        }
+    }
+
     Ok(Sig {
         span: func.span,
         ident: Symbol::intern(ctx.as_str(func.name)),
         params: ctx.intern_slice(&params),
         method_self: None,
-        ty: func
-            .ty
-            .as_ref()
-            .map(|t| ptype(ctx, t).map(|t| t.1))
-            .transpose()?
-            .unwrap_or(Ty::UNIT),
-        linkage: Linkage::External {
-            link: ctx.intern_str(ctx.as_str(link).as_ref()),
-        },
+        ty: ty.map(|t| t.1).unwrap_or(Ty::UNIT),
+        linkage,
+        variadic: func.variadic.is_some(),
     })
 }
 
@@ -744,7 +889,7 @@ fn ptype<'a>(ctx: &mut Ctx<'a>, ty: &rules::PType) -> Result<(Span, Ty), Diag> {
         }
         rules::PType::Array { span, size, inner } => {
             let (_, inner) = ptype(ctx, inner)?;
-            let Ok(size) = ctx.as_str(size).parse::<usize>() else {
+            let Some(size) = array_len(ctx.as_str(size)) else {
                 return Err(
                     ctx.report_error(size, "expected a positive integer size for an array type")
                 );
@@ -755,6 +900,80 @@ fn ptype<'a>(ctx: &mut Ctx<'a>, ty: &rules::PType) -> Result<(Span, Ty), Diag> {
             let (_, inner) = ptype(ctx, inner)?;
             (*span, ctx.tys.intern_kind(TyKind::Slice(inner.0)))
         }
+        rules::PType::Tuple { span, elems } => {
+            if elems.is_empty() {
+                return Ok((*span, Ty::UNIT));
+            }
+
+            let mut tys = Vec::with_capacity(elems.len());
+            for elem in elems.iter() {
+                let (span, ty) = ptype(ctx, elem)?;
+                if !ty.is_sized() {
+                    return Err(ctx.report_error(span, "tuple elements must be sized"));
+                }
+                tys.push(ty);
+            }
+            (*span, ctx.tys.intern_tuple(&tys))
+        }
+        rules::PType::Fn { span, params, ret } => {
+            let mut tys = Vec::with_capacity(params.len());
+            for param in params.iter() {
+                let (span, ty) = ptype(ctx, param)?;
+                if !ty.is_sized() {
+                    return Err(ctx.report_error(span, "function parameters must be sized"));
+                }
+                tys.push(ty);
+            }
+            let ret = match ret {
+                Some(ret) => {
+                    let (span, ret) = ptype(ctx, ret)?;
+                    escaping_closure(ctx, span, ret, "returned from functions")?;
+                    ret
+                }
+                None => Ty::UNIT,
+            };
+            (*span, ctx.tys.intern_fn(&tys, ret))
+        }
+        rules::PType::Closure { span, params, ret } => {
+            let mut tys = Vec::with_capacity(params.len());
+            for param in params.iter() {
+                let (span, ty) = ptype(ctx, param)?;
+                if !ty.is_sized() {
+                    return Err(ctx.report_error(span, "closure parameters must be sized"));
+                }
+                tys.push(ty);
+            }
+            let ret = match ret {
+                Some(ret) => {
+                    let (span, ret) = ptype(ctx, ret)?;
+                    escaping_closure(ctx, span, ret, "returned from closures")?;
+                    ret
+                }
+                None => Ty::UNIT,
+            };
+            (*span, ctx.tys.intern_closure(&tys, ret))
+        }
+        rules::PType::Opt { span, inner } => {
+            let (inner_span, inner) = ptype(ctx, inner)?;
+            let thin_ptr = match inner.0 {
+                TyKind::Ref(TyKind::Str | TyKind::Slice(_)) => false,
+                TyKind::Ref(_) | TyKind::Fn(_, _) => true,
+                _ => false,
+            };
+            if !thin_ptr {
+                return Err(ctx
+                    .report_error(
+                        inner_span,
+                        format!("`{}` cannot be optional", inner.to_string(ctx)),
+                    )
+                    .msg(Msg::help(
+                        &ctx.source_map,
+                        *span,
+                        "only references and function pointers can be `null`",
+                    )));
+            }
+            (*span, ctx.tys.intern_kind(TyKind::Opt(inner.0)))
+        }
     })
 }
 
@@ -784,14 +1003,27 @@ fn func<'a>(
 ) -> Result<Func<'a>, Diag> {
     let sig = func_sig(ctx, method_self, func)?;
 
+    ctx.locals = param_syms(sig.params);
+    let block = block(ctx, &func.block);
+    ctx.locals.clear();
+
     Ok(Func {
         name_span: ctx.span(func.name),
-        block: block(ctx, &func.block)?,
+        block: block?,
         attrs: func.attributes.clone(),
         sig: ctx.intern(sig),
     })
 }
 
+fn param_syms(params: &[Param]) -> Vec<Symbol> {
+    params
+        .iter()
+        .map(|param| match param {
+            Param::Named { ident, .. } | Param::Slf(ident) => ident.sym,
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub struct Block<'a> {
     pub span: Span,
@@ -800,11 +1032,14 @@ pub struct Block<'a> {
 }
 
 fn block<'a>(ctx: &mut Ctx<'a>, block: &rules::Block) -> Result<Block<'a>, Diag> {
-    let mut stmts = block
+    let locals = ctx.locals.len();
+    let stmts = block
         .stmts
         .iter()
         .map(|st| stmt(ctx, st))
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Result<Vec<_>, _>>();
+    ctx.locals.truncate(locals);
+    let mut stmts = stmts?;
 
     let end = if let Some(last) = stmts.last() {
         match last {
@@ -847,6 +1082,7 @@ pub enum SemiStmt<'a> {
     Assign(Assign<'a>),
     Ret(Return<'a>),
     Expr(Expr<'a>),
+    Defer(Defer<'a>),
 }
 
 impl SemiStmt<'_> {
@@ -856,24 +1092,37 @@ impl SemiStmt<'_> {
             Self::Assign(assign) => assign.span,
             Self::Ret(ret) => ret.span,
             Self::Expr(expr) => expr.span(),
+            Self::Defer(defer) => defer.span,
         }
     }
 }
 
+/// Runs `stmt` when the enclosing block exits, see [`Ctx::defers`].
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct Defer<'a> {
+    pub span: Span,
+    pub stmt: &'a Stmt<'a>,
+}
+
 fn stmt<'a>(ctx: &mut Ctx<'a>, stmt: &rules::Stmt) -> Result<Stmt<'a>, Diag> {
     Ok(match stmt {
         rules::Stmt::Let {
             span,
-            name,
+            pat,
             ty,
             assign,
             ..
-        } => Stmt::Semi(SemiStmt::Let(Let {
-            span: *span,
-            lhs: let_target(ctx, &rules::Expr::Ident(*name)),
-            rhs: pexpr(ctx, assign)?,
-            ty: ty.as_ref().map(|t| ptype(ctx, &t)).transpose()?,
-        })),
+        } => {
+            let let_ = Let {
+                span: *span,
+                lhs: let_target(ctx, pat)?,
+                rhs: pexpr(ctx, assign)?,
+                ty: ty.as_ref().map(|t| ptype(ctx, &t)).transpose()?,
+            };
+            // bound after the rhs, which may refer to a function of the same name
+            let_target_syms(&let_.lhs, &mut ctx.locals);
+            Stmt::Semi(SemiStmt::Let(let_))
+        }
         rules::Stmt::Semi(expr) => match expr {
             rules::Expr::Assign(assign) => Stmt::Semi(SemiStmt::Assign(Assign {
                 span: assign.span,
@@ -881,6 +1130,21 @@ fn stmt<'a>(ctx: &mut Ctx<'a>, stmt: &rules::Stmt) -> Result<Stmt<'a>, Diag> {
                 lhs: pexpr(ctx, &assign.lhs)?,
                 rhs: pexpr(ctx, &assign.rhs)?,
             })),
+            rules::Expr::Ret(span, _) if ctx.defer_loops.is_some() => {
+                return Err(ctx.report_error(*span, "cannot `return` from a deferred statement"));
+            }
+            rules::Expr::Ret(span, _) if ctx.inferred_closure_ret => {
+                return Err(ctx
+                    .report_error(
+                        *span,
+                        "cannot `return` from a closure without a return type",
+                    )
+                    .msg(Msg::help(
+                        &ctx.source_map,
+                        *span,
+                        "annotate the closure with `-> <type>`",
+                    )));
+            }
             rules::Expr::Ret(span, expr) => Stmt::Semi(SemiStmt::Ret(Return {
                 span: *span,
                 expr: match expr {
@@ -891,6 +1155,33 @@ fn stmt<'a>(ctx: &mut Ctx<'a>, stmt: &rules::Stmt) -> Result<Stmt<'a>, Diag> {
             e => Stmt::Semi(SemiStmt::Expr(pexpr(ctx, e)?)),
         },
         rules::Stmt::Open(expr) => Stmt::Open(pexpr(ctx, &expr)?),
+        rules::Stmt::Attributed {
+            span,
+            attributes,
+            stmt,
+        } => {
+            let stream = ctx.source_map.buffer(span.source as usize).stream();
+            for attr in attributes.iter() {
+                attr.validate(&stream, AttrTarget::Stmt)?;
+            }
+            self::stmt(ctx, stmt)?
+        }
+        rules::Stmt::Defer {
+            span,
+            stmt: deferred,
+            ..
+        } => {
+            let defer_loops = ctx.defer_loops.replace(ctx.loops.len());
+            let deferred = self::stmt(ctx, deferred);
+            ctx.defer_loops = defer_loops;
+
+            let deferred = ctx.intern(deferred?);
+            ctx.defers.insert(*span, deferred);
+            Stmt::Semi(SemiStmt::Defer(Defer {
+                span: *span,
+                stmt: deferred,
+            }))
+        }
     })
 }
 
@@ -898,50 +1189,77 @@ fn stmt<'a>(ctx: &mut Ctx<'a>, stmt: &rules::Stmt) -> Result<Stmt<'a>, Diag> {
 pub struct Let<'a> {
     pub span: Span,
     pub ty: Option<(Span, Ty)>,
-    pub lhs: LetTarget,
+    pub lhs: LetTarget<'a>,
     pub rhs: Expr<'a>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum LetTarget {
+pub enum LetTarget<'a> {
     Ident(Ident),
+    Tuple {
+        span: Span,
+        targets: &'a [LetTarget<'a>],
+    },
+    Struct {
+        span: Span,
+        id: StructId,
+        fields: &'a [(Ident, LetTarget<'a>)],
+    },
+}
+
+impl LetTarget<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Ident(ident) => ident.span,
+            Self::Tuple { span, .. } => *span,
+            Self::Struct { span, .. } => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub enum Expr<'a> {
-    Break(Span),
-    Continue(Span),
+    Break(Break<'a>),
+    Continue(Continue),
     Ident(Ident),
     Lit(Lit<'a>),
     Str(StrLit<'a>),
     Bool(BoolLit),
+    Null(Span),
     Bin(BinOp<'a>),
     Access(Access<'a>),
     Unary(Unary<'a>),
     Struct(StructDef<'a>),
     Call(Call<'a>),
     MethodCall(MethodCall<'a>),
+    IndirectCall(IndirectCall<'a>),
+    Fn(FnRef<'a>),
     Block(Block<'a>),
     If(If<'a>),
     Loop(Loop<'a>),
     While(While<'a>),
     For(ForLoop<'a>),
     Array(ArrDef<'a>),
+    Tuple(TupleDef<'a>),
     IndexOf(IndexOf<'a>),
     Range(Range<'a>),
     Cast(Cast<'a>),
+    Closure(&'a Closure<'a>),
 }
 
 impl Expr<'_> {
     pub fn span(&self) -> Span {
         match self {
-            Self::Continue(span) => *span,
-            Self::Break(span) => *span,
+            Self::Continue(cont) => cont.span,
+            Self::Break(brk) => brk.span,
             Self::Bool(bool) => bool.span,
+            Self::Null(span) => *span,
             Self::Ident(ident) => ident.span,
             Self::Lit(lit) => lit.span,
             Self::Call(call) => call.span,
             Self::MethodCall(call) => call.span,
+            Self::IndirectCall(call) => call.span,
+            Self::Fn(func) => func.span,
             Self::Bin(bin) => bin.span,
             Self::Struct(def) => def.span,
             Self::Block(block) => block.span,
@@ -956,9 +1274,11 @@ impl Expr<'_> {
                 ArrDef::Elems { span, .. } => *span,
                 ArrDef::Repeated { span, .. } => *span,
             },
+            Self::Tuple(tuple) => tuple.span,
             Self::IndexOf(index) => index.span,
             Self::Range(range) => range.span,
             Self::Cast(cast) => cast.span,
+            Self::Closure(closure) => closure.span,
         }
     }
 }
@@ -1007,6 +1327,12 @@ impl ArrDef<'_> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct TupleDef<'a> {
+    pub span: Span,
+    pub exprs: &'a [Expr<'a>],
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub struct Unary<'a> {
     pub span: Span,
@@ -1067,11 +1393,42 @@ pub struct BoolLit {
 pub struct Loop<'a> {
     pub span: Span,
     pub block: Block<'a>,
+    /// Set when a `break` carries a value out of the loop, which becomes the value of the loop.
+    pub breaks_with_value: bool,
+}
+
+impl Loop<'_> {
+    /// Names the type of the values that break out of the loop, for the `TypeKey`.
+    pub fn value_ident(&self) -> Option<Ident> {
+        self.breaks_with_value.then(|| Ident {
+            sym: Symbol::intern("loop"),
+            span: self.span,
+        })
+    }
+}
+
+/// `break`, leaving the loop at `target`.
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct Break<'a> {
+    pub span: Span,
+    /// Span of the loop that is exited.
+    pub target: Span,
+    pub value: Option<&'a Expr<'a>>,
+}
+
+/// `continue`, starting the next iteration of the loop at `target`.
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct Continue {
+    pub span: Span,
+    /// Span of the loop that is continued.
+    pub target: Span,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub struct While<'a> {
     pub span: Span,
+    /// The `x` in `while let x = <optional>`, otherwise `condition` is a `bool`.
+    pub binding: Option<Ident>,
     pub condition: &'a Expr<'a>,
     pub block: Block<'a>,
 }
@@ -1087,6 +1444,8 @@ pub struct ForLoop<'a> {
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub struct If<'a> {
     pub span: Span,
+    /// The `x` in `if let x = <optional>`, otherwise `condition` is a `bool`.
+    pub binding: Option<Ident>,
     pub condition: &'a Expr<'a>,
     pub block: &'a Expr<'a>,
     pub otherwise: Option<&'a Expr<'a>>,
@@ -1095,14 +1454,21 @@ pub struct If<'a> {
 fn if_<'a>(
     ctx: &mut Ctx<'a>,
     span: Span,
+    binding: Option<TokenId>,
     expr: &rules::Expr,
     blck: &rules::Block,
     otherwise: Option<&rules::Block>,
 ) -> Result<If<'a>, Diag> {
     let condition = pexpr(ctx, expr)?;
+    let binding = binding.map(|binding| ctx.token_ident(binding));
 
     // TODO: pre reduce these
-    let blck = Expr::Block(block(ctx, blck)?);
+    ctx.locals.extend(binding.map(|binding| binding.sym));
+    let blck = block(ctx, blck);
+    if binding.is_some() {
+        ctx.locals.pop();
+    }
+    let blck = Expr::Block(blck?);
     let otherwise = match otherwise {
         Some(blck) => {
             let otherwise = Expr::Block(block(ctx, blck)?);
@@ -1113,17 +1479,49 @@ fn if_<'a>(
 
     Ok(If {
         span,
+        binding,
         condition: ctx.intern(condition),
         block: ctx.intern(blck),
         otherwise,
     })
 }
 
-fn let_target<'a>(ctx: &mut Ctx<'a>, expr: &rules::Expr) -> LetTarget {
-    match expr {
-        rules::Expr::Ident(ident) => LetTarget::Ident(ctx.token_ident(ident)),
-        _ => todo!(),
-    }
+fn let_target<'a>(ctx: &mut Ctx<'a>, pat: &rules::Pat) -> Result<LetTarget<'a>, Diag> {
+    Ok(match pat {
+        rules::Pat::Ident(ident) => LetTarget::Ident(ctx.token_ident(ident)),
+        rules::Pat::Tuple { span, pats } => {
+            let targets = pats
+                .iter()
+                .map(|pat| let_target(ctx, pat))
+                .collect::<Result<Vec<_>, _>>()?;
+            LetTarget::Tuple {
+                span: *span,
+                targets: ctx.intern_slice(&targets),
+            }
+        }
+        rules::Pat::Struct { span, name, fields } => {
+            let id = ctx
+                .tys
+                .struct_id(Symbol::intern(ctx.as_str(name)))
+                .ok_or_else(|| ctx.report_error(name, "undefined type"))?;
+            let fields = fields
+                .iter()
+                .map(|field| {
+                    let name = ctx.token_ident(field.name);
+                    let target = match &field.pat {
+                        Some(pat) => let_target(ctx, pat)?,
+                        None => LetTarget::Ident(name),
+                    };
+                    Ok((name, target))
+                })
+                .collect::<Result<Vec<_>, Diag>>()?;
+            LetTarget::Struct {
+                span: *span,
+                id,
+                fields: ctx.intern_slice(&fields),
+            }
+        }
+    })
 }
 
 //fn enum_def<'a>(ctx: &mut Ctx<'a>, def: &rules::EnumDef) -> Result<EnumDef, Diag> {
@@ -1177,6 +1575,8 @@ pub struct Access<'a> {
     pub span: Span,
     pub lhs: &'a Expr<'a>,
     pub accessors: &'a [Ident],
+    /// Within an `unsafe` block, required to access the fields of a union.
+    pub in_unsafe: bool,
 }
 
 fn bin_op<'a>(
@@ -1230,57 +1630,159 @@ fn access<'a>(
         span,
         lhs: ctx.intern(lhs),
         accessors: ctx.intern_slice(&accessors),
+        in_unsafe: ctx.in_unsafe,
     })
 }
 
 fn pexpr<'a>(ctx: &mut Ctx<'a>, expr: &rules::Expr) -> Result<Expr<'a>, Diag> {
     Ok(match expr {
         rules::Expr::Ident(ident) => Expr::Ident(ctx.token_ident(ident)),
-        rules::Expr::Lit(lit) => Expr::Lit(plit(ctx, *lit)?),
+        rules::Expr::Lit(lit) => Expr::Lit(plit(ctx, *lit, false)?),
         rules::Expr::Bin(span, op, lhs, rhs) => Expr::Bin(bin_op(ctx, *span, *op, lhs, rhs)?),
-        rules::Expr::Call { span, func, args } => Expr::Call(call(ctx, *span, *func, args)?),
+        rules::Expr::Call { span, func, args } => {
+            let name = Symbol::intern(ctx.as_str(func));
+            if ctx.locals.contains(&name) || ctx.get_sig(name).is_none() {
+                // a variable holding a function pointer, which shadows a function of the same name
+                let callee = Expr::Ident(ctx.token_ident(*func));
+                Expr::IndirectCall(indirect_call(ctx, *span, callee, args)?)
+            } else if matches!(name.as_str(), "size_of" | "align_of")
+                && ctx.intrinsics.contains(&name)
+            {
+                type_query(ctx, *span, *func, args)?
+            } else {
+                Expr::Call(call(ctx, *span, *func, args)?)
+            }
+        }
+        rules::Expr::IndirectCall { span, callee, args } => {
+            let callee = pexpr(ctx, callee)?;
+            Expr::IndirectCall(indirect_call(ctx, *span, callee, args)?)
+        }
+        rules::Expr::Path(path) => {
+            return Err(
+                ctx.report_error(path.span, "methods must be called or referenced with `&`")
+            );
+        }
         rules::Expr::StructDef(def) => Expr::Struct(struct_def(ctx, def)?),
         rules::Expr::If {
             span,
+            binding,
             condition,
             block,
             otherwise,
-        } => Expr::If(if_(ctx, *span, condition, block, otherwise.as_ref())?),
+        } => Expr::If(if_(
+            ctx,
+            *span,
+            *binding,
+            condition,
+            block,
+            otherwise.as_ref(),
+        )?),
         rules::Expr::Bool(id) => Expr::Bool(BoolLit {
             span: ctx.span(*id),
             val: ctx.kind(*id) == TokenKind::True,
         }),
+        rules::Expr::Null(id) => Expr::Null(ctx.span(*id)),
         rules::Expr::Str(str) => Expr::Str(StrLit {
             span: ctx.span(*str),
-            val: ctx.intern_str(ctx.as_str(str).as_ref()),
+            val: if ctx.kind(*str) == TokenKind::RawStr {
+                ctx.intern_str(ctx.as_str(str))
+            } else {
+                let val = escape::unescape_str(ctx.as_str(str))
+                    .map_err(|err| ctx.report_error(*str, err.msg))?;
+                ctx.intern_str(&val)
+            },
         }),
         rules::Expr::Loop {
-            span, block: blck, ..
-        } => Expr::Loop(Loop {
-            span: *span,
-            block: block(ctx, blck)?,
-        }),
-        rules::Expr::Access { span, lhs, field } => Expr::Access(access(ctx, *span, lhs, *field)?),
-        rules::Expr::Unary(span, _, kind, expr) => Expr::Unary({
-            let expr = pexpr(ctx, expr)?;
-            Unary {
+            span,
+            label,
+            block: blck,
+            ..
+        } => {
+            let (block, scope) = loop_block(ctx, *span, *label, true, blck)?;
+            if let (Some(_), Some(unit)) = (scope.value_break, scope.unit_break) {
+                return Err(ctx.report_error(
+                    unit,
+                    "`break` without a value in a `loop` that breaks with a value",
+                ));
+            }
+
+            Expr::Loop(Loop {
                 span: *span,
-                kind: *kind,
-                inner: ctx.intern(expr),
+                block,
+                breaks_with_value: scope.value_break.is_some(),
+            })
+        }
+        rules::Expr::Access { span, lhs, field } => Expr::Access(access(ctx, *span, lhs, *field)?),
+        rules::Expr::Unsafe { block: blck, .. } => {
+            let in_unsafe = std::mem::replace(&mut ctx.in_unsafe, true);
+            let blck = block(ctx, blck);
+            ctx.in_unsafe = in_unsafe;
+            Expr::Block(blck?)
+        }
+        rules::Expr::Unary(span, _, kind, expr) => {
+            if *kind == UOpKind::Ref
+                && let Some(func) = fn_ref(ctx, *span, expr)?
+            {
+                Expr::Fn(func)
+            } else {
+                let expr = match &**expr {
+                    // a suffixed literal is range checked here, so it must know that it is
+                    // negated, e.g. `-128i8`
+                    rules::Expr::Lit(lit) => Expr::Lit(plit(ctx, *lit, *kind == UOpKind::Neg)?),
+                    expr => pexpr(ctx, expr)?,
+                };
+                Expr::Unary(Unary {
+                    span: *span,
+                    kind: *kind,
+                    inner: ctx.intern(expr),
+                })
             }
-        }),
+        }
         rules::Expr::Array(arr) => Expr::Array(array(ctx, arr)?),
         rules::Expr::IndexOf { span, array, index } => {
             Expr::IndexOf(index_of(ctx, *span, array, index)?)
         }
-        rules::Expr::Break(t) => Expr::Break(ctx.span(*t)),
-        rules::Expr::Continue(t) => Expr::Continue(ctx.span(*t)),
+        rules::Expr::Break { span, label, value } => {
+            let target = loop_target(ctx, *span, *label, "break")?;
+            let scope = &mut ctx.loops[target];
+            match value {
+                Some(_) if !scope.is_loop => {
+                    return Err(
+                        ctx.report_error(*span, "`break` with a value is only allowed in `loop`")
+                    );
+                }
+                Some(_) => _ = scope.value_break.get_or_insert(*span),
+                None => _ = scope.unit_break.get_or_insert(*span),
+            }
+
+            let target = scope.span;
+            let value = match value {
+                Some(value) => {
+                    let value = pexpr(ctx, value)?;
+                    Some(ctx.intern(value))
+                }
+                None => None,
+            };
+            Expr::Break(Break {
+                span: *span,
+                target,
+                value,
+            })
+        }
+        rules::Expr::Continue { span, label } => {
+            let target = loop_target(ctx, *span, *label, "continue")?;
+            Expr::Continue(Continue {
+                span: *span,
+                target: ctx.loops[target].span,
+            })
+        }
         rules::Expr::For {
             span,
+            label,
             iter,
             iterable,
             block,
-        } => Expr::For(for_loop(ctx, *span, *iter, iterable, block)?),
+        } => Expr::For(for_loop(ctx, *span, *label, *iter, iterable, block)?),
         rules::Expr::Range {
             span,
             start,
@@ -1303,6 +1805,16 @@ fn pexpr<'a>(ctx: &mut Ctx<'a>, expr: &rules::Expr) -> Result<Expr<'a>, Diag> {
             }
         }),
         rules::Expr::Paren(inner) => pexpr(ctx, inner)?,
+        rules::Expr::Tuple { span, exprs } => {
+            let exprs = exprs
+                .iter()
+                .map(|expr| pexpr(ctx, expr))
+                .collect::<Result<Vec<_>, _>>()?;
+            Expr::Tuple(TupleDef {
+                span: *span,
+                exprs: ctx.intern_slice(&exprs),
+            })
+        }
         rules::Expr::Cast { span, lhs, ty, .. } => {
             let lhs = pexpr(ctx, lhs)?;
             Expr::Cast(Cast {
@@ -1319,6 +1831,8 @@ fn pexpr<'a>(ctx: &mut Ctx<'a>, expr: &rules::Expr) -> Result<Expr<'a>, Diag> {
         } => Expr::MethodCall(method_call(ctx, *span, receiver, *method, args)?),
         rules::Expr::While {
             span,
+            label,
+            binding,
             condition,
             block: blck,
             ..
@@ -1326,27 +1840,92 @@ fn pexpr<'a>(ctx: &mut Ctx<'a>, expr: &rules::Expr) -> Result<Expr<'a>, Diag> {
             let expr = pexpr(ctx, condition)?;
             While {
                 span: *span,
+                binding: binding.map(|binding| ctx.token_ident(binding)),
                 condition: ctx.intern(expr),
-                block: block(ctx, blck)?,
+                block: loop_block(ctx, *span, *label, false, blck)?.0,
             }
         }),
+        rules::Expr::Closure(def) => Expr::Closure(closure(ctx, def)?),
         rules::Expr::Ret(_, _) | rules::Expr::Assign(_) => unreachable!(),
     })
 }
 
+/// Lowers the body of a loop, which `break` and `continue` may target by `label`.
+fn loop_block<'a>(
+    ctx: &mut Ctx<'a>,
+    span: Span,
+    label: Option<TokenId>,
+    is_loop: bool,
+    blck: &rules::Block,
+) -> Result<(Block<'a>, LoopScope), Diag> {
+    ctx.loops.push(LoopScope {
+        span,
+        label: label.map(|label| ctx.token_ident(label).sym),
+        is_loop,
+        value_break: None,
+        unit_break: None,
+    });
+    let block = block(ctx, blck);
+    let scope = ctx.loops.pop().unwrap();
+    Ok((block?, scope))
+}
+
+/// Finds the index of the loop in `ctx.loops` that a `break` or `continue` leaves.
+fn loop_target(
+    ctx: &mut Ctx,
+    span: Span,
+    label: Option<TokenId>,
+    keyword: &str,
+) -> Result<usize, Diag> {
+    let target = match label {
+        Some(label) => {
+            let ident = ctx.token_ident(label);
+            ctx.loops
+                .iter()
+                .rposition(|l| l.label == Some(ident.sym))
+                .ok_or_else(|| {
+                    ctx.report_error(
+                        ident.span,
+                        format!("use of undeclared label `{}`", ident.as_str()),
+                    )
+                })?
+        }
+        None => match ctx.loops.len() {
+            0 => {
+                return Err(ctx.report_error(span, format!("`{}` outside of a loop", keyword)));
+            }
+            len => len - 1,
+        },
+    };
+
+    if ctx.defer_loops.is_some_and(|loops| target < loops) {
+        return Err(ctx.report_error(
+            span,
+            format!("cannot `{}` out of a deferred statement", keyword),
+        ));
+    }
+
+    Ok(target)
+}
+
 fn for_loop<'a>(
     ctx: &mut Ctx<'a>,
     span: Span,
+    label: Option<TokenId>,
     iter: TokenId,
     iterable: &rules::Expr,
     blck: &rules::Block,
 ) -> Result<ForLoop<'a>, Diag> {
     let iterable = pexpr(ctx, iterable)?;
+    let iter = ctx.token_ident(iter);
+    ctx.locals.push(iter.sym);
+    let block = loop_block(ctx, span, label, false, blck);
+    ctx.locals.pop();
     Ok(ForLoop {
         span,
-        iter: ctx.token_ident(iter),
+        iter,
         iterable: ctx.intern(iterable),
-        block: block(ctx, blck)?,
+        block: block?.0,
     })
 }
 
@@ -1404,27 +1983,66 @@ fn array<'a>(ctx: &mut Ctx<'a>, arr: &rules::ArrDef) -> Result<ArrDef<'a>, Diag>
     }
 }
 
-fn plit<'a>(ctx: &mut Ctx<'a>, lit: TokenId) -> Result<Lit<'a>, Diag> {
+/// Parses a literal, which is negated if `negative`.
+fn plit<'a>(ctx: &mut Ctx<'a>, lit: TokenId, negative: bool) -> Result<Lit<'a>, Diag> {
     let str = ctx.as_str(lit);
-    match (
-        if str.contains("0x") {
-            u64::from_str_radix(&str[2..], 16)
-        } else if str.contains("0b") {
-            u64::from_str_radix(&str[2..], 2)
-        } else {
-            str.parse()
-        },
-        str.parse::<f64>(),
-    ) {
-        (Ok(val), _) => Ok(Lit {
+    if ctx.kind(lit) == TokenKind::Char {
+        let (_, inner) = char_lit_inner(str);
+        let val = escape::unescape_byte(inner).map_err(|err| ctx.report_error(lit, err.msg))?;
+        return Ok(Lit {
             span: ctx.span(lit),
-            kind: ctx.intern(LitKind::Int(val)),
-        }),
-        (Err(_), Ok(val)) => Ok(Lit {
-            span: ctx.span(lit),
-            kind: ctx.intern(LitKind::Float(val)),
-        }),
-        (Err(_), Err(_)) => Err(ctx.report_error(lit, "expected a literal")),
+            kind: ctx.intern(LitKind::Int(val as u64)),
+            ty: Some(Ty::U8),
+        });
+    }
+
+    let number = number::parse(str).map_err(|err| ctx.report_error(lit, err.msg))?;
+    let val = number.val;
+    let ty = number.suffix.map(|suffix| match suffix {
+        "u8" => TyKind::Int(IntTy::new_8(Sign::U)),
+        "u16" => TyKind::Int(IntTy::new_16(Sign::U)),
+        "u32" => TyKind::Int(IntTy::new_32(Sign::U)),
+        "u64" => TyKind::Int(IntTy::new_64(Sign::U)),
+        "i8" => TyKind::Int(IntTy::new_8(Sign::I)),
+        "i16" => TyKind::Int(IntTy::new_16(Sign::I)),
+        "i32" => TyKind::Int(IntTy::new_32(Sign::I)),
+        "i64" => TyKind::Int(IntTy::new_64(Sign::I)),
+        "f32" => TyKind::Float(FloatTy::F32),
+        "f64" => TyKind::Float(FloatTy::F64),
+        _ => unreachable!(),
+    });
+    let ty = ty.map(|ty| ctx.tys.intern_kind(ty));
+
+    let kind = match val {
+        NumberVal::Int(val) => match ty {
+            Some(ty) if ty.is_float() => LitKind::Float(val as f64),
+            _ => LitKind::Int(val),
+        },
+        NumberVal::Float(val) => LitKind::Float(val),
+    };
+    let lit = Lit {
+        span: ctx.span(lit),
+        kind: ctx.intern(kind),
+        ty,
+    };
+
+    if let Some(ty) = ty
+        && !lit.kind.fits(ty, negative)
+    {
+        return Err(ctx.out_of_range(lit.span, ty));
+    }
+
+    Ok(lit)
+}
+
+/// Parses an integer literal used as an array length, e.g. `4` or `1_024`.
+fn array_len(str: &str) -> Option<usize> {
+    match number::parse(str) {
+        Ok(Number {
+            val: NumberVal::Int(val),
+            ..
+        }) => usize::try_from(val).ok(),
+        _ => None,
     }
 }
 
@@ -1526,3 +2144,466 @@ fn call<'a>(
         span,
     })
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct IndirectCall<'a> {
+    pub span: Span,
+    pub callee: &'a Expr<'a>,
+    pub args: &'a [Expr<'a>],
+}
+
+fn indirect_call<'a>(
+    ctx: &mut Ctx<'a>,
+    span: Span,
+    callee: Expr<'a>,
+    call_args: &[rules::Expr],
+) -> Result<IndirectCall<'a>, Diag> {
+    let args = args(ctx, call_args)?;
+    Ok(IndirectCall {
+        span,
+        callee: ctx.intern(callee),
+        args,
+    })
+}
+
+/// The address of a function, e.g. `&add` or `&Vec2::length`.
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct FnRef<'a> {
+    pub span: Span,
+    pub sig: &'a Sig<'a>,
+    pub ty: Ty,
+}
+
+/// Returns `None` if `expr` does not name a function, in which case it is an ordinary reference.
+fn fn_ref<'a>(
+    ctx: &mut Ctx<'a>,
+    span: Span,
+    expr: &rules::Expr,
+) -> Result<Option<FnRef<'a>>, Diag> {
+    let sig = match expr {
+        rules::Expr::Ident(ident) => {
+            let name = Symbol::intern(ctx.as_str(ident));
+            match ctx.get_sig(name) {
+                Some(sig) if !ctx.locals.contains(&name) => sig,
+                _ => return Ok(None),
+            }
+        }
+        rules::Expr::Path(path) => {
+            let [ty, method] = path.segments[..] else {
+                return Err(ctx.report_error(path.span, "expected `<type>::<method>`"));
+            };
+            let ty = ptype(ctx, &PType::Simple(ctx.span(ty), ty))?.1;
+            match ctx.get_method_sig(ty, Symbol::intern(ctx.as_str(method))) {
+                Some(sig) => sig,
+                None => {
+                    return Err(ctx.report_error(
+                        method,
+                        format!(
+                            "`{}` has no method `{}`",
+                            ty.to_string(ctx),
+                            ctx.as_str(method)
+                        ),
+                    ));
+                }
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    if sig.linkage != Linkage::Local {
+        return Err(ctx.report_error(
+            span,
+            format!(
+                "cannot take the address of external function `{}`",
+                sig.ident.as_str()
+            ),
+        ));
+    }
+    // these intrinsics are special cased at their call sites
+    if (sig::is_print(sig.ident.as_str())
+        || (matches!(sig.ident.as_str(), "size_of" | "align_of")
+            && ctx.intrinsics.contains(&sig.ident)))
+        && sig.method_self.is_none()
+    {
+        return Err(ctx.report_error(
+            span,
+            format!("cannot take the address of `{}`", sig.ident.as_str()),
+        ));
+    }
+
+    let params = sig
+        .params
+        .iter()
+        .map(|param| match param {
+            Param::Named { ty, .. } => *ty,
+            Param::Slf(_) => ctx.tys.intern_kind(TyKind::Ref(sig.method_self.unwrap().0)),
+        })
+        .collect::<Vec<_>>();
+    let ty = ctx.tys.intern_fn(&params, sig.ty);
+
+    Ok(Some(FnRef { span, sig, ty }))
+}
+
+/// `|x: i32| x + offset`
+///
+/// A closure is lowered into its own function, see [`closure_funcs`]. The first parameter of this
+/// function points to the closure's environment, which holds the captured locals.
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct Closure<'a> {
+    pub span: Span,
+    /// Captured locals are copied into the environment instead of borrowed.
+    pub moves: bool,
+    /// The closure's own parameters, with the return type if it is annotated, otherwise unit.
+    pub sig: &'a Sig<'a>,
+    pub ret: Option<Ty>,
+    pub block: Block<'a>,
+    /// Locals of the enclosing function that are used within the closure, in order of first use.
+    pub captures: &'a [Ident],
+}
+
+fn closure<'a>(ctx: &mut Ctx<'a>, def: &rules::Closure) -> Result<&'a Closure<'a>, Diag> {
+    let params = params(ctx, &def.params)?;
+    for param in params.iter() {
+        if let Param::Named { span, ty, .. } = param
+            && !ty.is_sized()
+        {
+            return Err(ctx.report_error(*span, "closure parameters must be sized"));
+        }
+    }
+
+    let ret = match &def.ty {
+        Some(ty) => {
+            let (span, ty) = ptype(ctx, ty)?;
+            escaping_closure(ctx, span, ty, "returned from closures")?;
+            Some(ty)
+        }
+        None => None,
+    };
+
+    let inferred_ret = std::mem::replace(&mut ctx.inferred_closure_ret, ret.is_none());
+    let defer_loops = ctx.defer_loops.take();
+    let loops = std::mem::take(&mut ctx.loops);
+    let locals = ctx.locals.len();
+    ctx.locals.extend(param_syms(&params));
+    let body = match &def.body {
+        rules::ClosureBody::Block(body) => block(ctx, body),
+        rules::ClosureBody::Expr(expr) => match expr.as_ref() {
+            rules::Expr::Ret(_, _) | rules::Expr::Assign(_) => {
+                stmt(ctx, &rules::Stmt::Semi((**expr).clone())).map(|stmt| Block {
+                    span: stmt.span(),
+                    stmts: ctx.intern_slice(&[stmt]),
+                    end: None,
+                })
+            }
+            expr => pexpr(ctx, expr).map(|expr| Block {
+                span: expr.span(),
+                stmts: &[],
+                end: Some(ctx.intern(expr)),
+            }),
+        },
+    };
+    ctx.inferred_closure_ret = inferred_ret;
+    ctx.defer_loops = defer_loops;
+    ctx.loops = loops;
+    ctx.locals.truncate(locals);
+    let body = body?;
+
+    let mut bound = param_syms(&params);
+    let mut captures = Vec::new();
+    block_captures(&body, &mut bound, &mut captures);
+    captures.retain(|ident: &Ident| ctx.get_const(ident.sym).is_none());
+
+    let sig = Sig {
+        span: def.span,
+        ident: Symbol::intern("{closure}"),
+        params: ctx.intern_slice(&params),
+        method_self: None,
+        ty: ret.unwrap_or(Ty::UNIT),
+        linkage: Linkage::Local,
+        variadic: false,
+    };
+    let closure = ctx.intern(Closure {
+        span: def.span,
+        moves: def.moove.is_some(),
+        sig: ctx.intern(sig),
+        ret,
+        block: body,
+        captures: ctx.intern_slice(&captures),
+    });
+    ctx.closures.push(closure);
+
+    Ok(closure)
+}
+
+/// Collects the identifiers in `block` that are not bound within it, or by `bound`.
+fn block_captures(block: &Block, bound: &mut Vec<Symbol>, captures: &mut Vec<Ident>) {
+    let len = bound.len();
+    for stmt in block.stmts.iter() {
+        stmt_captures(stmt, bound, captures);
+    }
+    if let Some(end) = block.end {
+        expr_captures(end, bound, captures);
+    }
+    bound.truncate(len);
+}
+
+fn stmt_captures(stmt: &Stmt, bound: &mut Vec<Symbol>, captures: &mut Vec<Ident>) {
+    match stmt {
+        Stmt::Semi(SemiStmt::Let(let_)) => {
+            expr_captures(&let_.rhs, bound, captures);
+            let_target_syms(&let_.lhs, bound);
+        }
+        Stmt::Semi(SemiStmt::Assign(assign)) => {
+            expr_captures(&assign.lhs, bound, captures);
+            expr_captures(&assign.rhs, bound, captures);
+        }
+        Stmt::Semi(SemiStmt::Ret(ret)) => {
+            if let Some(expr) = &ret.expr {
+                expr_captures(expr, bound, captures);
+            }
+        }
+        Stmt::Semi(SemiStmt::Defer(defer)) => stmt_captures(defer.stmt, bound, captures),
+        Stmt::Semi(SemiStmt::Expr(expr)) | Stmt::Open(expr) => expr_captures(expr, bound, captures),
+    }
+}
+
+fn let_target_syms(target: &LetTarget, bound: &mut Vec<Symbol>) {
+    match target {
+        LetTarget::Ident(ident) => bound.push(ident.sym),
+        LetTarget::Tuple { targets, .. } => {
+            for target in targets.iter() {
+                let_target_syms(target, bound);
+            }
+        }
+        LetTarget::Struct { fields, .. } => {
+            for (_, target) in fields.iter() {
+                let_target_syms(target, bound);
+            }
+        }
+    }
+}
+
+fn capture(ident: &Ident, bound: &[Symbol], captures: &mut Vec<Ident>) {
+    if !bound.contains(&ident.sym) && !captures.iter().any(|c| c.sym == ident.sym) {
+        captures.push(*ident);
+    }
+}
+
+fn expr_captures(expr: &Expr, bound: &mut Vec<Symbol>, captures: &mut Vec<Ident>) {
+    match expr {
+        Expr::Ident(ident) => capture(ident, bound, captures),
+        Expr::Bin(bin) => {
+            expr_captures(bin.lhs, bound, captures);
+            expr_captures(bin.rhs, bound, captures);
+        }
+        Expr::Access(access) => expr_captures(access.lhs, bound, captures),
+        Expr::Unary(unary) => expr_captures(unary.inner, bound, captures),
+        Expr::Struct(def) => {
+            for field in def.fields.iter() {
+                expr_captures(&field.expr, bound, captures);
+            }
+        }
+        Expr::Call(call) => {
+            for arg in call.args.iter() {
+                expr_captures(arg, bound, captures);
+            }
+        }
+        Expr::MethodCall(call) => {
+            if let MethodPath::Field(receiver) = call.receiver {
+                expr_captures(receiver, bound, captures);
+            }
+            for arg in call.args.iter() {
+                expr_captures(arg, bound, captures);
+            }
+        }
+        Expr::IndirectCall(call) => {
+            expr_captures(call.callee, bound, captures);
+            for arg in call.args.iter() {
+                expr_captures(arg, bound, captures);
+            }
+        }
+        Expr::Block(block) => block_captures(block, bound, captures),
+        Expr::If(if_) => {
+            expr_captures(if_.condition, bound, captures);
+            let len = bound.len();
+            bound.extend(if_.binding.map(|binding| binding.sym));
+            expr_captures(if_.block, bound, captures);
+            bound.truncate(len);
+            if let Some(otherwise) = if_.otherwise {
+                expr_captures(otherwise, bound, captures);
+            }
+        }
+        Expr::Loop(loop_) => block_captures(&loop_.block, bound, captures),
+        Expr::While(while_) => {
+            expr_captures(while_.condition, bound, captures);
+            let len = bound.len();
+            bound.extend(while_.binding.map(|binding| binding.sym));
+            block_captures(&while_.block, bound, captures);
+            bound.truncate(len);
+        }
+        Expr::For(for_) => {
+            expr_captures(for_.iterable, bound, captures);
+            bound.push(for_.iter.sym);
+            block_captures(&for_.block, bound, captures);
+            bound.pop();
+        }
+        Expr::Array(ArrDef::Elems { exprs, .. }) | Expr::Tuple(TupleDef { exprs, .. }) => {
+            for expr in exprs.iter() {
+                expr_captures(expr, bound, captures);
+            }
+        }
+        Expr::Array(ArrDef::Repeated { expr, num, .. }) => {
+            expr_captures(expr, bound, captures);
+            expr_captures(num, bound, captures);
+        }
+        Expr::IndexOf(index) => {
+            expr_captures(index.array, bound, captures);
+            expr_captures(index.index, bound, captures);
+        }
+        Expr::Range(range) => {
+            for expr in range.start.iter().chain(range.end.iter()) {
+                expr_captures(expr, bound, captures);
+            }
+        }
+        Expr::Cast(cast) => expr_captures(cast.lhs, bound, captures),
+        Expr::Closure(closure) => {
+            for ident in closure.captures.iter() {
+                capture(ident, bound, captures);
+            }
+        }
+        Expr::Break(brk) => {
+            if let Some(value) = brk.value {
+                expr_captures(value, bound, captures);
+            }
+        }
+        Expr::Continue(_)
+        | Expr::Lit(_)
+        | Expr::Str(_)
+        | Expr::Bool(_)
+        | Expr::Null(_)
+        | Expr::Fn(_) => {}
+    }
+}
+
+/// Builds the functions that closures are lowered into, once their types are resolved.
+///
+/// The environment is passed as the first argument, followed by the closure's own parameters.
+fn closure_funcs(ctx: &mut Ctx) {
+    for closure in ctx.closures.clone() {
+        let Some(TyKind::Closure(_, ret)) = ctx.closure_tys.get(&closure.span).map(|ty| ty.0)
+        else {
+            continue;
+        };
+
+        let mut params = vec![Param::Named {
+            span: closure.span,
+            ident: closure_env(closure),
+            ty: Ty::PTR,
+        }];
+        params.extend(closure.sig.params.iter().copied());
+
+        let sig = Sig {
+            params: ctx.intern_slice(&params),
+            ty: Ty(ret),
+            ..*closure.sig
+        };
+        let func = Func {
+            name_span: closure.span,
+            sig: ctx.intern(sig),
+            block: closure.block,
+            attrs: Vec::new(),
+        };
+        ctx.closure_funcs.insert(closure.span, func);
+    }
+}
+
+/// The parameter that points to the environment of `closure`.
+pub fn closure_env(closure: &Closure) -> Ident {
+    Ident {
+        sym: Symbol::intern("{env}"),
+        span: closure.span,
+    }
+}
+
+/// `size_of` and `align_of` take a type as their argument, which cannot be represented as a value,
+/// so they are evaluated into a literal here.
+fn type_query<'a>(
+    ctx: &mut Ctx<'a>,
+    span: Span,
+    name: TokenId,
+    call_args: &[rules::Expr],
+) -> Result<Expr<'a>, Diag> {
+    let id = Symbol::intern(ctx.as_str(name));
+    let [arg] = call_args else {
+        return Err(ctx.report_error(
+            span,
+            format!(
+                "`{}` expects a type as its only argument, got `{}` arguments",
+                id.as_str(),
+                call_args.len()
+            ),
+        ));
+    };
+
+    let (ty_span, ty) = expr_ty(ctx, span.source as usize, arg)?;
+    if matches!(ty.0, TyKind::Str | TyKind::Slice(_)) {
+        return Err(ctx.report_error(
+            ty_span,
+            format!(
+                "`{}` does not have a size known at compile-time",
+                ty.to_string(ctx)
+            ),
+        ));
+    }
+
+    let layout = ctx.tys.ty_layout(ty);
+    let val = match id.as_str() {
+        "size_of" => layout.size,
+        "align_of" => layout.alignment,
+        _ => unreachable!(),
+    };
+
+    Ok(Expr::Lit(Lit {
+        span,
+        kind: ctx.intern(LitKind::Int(val as u64)),
+        ty: None,
+    }))
+}
+
+/// Interpret an expression as a type, e.g. `&u8`, `[Point; 4]` or `(i32, f32)`.
+fn expr_ty(ctx: &mut Ctx, source: usize, expr: &rules::Expr) -> Result<(Span, Ty), Diag> {
+    match expr {
+        rules::Expr::Ident(ident) => ptype(ctx, &PType::Simple(ctx.span(*ident), *ident)),
+        rules::Expr::Paren(inner) => expr_ty(ctx, source, inner),
+        rules::Expr::Unary(span, _, UOpKind::Ref, inner) => {
+            let (_, inner) = expr_ty(ctx, source, inner)?;
+            Ok((*span, ctx.tys.intern_kind(TyKind::Ref(inner.0))))
+        }
+        rules::Expr::Array(rules::ArrDef::Repeated { span, expr, num }) => {
+            let (_, inner) = expr_ty(ctx, source, expr)?;
+            let len = match num.as_ref() {
+                rules::Expr::Lit(lit) => array_len(ctx.as_str(lit)),
+                _ => None,
+            };
+            let Some(len) = len else {
+                return Err(ctx.report_error(
+                    num.span(ctx.source_map.buffer(source)),
+                    "expected a positive integer size for an array type",
+                ));
+            };
+            Ok((*span, ctx.tys.intern_kind(TyKind::Array(len, inner.0))))
+        }
+        rules::Expr::Array(rules::ArrDef::Elems { span, exprs }) if exprs.len() == 1 => {
+            let (_, inner) = expr_ty(ctx, source, &exprs[0])?;
+            Ok((*span, ctx.tys.intern_kind(TyKind::Slice(inner.0))))
+        }
+        rules::Expr::Tuple { span, exprs } => {
+            let mut elems = Vec::with_capacity(exprs.len());
+            for expr in exprs.iter() {
+                elems.push(expr_ty(ctx, source, expr)?.1);
+            }
+            Ok((*span, ctx.tys.intern_tuple(&elems)))
+        }
+        expr => Err(ctx.report_error(expr.span(ctx.source_map.buffer(source)), "expected a type")),
+    }
+}
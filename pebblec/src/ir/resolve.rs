@@ -2,8 +2,9 @@ use super::Stmt;
 use super::ty::infer::Integral;
 use super::*;
 use crate::ir::ctx::{Ctx, CtxFmt};
-use crate::ir::sig::Param;
+use crate::ir::format::{ArgKind, Format};
 use crate::ir::sig::Sig;
+use crate::ir::sig::{Param, is_print};
 use crate::ir::ty::Ty;
 use crate::ir::ty::infer::InferCtx;
 use crate::ir::ty::{TyVar, TypeKey};
@@ -133,6 +134,25 @@ fn init_params(ctx: &mut Ctx, infer: &mut InferCtx, func: &Func) {
     }
 }
 
+fn var_infer_ty(ctx: &Ctx, infer: &InferCtx, var: TyVar, span: Span) -> Result<InferTy, Diag> {
+    Ok(match infer.guess_var_ty(ctx, var) {
+        Some(ty) => {
+            if ty == Ty::ISIZE && !infer.is_var_absolute(var) {
+                InferTy::Int
+            } else if ty == Ty::FSIZE && !infer.is_var_absolute(var) {
+                InferTy::Float
+            } else {
+                InferTy::Ty(ty)
+            }
+        }
+        None => infer
+            .is_var_integral_int(var)
+            .then_some(InferTy::Int)
+            .or_else(|| infer.is_var_integral_float(var).then_some(InferTy::Float))
+            .ok_or_else(|| ctx.report_error(span, "could not infer type"))?,
+    })
+}
+
 impl Expr<'_> {
     /// Fails when:
     ///     aquiring field access type Fails
@@ -141,35 +161,22 @@ impl Expr<'_> {
     #[track_caller]
     pub fn resolve_infer<'a>(&self, ctx: &mut Ctx<'a>, infer: &InferCtx) -> Result<InferTy, Diag> {
         Ok(match self {
-            Self::Lit(lit) => match lit.kind {
-                LitKind::Int(_) => InferTy::Int,
-                LitKind::Float(_) => InferTy::Float,
+            Self::Lit(lit) => match (lit.ty, lit.kind) {
+                (Some(ty), _) => InferTy::Ty(ty),
+                (None, LitKind::Int(_)) => InferTy::Int,
+                (None, LitKind::Float(_)) => InferTy::Float,
             },
             Self::Ident(ident) => {
                 let Some(var) = infer.var(ident.sym) else {
                     return Err(ctx.undeclared(ident));
                 };
-
-                match infer.guess_var_ty(ctx, var) {
-                    Some(ty) => {
-                        if ty == Ty::ISIZE && !infer.is_var_absolute(var) {
-                            InferTy::Int
-                        } else if ty == Ty::FSIZE && !infer.is_var_absolute(var) {
-                            InferTy::Float
-                        } else {
-                            InferTy::Ty(ty)
-                        }
-                    }
-                    None => infer
-                        .is_var_integral_int(var)
-                        .then_some(InferTy::Int)
-                        .or_else(|| infer.is_var_integral_float(var).then_some(InferTy::Float))
-                        .ok_or_else(|| ctx.report_error(ident.span, "could not infer type"))?,
-                }
+                var_infer_ty(ctx, infer, var, ident.span)?
             }
             Self::Access(access) => InferTy::Ty(aquire_access_ty(ctx, infer, access)?.1),
             Self::Call(call) => InferTy::Ty(call.sig.ty),
             Self::MethodCall(call) => InferTy::Ty(call.get_sig(ctx, infer)?.ty),
+            Self::IndirectCall(call) => InferTy::Ty(call.fn_ty(ctx, infer)?.1),
+            Self::Fn(func) => InferTy::Ty(func.ty),
             Self::Str(_) => InferTy::Ty(Ty::STR_LIT),
             Self::Bin(bin) => {
                 let lhs = bin.lhs.resolve_infer(ctx, infer)?;
@@ -184,6 +191,7 @@ impl Expr<'_> {
                 }
             }
             Self::Bool(_) => InferTy::Ty(Ty::BOOL),
+            Self::Null(_) => InferTy::Ty(Ty::NULL),
             Self::IndexOf(index) => match index.array.resolve_infer(ctx, infer)? {
                 InferTy::Ty(arr_ty) => match arr_ty.0 {
                     TyKind::Array(_, inner) => InferTy::Ty(Ty(*inner)),
@@ -197,7 +205,11 @@ impl Expr<'_> {
                 None => InferTy::Ty(Ty::UNIT),
                 Some(end) => end.resolve_infer(ctx, infer)?,
             },
-            Self::For(_) | Self::Loop(_) => InferTy::Ty(Ty::UNIT),
+            Self::Loop(loop_) => match infer.loop_var(loop_.span) {
+                Some(var) => var_infer_ty(ctx, infer, var, loop_.span)?,
+                None => InferTy::Ty(Ty::UNIT),
+            },
+            Self::For(_) | Self::While(_) => InferTy::Ty(Ty::UNIT),
             Self::If(if_) => {
                 let block_infer = if_.block.resolve_infer(ctx, infer)?;
                 if let Some(otherwise) = if_.otherwise {
@@ -269,6 +281,18 @@ impl Expr<'_> {
                     })
                 }
             },
+            Self::Tuple(tuple) => {
+                let mut elems = Vec::with_capacity(tuple.exprs.len());
+                for expr in tuple.exprs.iter() {
+                    // TODO: same as arrays, there is no way to mark an element as infer
+                    elems.push(match expr.resolve_infer(ctx, infer)? {
+                        InferTy::Ty(ty) => ty,
+                        InferTy::Int => Ty::ISIZE,
+                        InferTy::Float => Ty::FSIZE,
+                    });
+                }
+                InferTy::Ty(ctx.tys.intern_tuple(&elems))
+            }
             Self::Unary(unary) => match unary.kind {
                 UOpKind::Not | UOpKind::Neg => unary.inner.resolve_infer(ctx, infer)?,
                 UOpKind::Ref => InferTy::Ty(match unary.inner.resolve_infer(ctx, infer)? {
@@ -298,6 +322,10 @@ impl Expr<'_> {
             },
             Self::Cast(cast) => InferTy::Ty(cast.ty),
             Self::Range(_) => InferTy::Int,
+            Self::Closure(closure) => match ctx.closure_tys.get(&closure.span) {
+                Some(ty) => InferTy::Ty(*ty),
+                None => return Err(ctx.report_error(closure.span, "could not infer type")),
+            },
             expr => todo!("{expr:#?}"),
         })
     }
@@ -310,6 +338,21 @@ impl Expr<'_> {
         ty: Ty,
         source: Span,
     ) -> Result<(), Diag> {
+        self.check_lit_range(ctx, ty)?;
+        if let Expr::Tuple(tuple) = self {
+            let elems = tuple.elem_tys(ctx, infer, ty)?;
+            for (expr, elem) in tuple.exprs.iter().zip(elems.iter()) {
+                expr.infer_equality(ctx, infer, Ty(elem), source)?;
+                // elements are not bound to a variable, so the type must flow into idents here
+                if let Expr::Ident(ident) = expr
+                    && let Some(var) = infer.var(ident.sym)
+                {
+                    infer.eq(var, Ty(elem), ident.span);
+                }
+            }
+            return Ok(());
+        }
+
         let span = self.span();
         match self.resolve_infer(ctx, infer)? {
             InferTy::Int => {
@@ -322,7 +365,7 @@ impl Expr<'_> {
                 }
             }
             InferTy::Float => {
-                if !ty.is_int() {
+                if !ty.is_float() {
                     return Err(ctx.mismatch(span, ty, "{float}").msg(Msg::help(
                         &ctx.source_map,
                         source,
@@ -353,7 +396,9 @@ impl Expr<'_> {
             InferTy::Float => infer.integral(Integral::Float, var, span),
             InferTy::Ty(infer_ty) => {
                 if infer.is_var_absolute(var)
-                    && infer.guess_var_ty(ctx, var).is_some_and(|ty| ty.is_arr())
+                    && infer
+                        .guess_var_ty(ctx, var)
+                        .is_some_and(|ty| ty.is_arr() || ty.is_tuple())
                 {
                     // do nothing if already binded
                 } else {
@@ -372,19 +417,37 @@ impl Expr<'_> {
         source: Span,
         var: TyVar,
     ) -> Result<(), Diag> {
+        self.check_lit_range(ctx, ty)?;
+        if let Expr::Tuple(_) = self {
+            self.infer_equality(ctx, infer, ty, source)?;
+            infer.eq(var, ty, source);
+            return Ok(());
+        }
+
         let span = self.span();
+        // an integer or float variable on the rhs has no type yet, so it takes the annotated one
+        let rhs_var = match self {
+            Expr::Ident(ident) => infer.var(ident.sym),
+            _ => None,
+        };
         match self.resolve_infer(ctx, infer)? {
             InferTy::Int => {
                 if !ty.is_int() {
                     return Err(ctx.mismatch(span, ty, "{int}"));
                 }
                 infer.eq(var, ty, source);
+                if let Some(rhs_var) = rhs_var {
+                    infer.eq(rhs_var, ty, source);
+                }
             }
             InferTy::Float => {
                 if !ty.is_float() {
                     return Err(ctx.mismatch(span, ty, "{float}"));
                 }
                 infer.eq(var, ty, source);
+                if let Some(rhs_var) = rhs_var {
+                    infer.eq(rhs_var, ty, source);
+                }
             }
             InferTy::Ty(infer_ty) => {
                 if !infer_ty.equiv(*ty.0) {
@@ -413,11 +476,19 @@ impl Expr<'_> {
         ty: Ty,
         source: Span,
     ) -> Result<(), Diag> {
+        self.check_lit_range(ctx, ty)?;
         match self {
             Expr::Ident(ident) => {
                 let var = infer.var(ident.sym).ok_or_else(|| ctx.undeclared(ident))?;
                 self.infer_equality(ctx, infer, ty, source)?;
-                infer.eq(var, ty, source);
+                // a reference coerces into an optional, the variable itself stays a reference
+                let coerced = ty.is_opt()
+                    && infer
+                        .guess_var_ty(ctx, var)
+                        .is_some_and(|var| !var.is_opt());
+                if !coerced {
+                    infer.eq(var, ty, source);
+                }
 
                 Ok(())
             }
@@ -433,6 +504,7 @@ impl Expr<'_> {
             },
             Expr::If(if_) => {
                 infer.in_scope(ctx, |ctx, infer| {
+                    constrain_condition(ctx, infer, if_.binding, if_.condition, if_.span)?;
                     match if_.block {
                         Expr::Block(block) => block.block_constrain(ctx, infer, sig)?,
                         _ => unreachable!(),
@@ -460,7 +532,18 @@ impl Expr<'_> {
                     }
                 })
             }
+            Expr::Loop(loop_) => match infer.loop_var(loop_.span) {
+                Some(var) => {
+                    self.infer_equality(ctx, infer, ty, source)?;
+                    infer.eq(var, ty, source);
+                    Ok(())
+                }
+                None => self.infer_equality(ctx, infer, ty, source),
+            },
             Expr::Lit(lit) => match lit.kind {
+                _ if lit.ty.is_some_and(|lit_ty| lit_ty != ty) => {
+                    Err(ctx.mismatch(lit.span, ty, lit.ty.unwrap()))
+                }
                 LitKind::Int(_) => {
                     if ty.is_int() {
                         Ok(())
@@ -554,10 +637,34 @@ impl Expr<'_> {
                     }
                 }
             }
+            Expr::Tuple(tuple) => {
+                let elems = tuple.elem_tys(ctx, infer, ty)?;
+                for (expr, elem) in tuple.exprs.iter().zip(elems.iter()) {
+                    expr.constrain_with(ctx, infer, sig, Ty(elem), source)?;
+                }
+                Ok(())
+            }
             _ => self.infer_equality(ctx, infer, ty, source),
         }
     }
 
+    /// Fails if `self` is a literal, or a negated literal, that is out of range for `ty`.
+    fn check_lit_range(&self, ctx: &Ctx, ty: Ty) -> Result<(), Diag> {
+        match self {
+            Expr::Lit(lit) if !lit.kind.fits(ty, false) => Err(ctx.out_of_range(lit.span, ty)),
+            Expr::Unary(Unary {
+                kind: UOpKind::Neg,
+                inner: Expr::Lit(lit),
+                span,
+            }) if !lit.kind.fits(ty, true) => Err(ctx.out_of_range(*span, ty)),
+            Expr::Bin(bin) if bin.kind.output_is_input() => {
+                bin.lhs.check_lit_range(ctx, ty)?;
+                bin.rhs.check_lit_range(ctx, ty)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn find_ty_var(&self, ctx: &Ctx, infer: &mut InferCtx) -> Option<TyVar> {
         match self {
             Self::Ident(ident) => infer.var(ident.sym),
@@ -580,6 +687,7 @@ impl Expr<'_> {
             Self::Ident(_)
             | Self::Unary(_)
             | Self::Array(_)
+            | Self::Tuple(_)
             | Self::Str(_)
             | Self::Struct(_)
             | Self::Access(_)
@@ -588,9 +696,13 @@ impl Expr<'_> {
             | Self::Range(_)
             | Self::IndexOf(_)
             | Self::Cast(_)
+            | Self::Fn(_)
+            | Self::Closure(_)
+            | Self::Null(_)
             | Self::Bool(_) => false,
             Self::Call(call) => call.sig.ty.is_unit(),
             Self::MethodCall(call) => call.get_sig(ctx, infer)?.ty.is_unit(),
+            Self::IndirectCall(call) => call.fn_ty(ctx, infer)?.1.is_unit(),
             Self::If(if_) => {
                 let block = if_.block.is_unit(ctx, infer)?;
                 let otherwise = if_.otherwise.map(|o| o.is_unit(ctx, infer));
@@ -599,9 +711,8 @@ impl Expr<'_> {
                     (c, None) => c,
                 }
             }
-            Self::Break(_) | Self::Continue(_) | Self::Loop(_) | Self::While(_) | Self::For(_) => {
-                true
-            }
+            Self::Loop(loop_) => !loop_.breaks_with_value,
+            Self::Break(_) | Self::Continue(_) | Self::While(_) | Self::For(_) => true,
             Self::Block(block) => match block.end {
                 Some(end) => end.is_unit(ctx, infer)?,
                 None => true,
@@ -610,6 +721,101 @@ impl Expr<'_> {
     }
 }
 
+/// Collects the identifiers bound by a destructuring `target` along with their types.
+///
+/// The elements of a tuple expression are matched with a tuple target directly, so that
+/// `let (a, b) = (1, 2.5);` binds `a` to an `{integer}` and `b` to a `{float}`.
+fn let_bindings(
+    ctx: &mut Ctx,
+    infer: &InferCtx,
+    target: &LetTarget,
+    rhs: Option<&Expr>,
+    ty: Option<Ty>,
+    bindings: &mut Vec<(Ident, InferTy)>,
+) -> Result<(), Diag> {
+    let infer_ty = |ctx: &mut Ctx| match (ty, rhs) {
+        (Some(ty), _) => Ok(InferTy::Ty(ty)),
+        (None, Some(rhs)) => rhs.resolve_infer(ctx, infer),
+        (None, None) => unreachable!(),
+    };
+
+    match target {
+        LetTarget::Ident(ident) => {
+            if bindings.iter().any(|(other, _)| other.sym == ident.sym) {
+                return Err(ctx.report_error(
+                    ident.span,
+                    format!(
+                        "`{}` is bound more than once in this pattern",
+                        ident.as_str()
+                    ),
+                ));
+            }
+            bindings.push((*ident, infer_ty(ctx)?));
+        }
+        LetTarget::Tuple { span, targets } => {
+            if ty.is_none()
+                && let Some(Expr::Tuple(tuple)) = rhs
+                && tuple.exprs.len() == targets.len()
+            {
+                for (target, expr) in targets.iter().zip(tuple.exprs.iter()) {
+                    let_bindings(ctx, infer, target, Some(expr), None, bindings)?;
+                }
+                return Ok(());
+            }
+
+            match infer_ty(ctx)? {
+                InferTy::Ty(Ty(TyKind::Tuple(elems))) if elems.len() == targets.len() => {
+                    for (target, elem) in targets.iter().zip(elems.iter()) {
+                        let_bindings(ctx, infer, target, None, Some(Ty(elem)), bindings)?;
+                    }
+                }
+                other => {
+                    return Err(ctx.report_error(
+                        span,
+                        format!(
+                            "mismatched types: expected a tuple with {} elements, got `{}`",
+                            targets.len(),
+                            other.to_string(ctx)
+                        ),
+                    ));
+                }
+            }
+        }
+        LetTarget::Struct { span, id, fields } => {
+            let strukt = ctx.tys.strukt(*id);
+            if strukt.union {
+                return Err(ctx.report_error(
+                    *span,
+                    format!("union `{}` cannot be destructured", strukt.name.as_str()),
+                ));
+            }
+
+            let struct_ty = ctx.tys.struct_ty_id(*id);
+            match infer_ty(ctx)? {
+                InferTy::Ty(ty) if ty == struct_ty => {}
+                other => return Err(ctx.mismatch(*span, struct_ty, other.to_string(ctx))),
+            }
+
+            for (name, target) in fields.iter() {
+                let strukt = ctx.tys.strukt(*id);
+                let Some(field_ty) = strukt.get_field_ty(name.sym) else {
+                    return Err(ctx.report_error(
+                        name.span,
+                        format!(
+                            "`{}` has no field `{}`",
+                            strukt.name.as_str(),
+                            name.as_str()
+                        ),
+                    ));
+                };
+                let_bindings(ctx, infer, target, None, Some(field_ty), bindings)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub trait Constrain<'a>: Debug {
     fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag>;
 }
@@ -658,6 +864,26 @@ impl<'a> Constrain<'a> for SemiStmt<'a> {
                         return Err(Diag::bundle(errors));
                     }
                 }
+                target => {
+                    let_.rhs.constrain(ctx, infer, sig)?;
+                    if let Some((span, ty)) = let_.ty {
+                        let_.rhs.infer_equality(ctx, infer, ty, span)?;
+                    }
+
+                    let mut bindings = Vec::new();
+                    let ty = let_.ty.map(|(_, ty)| ty);
+                    let_bindings(ctx, infer, &target, Some(&let_.rhs), ty, &mut bindings)?;
+
+                    // bound after resolving `rhs`, which may refer to shadowed variables
+                    for (ident, infer_ty) in bindings {
+                        let var = infer.new_var(ident);
+                        match infer_ty {
+                            InferTy::Int => infer.integral(Integral::Int, var, ident.span),
+                            InferTy::Float => infer.integral(Integral::Float, var, ident.span),
+                            InferTy::Ty(ty) => infer.eq(var, ty, ident.span),
+                        }
+                    }
+                }
             },
             SemiStmt::Ret(r) => {
                 if let Some(expr) = &r.expr {
@@ -714,8 +940,12 @@ impl<'a> Constrain<'a> for SemiStmt<'a> {
                         rhs.to_string(ctx),
                     ));
                 }
+                if let InferTy::Ty(ty) = lhs {
+                    assign.rhs.check_lit_range(ctx, ty)?;
+                }
             }
             SemiStmt::Expr(expr) => expr.constrain(ctx, infer, sig)?,
+            SemiStmt::Defer(defer) => defer.stmt.constrain(ctx, infer, sig)?,
         }
 
         Ok(())
@@ -726,13 +956,15 @@ impl<'a> Constrain<'a> for Expr<'a> {
     #[track_caller]
     fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
         match self {
-            Self::Lit(_) | Self::Str(_) | Self::Bool(_) => Ok(()),
+            Self::Lit(_) | Self::Str(_) | Self::Bool(_) | Self::Null(_) => Ok(()),
             Self::Ident(ident) => ident.constrain(ctx, infer, sig),
             Self::Bin(bin) => bin.constrain(ctx, infer, sig),
             Self::Access(access) => access.constrain(ctx, infer, sig),
             Self::Struct(def) => def.constrain(ctx, infer, sig),
             Self::Call(call) => call.constrain(ctx, infer, sig),
             Self::MethodCall(call) => call.constrain(ctx, infer, sig),
+            Self::IndirectCall(call) => call.constrain(ctx, infer, sig),
+            Self::Fn(_) => Ok(()),
             Self::Block(block) => block.constrain(ctx, infer, sig),
             Self::If(if_) => if_.constrain(ctx, infer, sig),
             Self::Loop(loop_) => loop_.constrain(ctx, infer, sig),
@@ -740,10 +972,13 @@ impl<'a> Constrain<'a> for Expr<'a> {
             Self::For(for_) => for_.constrain(ctx, infer, sig),
             Self::Unary(unary) => unary.constrain(ctx, infer, sig),
             Self::Array(arr) => arr.constrain(ctx, infer, sig),
+            Self::Tuple(tuple) => tuple.constrain(ctx, infer, sig),
             Self::IndexOf(index) => index.constrain(ctx, infer, sig),
             Self::Range(range) => range.constrain(ctx, infer, sig),
             Self::Cast(cast) => cast.constrain(ctx, infer, sig),
-            Self::Continue(_) | Self::Break(_) => Ok(()),
+            Self::Closure(closure) => closure.constrain(ctx, infer, sig),
+            Self::Break(brk) => brk.constrain(ctx, infer, sig),
+            Self::Continue(_) => Ok(()),
         }
     }
 }
@@ -831,7 +1066,24 @@ impl<'a> Constrain<'a> for StructDef<'a> {
         }
 
         let strukt = ctx.tys.strukt(self.id);
-        if self.fields.len() != strukt.fields.len() {
+        if strukt.union {
+            if self.fields.len() != 1 {
+                errors.push(
+                    ctx.report_error(
+                        self.span,
+                        format!(
+                            "union definition must initialize exactly one field, found {}",
+                            self.fields.len()
+                        ),
+                    )
+                    .msg(Msg::note(
+                        &ctx.source_map,
+                        strukt.span,
+                        "defined here",
+                    )),
+                );
+            }
+        } else if self.fields.len() != strukt.fields.len() {
             let missing_fields = strukt
                 .fields
                 .iter()
@@ -882,6 +1134,34 @@ impl<'a> Constrain<'a> for ArrDef<'a> {
     }
 }
 
+impl<'a> Constrain<'a> for TupleDef<'a> {
+    fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
+        for expr in self.exprs.iter() {
+            expr.constrain(ctx, infer, sig)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TupleDef<'_> {
+    /// Fails if `ty` is not a tuple with as many elements as `self`.
+    fn elem_tys(
+        &self,
+        ctx: &mut Ctx,
+        infer: &InferCtx,
+        ty: Ty,
+    ) -> Result<&'static [&'static TyKind], Diag> {
+        match ty.0 {
+            TyKind::Tuple(elems) if elems.len() == self.exprs.len() => Ok(elems),
+            _ => {
+                let got = Expr::Tuple(*self).resolve_infer(ctx, infer)?;
+                Err(ctx.mismatch(self.span, ty, got.to_string(ctx)))
+            }
+        }
+    }
+}
+
 impl<'a> Constrain<'a> for Call<'a> {
     fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
         let mut errors = Vec::new();
@@ -889,14 +1169,20 @@ impl<'a> Constrain<'a> for Call<'a> {
         let args = self.args.len();
 
         let name = self.sig.ident.as_str();
-        if params != args
-            && (name != "print" && name != "println"
-                || ((name == "print" || name == "println") && args == 0))
-        {
+        let mismatch = if self.sig.variadic {
+            args < params
+        } else {
+            params != args
+        };
+        if mismatch && (!is_print(name) || args == 0) {
+            let expected = if self.sig.variadic { "at least " } else { "" };
             return Err(ctx
                 .report_error(
                     self.ident_span,
-                    format!("expected `{}` arguments, got `{}`", params, args),
+                    format!(
+                        "expected {}`{}` arguments, got `{}`",
+                        expected, params, args
+                    ),
                 )
                 .msg(Msg::help(
                     &ctx.source_map,
@@ -905,37 +1191,193 @@ impl<'a> Constrain<'a> for Call<'a> {
                 )));
         }
 
-        if name == "printf" {
-            for expr in self.args.iter() {
+        if is_print(name) {
+            for expr in self.args.iter().skip(1) {
                 expr.constrain(ctx, infer, sig)?;
             }
-        } else {
-            for (expr, param) in self.args.iter().zip(self.sig.params.iter()) {
-                let (span, ty) = match param {
-                    Param::Named { span, ty, .. } => (span, ty),
-                    param => {
-                        errors.push(ctx.report_error(param.span(), "invalid argument"));
-                        continue;
-                    }
-                };
 
+            if let Err(diag) = constrain_format(ctx, infer, self) {
+                errors.push(diag);
+            }
+        }
+
+        // only the fixed parameters are type checked, the rest are promoted, see
+        // `TyKind::default_promotion`
+        if self.sig.variadic {
+            for expr in self.args.iter().skip(params) {
                 expr.constrain(ctx, infer, sig)?;
-                match &expr {
-                    Expr::Ident(ident) => match infer.var(ident.sym) {
-                        Some(var) => {
-                            if ident.as_str() != "NULL" {
-                                infer.eq(var, *ty, *span);
-                            }
-                        }
-                        None => {
-                            errors.push(ctx.undeclared(ident));
+                match expr.resolve_infer(ctx, infer) {
+                    Ok(InferTy::Ty(ty)) if ty.0.default_promotion().is_none() => {
+                        errors.push(ctx.report_error(
+                            expr.span(),
+                            format!(
+                                "a value of type `{}` cannot be passed as a variadic argument",
+                                ty.to_string(ctx)
+                            ),
+                        ));
+                    }
+                    Ok(_) => {}
+                    Err(diag) => errors.push(diag),
+                }
+            }
+        }
+
+        for (expr, param) in self.args.iter().zip(self.sig.params.iter()) {
+            let (span, ty) = match param {
+                Param::Named { span, ty, .. } => (span, ty),
+                param => {
+                    errors.push(ctx.report_error(param.span(), "invalid argument"));
+                    continue;
+                }
+            };
+
+            expr.constrain(ctx, infer, sig)?;
+            match &expr {
+                Expr::Ident(ident) => match infer.var(ident.sym) {
+                    Some(var) => {
+                        if ident.as_str() != "NULL"
+                            && let Err(diag) = constrain_arg(ctx, infer, ident, var, *ty, *span)
+                        {
+                            errors.push(diag);
                         }
-                    },
-                    _ => {
-                        if let Err(diag) = expr.infer_equality(ctx, infer, *ty, *span) {
+                    }
+                    None => {
+                        errors.push(ctx.undeclared(ident));
+                    }
+                },
+                _ => {
+                    if let Err(diag) = expr.infer_equality(ctx, infer, *ty, *span) {
+                        errors.push(diag);
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            Err(Diag::bundle(errors))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl IndirectCall<'_> {
+    /// Fails if the callee is not a function pointer.
+    pub fn fn_ty(
+        &self,
+        ctx: &mut Ctx,
+        infer: &InferCtx,
+    ) -> Result<(&'static [&'static TyKind], Ty), Diag> {
+        if let Expr::Ident(ident) = self.callee
+            && infer.var(ident.sym).is_none()
+        {
+            return Err(ctx.report_error(ident.span, "function is not defined"));
+        }
+
+        match self.callee.resolve_infer(ctx, infer)? {
+            InferTy::Ty(Ty(TyKind::Fn(params, ret) | TyKind::Closure(params, ret))) => {
+                Ok((params, Ty(ret)))
+            }
+            infer_ty => Err(ctx.report_error(
+                self.callee.span(),
+                format!("expected a function, found `{}`", infer_ty.to_string(ctx)),
+            )),
+        }
+    }
+}
+
+impl<'a> Constrain<'a> for Closure<'a> {
+    fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, _: &Sig) -> Result<(), Diag> {
+        let sig = self.sig;
+        let ret = infer.in_scope(ctx, |ctx, infer| {
+            for param in sig.params.iter() {
+                if let Param::Named { span, ident, ty } = param {
+                    let var = infer.new_var(*ident);
+                    infer.eq(var, *ty, *span);
+                }
+            }
+
+            self.block.block_constrain(ctx, infer, sig)?;
+            match (self.ret, self.block.end) {
+                (Some(ret), Some(end)) => {
+                    end.constrain_with(ctx, infer, sig, ret, sig.span)?;
+                    Ok(ret)
+                }
+                (Some(ret), None) => {
+                    let returns =
+                        matches!(self.block.stmts.last(), Some(Stmt::Semi(SemiStmt::Ret(_))));
+                    if !ret.is_unit() && !returns {
+                        return Err(ctx.mismatch(self.block.span, ret, Ty::UNIT));
+                    }
+                    Ok(ret)
+                }
+                (None, Some(end)) => {
+                    let ret = match end.resolve_infer(ctx, infer)? {
+                        InferTy::Ty(ty) => return Ok(ty),
+                        InferTy::Int => Ty::ISIZE,
+                        InferTy::Float => Ty::FSIZE,
+                    };
+                    // the closure's type is fixed here, so an `{integer}` or `{float}` must be too
+                    end.constrain_with(ctx, infer, sig, ret, sig.span)?;
+                    Ok(ret)
+                }
+                (None, None) => Ok(Ty::UNIT),
+            }
+        })?;
+        if let Some(end) = self.block.end {
+            escaping_closure(ctx, end.span(), ret, "returned from closures")?;
+        }
+
+        let params = sig
+            .params
+            .iter()
+            .map(|param| match param {
+                Param::Named { ty, .. } => *ty,
+                Param::Slf(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        let ty = ctx.tys.intern_closure(&params, ret);
+        ctx.closure_tys.insert(self.span, ty);
+        Ok(())
+    }
+}
+
+impl<'a> Constrain<'a> for IndirectCall<'a> {
+    fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
+        let (params, _) = self.fn_ty(ctx, infer)?;
+        self.callee.constrain(ctx, infer, sig)?;
+
+        if params.len() != self.args.len() {
+            return Err(ctx.report_error(
+                self.callee.span(),
+                format!(
+                    "expected `{}` arguments, got `{}`",
+                    params.len(),
+                    self.args.len()
+                ),
+            ));
+        }
+
+        let mut errors = Vec::new();
+        for (expr, param) in self.args.iter().zip(params.iter()) {
+            expr.constrain(ctx, infer, sig)?;
+            match &expr {
+                Expr::Ident(ident) => match infer.var(ident.sym) {
+                    Some(var) => {
+                        if let Err(diag) =
+                            constrain_arg(ctx, infer, ident, var, Ty(param), ident.span)
+                        {
                             errors.push(diag);
                         }
                     }
+                    None => errors.push(ctx.undeclared(ident)),
+                },
+                _ => {
+                    if let Err(diag) =
+                        expr.infer_equality(ctx, infer, Ty(param), self.callee.span())
+                    {
+                        errors.push(diag);
+                    }
                 }
             }
         }
@@ -948,6 +1390,97 @@ impl<'a> Constrain<'a> for Call<'a> {
     }
 }
 
+/// Validates the format string of a `print` or `println` call against its arguments.
+///
+/// Only literal format strings can be checked, and argument types that are not yet known are
+/// checked when they are formatted.
+/// Constrains the variable `var`, passed as an argument, to the parameter type `ty`.
+///
+/// A reference coerces into an optional parameter while the variable itself stays a reference,
+/// but an optional never coerces into a reference, see `TyKind::equiv`.
+fn constrain_arg(
+    ctx: &mut Ctx,
+    infer: &mut InferCtx,
+    ident: &Ident,
+    var: TyVar,
+    ty: Ty,
+    source: Span,
+) -> Result<(), Diag> {
+    match infer.guess_var_ty(ctx, var) {
+        Some(var_ty) if var_ty.is_opt() != ty.is_opt() => {
+            if var_ty.equiv(*ty.0) {
+                Ok(())
+            } else {
+                Err(ctx.mismatch(ident.span, ty, var_ty).msg(Msg::help(
+                    &ctx.source_map,
+                    source,
+                    "from this parameter",
+                )))
+            }
+        }
+        _ => {
+            infer.eq(var, ty, source);
+            Ok(())
+        }
+    }
+}
+
+fn constrain_format(ctx: &mut Ctx, infer: &InferCtx, call: &Call) -> Result<(), Diag> {
+    let Some(Expr::Str(str)) = call.args.first() else {
+        return Ok(());
+    };
+
+    // escape sequences shift the decoded string relative to the source
+    let escaped = (str.span.end - str.span.start) as usize != str.val.len();
+    let format_span = |range: std::ops::Range<usize>| {
+        if escaped {
+            return str.span;
+        }
+        Span::from_range_u32(str.span.start + range.start as u32..str.span.start + range.end as u32)
+            .with_source(str.span.source)
+    };
+
+    let format =
+        Format::parse(str.val).map_err(|err| ctx.report_error(format_span(err.span), err.msg))?;
+
+    let args = &call.args[1..];
+    let specs = format.specs().count();
+    if specs != args.len() {
+        return Err(ctx
+            .report_error(
+                str.span,
+                format!(
+                    "format string expects `{}` arguments, got `{}`",
+                    specs,
+                    args.len()
+                ),
+            )
+            .msg(Msg::help(
+                &ctx.source_map,
+                call.ident_span,
+                "arguments are substituted for `{}` and `%`",
+            )));
+    }
+
+    let mut errors = Vec::new();
+    for (spec, arg) in format.specs().zip(args.iter()) {
+        if let Ok(infer_ty) = arg.resolve_infer(ctx, infer)
+            && let Err(msg) = spec.check(ArgKind::from_infer(infer_ty))
+        {
+            errors.push(ctx.report_error(
+                arg.span(),
+                format!("a value of type `{}` {}", infer_ty.to_string(ctx), msg),
+            ));
+        }
+    }
+
+    if !errors.is_empty() {
+        Err(Diag::bundle(errors))
+    } else {
+        Ok(())
+    }
+}
+
 impl MethodPath<'_> {
     pub fn resolve_infer(&self, ctx: &mut Ctx, infer: &InferCtx) -> Result<InferTy, Diag> {
         match self {
@@ -1033,62 +1566,47 @@ impl<'a> Constrain<'a> for MethodCall<'a> {
                 )));
         }
 
-        let ty = self.get_ty(ctx, infer)?;
-        for (i, (expr, param)) in self.args.iter().zip(method_sig.params.iter()).enumerate() {
-            let (span, ty) = match param {
-                Param::Slf(ident) => (ident.span, ctx.tys.intern_kind(TyKind::Ref(ty.0))),
-                Param::Named { span, ty, .. } => (*span, *ty),
+        if let MethodPath::Path(_, ty) = self.receiver
+            && ctx.get_method_sig(ty, self.call.sym).is_none()
+        {
+            errors.push(ctx.report_error(
+                self.span,
+                format!(
+                    "type `{}` has not method `{}`",
+                    ty.to_string(ctx),
+                    self.call.as_str()
+                ),
+            ));
+        }
+
+        // the receiver is passed by reference as `self`, the remaining parameters line up with
+        // the arguments
+        let params = method_sig
+            .params
+            .iter()
+            .filter(|param| !matches!(param, Param::Slf(_)));
+        for (expr, param) in self.args.iter().zip(params) {
+            let Param::Named { span, ty, .. } = param else {
+                unreachable!()
             };
 
-            if i == 0 {
-                match self.receiver {
-                    MethodPath::Path(_, ty) => {
-                        if ctx.get_method_sig(ty, self.call.sym).is_none() {
-                            errors.push(ctx.report_error(
-                                self.span,
-                                format!(
-                                    "type `{}` has not method `{}`",
-                                    ty.to_string(ctx),
-                                    self.call.as_str()
-                                ),
-                            ));
-                        }
-                    }
-                    MethodPath::Field(expr) => match expr {
-                        Expr::Ident(ident) => match infer.var(ident.sym) {
-                            Some(var) => {
-                                if ident.as_str() != "NULL" {
-                                    infer.eq(var, ty, span);
-                                }
-                            }
-                            None => {
-                                errors.push(ctx.undeclared(ident));
-                            }
-                        },
-                        _ => {
-                            if let Err(diag) = expr.infer_equality(ctx, infer, ty, span) {
-                                errors.push(diag);
-                            }
-                        }
-                    },
-                }
-            } else {
-                match expr {
-                    Expr::Ident(ident) => match infer.var(ident.sym) {
-                        Some(var) => {
-                            if ident.as_str() != "NULL" {
-                                infer.eq(var, ty, span);
-                            }
-                        }
-                        None => {
-                            errors.push(ctx.undeclared(ident));
-                        }
-                    },
-                    _ => {
-                        if let Err(diag) = expr.infer_equality(ctx, infer, ty, span) {
+            match expr {
+                Expr::Ident(ident) => match infer.var(ident.sym) {
+                    Some(var) => {
+                        if ident.as_str() != "NULL"
+                            && let Err(diag) = constrain_arg(ctx, infer, ident, var, *ty, *span)
+                        {
                             errors.push(diag);
                         }
                     }
+                    None => {
+                        errors.push(ctx.undeclared(ident));
+                    }
+                },
+                _ => {
+                    if let Err(diag) = expr.infer_equality(ctx, infer, *ty, *span) {
+                        errors.push(diag);
+                    }
                 }
             }
         }
@@ -1139,35 +1657,90 @@ impl<'a> Constrain<'a> for Block<'a> {
 impl<'a> Constrain<'a> for If<'a> {
     fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
         self.condition.constrain(ctx, infer, sig)?;
-        self.condition
-            .infer_equality(ctx, infer, Ty::BOOL, self.span)?;
         infer.in_scope(ctx, |ctx, infer| match self.block {
             Expr::Block(block) => {
-                block.block_constrain(ctx, infer, sig)?;
-                if let Some(otherwise) = self.otherwise {
-                    otherwise.constrain(ctx, infer, sig)?;
-                }
-                Ok(())
+                constrain_condition(ctx, infer, self.binding, self.condition, self.span)?;
+                block.block_constrain(ctx, infer, sig)
             }
             _ => unreachable!(),
-        })
+        })?;
+        if let Some(otherwise) = self.otherwise {
+            otherwise.constrain(ctx, infer, sig)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the condition of an `if` or `while`, which is a `bool` unless it unwraps an optional
+/// with `let`. The unwrapped pointer is then bound in the current scope.
+fn constrain_condition(
+    ctx: &mut Ctx,
+    infer: &mut InferCtx,
+    binding: Option<Ident>,
+    condition: &Expr,
+    span: Span,
+) -> Result<(), Diag> {
+    let Some(binding) = binding else {
+        return condition.infer_equality(ctx, infer, Ty::BOOL, span);
+    };
+
+    match condition.resolve_infer(ctx, infer)? {
+        InferTy::Ty(Ty(TyKind::Opt(inner))) if *inner != &TyKind::Unit => {
+            let var = infer.new_var(binding);
+            infer.eq(var, Ty(inner), binding.span);
+            Ok(())
+        }
+        other => Err(ctx
+            .report_error(
+                condition.span(),
+                format!(
+                    "mismatched types: expected an optional, got `{}`",
+                    other.to_string(ctx)
+                ),
+            )
+            .msg(Msg::help(
+                &ctx.source_map,
+                binding.span,
+                "`let` unwraps optional types, such as `?&T`",
+            ))),
     }
 }
 
 impl<'a> Constrain<'a> for Loop<'a> {
     fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
-        validate_loop_block(ctx, infer, sig, &self.block)?;
+        validate_loop_block(ctx, infer, sig, &self.block, self.value_ident())?;
         self.block.constrain(ctx, infer, sig)?;
         Expr::Block(self.block).infer_equality(ctx, infer, Ty::UNIT, self.span)
     }
 }
 
+impl<'a> Constrain<'a> for Break<'a> {
+    fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
+        let Some(value) = self.value else {
+            return Ok(());
+        };
+
+        value.constrain(ctx, infer, sig)?;
+        if value.is_unit(ctx, infer)? {
+            return Err(ctx.report_error(value.span(), "cannot `break` with a value of type `()`"));
+        }
+
+        // registered by `validate_loop_block` before the loop's block is constrained
+        let var = infer.loop_var(self.target).unwrap();
+        if infer.is_var_absolute(var)
+            && let Some(ty) = infer.guess_var_ty(ctx, var)
+        {
+            value.infer_equality(ctx, infer, ty, self.target)?;
+        }
+        value.constrain_var(ctx, infer, var)
+    }
+}
+
 impl<'a> Constrain<'a> for While<'a> {
     fn constrain(&self, ctx: &mut Ctx<'a>, infer: &mut InferCtx, sig: &Sig) -> Result<(), Diag> {
         self.condition.constrain(ctx, infer, sig)?;
-        self.condition
-            .infer_equality(ctx, infer, Ty::BOOL, self.span)?;
         infer.in_scope(ctx, |ctx, infer| {
+            constrain_condition(ctx, infer, self.binding, self.condition, self.span)?;
             self.block.block_constrain(ctx, infer, sig)?;
             Ok(())
         })
@@ -1230,7 +1803,7 @@ impl<'a> Constrain<'a> for ForLoop<'a> {
             self.iter.constrain(ctx, infer, sig)?;
             self.block.constrain(ctx, infer, sig)?;
 
-            validate_loop_block(ctx, infer, sig, &self.block)?;
+            validate_loop_block(ctx, infer, sig, &self.block, None)?;
             Expr::Block(self.block).infer_equality(ctx, infer, Ty::UNIT, self.span)
         })
     }
@@ -1239,12 +1812,20 @@ impl<'a> Constrain<'a> for ForLoop<'a> {
 // TODO: this sort of thing should be automated.
 //
 // Have a function like `verify_all_returns`, and `verify_openness`
+
+/// `value` names the type of the values that `break` out of a `loop`, which are constrained to
+/// the same type variable.
 fn validate_loop_block<'a>(
     ctx: &mut Ctx<'a>,
-    infer: &InferCtx,
+    infer: &mut InferCtx,
     sig: &Sig,
     block: &Block,
+    value: Option<Ident>,
 ) -> Result<(), Diag> {
+    if let Some(value) = value {
+        infer.new_loop_var(value);
+    }
+
     if let Some(end) = block.end {
         if !end.is_unit(ctx, infer)? {
             return Err(ctx.report_error(end.span(), "mismatched types: expected `()`"));
@@ -1307,7 +1888,7 @@ impl<'a> Constrain<'a> for Cast<'a> {
                 match ty.0 {
                     TyKind::Int(int_ty) => {
                         // hard coded case for casting an int (NULL) to a ref
-                        if *int_ty == IntTy::USIZE && target.is_ref() {
+                        if *int_ty == IntTy::USIZE && (target.is_ref() || target.is_opt()) {
                             return Ok(());
                         }
 
@@ -1364,6 +1945,7 @@ impl<'a> Constrain<'a> for Cast<'a> {
                             Ok(())
                         }
                     }
+                    TyKind::Opt(_) if matches!(target, Ty::USIZE) => Ok(()),
                     TyKind::Ref(str) if **str == TyKind::Str => {
                         if !matches!(target.0, TyKind::Ref(inner) if *inner == Ty::U8.0) {
                             Err(ctx.report_error(
@@ -1447,6 +2029,19 @@ impl<'a> Constrain<'a> for Deref<'a> {
                     "expression of type `&str` cannot be dereferenced",
                 )),
                 TyKind::Ref(_) => Ok(()),
+                TyKind::Opt(_) => Err(ctx
+                    .report_error(
+                        self.inner.span(),
+                        format!(
+                            "expression of type `{}` may be `null` and cannot be dereferenced",
+                            ty.to_string(ctx)
+                        ),
+                    )
+                    .msg(Msg::help(
+                        &ctx.source_map,
+                        self.inner.span(),
+                        "unwrap it first with `if let`",
+                    ))),
                 ty => Err(ctx.report_error(
                     self.inner.span(),
                     format!(
@@ -1649,7 +2244,7 @@ pub fn aquire_access_ty<'a>(
     infer: &InferCtx,
     access: &Access,
 ) -> Result<(Span, Ty), Diag> {
-    let ty = match access.lhs.resolve_infer(ctx, infer)? {
+    let mut ty = match access.lhs.resolve_infer(ctx, infer)? {
         InferTy::Float | InferTy::Int => {
             return Err(
                 ctx.report_error(access.lhs.span(), "invalid access: literal has no fields")
@@ -1658,46 +2253,50 @@ pub fn aquire_access_ty<'a>(
         InferTy::Ty(ty) => ty,
     };
 
-    let id = match ty.0 {
-        TyKind::Struct(id) => id,
-        TyKind::Array(_, _)
-        | TyKind::Slice(_)
-        | TyKind::Int(_)
-        | TyKind::Unit
-        | TyKind::Bool
-        | TyKind::Ref(_)
-        | TyKind::Str
-        | TyKind::Float(_) => {
-            return Err(ctx.report_error(
-                access.lhs.span(),
-                format!(
-                    "invalid access: value is of type `{}`, which has no fields",
-                    ty.to_string(ctx)
-                ),
-            ));
-        }
-    };
-    let mut strukt = ctx.tys.strukt(*id);
-
-    for (i, acc) in access.accessors.iter().rev().enumerate() {
-        let Some(ty) = strukt.get_field_ty(acc.sym) else {
-            return Err(ctx.report_error(
-                acc.span,
-                format!(
-                    "invalid access: `{}` has no field `{}`",
-                    strukt.name.as_str(),
-                    acc.as_str()
-                ),
-            ));
-        };
-
-        if i == access.accessors.len() - 1 {
-            return Ok((access.span, ty));
-        }
-
-        match ty.0 {
+    let mut prev: Option<Ident> = None;
+    for acc in access.accessors.iter().rev() {
+        ty = match ty.0 {
             TyKind::Struct(id) => {
-                strukt = ctx.tys.strukt(*id);
+                let strukt = ctx.tys.strukt(*id);
+                if strukt.union && !access.in_unsafe {
+                    return Err(ctx
+                        .report_error(
+                            acc.span,
+                            format!(
+                                "access to union field `{}` requires an `unsafe` block",
+                                acc.as_str()
+                            ),
+                        )
+                        .msg(Msg::note(
+                            &ctx.source_map,
+                            strukt.name.span,
+                            "the field that a union holds is not tracked",
+                        )));
+                }
+                let Some(ty) = strukt.get_field_ty(acc.sym) else {
+                    return Err(ctx.report_error(
+                        acc.span,
+                        format!(
+                            "invalid access: `{}` has no field `{}`",
+                            strukt.name.as_str(),
+                            acc.as_str()
+                        ),
+                    ));
+                };
+                ty
+            }
+            TyKind::Tuple(_) => {
+                let Some((_, elem)) = ty.tuple_elem(acc.sym) else {
+                    return Err(ctx.report_error(
+                        acc.span,
+                        format!(
+                            "invalid access: `{}` has no field `{}`",
+                            ty.to_string(ctx),
+                            acc.as_str()
+                        ),
+                    ));
+                };
+                elem
             }
             TyKind::Array(_, _)
             | TyKind::Slice(_)
@@ -1705,21 +2304,33 @@ pub fn aquire_access_ty<'a>(
             | TyKind::Unit
             | TyKind::Bool
             | TyKind::Ref(_)
+            | TyKind::Fn(_, _)
+            | TyKind::Closure(_, _)
+            | TyKind::Opt(_)
             | TyKind::Str
             | TyKind::Float(_) => {
-                let access = access.accessors[i];
-                return Err(ctx.report_error(
-                    access.span,
-                    format!(
-                        "invalid access: `{}` is of type `{}`, which has no field `{}`",
-                        acc.to_string(ctx),
-                        ty.to_string(ctx),
-                        access.to_string(ctx),
+                return Err(match prev {
+                    None => ctx.report_error(
+                        access.lhs.span(),
+                        format!(
+                            "invalid access: value is of type `{}`, which has no fields",
+                            ty.to_string(ctx)
+                        ),
                     ),
-                ));
+                    Some(prev) => ctx.report_error(
+                        acc.span,
+                        format!(
+                            "invalid access: `{}` is of type `{}`, which has no field `{}`",
+                            prev.to_string(ctx),
+                            ty.to_string(ctx),
+                            acc.to_string(ctx),
+                        ),
+                    ),
+                });
             }
-        }
+        };
+        prev = Some(*acc);
     }
 
-    unreachable!()
+    Ok((access.span, ty))
 }
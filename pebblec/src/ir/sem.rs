@@ -62,8 +62,20 @@ impl<'a> Deref for SemCtx<'a> {
 
 fn entry(ctx: &mut SemCtx) -> Result<(), Diag> {
     if let Some(func) = ctx.funcs.iter().find(|f| f.sig.ident.as_str() == "main") {
-        if func.sig.params.len() > 0 {
-            Err(ctx.report_error(func.sig.span, "`main` cannot have any parameters (sorry)"))
+        let args = Ty(&TyKind::Ref(&TyKind::Slice(&TyKind::Ref(&TyKind::Str))));
+        let valid_params = match func.sig.params {
+            [] => true,
+            [Param::Named { ty, .. }] => *ty == args,
+            _ => false,
+        };
+        if !valid_params {
+            Err(ctx
+                .report_error(func.sig.span, "`main` must take no parameters or `&[&str]`")
+                .msg(Msg::help(
+                    &ctx.source_map,
+                    func.sig.span,
+                    "consider `main: (args: &[&str])`",
+                )))
         } else if func.sig.ty != Ty::I32 && !func.sig.ty.is_unit() {
             Err(ctx.report_error(func.sig.span, "`main` must return `i32` or `()`"))
         } else {
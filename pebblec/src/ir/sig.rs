@@ -12,6 +12,10 @@ pub struct Sig<'a> {
     pub params: &'a [Param],
     pub method_self: Option<Ty>,
     pub linkage: Linkage<'a>,
+    /// Accepts any number of arguments after `params`, see [`TyKind::default_promotion`].
+    ///
+    /// [`TyKind::default_promotion`]: super::ty::TyKind::default_promotion
+    pub variadic: bool,
 }
 
 impl Sig<'_> {
@@ -25,7 +29,14 @@ impl Sig<'_> {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Linkage<'a> {
     Local,
-    External { link: &'a str },
+    External {
+        link: &'a str,
+    },
+    /// Declared in an `extern("host")` block and registered by the embedding application, see
+    /// [`InterpInstance::host`].
+    ///
+    /// [`InterpInstance::host`]: crate::interp::InterpInstance::host
+    Host,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -47,3 +58,9 @@ impl Param {
         }
     }
 }
+
+/// `print`, `println`, `eprint` and `eprintln` take a format string followed by any number of
+/// arguments, so they are special cased at their call sites.
+pub fn is_print(ident: &str) -> bool {
+    matches!(ident, "print" | "println" | "eprint" | "eprintln")
+}
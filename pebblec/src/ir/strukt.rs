@@ -2,6 +2,7 @@ use super::Expr;
 use super::ty::Ty;
 use super::ty::store::TyStore;
 use pebblec_parse::lex::buffer::Span;
+use pebblec_parse::rules::prelude::Repr;
 use pebblec_parse::sym::{Ident, Symbol};
 use std::collections::HashMap;
 
@@ -11,6 +12,9 @@ pub struct Struct {
     pub span: Span,
     pub name: Ident,
     pub fields: Vec<Field>,
+    pub repr: Repr,
+    /// Declared with `union`, every field is stored at offset 0.
+    pub union: bool,
 }
 
 impl Struct {
@@ -43,6 +43,8 @@ pub struct InferCtx {
     tables: Vec<SymbolTable<TyVar>>,
     constraints: IndexMap<TyVar, (Ident, Vec<Cnst>)>,
     var_index: usize,
+    /// Type variables of the values that break out of each `loop`, keyed by its span.
+    loops: HashMap<Span, TyVar>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -120,6 +122,18 @@ impl InferCtx {
         result
     }
 
+    /// Registers the type variable of the values that break out of a `loop`, see
+    /// [`crate::ir::Loop::value_ident`].
+    pub fn new_loop_var(&mut self, ident: Ident) -> TyVar {
+        let var = self.new_var(ident);
+        self.loops.insert(ident.span, var);
+        var
+    }
+
+    pub fn loop_var(&self, span: Span) -> Option<TyVar> {
+        self.loops.get(&span).copied()
+    }
+
     fn init_var(&mut self, ident: Ident) -> TyVar {
         let idx = self.var_index;
         self.var_index += 1;
@@ -298,7 +312,8 @@ impl InferCtx {
                             .msg(Msg::info(&ctx.source_map, c.src, c.loc.to_string())));
                     }
 
-                    if abs.is_none() {
+                    // `null` only says that the type is optional, not which
+                    if abs.is_none_or(|(abs, _)| abs == Ty::NULL) {
                         abs = Some((*ty, c.src));
                     }
                 }
@@ -310,6 +325,13 @@ impl InferCtx {
         }
 
         if let Some((abs, _)) = abs {
+            if abs == Ty::NULL {
+                return Err(ctx.report_error(
+                    self.var_span(var),
+                    format!("could not infer type of `{}`", self.var_ident(var)),
+                ));
+            }
+
             if let Some((span, integral)) = integral {
                 if (matches!(integral, Integral::Int) && !abs.is_int())
                     && (matches!(integral, Integral::Float) && !abs.is_float())
@@ -31,6 +31,9 @@ impl Ty {
     pub const ANON_PTR: Self = Self::USIZE;
 
     pub const STR_LIT: Self = Ty(&TyKind::Ref(&TyKind::Str));
+
+    /// Type of `null`, which coerces into any optional type.
+    pub const NULL: Self = Ty(&TyKind::Opt(&TyKind::Unit));
 }
 
 impl Deref for Ty {
@@ -50,6 +53,19 @@ pub enum TyKind {
     Ref(&'static TyKind),
     Array(usize, &'static TyKind),
     Slice(&'static TyKind),
+    Tuple(&'static [&'static TyKind]),
+    /// Function pointer with parameter types and return type, e.g. `fn(i32) -> bool`.
+    Fn(&'static [&'static TyKind], &'static TyKind),
+    /// Closure with parameter types and return type, e.g. `|i32| -> bool`.
+    ///
+    /// A closure is a pair of pointers: its environment, followed by the function that takes the
+    /// environment as its first argument. The environment lives in the stack frame that created
+    /// the closure, so a closure must not outlive it.
+    Closure(&'static [&'static TyKind], &'static TyKind),
+    /// Optional reference or function pointer, e.g. `?&Window`, which may be `null`.
+    ///
+    /// Shares the ABI of the pointer it wraps, with `null` represented as 0.
+    Opt(&'static TyKind),
     Bool,
     Str,
     Unit,
@@ -68,12 +84,15 @@ impl TyKind {
                 Self::Str | Self::Slice(_) => Self::FAT_PTR_SIZE,
                 _ => Self::PTR_SIZE,
             },
+            Self::Fn(_, _) | Self::Opt(_) => Self::PTR_SIZE,
+            Self::Closure(_, _) => Self::FAT_PTR_SIZE,
             Self::Int(int) => int.size(),
             Self::Float(float) => float.size(),
             Self::Str => panic!("size of str is unknown"),
             Self::Struct(id) => tys.struct_layout(*id).size,
             Self::Array(len, inner) => inner.size(tys) * len,
             Self::Slice(_) => todo!("size of slice is unknown"),
+            Self::Tuple(elems) => tys.tuple_layout(elems).size,
         }
     }
 
@@ -105,10 +124,45 @@ impl TyKind {
         matches!(self, Self::Slice(_))
     }
 
+    pub fn is_tuple(&self) -> bool {
+        matches!(self, Self::Tuple(_))
+    }
+
+    pub fn is_fn(&self) -> bool {
+        matches!(self, Self::Fn(_, _))
+    }
+
+    pub fn is_closure(&self) -> bool {
+        matches!(self, Self::Closure(_, _))
+    }
+
+    pub fn is_opt(&self) -> bool {
+        matches!(self, Self::Opt(_))
+    }
+
+    /// The pointer wrapped by an optional type, e.g. `&T` for `?&T`.
+    pub fn opt_inner(&'static self) -> Option<Ty> {
+        match self {
+            Self::Opt(inner) => Some(Ty(inner)),
+            _ => None,
+        }
+    }
+
     pub fn is_castable(&self) -> bool {
         match self {
-            Self::Struct(_) | Self::Str | Self::Array(_, _) | Self::Unit => false,
-            Self::Int(_) | Self::Float(_) | Self::Bool | Self::Ref(_) | Self::Slice(_) => true,
+            Self::Struct(_)
+            | Self::Str
+            | Self::Array(_, _)
+            | Self::Tuple(_)
+            | Self::Fn(_, _)
+            | Self::Closure(_, _)
+            | Self::Unit => false,
+            Self::Int(_)
+            | Self::Float(_)
+            | Self::Bool
+            | Self::Ref(_)
+            | Self::Opt(_)
+            | Self::Slice(_) => true,
         }
     }
 
@@ -116,16 +170,63 @@ impl TyKind {
         !matches!(self, Self::Str)
     }
 
+    /// Whether a closure is stored by value within this type.
+    pub fn holds_closure(&self) -> bool {
+        match self {
+            Self::Closure(_, _) => true,
+            Self::Array(_, inner) => inner.holds_closure(),
+            Self::Tuple(elems) => elems.iter().any(|e| e.holds_closure()),
+            _ => false,
+        }
+    }
+
+    /// The type a variadic argument of this type is passed as, following C's default argument
+    /// promotions. `None` if the type has no C equivalent.
+    pub fn default_promotion(&'static self) -> Option<Ty> {
+        match self {
+            Self::Bool => Some(Ty::I32),
+            Self::Int(int) if int.size() < 4 => Some(Ty::I32),
+            Self::Float(FloatTy::F32) => Some(Ty::F64),
+            Self::Ref(Self::Str | Self::Slice(_)) => None,
+            Self::Int(_)
+            | Self::Float(_)
+            | Self::Ref(_)
+            | Self::Fn(_, _)
+            | Self::Opt(_)
+            | Self::Struct(_) => Some(Ty(self)),
+            Self::Array(_, _)
+            | Self::Slice(_)
+            | Self::Tuple(_)
+            | Self::Closure(_, _)
+            | Self::Str
+            | Self::Unit => None,
+        }
+    }
+
     #[track_caller]
     pub fn expect_int(&self) -> IntTy {
         match self {
             Self::Int(ty) => *ty,
             Self::Bool => IntTy::BOOL,
-            Self::Ref(_) => IntTy::PTR,
+            Self::Ref(_) | Self::Opt(_) => IntTy::PTR,
             _ => panic!("expected int, got {:?}", self),
         }
     }
 
+    /// Index and type of the tuple element named by `field`, e.g. the `1` in `t.1`.
+    pub fn tuple_elem(&self, field: Symbol) -> Option<(usize, Ty)> {
+        let Self::Tuple(elems) = self else {
+            return None;
+        };
+
+        let str = field.as_str();
+        if !str.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let index = str.parse::<usize>().ok()?;
+        elems.get(index).map(|elem| (index, Ty(elem)))
+    }
+
     #[track_caller]
     pub fn expect_struct(&self) -> StructId {
         match self {
@@ -135,16 +236,56 @@ impl TyKind {
     }
 
     pub fn to_string(&self, ctx: &Ctx) -> String {
+        self.to_string_with(&ctx.tys)
+    }
+
+    /// Same as [`TyKind::to_string`], for when the [`Ctx`] is no longer available.
+    pub fn to_string_with(&self, tys: &TyStore) -> String {
         match self {
             Self::Unit => "()".to_string(),
             Self::Bool => "bool".to_string(),
-            Self::Ref(inner) => format!("&{}", inner.to_string(ctx)),
+            Self::Ref(inner) => format!("&{}", inner.to_string_with(tys)),
+            Self::Opt(Self::Unit) => "null".to_string(),
+            Self::Opt(inner) => format!("?{}", inner.to_string_with(tys)),
             Self::Str => "str".to_string(),
             Self::Int(int) => int.as_str().to_string(),
             Self::Float(float) => float.as_str().to_string(),
-            Self::Struct(s) => ctx.tys.strukt(*s).name.as_str().to_string(),
-            Self::Array(len, inner) => format!("[{}; {}]", inner.to_string(ctx), len),
-            Self::Slice(inner) => format!("[{}]", inner.to_string(ctx)),
+            Self::Struct(s) => tys.strukt(*s).name.as_str().to_string(),
+            Self::Array(len, inner) => format!("[{}; {}]", inner.to_string_with(tys), len),
+            Self::Slice(inner) => format!("[{}]", inner.to_string_with(tys)),
+            Self::Tuple(elems) => {
+                let elems = elems
+                    .iter()
+                    .map(|ty| ty.to_string_with(tys))
+                    .collect::<Vec<_>>();
+                if elems.len() == 1 {
+                    format!("({},)", elems[0])
+                } else {
+                    format!("({})", elems.join(", "))
+                }
+            }
+            Self::Fn(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|ty| ty.to_string_with(tys))
+                    .collect::<Vec<_>>();
+                if ret.is_unit() {
+                    format!("fn({})", params.join(", "))
+                } else {
+                    format!("fn({}) -> {}", params.join(", "), ret.to_string_with(tys))
+                }
+            }
+            Self::Closure(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|ty| ty.to_string_with(tys))
+                    .collect::<Vec<_>>();
+                if ret.is_unit() {
+                    format!("|{}|", params.join(", "))
+                } else {
+                    format!("|{}| -> {}", params.join(", "), ret.to_string_with(tys))
+                }
+            }
         }
     }
 
@@ -177,6 +318,10 @@ impl TyKind {
             (TyKind::Ref(TyKind::Array(_, lhs)), TyKind::Ref(TyKind::Slice(rhs))) => lhs == rhs,
             (TyKind::Ref(_), TyKind::Int(IntTy::PTR)) => true,
             (TyKind::Int(IntTy::PTR), TyKind::Ref(_)) => true,
+            // a pointer that is known to be valid can always be used where it may be `null`
+            (TyKind::Ref(_) | TyKind::Fn(_, _), TyKind::Opt(inner)) => self == *inner,
+            (TyKind::Opt(TyKind::Unit), TyKind::Opt(_)) => true,
+            (TyKind::Opt(_), TyKind::Opt(TyKind::Unit)) => true,
             _ => self == other,
         }
     }
@@ -230,6 +375,22 @@ impl IntTy {
         self.sign
     }
 
+    /// Smallest value of the type.
+    pub const fn min(&self) -> i128 {
+        match self.sign {
+            Sign::U => 0,
+            Sign::I => -(1 << (self.width.bytes() * 8 - 1)),
+        }
+    }
+
+    /// Largest value of the type.
+    pub const fn max(&self) -> i128 {
+        match self.sign {
+            Sign::U => (1 << (self.width.bytes() * 8)) - 1,
+            Sign::I => (1 << (self.width.bytes() * 8 - 1)) - 1,
+        }
+    }
+
     pub const fn as_str(&self) -> &'static str {
         match self.sign {
             Sign::I => match self.width {
@@ -46,6 +46,32 @@ impl TyStore {
         }
     }
 
+    pub fn intern_tuple(&mut self, elems: &[Ty]) -> Ty {
+        let elems = elems.iter().map(|ty| ty.0).collect::<Vec<_>>();
+        let kind = TyKind::Tuple(self.alloc_tys(&elems));
+        self.intern_kind(kind)
+    }
+
+    pub fn intern_fn(&mut self, params: &[Ty], ret: Ty) -> Ty {
+        let params = params.iter().map(|ty| ty.0).collect::<Vec<_>>();
+        let kind = TyKind::Fn(self.alloc_tys(&params), ret.0);
+        self.intern_kind(kind)
+    }
+
+    pub fn intern_closure(&mut self, params: &[Ty], ret: Ty) -> Ty {
+        let params = params.iter().map(|ty| ty.0).collect::<Vec<_>>();
+        let kind = TyKind::Closure(self.alloc_tys(&params), ret.0);
+        self.intern_kind(kind)
+    }
+
+    fn alloc_tys(&self, tys: &[&'static TyKind]) -> &'static [&'static TyKind] {
+        if tys.is_empty() {
+            &[]
+        } else {
+            self.storage.alloc_slice(tys)
+        }
+    }
+
     // TODO: structs should be stored within `TyKind`
     pub fn store_struct(&mut self, strukt: Struct) -> StructId {
         let idx = self.structs.len();
@@ -88,11 +114,86 @@ impl TyStore {
         self.fields.get(&struct_id).expect("invalid struct id")
     }
 
+    /// Finds a packed or over-aligned struct that is stored by value within `ty`. libffi can only
+    /// describe structs with C padding rules.
+    pub fn non_c_struct(&self, ty: &TyKind) -> Option<StructId> {
+        match ty {
+            TyKind::Struct(id) => {
+                let strukt = self.strukt(*id);
+                if strukt.repr.packed || strukt.repr.align.is_some() {
+                    Some(*id)
+                } else {
+                    strukt.fields.iter().find_map(|f| self.non_c_struct(f.ty.0))
+                }
+            }
+            TyKind::Array(_, inner) => self.non_c_struct(inner),
+            TyKind::Tuple(elems) => elems.iter().find_map(|e| self.non_c_struct(e)),
+            TyKind::Fn(params, ret) => params
+                .iter()
+                .chain(std::iter::once(ret))
+                .find_map(|ty| self.non_c_struct(ty)),
+            _ => None,
+        }
+    }
+
+    /// Finds a struct that stores a function pointer by value within `ty`. Pebble functions are
+    /// only converted to C function pointers when they are passed directly.
+    pub fn struct_with_fn(&self, ty: &TyKind) -> Option<StructId> {
+        match ty {
+            TyKind::Struct(id) => {
+                let strukt = self.strukt(*id);
+                if strukt.fields.iter().any(|f| self.stores_fn(f.ty.0)) {
+                    Some(*id)
+                } else {
+                    strukt
+                        .fields
+                        .iter()
+                        .find_map(|f| self.struct_with_fn(f.ty.0))
+                }
+            }
+            TyKind::Array(_, inner) => self.struct_with_fn(inner),
+            TyKind::Tuple(elems) => elems.iter().find_map(|e| self.struct_with_fn(e)),
+            TyKind::Fn(params, ret) => params
+                .iter()
+                .chain(std::iter::once(ret))
+                .find_map(|ty| self.struct_with_fn(ty)),
+            TyKind::Opt(inner) => self.struct_with_fn(inner),
+            _ => None,
+        }
+    }
+
+    fn stores_fn(&self, ty: &TyKind) -> bool {
+        match ty {
+            TyKind::Fn(_, _) => true,
+            TyKind::Opt(inner) | TyKind::Array(_, inner) => self.stores_fn(inner),
+            TyKind::Tuple(elems) => elems.iter().any(|e| self.stores_fn(e)),
+            _ => false,
+        }
+    }
+
     #[track_caller]
     pub fn layout(&self, ty: Ty) -> Layout {
         *self.layouts.get(&ty).expect("invalid type id")
     }
 
+    /// Layout of any sized type. Struct layouts must be built first.
+    #[track_caller]
+    pub fn ty_layout(&self, ty: Ty) -> Layout {
+        ty.layout_with(&self.struct_ty_map, &self.layouts)
+    }
+
+    /// Layout of a tuple with element types `elems`. Struct layouts must be built first.
+    #[track_caller]
+    pub fn tuple_layout(&self, elems: &[&TyKind]) -> Layout {
+        tuple_layout_with(elems, &self.struct_ty_map, &self.layouts).0
+    }
+
+    /// Byte offset of the `index`th element of a tuple with element types `elems`.
+    #[track_caller]
+    pub fn tuple_offset(&self, elems: &[&TyKind], index: usize) -> i32 {
+        tuple_layout_with(elems, &self.struct_ty_map, &self.layouts).1[index] as i32
+    }
+
     #[track_caller]
     pub fn struct_layout(&self, struct_id: StructId) -> Layout {
         let ty_id = self
@@ -102,6 +203,63 @@ impl TyStore {
         self.layout(*ty_id)
     }
 
+    /// Describes the layout of the struct named `name`, with the offset and size of each field
+    /// and any padding between them.
+    pub fn print_layout(&self, name: &str) -> Option<String> {
+        let id = self.struct_id(Symbol::intern(name))?;
+        let strukt = self.strukt(id);
+        let layout = self.struct_layout(id);
+        let fields = self.fields(id);
+
+        let mut repr = Vec::new();
+        if strukt.union {
+            repr.push("union".to_string());
+        }
+        if strukt.repr.packed {
+            repr.push("packed".to_string());
+        }
+        if let Some(align) = strukt.repr.align {
+            repr.push(format!("align({})", align));
+        }
+        let mut buf = format!(
+            "{}: size {}, align {}{}\n",
+            name,
+            layout.size,
+            layout.alignment,
+            if repr.is_empty() {
+                String::new()
+            } else {
+                format!(", {}", repr.join(", "))
+            }
+        );
+
+        let mut byte = 0;
+        for field in strukt.fields.iter() {
+            let offset = fields.fields.get(&field.name.sym).unwrap().1 as usize;
+            if offset > byte {
+                buf.push_str(&format!("{:>6}  [{} bytes padding]\n", byte, offset - byte));
+            }
+            let size = field.ty.size(self);
+            buf.push_str(&format!(
+                "{:>6}  {}: {}, size {}\n",
+                offset,
+                field.name.as_str(),
+                field.ty.to_string_with(self),
+                size
+            ));
+            byte = byte.max(offset + size);
+        }
+        if layout.size > byte {
+            buf.push_str(&format!(
+                "{:>6}  [{} bytes padding]\n",
+                byte,
+                layout.size - byte
+            ));
+        }
+
+        Some(buf)
+    }
+
     pub fn build_layouts(&mut self) {
         for (i, strukt) in self.structs.iter().enumerate() {
             let ty_id = self.struct_ty_map.get(&StructId(i)).unwrap();
@@ -160,7 +318,14 @@ impl TyStore {
             struct_layouts.push(field.ty.layout_with(struct_ty_map, layouts));
         }
 
-        let mut alignment = 1;
+        // packed fields are byte aligned, removing all padding
+        if strukt.repr.packed {
+            for layout in struct_layouts.iter_mut() {
+                layout.alignment = 1;
+            }
+        }
+
+        let mut alignment = strukt.repr.align.unwrap_or(1);
         for layout in struct_layouts.iter() {
             if layout.alignment > alignment {
                 alignment = layout.alignment;
@@ -170,6 +335,12 @@ impl TyStore {
         let mut struct_offsets = HashMap::new();
         let mut byte = 0;
         for (layout, field) in struct_layouts.iter().zip(strukt.fields.iter()) {
+            if strukt.union {
+                struct_offsets.insert(field.name.sym, (field.ty, 0));
+                byte = byte.max(layout.size);
+                continue;
+            }
+
             while byte % layout.alignment != 0 {
                 byte += 1;
             }
@@ -207,13 +378,46 @@ impl TyKind {
             Self::Float(float) => float.layout(),
             Self::Ref(Self::Str) => Layout::FAT_PTR,
             Self::Ref(Self::Slice(_)) => Layout::FAT_PTR,
-            Self::Str | Self::Ref(_) => Layout::PTR,
+            Self::Str | Self::Ref(_) | Self::Fn(_, _) | Self::Opt(_) => Layout::PTR,
+            Self::Closure(_, _) => Layout::FAT_PTR,
             Self::Array(len, inner) => inner.layout_with(struct_ty_map, layouts).to_array(*len),
             Self::Slice(_) => todo!("unsized"),
             Self::Struct(id) => {
                 let ty_id = struct_ty_map.get(&id).unwrap();
                 *layouts.get(ty_id).unwrap()
             }
+            Self::Tuple(elems) => tuple_layout_with(elems, struct_ty_map, layouts).0,
         }
     }
 }
+
+/// Tuples are laid out in order, like structs. Returns the layout and the offset of each element.
+#[track_caller]
+fn tuple_layout_with(
+    elems: &[&TyKind],
+    struct_ty_map: &HashMap<StructId, Ty>,
+    layouts: &HashMap<Ty, Layout>,
+) -> (Layout, Vec<usize>) {
+    let mut alignment = 1;
+    let mut offsets = Vec::with_capacity(elems.len());
+    let mut byte = 0;
+    for elem in elems.iter() {
+        let layout = elem.layout_with(struct_ty_map, layouts);
+        if layout.alignment > alignment {
+            alignment = layout.alignment;
+        }
+
+        while byte % layout.alignment != 0 {
+            byte += 1;
+        }
+
+        offsets.push(byte);
+        byte += layout.size;
+    }
+
+    while byte % alignment != 0 {
+        byte += 1;
+    }
+
+    (Layout::new(byte, alignment), offsets)
+}
@@ -1,6 +1,6 @@
 use clap::Parser;
 use pebblec::comp::{CompErr, CompUnit, Config};
-use pebblec::interp::InterpInstance;
+use pebblec::interp::{DEFAULT_STACK_SIZE, InterpInstance};
 use std::process::ExitCode;
 
 /// Pebble Compiler
@@ -17,6 +17,22 @@ struct Args {
     /// log the interpreter
     #[arg(short, long, default_value_t = false)]
     log: bool,
+
+    /// size of the interpreter stack in bytes
+    #[arg(short, long, default_value_t = DEFAULT_STACK_SIZE)]
+    stack_size: usize,
+
+    /// print the field offsets of a struct instead of running the program
+    #[arg(long, value_name = "TYPE")]
+    print_layout: Option<String>,
+
+    /// pin the seed of `core::rand` to reproduce a run
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// arguments passed to `main`, after `--`
+    #[arg(last = true)]
+    args: Vec<String>,
 }
 
 impl Args {
@@ -30,10 +46,29 @@ impl Args {
 
 fn main() -> ExitCode {
     let args = Args::parse();
-    match CompUnit::new(args.config()).compile(args.file) {
-        Ok(bytecode) => {
-            ExitCode::from(InterpInstance::new(&bytecode).run(args.log) as u8)
-        }
+    let config = args.config();
+    match CompUnit::new(config.clone()).compile(&args.file) {
+        Ok(bytecode) => match &args.print_layout {
+            Some(name) => match bytecode.tys.print_layout(name) {
+                Some(layout) => {
+                    print!("{layout}");
+                    ExitCode::SUCCESS
+                }
+                None => {
+                    eprintln!("error: no struct named `{name}`");
+                    ExitCode::FAILURE
+                }
+            },
+            None => {
+                let instance = InterpInstance::with_stack_size(&bytecode, args.stack_size)
+                    .args(std::iter::once(args.file.clone()).chain(args.args));
+                let mut instance = match args.seed {
+                    Some(seed) => instance.seed(seed),
+                    None => instance,
+                };
+                ExitCode::from(instance.run(config.log) as u8)
+            }
+        },
         Err(err) => {
             match err {
                 CompErr::Source(err) => {
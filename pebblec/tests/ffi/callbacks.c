@@ -0,0 +1,43 @@
+// Built by the `ffi_callbacks` test in `tests.rs`.
+
+#include <stdint.h>
+#include <stdlib.h>
+
+int32_t apply_twice(int32_t (*f)(int32_t), int32_t x) {
+    return f(f(x));
+}
+
+double apply_f64(double (*f)(double, double), double a, double b) {
+    return f(a, b);
+}
+
+int8_t apply_i8(int8_t (*f)(int8_t), int8_t x) {
+    return f(x);
+}
+
+void sort_i32(int32_t *values, uint64_t len, int (*cmp)(const void *, const void *)) {
+    qsort(values, len, sizeof(int32_t), cmp);
+}
+
+typedef struct {
+    int32_t x;
+    int32_t y;
+} Point;
+
+int32_t sum_point(Point (*f)(int32_t), int32_t n) {
+    Point p = f(n);
+    return p.x + p.y;
+}
+
+int32_t apply_or(int32_t (*f)(int32_t), int32_t x) {
+    return f ? f(x) : x;
+}
+
+int32_t *find_i32(int32_t *values, uint64_t len, int32_t value) {
+    for (uint64_t i = 0; i < len; i++) {
+        if (values[i] == value) {
+            return &values[i];
+        }
+    }
+    return NULL;
+}
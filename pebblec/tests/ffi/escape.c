@@ -0,0 +1,26 @@
+// Built by the `ffi_escape` test in `tests.rs`.
+
+#include <pthread.h>
+#include <stdint.h>
+#include <stddef.h>
+
+static int32_t (*stored)(int32_t) = NULL;
+
+void store(int32_t (*f)(int32_t)) {
+    stored = f;
+}
+
+int32_t call_stored(int32_t x) {
+    return stored(x);
+}
+
+static void *call_on_thread_main(void *f) {
+    ((int32_t (*)(int32_t))f)(1);
+    return NULL;
+}
+
+void call_on_thread(int32_t (*f)(int32_t)) {
+    pthread_t thread;
+    pthread_create(&thread, NULL, call_on_thread_main, (void *)f);
+    pthread_join(thread, NULL);
+}
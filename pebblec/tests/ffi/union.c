@@ -0,0 +1,53 @@
+// Built by the `ffi_union` test in `tests.rs`.
+
+#include <stdint.h>
+
+typedef union {
+    uint32_t bits;
+    float f;
+    uint8_t bytes[4];
+} Value;
+
+typedef union {
+    uint8_t small;
+    uint64_t big;
+    uint8_t bytes[12];
+} Wide;
+
+typedef union {
+    double d;
+    float f[2];
+} Floats;
+
+uint32_t value_bits(Value v) {
+    return v.bits;
+}
+
+Value value_from_float(float f) {
+    Value v;
+    v.f = f;
+    return v;
+}
+
+uint64_t wide_sum(Wide w) {
+    uint64_t sum = 0;
+    for (int i = 0; i < 12; i++) {
+        sum += w.bytes[i];
+    }
+    return sum;
+}
+
+void wide_set(Wide *w, uint64_t big) {
+    w->big = big;
+}
+
+double floats_double(Floats f) {
+    return f.d;
+}
+
+Floats floats_pair(float a, float b) {
+    Floats f;
+    f.f[0] = a;
+    f.f[1] = b;
+    return f;
+}
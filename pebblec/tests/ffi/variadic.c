@@ -0,0 +1,44 @@
+// Built by the `ffi_variadic` test in `tests.rs`.
+
+#include <stdarg.h>
+#include <stdint.h>
+#include <string.h>
+
+// variadic arguments narrower than `int` are promoted by the caller
+int64_t sum_ints(int32_t count, ...) {
+    va_list args;
+    va_start(args, count);
+    int64_t sum = 0;
+    for (int32_t i = 0; i < count; i++) {
+        sum += va_arg(args, int);
+    }
+    va_end(args);
+    return sum;
+}
+
+int64_t sum_longs(int32_t count, ...) {
+    va_list args;
+    va_start(args, count);
+    int64_t sum = 0;
+    for (int32_t i = 0; i < count; i++) {
+        sum += va_arg(args, int64_t);
+    }
+    va_end(args);
+    return sum;
+}
+
+// `float` is promoted to `double` by the caller
+double sum_doubles(int32_t count, ...) {
+    va_list args;
+    va_start(args, count);
+    double sum = 0;
+    for (int32_t i = 0; i < count; i++) {
+        sum += va_arg(args, double);
+    }
+    va_end(args);
+    return sum;
+}
+
+int32_t str_eq(const char *a, const char *b) {
+    return strcmp(a, b) == 0;
+}
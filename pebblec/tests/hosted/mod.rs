@@ -1,19 +1,154 @@
 #[test]
-fn cast() {
+fn defer() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/defer.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn label() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/label.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn env() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/env.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn fn_ptr() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/fn_ptr.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn repr() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/repr.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn format() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/format.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn map() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/map.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn tuple() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/tuple.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn time() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/time.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn mem() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/mem.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn negative_const() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/cast.peb").unwrap())
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/negative_const.peb").unwrap())
         .run(true)
     );
 }
 #[test]
+fn escape() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/escape.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn slice() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/slice.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn union() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/union.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn vec() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/vec.peb").unwrap()).run(true)
+    );
+}
+#[test]
 fn methods() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/methods.peb")
-        .unwrap()).run(true)
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/methods.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn cast() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/cast.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn optional() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/optional.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn closure() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/closure.peb").unwrap()).run(true)
     );
 }
 #[test]
@@ -21,16 +156,22 @@ fn general() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/general.peb")
-        .unwrap()).run(true)
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/general.peb").unwrap()).run(true)
     );
 }
 #[test]
-fn slice() {
+fn number() {
+    assert_eq!(
+        0, pebblec::interp::InterpInstance::new(&
+        pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/number.peb").unwrap()).run(true)
+    );
+}
+#[test]
+fn rand() {
     assert_eq!(
         0, pebblec::interp::InterpInstance::new(&
         pebblec::comp::CompUnit::new(pebblec::comp::Config::default().no_capture(true))
-        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/slice.peb")
-        .unwrap()).run(true)
+        .compile("/Users/nicolasball/dev/pebble/pebblec/tests/hosted/rand.peb").unwrap()).run(true)
     );
 }
@@ -1,10 +1,59 @@
-use pebblec::comp::{CompUnit, Config};
-use pebblec_parse::lex::io;
+use pebblec::air::ByteCode;
+use pebblec::comp::{CompErr, CompUnit, Config};
+use pebblec::ir::ctx::Ctx;
+use pebblec_parse::diagnostic::Diag;
+use pebblec_parse::lex::source::{Source, SourceMap};
+use pebblec_parse::lex::{Lexer, io};
 
 mod hosted;
 
 const INVADERS: &str = "../demo/invaders/invaders.peb";
 
+/// Compiles `src` as a single file.
+fn compile_src(src: &str) -> Result<ByteCode<'static>, CompErr> {
+    CompUnit::new(Config::default().no_capture(true)).compile_string("test.peb", src.to_string())
+}
+
+/// Compiles `items` followed by `main: () { <body> }`.
+fn compile_body(items: &str, body: &str) -> Result<ByteCode<'static>, CompErr> {
+    compile_src(&format!("{items} main: () {{ {body} }}"))
+}
+
+/// Compiles `items` followed by an empty `main`.
+fn compile_items(items: &str) -> Result<ByteCode<'static>, CompErr> {
+    compile_body(items, "")
+}
+
+/// Compiles `src` as a single file and returns the title of the first error that the lexer,
+/// parser or lowering reports. Panics if `src` lowers without errors.
+fn compile_err(src: &str) -> String {
+    let source = Source::from_string("test.peb", src.to_string());
+    let buffer = match Lexer::new(source).lex() {
+        Ok(buffer) => buffer,
+        Err(diag) => return diag.title().to_string(),
+    };
+    if let Err(diag) = pebblec_parse::parse(&buffer) {
+        return diag.title().to_string();
+    }
+
+    let mut source_map = SourceMap::from_string("test.peb", src.to_string()).unwrap();
+    let items = source_map.parse().unwrap();
+    match pebblec::ir::lower_items(Ctx::new(source_map), items) {
+        Ok(_) => panic!("`{src}` compiled"),
+        Err(diag) => diag.title().to_string(),
+    }
+}
+
+/// [`compile_err`] for `items` followed by `main: () { <body> }`.
+fn compile_body_err(items: &str, body: &str) -> String {
+    compile_err(&format!("{items} main: () {{ {body} }}"))
+}
+
+/// [`compile_err`] for `items` followed by an empty `main`.
+fn compile_items_err(items: &str) -> String {
+    compile_body_err(items, "")
+}
+
 #[test]
 fn deterministic() {
     let first = CompUnit::new(Config::default().no_capture(true))
@@ -40,3 +89,1658 @@ fn core() {
         )
     }
 }
+
+/// Compiles and runs `src`, returning the exit code of `main`.
+fn run(name: &str, src: &str) -> i32 {
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string(name, src.to_string())
+        .unwrap();
+    pebblec::interp::InterpInstance::new(&bytecode).run(false)
+}
+
+const EMPTY_STR: &str = "
+main: () -> i32 {
+    let s = \"\";
+    let len = (((&s) as u64 + 8) as &u64)*;
+    if len == 0 { 0 } else { 1 }
+}
+";
+
+#[test]
+fn empty_str() {
+    assert_eq!(0, run("empty_str.peb", EMPTY_STR));
+}
+
+const TRAILING_WHILE: &str = "
+count: (n: i32) {
+    let i = 0;
+    while i < n {
+        i += 1;
+    }
+}
+
+main: () -> i32 {
+    count(3);
+    0
+}
+";
+
+#[test]
+fn trailing_while() {
+    assert_eq!(0, run("trailing_while.peb", TRAILING_WHILE));
+}
+
+const METHOD_ARGS: &str = "
+Counter: struct {
+    total: u8,
+}
+
+impl Counter {
+    add: (self, n: u8, scale: u8) -> u8 {
+        self*.total += n * scale;
+        self*.total
+    }
+}
+
+main: () -> i32 {
+    let c = Counter { total: 1 };
+    let n = 2;
+    c.add(n, 3);
+    if c.add(1, 1) == 8 { 0 } else { 1 }
+}
+";
+
+#[test]
+fn method_args() {
+    assert_eq!(0, run("method_args.peb", METHOD_ARGS));
+}
+
+const RECURSIVE_LOCALS: &str = "
+sum: (n: u64) -> u64 {
+    let x = n;
+    if x == 0 {
+        return 0;
+    }
+    let rest = sum(x - 1);
+    x + rest
+}
+
+main: () -> i32 {
+    if sum(4) == 10 { 0 } else { 1 }
+}
+";
+
+#[test]
+fn recursive_locals() {
+    assert_eq!(0, run("recursive_locals.peb", RECURSIVE_LOCALS));
+}
+
+const DEREF_COPY: &str = "
+Point: struct {
+    x: i32,
+    y: i32,
+}
+
+main: () -> i32 {
+    let p = Point { x: 1, y: 2 };
+    let q = Point { x: 3, y: 4 };
+    let r = &q;
+    let s = &p;
+    (s*) = r*;
+    q.x = 7;
+    let copy = r*;
+    copy.y = 9;
+    if p.x == 3 && p.y == 4 && q.y == 4 { 0 } else { 1 }
+}
+";
+
+#[test]
+fn deref_copy() {
+    assert_eq!(0, run("deref_copy.peb", DEREF_COPY));
+}
+
+#[test]
+fn stack_overflow() {
+    let src = "recurse: (n: i32) -> i32 { recurse(n + 1) } main: () -> i32 { recurse(0) }";
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("stack_overflow.peb", src.to_string())
+        .unwrap();
+
+    assert_eq!(
+        1,
+        pebblec::interp::InterpInstance::with_stack_size(&bytecode, 4096).run(false)
+    );
+    // the exit code alone does not tell a stack overflow apart from other failures
+    assert!(matches!(
+        pebblec::interp::InterpInstance::with_stack_size(&bytecode, 4096)
+            .call("recurse", &[pebblec::interp::Value::I32(0)]),
+        Err(pebblec::interp::InterpError::StackOverflow)
+    ));
+}
+
+const INVALID_MEMORY: &str = "
+use core::mem;
+
+stray: () -> u64 { 4096 }
+read: () -> i32 { (stray() as &i32)* }
+write: () { ((stray() as &i32)*) = 1; }
+copy_from: () { let x = (stray() as &[i32; 4])*; }
+free_stray: () { free(stray() as &u8); }
+realloc_stray: () { realloc(stray() as &u8, 8); }
+misaligned: () { alloc(8, 3); }
+main: () {}
+";
+
+#[test]
+fn invalid_memory() {
+    use pebblec::interp::{InterpError, InterpInstance};
+
+    let bytecode = compile_src(INVALID_MEMORY).unwrap();
+    for func in ["read", "write", "copy_from"] {
+        assert!(matches!(
+            InterpInstance::new(&bytecode).call(func, &[]),
+            Err(InterpError::InvalidAddress { addr: 4096, .. })
+        ));
+    }
+    assert!(matches!(
+        InterpInstance::new(&bytecode).call("free_stray", &[]),
+        Err(InterpError::Free(4096))
+    ));
+    assert!(matches!(
+        InterpInstance::new(&bytecode).call("realloc_stray", &[]),
+        Err(InterpError::Realloc(4096))
+    ));
+    assert!(matches!(
+        InterpInstance::new(&bytecode).call("misaligned", &[]),
+        Err(InterpError::Alloc { bytes: 8, align: 3 })
+    ));
+}
+
+#[test]
+fn shadowed_size_of() {
+    // only the intrinsic of `core::mem` takes a type
+    let src = "size_of: (x: i32) -> i32 { x * 2 } main: () -> i32 { size_of(3) }";
+    assert_eq!(6, run("size_of.peb", src));
+}
+
+const SHADOWED_FNS: &str = "
+f: (x: i32) -> i32 { x + 100 }
+double: (x: i32) -> i32 { x * 2 }
+apply: (f: fn(i32) -> i32, x: i32) -> i32 { f(x) }
+
+H: struct { f: fn(i32) -> i32 }
+stray: () {
+    let h = H { f: &double };
+    let addr = (&h) as u64;
+    ((addr as &u64)*) = 4096;
+    (h.f)(1);
+}
+
+main: () -> i32 {
+    let a = apply(&double, 5);
+    let f = &double;
+    let b = f(1);
+    let c = |f: fn(i32) -> i32| f(2);
+    a + b + c(&double)
+}
+";
+
+#[test]
+fn shadowed_fns() {
+    use pebblec::interp::{InterpError, InterpInstance};
+
+    // parameters and bindings are called through, not the function `f`
+    assert_eq!(16, run("shadowed_fns.peb", SHADOWED_FNS));
+
+    let bytecode = compile_src(SHADOWED_FNS).unwrap();
+    assert!(matches!(
+        InterpInstance::new(&bytecode).call("stray", &[]),
+        Err(InterpError::InvalidFnPtr(4096))
+    ));
+}
+
+#[test]
+fn format_output() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_pebblec"))
+        .arg("tests/hosted/format.peb")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // the timings of the compile report differ between runs
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stdout = stdout
+        .split_inclusive('\n')
+        .map(|line| match line.split_once("s ... ") {
+            Some((time, title)) if time.starts_with("  ") => format!("  _s ... {title}"),
+            _ => line.to_string(),
+        })
+        .collect::<String>();
+    assert_eq!(
+        stdout,
+        "30 lines
+  _s ... parse
+  _s ... bytecode
+  _s ... total
+
+1 2 {} %
+%50 of total
+ff FF 0xff 101 0o10
+-001.500 +5 1.5e3 1.23e-2
+[   42] [ab   ] [  mid  ] [***3***]
+tru
+12.4 \"quoted\" true
+Point { x: 1, y: -2 } [1, 2, 3] [1, 2, 3]
+"
+    );
+}
+
+#[test]
+fn format_runtime_errors() {
+    use pebblec::interp::{InterpError, InterpInstance};
+
+    // only literal format strings are checked when compiling
+    let bytecode = compile_src(
+        "use core::io;
+        unclosed: () { let fmt = \"{\"; println(fmt); }
+        missing: () { let fmt = \"% %\"; println(fmt); }
+        main: () {}",
+    )
+    .unwrap();
+    for func in ["unclosed", "missing"] {
+        assert!(matches!(
+            InterpInstance::new(&bytecode).call(func, &[]),
+            Err(InterpError::Format { .. })
+        ));
+    }
+}
+
+#[test]
+fn format_errors() {
+    let items = "use core::io; Point: struct { x: i32 }";
+    let compile = |body: &str| compile_body(items, &format!("let p = Point {{ x: 1 }}; {body}"));
+    let error = |body: &str| compile_body_err(items, &format!("let p = Point {{ x: 1 }}; {body}"));
+
+    assert!(compile("println(\"{} {:?} {:x} {:.2}\", 1, p, 2, 1.5);").is_ok());
+    assert_eq!(
+        error("println(\"{} {}\", 1);"),
+        "format string expects `2` arguments, got `1`"
+    );
+    assert_eq!(
+        error("println(\"{}\", 1, 2);"),
+        "format string expects `1` arguments, got `2`"
+    );
+    assert_eq!(
+        error("println(\"{}\", p);"),
+        "a value of type `Point` cannot be formatted with `{}`, use `{:?}`"
+    );
+    assert_eq!(
+        error("println(\"{:x}\", 1.5);"),
+        "a value of type `{float}` cannot be formatted with `{:x}`"
+    );
+    assert_eq!(error("println(\"{:q}\", 1);"), "invalid format spec `q`");
+    assert_eq!(error("println(\"{\", 1);"), "unterminated `{`");
+    assert_eq!(
+        error("println(\"}\");"),
+        "unmatched `}`, use `}}` to print a `}`"
+    );
+}
+
+#[test]
+fn escape_errors() {
+    let compile = |expr: &str| compile_body("", &format!("let x = {expr};"));
+    let error = |expr: &str| compile_body_err("", &format!("let x = {expr};"));
+
+    assert!(compile(r#""\n\t\x7f\u{10ffff}""#).is_ok());
+    assert!(compile(r#"r"\q""#).is_ok());
+    assert!(compile(r"b'\xff'").is_ok());
+    assert_eq!(error(r#""\q""#), "unknown escape sequence");
+    assert_eq!(
+        error(r#""\x80""#),
+        "out of range hex escape, must be at most `\\x7f`"
+    );
+    assert_eq!(
+        error(r#""\x4""#),
+        "expected two hex digits in `\\x` escape, e.g. `\\x7f`"
+    );
+    assert_eq!(
+        error(r#""\u{110000}""#),
+        "invalid unicode escape, not a valid character"
+    );
+    assert_eq!(
+        error(r#""\u{41""#),
+        "unterminated unicode escape, expected `}`"
+    );
+    assert_eq!(
+        error("'ab'"),
+        "character literal may only contain one character"
+    );
+    assert_eq!(error("''"), "empty character literal");
+    assert_eq!(
+        error("'é'"),
+        "non-ASCII character in character literal, use a `\\x` escape"
+    );
+    assert_eq!(
+        error(r"'\u{100}'"),
+        "character literal does not fit in a `u8`"
+    );
+}
+
+#[test]
+fn lex_errors() {
+    let lex = |src: &str| {
+        pebblec_parse::lex::Lexer::new(pebblec_parse::lex::source::Source::from_string(
+            "lex.peb",
+            src.to_string(),
+        ))
+        .lex()
+    };
+    let errors = |src: &str| match lex(src) {
+        Ok(_) => 0,
+        Err(Diag::Single(_)) => 1,
+        Err(Diag::Bundle(bundle)) => bundle.len(),
+    };
+
+    assert!(lex("main: () { let x = 'a'; /* comment */ }").is_ok());
+    assert_eq!(errors("main: () { let x = 1 @ 2; }"), 1);
+    assert_eq!(errors("main: () { let x = \"abc; }"), 1);
+    assert_eq!(errors("main: () { let x = 'a; }"), 1);
+    assert_eq!(errors("main: () {} /* unterminated"), 1);
+    assert_eq!(errors("main: () { let x = @; let y = $; let z = 'a; }"), 3);
+}
+
+#[test]
+fn number_errors() {
+    let compile = |body: &str| compile_body("", body);
+    let error = |body: &str| compile_body_err("", body);
+
+    assert!(compile("let x = 255u8; let y: i8 = -128; let z = 0b1010_1010u8;").is_ok());
+    assert!(compile("let x = 0xffff_ffff_u32; let y = 1.5e3f32; let z = 2f64;").is_ok());
+    assert!(compile("let x = -128i8; let y = -9223372036854775808i64;").is_ok());
+    assert_eq!(
+        error("let x = 300u8;"),
+        "literal out of range for `u8`, the range is `0..=255`"
+    );
+    assert_eq!(
+        error("let x = 128i8;"),
+        "literal out of range for `i8`, the range is `-128..=127`"
+    );
+    assert_eq!(
+        error("let x = -129i8;"),
+        "literal out of range for `i8`, the range is `-128..=127`"
+    );
+    assert_eq!(
+        error("let x: i8 = 128;"),
+        "literal out of range for `i8`, the range is `-128..=127`"
+    );
+    assert_eq!(
+        error("let x: i8 = -129;"),
+        "literal out of range for `i8`, the range is `-128..=127`"
+    );
+    assert_eq!(
+        error("let x: u8 = -1;"),
+        "literal out of range for `u8`, the range is `0..=255`"
+    );
+    assert_eq!(
+        error("let x: u8 = 0; x = 256;"),
+        "literal out of range for `u8`, the range is `0..=255`"
+    );
+    assert_eq!(error("let x = 1e40f32;"), "literal out of range for `f32`");
+    assert_eq!(
+        error("let x = 0b102;"),
+        "invalid digit for a binary literal"
+    );
+    assert_eq!(
+        error("let x = 0x;"),
+        "expected digits in hexadecimal literal"
+    );
+    assert_eq!(
+        error("let x = 12abc;"),
+        "invalid suffix `abc` for number literal"
+    );
+    assert_eq!(error("let x = 1.5u8;"), "integer suffix on a float literal");
+}
+
+#[test]
+fn tuple_errors() {
+    let compile = |body: &str| compile_body("P: struct { x: i32, y: i32 }", body);
+    let error = |body: &str| compile_body_err("P: struct { x: i32, y: i32 }", body);
+
+    assert!(compile("let (a, (b, c)) = (1, (2.5, 3u8)); let t: (i32, f32) = (a, 1.5);").is_ok());
+    assert!(compile("let P { x, y: z } = P { x: 1, y: 2 }; let t = (x, z); let w = t.1;").is_ok());
+    assert_eq!(
+        error("let (a, b) = (1, 2, 3);"),
+        "mismatched types: expected a tuple with 2 elements, got `(i64, i64, i64)`"
+    );
+    assert_eq!(
+        error("let (a, b) = 5;"),
+        "mismatched types: expected a tuple with 2 elements, got `{integer}`"
+    );
+    assert_eq!(
+        error("let (a, a) = (1, 2);"),
+        "`a` is bound more than once in this pattern"
+    );
+    assert_eq!(
+        error("let t = (1, 2); let x = t.2;"),
+        "invalid access: `(i64, i64)` has no field `2`"
+    );
+    assert_eq!(
+        error("let t: (i32, u8) = (1, 2.5);"),
+        "mismatched types: expected `u8`, got `{float}`"
+    );
+    assert_eq!(
+        error("let P { z } = P { x: 1, y: 2 };"),
+        "`P` has no field `z`"
+    );
+    assert_eq!(
+        error("let P { x } = (1, 2);"),
+        "mismatched types: expected `P`, got `(i64, i64)`"
+    );
+}
+
+const TUPLE_LET_ANNOTATED: &str = "
+main: () -> i32 {
+    let (a, b) = (1, 2);
+    let x: i32 = a;
+    x - 1
+}
+";
+
+#[test]
+fn tuple_let_annotated() {
+    assert_eq!(0, run("tuple_let_annotated.peb", TUPLE_LET_ANNOTATED));
+}
+
+/// Builds `tests/ffi/<name>.c` into a shared library and returns the source of
+/// `tests/ffi/<name>.peb`, linked against it.
+fn build_ffi(name: &str) -> String {
+    let lib = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("lib{name}.so"));
+    let status = std::process::Command::new("cc")
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(&lib)
+        .arg(format!("tests/ffi/{name}.c"))
+        .status()
+        .expect("failed to run `cc`");
+    assert!(status.success());
+
+    io::read_string(format!("tests/ffi/{name}.peb"))
+        .unwrap()
+        .replace(
+            &format!("\"lib{name}.so\""),
+            &format!("{:?}", lib.to_str().unwrap()),
+        )
+}
+
+/// Builds `tests/ffi/<name>.c` into a shared library and runs `tests/ffi/<name>.peb` against it.
+fn run_ffi(name: &str) -> i32 {
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string(format!("tests/ffi/{name}.peb"), build_ffi(name))
+        .unwrap();
+    pebblec::interp::InterpInstance::new(&bytecode).run(false)
+}
+
+#[test]
+fn ffi_callbacks() {
+    assert_eq!(0, run_ffi("callbacks"));
+}
+
+#[test]
+fn ffi_variadic() {
+    assert_eq!(0, run_ffi("variadic"));
+}
+
+#[test]
+fn ffi_union() {
+    assert_eq!(0, run_ffi("union"));
+}
+
+#[test]
+fn ffi_escape() {
+    // a callback that outlives its external call aborts the interpreter, so each case runs in
+    // its own process
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("escape.peb");
+    std::fs::write(&path, build_ffi("escape")).unwrap();
+    for (args, msg) in [
+        (
+            &[][..],
+            "error: `add_one` was called after the external call that received it returned",
+        ),
+        (
+            &["thread"][..],
+            "error: `add_one` was called from a thread other than the interpreter's",
+        ),
+    ] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pebblec"))
+            .arg(&path)
+            .arg("--")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains(msg));
+    }
+}
+
+#[test]
+fn variadic_errors() {
+    let items = "use core::string; \
+        #[link(\"libc.so.6\")] extern(\"C\") { printf: (format: &u8, ...) -> i32; }";
+    let compile = |body: &str| compile_body(items, body);
+    let error = |body: &str| compile_body_err(items, body);
+
+    assert!(compile("let x: i32 = 4; let n = printf(\"%d\\0\"*.as_ptr(), x, 2.5, true);").is_ok());
+    assert_eq!(
+        error("let n = printf();"),
+        "expected at least `1` arguments, got `0`"
+    );
+    assert_eq!(
+        error("let n = printf(4);"),
+        "mismatched types: expected `&u8`, got `{int}`"
+    );
+    assert_eq!(
+        error("let n = printf(\"%s\\0\"*.as_ptr(), \"str\");"),
+        "a value of type `&str` cannot be passed as a variadic argument"
+    );
+    assert_eq!(
+        error("let a: [i32; 2] = [1, 2]; let n = printf(\"\\0\"*.as_ptr(), a);"),
+        "a value of type `[i32; 2]` cannot be passed as a variadic argument"
+    );
+
+    assert_eq!(
+        compile_err("f: (a: i32, ...) {} main: () {}"),
+        "variadic parameters are only allowed in external functions"
+    );
+    assert_eq!(
+        compile_err("#[link(\"libc.so.6\")] extern(\"C\") { f: (...); } main: () {}"),
+        "variadic functions require a fixed parameter"
+    );
+    assert_eq!(
+        compile_err(
+            "#[link(\"libc.so.6\")] extern(\"C\") { f: (a: i32, ..., b: i32); } main: () {}"
+        ),
+        "`...` must be the last parameter"
+    );
+}
+
+#[test]
+fn fn_ptr_errors() {
+    let items = "use core::io; add: (a: i32, b: i32) -> i32 { a + b } \
+        V: struct { x: i32 } impl V { get: (self) -> i32 { self*.x } }";
+    let compile = |body: &str| compile_body(items, body);
+    let error = |body: &str| compile_body_err(items, body);
+
+    assert!(compile("let f = &add; let x = f(1, 2);").is_ok());
+    assert!(compile("let f: fn(&V) -> i32 = &V::get; let v = V { x: 1 }; let x = f(&v);").is_ok());
+    assert_eq!(
+        error("let f = &add; let x = f(1);"),
+        "expected `2` arguments, got `1`"
+    );
+    assert_eq!(
+        error("let f = &add; let x = f(1, 2.5);"),
+        "mismatched types: expected `i32`, got `{float}`"
+    );
+    assert_eq!(
+        error("let f: fn(i32) -> i32 = &add;"),
+        "mismatched types: expected `fn(i32) -> i32`, got `fn(i32, i32) -> i32`"
+    );
+    assert_eq!(
+        error("let f = 5; let x = f(1);"),
+        "expected a function, found `{integer}`"
+    );
+    assert_eq!(error("let x = undefined(1);"), "function is not defined");
+    assert_eq!(
+        error("let f = V::get;"),
+        "methods must be called or referenced with `&`"
+    );
+    assert_eq!(error("let f = &V::missing;"), "`V` has no method `missing`");
+    assert_eq!(
+        error("let f = &println;"),
+        "cannot take the address of `println`"
+    );
+}
+
+#[test]
+fn extern_fn_errors() {
+    let items = |sig: &str| {
+        format!(
+            "H: struct {{ f: fn(i32) -> i32 }} W: struct {{ h: [H; 2] }} \
+            O: struct {{ f: ?fn() }} \
+            #[link(\"libc.so.6\")] extern(\"C\") {{ {sig} }}"
+        )
+    };
+    let compile = |sig: &str| compile_items(&items(sig));
+    let error = |sig: &str| compile_items_err(&items(sig));
+
+    assert!(compile("f: (f: fn(i32) -> i32, h: &H);").is_ok());
+    assert!(compile("f: (f: ?fn(i32) -> i32) -> fn(i32) -> i32;").is_ok());
+    assert_eq!(
+        error("f: (h: H);"),
+        "`H` stores a function pointer and cannot be passed to or returned from C functions"
+    );
+    assert_eq!(
+        error("f: () -> H;"),
+        "`H` stores a function pointer and cannot be passed to or returned from C functions"
+    );
+    assert_eq!(
+        error("f: (w: W);"),
+        "`H` stores a function pointer and cannot be passed to or returned from C functions"
+    );
+    assert_eq!(
+        error("f: (o: O);"),
+        "`O` stores a function pointer and cannot be passed to or returned from C functions"
+    );
+    assert_eq!(
+        error("f: (f: fn(H));"),
+        "`H` stores a function pointer and cannot be passed to or returned from C functions"
+    );
+    assert_eq!(
+        error("f: (f: fn() -> (i32, H));"),
+        "`H` stores a function pointer and cannot be passed to or returned from C functions"
+    );
+    assert!(
+        compile_items(
+            "H: struct { f: fn(i32) -> i32 } #[link(\"libc.so.6\")] extern(\"host\") { f: (h: H); }"
+        )
+        .is_ok()
+    );
+}
+
+#[test]
+fn closure_errors() {
+    let items = "apply: (f: |i32| -> i32) -> i32 { f(1) }";
+    let compile = |body: &str| compile_body(items, &format!("let a: i32 = 2; {body}"));
+    let error = |body: &str| compile_body_err(items, &format!("let a: i32 = 2; {body}"));
+
+    assert!(compile("let f = |x: i32| x + a; let y = apply(f);").is_ok());
+    assert!(compile("let f = move |x: i32| -> i32 { return x * a; }; let y = f(2);").is_ok());
+    assert!(compile("let f = || { a += 1; }; f();").is_ok());
+    assert_eq!(
+        error("let f = |x| x + a;"),
+        "closure parameters require a type"
+    );
+    assert_eq!(
+        error("let f = |x: i32| { return x; };"),
+        "cannot `return` from a closure without a return type"
+    );
+    assert_eq!(
+        error("let f = |x: i32| -> i32 { x; };"),
+        "mismatched types: expected `i32`, got `()`"
+    );
+    assert_eq!(
+        error("let f = |x: i32| x; let y = f(1, 2);"),
+        "expected `1` arguments, got `2`"
+    );
+    assert_eq!(
+        error("let f = |x: i32| x; let y = f(2.5);"),
+        "mismatched types: expected `i32`, got `{float}`"
+    );
+    assert_eq!(
+        error("let f = |x: f32| x; let y = apply(f);"),
+        "value of type `|f32| -> f32` cannot be coerced into a `|i32| -> i32`"
+    );
+    assert_eq!(
+        error("let f = |x: i32| x; let g: fn(i32) -> i32 = f;"),
+        "mismatched types: expected `fn(i32) -> i32`, got `|i32| -> i32`"
+    );
+    assert_eq!(error("let f = |x: i32| x + b;"), "`b` is not declared");
+
+    // a closure's environment is in the frame that created it, so closures cannot escape it
+    let escapes = "closures cannot be returned from functions";
+    assert_eq!(
+        compile_items_err("f: () -> |i32| -> i32 { |x: i32| x }"),
+        escapes
+    );
+    assert_eq!(
+        compile_items_err("f: () -> (i32, |i32| -> i32) {}"),
+        escapes
+    );
+    assert_eq!(compile_items_err("f: (g: fn() -> [|i32|; 2]) {}"), escapes);
+    assert_eq!(
+        compile_items_err("S: struct { f: |i32| -> i32 }"),
+        "closures cannot be stored in struct fields"
+    );
+    assert_eq!(
+        compile_items_err("C: const || = 0;"),
+        "closures cannot be stored in constants"
+    );
+    assert_eq!(
+        error("let f = move || move |x: i32| x + a;"),
+        "closures cannot be returned from closures"
+    );
+    assert_eq!(
+        error("let f = || -> || { || {} };"),
+        "closures cannot be returned from closures"
+    );
+    assert_eq!(
+        error("let f = |g: || -> ||| {};"),
+        "closures cannot be returned from closures"
+    );
+}
+
+#[test]
+fn defer_errors() {
+    let compile = |body: &str| compile_body("", &format!("let x: i32 = 0; {body}"));
+    let error = |body: &str| compile_body_err("", &format!("let x: i32 = 0; {body}"));
+
+    assert!(compile("defer x += 1;").is_ok());
+    assert!(compile("loop { defer x += 1; break; }").is_ok());
+    assert!(compile("defer loop { break; }").is_ok());
+    assert_eq!(
+        error("defer return;"),
+        "cannot `return` from a deferred statement"
+    );
+    assert_eq!(
+        error("defer if true { return; }"),
+        "cannot `return` from a deferred statement"
+    );
+    assert_eq!(
+        error("loop { defer break; }"),
+        "cannot `break` out of a deferred statement"
+    );
+    assert_eq!(
+        error("loop { defer continue; }"),
+        "cannot `continue` out of a deferred statement"
+    );
+    assert_eq!(
+        error("defer let y = 2;"),
+        "expected an expression or assignment after `defer`"
+    );
+    assert_eq!(
+        error("defer defer x += 1;"),
+        "expected an expression or assignment after `defer`"
+    );
+    assert_eq!(
+        error("defer x += 1.5;"),
+        "mismatched types: expected `i32`, got `{float}`"
+    );
+    assert_eq!(
+        error("'a: loop { defer loop { break 'a; } break; }"),
+        "cannot `break` out of a deferred statement"
+    );
+    assert!(compile("'a: loop { defer 'b: loop { break 'b; } break; }").is_ok());
+}
+
+#[test]
+fn label_errors() {
+    let compile = |body: &str| compile_body("", &format!("let x: i32 = 0; {body}"));
+    let error = |body: &str| compile_body_err("", &format!("let x: i32 = 0; {body}"));
+
+    assert!(compile("'a: loop { loop { break 'a; } }").is_ok());
+    assert!(compile("'a: while x < 2 { x += 1; continue 'a; }").is_ok());
+    assert!(compile("let y: u8 = loop { break 2; };").is_ok());
+    assert!(compile("let c = 'a'; let y = 'a: loop { break 'a c; };").is_ok());
+    assert_eq!(error("break;"), "`break` outside of a loop");
+    assert_eq!(error("continue;"), "`continue` outside of a loop");
+    assert_eq!(error("loop { break 'a; }"), "use of undeclared label `'a`");
+    assert_eq!(
+        error("'a: loop { break; } loop { continue 'a; }"),
+        "use of undeclared label `'a`"
+    );
+    assert_eq!(
+        error("while x < 2 { break 1; }"),
+        "`break` with a value is only allowed in `loop`"
+    );
+    assert_eq!(
+        error("'a: for i in 0..2 { loop { break 'a 1; } }"),
+        "`break` with a value is only allowed in `loop`"
+    );
+    assert_eq!(
+        error("let y = loop { if x == 0 { break; } break 1; };"),
+        "`break` without a value in a `loop` that breaks with a value"
+    );
+    assert_eq!(
+        error("let y = loop { if x == 0 { break true; } break 1; };"),
+        "mismatched types: expected `bool`, got `{int}`"
+    );
+    assert_eq!(
+        error("let y: bool = loop { break 1; };"),
+        "mismatched types: expected `bool`, got `{int}`"
+    );
+    assert_eq!(
+        error("loop { break loop { break; }; }"),
+        "cannot `break` with a value of type `()`"
+    );
+    assert_eq!(error("let f = || { break; };"), "`break` outside of a loop");
+    assert_eq!(
+        error("loop { let f = || { break; }; break; }"),
+        "`break` outside of a loop"
+    );
+}
+
+#[test]
+fn optional_errors() {
+    let items = "by_ref: (r: &i32) {} by_opt: (r: ?&i32) {} \
+        V: struct { x: i32 } impl V { by_ref: (self, r: &i32) {} }";
+    let compile =
+        |body: &str| compile_body(items, &format!("let x: i32 = 0; let p: ?&i32 = &x; {body}"));
+    let error =
+        |body: &str| compile_body_err(items, &format!("let x: i32 = 0; let p: ?&i32 = &x; {body}"));
+
+    assert!(compile("if let r = p { (r*) += 1; }").is_ok());
+    assert!(compile("let q: ?&i32 = null; let r: &i32 = &x; q = r;").is_ok());
+    assert!(compile("let b = p == null || p != null;").is_ok());
+    assert!(compile("let r: &i32 = &x; by_opt(r); by_opt(p); by_opt(null);").is_ok());
+    assert_eq!(
+        error("let y = p*;"),
+        "expression of type `?&i32` may be `null` and cannot be dereferenced"
+    );
+    assert_eq!(
+        error("let r: &i32 = p;"),
+        "mismatched types: expected `&i32`, got `?&i32`"
+    );
+    assert_eq!(
+        error("if let r = x { }"),
+        "mismatched types: expected an optional, got `i32`"
+    );
+    assert_eq!(
+        error("if let r = null { }"),
+        "mismatched types: expected an optional, got `null`"
+    );
+    assert_eq!(
+        error("if let r = p { } else { let y = r*; }"),
+        "`r` is not declared"
+    );
+    assert_eq!(
+        error("while let r = p { } let y = r*;"),
+        "`r` is not declared"
+    );
+    assert_eq!(error("let q = null;"), "could not infer type of `q`");
+    assert_eq!(error("let q: ?i32 = null;"), "`i32` cannot be optional");
+    assert_eq!(error("let q: ?&str = null;"), "`&str` cannot be optional");
+
+    // only a reference coerces into an optional
+    let mismatch = "mismatched types: expected `&i32`, got `?&i32`";
+    assert_eq!(error("by_ref(p);"), mismatch);
+    assert_eq!(error("let v = V { x: 1 }; v.by_ref(p);"), mismatch);
+    assert_eq!(error("let f = &by_ref; f(p);"), mismatch);
+    assert_eq!(
+        error("let b: u8 = 1; let r: &u8 = &b; by_opt(r);"),
+        "mismatched types: expected `?&i32`, got `&u8`"
+    );
+}
+
+#[test]
+fn attribute_errors() {
+    assert!(compile_items("#[intrinsic] exit: (code: i32) {}").is_ok());
+    assert!(compile_items("#[link(\"libc\")] extern(\"C\") { abs: (x: i32) -> i32; }").is_ok());
+    assert!(compile_items("#[link = \"libc\"] extern(\"C\") { abs: (x: i32) -> i32; }").is_ok());
+    assert_eq!(
+        compile_items_err("#[unknown] f: () {}"),
+        "unknown attribute `unknown`"
+    );
+    assert_eq!(
+        compile_items_err("#[core::intrinsic] f: () {}"),
+        "unknown attribute `core::intrinsic`"
+    );
+    assert_eq!(
+        compile_items_err("#[intrinsic(x)] f: () {}"),
+        "`intrinsic` does not take arguments"
+    );
+    assert_eq!(
+        compile_items_err("#[intrinsic = 1] f: () {}"),
+        "`intrinsic` does not take arguments"
+    );
+    assert_eq!(
+        compile_items_err("#[link] extern(\"C\") { abs: (x: i32) -> i32; }"),
+        "expected `#[link(\"...\")]` or `#[link = \"...\"]`"
+    );
+    assert_eq!(
+        compile_items_err("#[link(1)] extern(\"C\") { abs: (x: i32) -> i32; }"),
+        "expected `#[link(\"...\")]` or `#[link = \"...\"]`"
+    );
+    assert_eq!(
+        compile_items_err("#[link(\"a\", \"b\")] extern(\"C\") { abs: (x: i32) -> i32; }"),
+        "expected `#[link(\"...\")]` or `#[link = \"...\"]`"
+    );
+    assert_eq!(
+        compile_items_err("#[link(\"libc\")] f: () {}"),
+        "`link` cannot be applied to a function"
+    );
+    assert_eq!(
+        compile_items_err("#[intrinsic] S: struct { x: i32 }"),
+        "`intrinsic` cannot be applied to a struct"
+    );
+    assert_eq!(
+        compile_items_err("S: struct { #[intrinsic] x: i32 }"),
+        "`intrinsic` cannot be applied to a field"
+    );
+    assert_eq!(
+        compile_items_err("#[intrinsic] C: const i32 = 1;"),
+        "`intrinsic` cannot be applied to a const"
+    );
+    assert_eq!(
+        compile_items_err("S: struct { x: i32 } #[repr(C, align(8))] impl S { f: (self) {} }"),
+        "`repr` cannot be applied to an impl"
+    );
+    assert_eq!(
+        compile_items_err("f: () { #[intrinsic] let x = 1; }"),
+        "`intrinsic` cannot be applied to a statement"
+    );
+    assert_eq!(compile_items_err("#[] f: () {}"), "expected an attribute");
+    assert_eq!(
+        compile_items_err("#[intrinsic f: () {}"),
+        "unclosed attribute"
+    );
+    assert_eq!(
+        compile_items_err("#[repr(C,, packed)] f: () {}"),
+        "expected an identifier or literal"
+    );
+}
+
+#[test]
+fn repr_errors() {
+    let items = |structs: &str, sig: &str| {
+        format!("{structs} #[link(\"libc.so.6\")] extern(\"C\") {{ {sig} }}")
+    };
+    let compile_extern = |structs: &str, sig: &str| compile_items(&items(structs, sig));
+    let extern_err = |structs: &str, sig: &str| compile_items_err(&items(structs, sig));
+
+    assert!(compile_items("#[repr(C)] #[align(16)] S: struct { x: u8 }").is_ok());
+    assert!(compile_items("#[repr(C, packed)] S: struct { x: u8, y: u64 }").is_ok());
+    assert!(compile_items("#[align = 8] S: struct { x: u8 }").is_ok());
+    assert!(compile_extern("#[repr(packed)] S: struct { x: u8 }", "f: (s: &S);").is_ok());
+    assert!(compile_extern("#[repr(C)] S: struct { x: u8 }", "f: (s: S) -> S;").is_ok());
+    assert_eq!(
+        compile_items_err("#[repr] S: struct { x: u8 }"),
+        "expected `#[repr(...)]`"
+    );
+    assert_eq!(
+        compile_items_err("#[repr(Rust)] S: struct { x: u8 }"),
+        "unknown representation, expected `C` or `packed`"
+    );
+    assert_eq!(
+        compile_items_err("#[repr(C = 1)] S: struct { x: u8 }"),
+        "unknown representation, expected `C` or `packed`"
+    );
+    assert_eq!(
+        compile_items_err("#[align(3)] S: struct { x: u8 }"),
+        "alignment must be a power of two"
+    );
+    assert_eq!(
+        compile_items_err("#[align(0)] S: struct { x: u8 }"),
+        "alignment must be a power of two"
+    );
+    assert_eq!(
+        compile_items_err("#[align(\"8\")] S: struct { x: u8 }"),
+        "expected `#[align(<int>)]` or `#[align = <int>]`"
+    );
+    assert_eq!(
+        compile_items_err("#[align(8u64)] S: struct { x: u8 }"),
+        "expected `#[align(<int>)]` or `#[align = <int>]`"
+    );
+    assert_eq!(
+        compile_items_err("#[repr(packed)] #[align(8)] S: struct { x: u8 }"),
+        "packed structs cannot be over-aligned"
+    );
+    assert_eq!(
+        compile_items_err("#[align(8)] #[repr(packed)] S: struct { x: u8 }"),
+        "packed structs cannot be over-aligned"
+    );
+    assert_eq!(
+        compile_items_err("#[align(8)] f: () {}"),
+        "`align` cannot be applied to a function"
+    );
+    assert_eq!(
+        extern_err("#[repr(packed)] S: struct { x: u8 }", "f: (s: S);"),
+        "`S` does not have a C layout and cannot be passed to extern functions by value"
+    );
+    assert_eq!(
+        extern_err("#[align(16)] S: struct { x: u8 }", "f: () -> S;"),
+        "`S` does not have a C layout and cannot be passed to extern functions by value"
+    );
+    assert_eq!(
+        extern_err("#[repr(packed)] S: struct { x: u8 }", "f: (cb: fn(S));"),
+        "`S` does not have a C layout and cannot be passed to extern functions by value"
+    );
+    assert_eq!(
+        extern_err(
+            "#[repr(packed)] S: struct { x: u8 } T: struct { s: S }",
+            "f: (t: T);"
+        ),
+        "`S` does not have a C layout and cannot be passed to extern functions by value"
+    );
+}
+
+#[test]
+fn union_errors() {
+    let items = "U: union { x: u32, y: f32 } S: struct { u: U }";
+    let compile = |body: &str| compile_body(items, body);
+    let error = |body: &str| compile_body_err(items, body);
+
+    assert!(compile("let u = U { x: 1 }; let x = unsafe { u.x };").is_ok());
+    assert!(compile("let u = U { x: 1 }; unsafe { u.y = 2.0; }").is_ok());
+    assert!(compile("let s = S { u: U { y: 1.0 } }; let x: u32 = unsafe { s.u.x };").is_ok());
+    assert_eq!(
+        error("let u = U { x: 1 }; let x = u.x;"),
+        "access to union field `x` requires an `unsafe` block"
+    );
+    assert_eq!(
+        error("let u = U { x: 1 }; u.y = 2.0;"),
+        "access to union field `y` requires an `unsafe` block"
+    );
+    assert_eq!(
+        error("let u = U { x: 1 }; let f = || u.x;"),
+        "access to union field `x` requires an `unsafe` block"
+    );
+    assert_eq!(
+        error("let u = U {};"),
+        "union definition must initialize exactly one field, found 0"
+    );
+    assert_eq!(
+        error("let u = U { x: 1, y: 2.0 };"),
+        "union definition must initialize exactly one field, found 2"
+    );
+    assert_eq!(
+        error("let U { x, y } = U { x: 1 };"),
+        "union `U` cannot be destructured"
+    );
+    assert_eq!(
+        error("let u = U { x: 1 }; let x = unsafe u.x;"),
+        "expected `{`"
+    );
+    assert_eq!(
+        compile_items_err("V: union {}"),
+        "union `V` must have at least one field"
+    );
+    assert_eq!(
+        compile_items_err("V: union { x: u8, x: u16 }"),
+        "failed to parse struct"
+    );
+}
+
+const HOST: &str = "
+extern(\"host\") {
+    scale: (x: f64, by: i32) -> f64;
+    record: (value: i64);
+    ready: () -> bool;
+}
+
+COUNT: const u32 = 3;
+
+step: (n: i32) -> i32 {
+    COUNT += 1;
+    n * 2
+}
+
+main: () -> i32 {
+    record(-10);
+    record(COUNT as i64);
+    if ready() && scale(1.5, 2) == 3.0 { 0 } else { 1 }
+}
+";
+
+#[test]
+fn host_functions() {
+    use pebblec::interp::{InterpError, InterpInstance, Value};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("host.peb", HOST.to_string())
+        .unwrap();
+    let recorded = Rc::new(RefCell::new(Vec::new()));
+    let instance = || {
+        let recorded = recorded.clone();
+        InterpInstance::new(&bytecode)
+            .host("scale", |x: f64, by: i32| x * by as f64)
+            .host("record", move |value: i64| {
+                recorded.borrow_mut().push(value)
+            })
+            .host("ready", || true)
+    };
+
+    assert_eq!(0, instance().run(false));
+    assert_eq!(*recorded.borrow(), [-10, 3]);
+
+    let mut embedded = instance();
+    assert_eq!(embedded.global("COUNT").unwrap(), Value::U32(3));
+    assert_eq!(
+        embedded.call("step", &[Value::I32(4)]).unwrap(),
+        Value::I32(8)
+    );
+    assert_eq!(embedded.global("COUNT").unwrap(), Value::U32(4));
+    embedded.set_global("COUNT", 10u32).unwrap();
+    assert_eq!(
+        embedded.call("step", &[Value::I32(-1)]).unwrap(),
+        Value::I32(-2)
+    );
+    assert_eq!(u32::try_from(embedded.global("COUNT").unwrap()), Ok(11));
+    assert_eq!(embedded.call("main", &[]).unwrap(), Value::I32(0));
+    assert_eq!(recorded.borrow().last(), Some(&11));
+
+    assert!(matches!(
+        embedded.set_global("COUNT", 1i32),
+        Err(InterpError::GlobalType { .. })
+    ));
+    assert!(matches!(
+        embedded.global("MISSING"),
+        Err(InterpError::UnknownGlobal(_))
+    ));
+
+    let mut missing = InterpInstance::new(&bytecode).host("ready", || true);
+    assert!(matches!(
+        missing.call("step", &[Value::I32(1)]),
+        Err(InterpError::UnknownHost(_))
+    ));
+    assert_eq!(1, missing.run(false));
+
+    let mut mismatched = InterpInstance::new(&bytecode)
+        .host("scale", |x: f32, by: i32| x * by as f32)
+        .host("record", |_: i64| {})
+        .host("ready", || true);
+    assert!(matches!(
+        mismatched.call("step", &[Value::I32(1)]),
+        Err(InterpError::HostSignature { .. })
+    ));
+
+    // a closure returning `Value` is checked against the declaration when it returns
+    for wrong in [Value::I32(3), Value::Str("3".to_string())] {
+        let mut untyped = InterpInstance::new(&bytecode)
+            .host("scale", move |_: Value, _: Value| wrong.clone())
+            .host("record", |_: i64| {})
+            .host("ready", || true);
+        assert!(matches!(
+            untyped.call("main", &[]),
+            Err(InterpError::HostSignature { .. })
+        ));
+    }
+}
+
+#[test]
+fn host_errors() {
+    assert!(compile_items("extern(\"host\") { f: (x: i32) -> i32; }").is_ok());
+    assert_eq!(
+        compile_items_err("extern(\"host\") { f: (x: i32, ...); }"),
+        "host functions cannot be variadic"
+    );
+    assert_eq!(
+        compile_items_err("extern(\"rust\") { f: (x: i32); }"),
+        "Unknown calling convention `rust`"
+    );
+    assert_eq!(
+        compile_items_err("extern(\"host\") { f: (); } g: () { let p = &f; }"),
+        "cannot take the address of external function `f`"
+    );
+}
+
+const VALUES: &str = "
+Point: struct {
+    x: i32,
+    y: f32,
+}
+
+Named: struct {
+    name: &str,
+    at: Point,
+    visible: bool,
+}
+
+LABEL: const &str = \"none\";
+
+shift: (p: Point, dx: i32) -> Point {
+    Point { x: p.x + dx, y: p.y * 2.0 }
+}
+
+rename: (named: Named, name: &str) -> Named {
+    Named { name: name, at: shift(named.at, 1), visible: !named.visible }
+}
+
+label: () -> &str {
+    LABEL
+}
+
+main: () {}
+";
+
+#[test]
+fn call_values() {
+    use pebblec::interp::{InterpError, InterpInstance, Value};
+
+    let point = |x: i32, y: f32| Value::Struct {
+        name: "Point".to_string(),
+        fields: vec![
+            ("x".to_string(), Value::I32(x)),
+            ("y".to_string(), Value::F32(y)),
+        ],
+    };
+    let named = |name: &str, at: Value, visible: bool| Value::Struct {
+        name: "Named".to_string(),
+        fields: vec![
+            ("name".to_string(), Value::Str(name.to_string())),
+            ("at".to_string(), at),
+            ("visible".to_string(), Value::Bool(visible)),
+        ],
+    };
+
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("values.peb", VALUES.to_string())
+        .unwrap();
+    let mut instance = InterpInstance::new(&bytecode);
+
+    assert_eq!(
+        instance
+            .call("shift", &[point(2, 1.5), Value::I32(-3)])
+            .unwrap(),
+        point(-1, 3.0)
+    );
+    // fields may be given in any order
+    let reordered = Value::Struct {
+        name: "Point".to_string(),
+        fields: vec![
+            ("y".to_string(), Value::F32(0.0)),
+            ("x".to_string(), Value::I32(0)),
+        ],
+    };
+    assert_eq!(
+        instance.call("shift", &[reordered, Value::I32(1)]).unwrap(),
+        point(1, 0.0)
+    );
+    assert_eq!(
+        instance
+            .call(
+                "rename",
+                &[
+                    named("old", point(4, 0.25), true),
+                    Value::Str("new".to_string())
+                ]
+            )
+            .unwrap(),
+        named("new", point(5, 0.5), false)
+    );
+
+    assert_eq!(
+        instance.call("label", &[]).unwrap(),
+        Value::Str("none".to_string())
+    );
+    instance
+        .set_global("LABEL", Value::Str("some".to_string()))
+        .unwrap();
+    assert_eq!(
+        instance.global("LABEL").unwrap(),
+        Value::Str("some".to_string())
+    );
+    assert_eq!(
+        instance.call("label", &[]).unwrap(),
+        Value::Str("some".to_string())
+    );
+
+    assert!(matches!(
+        instance.call("missing", &[]),
+        Err(InterpError::UnknownFunction(_))
+    ));
+    assert!(matches!(
+        instance.call("shift", &[point(0, 0.0)]),
+        Err(InterpError::Arguments {
+            expected: 2,
+            found: 1,
+            ..
+        })
+    ));
+    assert!(matches!(
+        instance.call("shift", &[Value::I32(0), Value::I32(0)]),
+        Err(InterpError::ArgumentType { index: 0, .. })
+    ));
+    let missing_field = Value::Struct {
+        name: "Point".to_string(),
+        fields: vec![("x".to_string(), Value::I32(0))],
+    };
+    assert!(matches!(
+        instance.call("shift", &[missing_field, Value::I32(0)]),
+        Err(InterpError::ArgumentType { index: 0, .. })
+    ));
+    let duplicate_field = Value::Struct {
+        name: "Point".to_string(),
+        fields: vec![
+            ("x".to_string(), Value::I32(0)),
+            ("x".to_string(), Value::I32(1)),
+        ],
+    };
+    assert!(matches!(
+        instance.call("shift", &[duplicate_field, Value::I32(0)]),
+        Err(InterpError::ArgumentType { index: 0, .. })
+    ));
+    assert!(matches!(
+        instance.call("rename", &[named("a", point(0, 0.0), true), Value::U8(0)]),
+        Err(InterpError::ArgumentType { index: 1, .. })
+    ));
+    assert!(matches!(
+        instance.set_global("LABEL", 1u32),
+        Err(InterpError::GlobalType { .. })
+    ));
+}
+
+const ENV: &str = "
+use core::env;
+
+count: (args: &[&str]) -> i32 {
+    let count = 0;
+    for _ in args {
+        count += 1;
+    }
+    count
+}
+
+arg: (i: u64) -> &str {
+    let args = args();
+    args[i]
+}
+
+path: () -> &str {
+    if let path = var(\"PATH\") { path* } else { \"unset\" }
+}
+
+dir: () -> &str {
+    current_dir()
+}
+
+main: (args: &[&str]) -> i32 {
+    count(args)
+}
+";
+
+#[test]
+fn env() {
+    use pebblec::interp::{InterpInstance, Value};
+
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("env.peb", ENV.to_string())
+        .unwrap();
+    assert_eq!(0, InterpInstance::new(&bytecode).run(false));
+
+    let mut instance = InterpInstance::new(&bytecode).args(["env.peb", "-v", "out dir"]);
+    assert_eq!(3, instance.run(false));
+    assert_eq!(
+        instance.call("arg", &[Value::U64(2)]).unwrap(),
+        Value::Str("out dir".to_string())
+    );
+    let path = std::env::var("PATH").unwrap_or_else(|_| "unset".to_string());
+    assert_eq!(instance.call("path", &[]).unwrap(), Value::Str(path));
+    let dir = std::env::current_dir().unwrap();
+    assert_eq!(
+        instance.call("dir", &[]).unwrap(),
+        Value::Str(dir.to_string_lossy().into_owned())
+    );
+
+    assert!(compile_src("main: (args: &[&str]) {}").is_ok());
+    assert!(compile_src("main: (args: &[&str]) -> i32 { 0 }").is_ok());
+    assert_eq!(
+        compile_err("main: (argc: i32) {}"),
+        "`main` must take no parameters or `&[&str]`"
+    );
+    assert_eq!(
+        compile_err("main: (args: &[&u8]) {}"),
+        "`main` must take no parameters or `&[&str]`"
+    );
+    assert_eq!(
+        compile_err("main: (args: &[&str], n: i32) {}"),
+        "`main` must take no parameters or `&[&str]`"
+    );
+}
+
+const ARGS: &str = "
+use core::env;
+
+MAIN_0: const &str = \"\";
+MAIN_1: const &str = \"\";
+MAIN_2: const &str = \"\";
+
+env_arg: (i: u64) -> &str {
+    let args = args();
+    args[i]
+}
+
+main: (args: &[&str]) -> i32 {
+    MAIN_0 = args[0];
+    MAIN_1 = args[1];
+    MAIN_2 = args[2];
+    0
+}
+";
+
+#[test]
+fn args() {
+    use pebblec::interp::{InterpInstance, Value};
+
+    let bytecode = compile_src(ARGS).unwrap();
+    let mut instance = InterpInstance::new(&bytecode).args(["prog", "a", "b c"]);
+    assert_eq!(0, instance.run(false));
+
+    // the arguments reach both `main` and `core::env::args` unchanged
+    for (i, expected) in ["prog", "a", "b c"].into_iter().enumerate() {
+        let expected = Value::Str(expected.to_string());
+        assert_eq!(instance.global(&format!("MAIN_{i}")).unwrap(), expected);
+        assert_eq!(
+            instance.call("env_arg", &[Value::U64(i as u64)]).unwrap(),
+            expected
+        );
+    }
+}
+
+const FILES: &str = "
+use core::io;
+use core::map;
+
+files: (path: &str, missing: &str) -> i32 {
+    if write_file(path, \"first\\nsecond\\r\\nthird\") != IO_OK {
+        return 1;
+    }
+    if !file_exists(path) || file_exists(missing) {
+        return 2;
+    }
+    let meta = Metadata { size: 0, is_dir: false, readonly: false, modified: 0 };
+    if file_metadata(path, &meta) != IO_OK || meta.size != 19 || meta.is_dir || meta.modified == 0 {
+        return 3;
+    }
+
+    let file = File::none();
+    if file.write_str(\"x\") != IO_INVALID_INPUT || file.close() != IO_INVALID_INPUT {
+        return 13;
+    }
+    if File::open(path, &file) != IO_OK {
+        return 4;
+    }
+    let line = \"\";
+    let count = 0;
+    while file.read_line(&line) == IO_OK {
+        free_str(line);
+        count += 1;
+    }
+    file.close();
+    if count != 3 {
+        return 5;
+    }
+
+    let contents = \"\";
+    if read_file(path, &contents) != IO_OK {
+        return 6;
+    }
+    if !map_str_eq(contents, \"first\\nsecond\\r\\nthird\") {
+        return 12;
+    }
+    free_str(contents);
+
+    let buf: [u8; 4] = [0; 4];
+    let read: u64 = 0;
+    File::open(path, &file);
+    if file.read(&buf[0], 4, &read) != IO_OK || read != 4 || buf[0] != 102 {
+        return 7;
+    }
+    file.close();
+    if file.close() != IO_INVALID_INPUT {
+        return 8;
+    }
+
+    if read_file(missing, &contents) != IO_NOT_FOUND {
+        return 9;
+    }
+    if File::stdin().write_str(\"x\") != IO_INVALID_INPUT {
+        return 10;
+    }
+
+    let append = File::none();
+    File::open_with(path, FILE_APPEND, &append);
+    append.write_str(\"\\nfourth\");
+    append.close();
+    read_file(path, &contents);
+
+    if !map_str_eq(contents, \"first\\nsecond\\r\\nthird\\nfourth\") {
+        return 11;
+    }
+    free_str(contents);
+    0
+}
+
+main: () {
+    eprint(\"% \", 1);
+    eprintln(\"%\", \"two\");
+}
+";
+
+#[test]
+fn files() {
+    use pebblec::interp::{InterpInstance, Value};
+
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("files.peb", FILES.to_string())
+        .unwrap();
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join("files.txt").to_string_lossy().into_owned();
+    let missing = dir.join("missing.txt").to_string_lossy().into_owned();
+    let mut instance = InterpInstance::new(&bytecode);
+    assert_eq!(
+        instance
+            .call("files", &[Value::Str(path.clone()), Value::Str(missing)])
+            .unwrap(),
+        Value::I32(0)
+    );
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "first\nsecond\r\nthird\nfourth"
+    );
+    assert_eq!(0, instance.run(false));
+
+    let compile = |body: &str| compile_body("use core::io;", body);
+    let error = |body: &str| compile_body_err("use core::io;", body);
+    assert!(compile("eprintln(\"% %\", 1, true);").is_ok());
+    assert_eq!(
+        error("eprintln(\"%\");"),
+        "format string expects `1` arguments, got `0`"
+    );
+    assert_eq!(
+        error("eprint(\"\", 1);"),
+        "format string expects `0` arguments, got `1`"
+    );
+    assert_eq!(
+        error("let f = &eprintln;"),
+        "cannot take the address of `eprintln`"
+    );
+}
+
+const TIME: &str = "
+use core::time;
+
+clock: () -> u64 {
+    now()
+}
+
+wall_clock: () -> u64 {
+    unix_time()
+}
+
+nap: (ms: u64) -> u64 {
+    let start = Instant::now();
+    sleep_for(Duration::from_millis(ms));
+    start.elapsed().as_millis()
+}
+
+nap_nanos: (nanos: u64) -> u64 {
+    let start = Instant::now();
+    sleep_for(Duration::from_nanos(nanos));
+    start.elapsed().as_nanos()
+}
+
+main: () -> i32 {
+    if nap(1000) == 1000 { 0 } else { 1 }
+}
+";
+
+#[test]
+fn fake_clock() {
+    use pebblec::interp::{InterpInstance, Value};
+    use std::time::{Duration, Instant};
+
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("time.peb", TIME.to_string())
+        .unwrap();
+    let mut instance = InterpInstance::new(&bytecode).fake_clock();
+    assert_eq!(instance.call("clock", &[]).unwrap(), Value::U64(0));
+    assert_eq!(instance.call("wall_clock", &[]).unwrap(), Value::U64(0));
+
+    instance.advance_clock(Duration::from_millis(1500));
+    assert_eq!(
+        instance.call("clock", &[]).unwrap(),
+        Value::U64(1_500_000_000)
+    );
+    assert_eq!(
+        instance.call("wall_clock", &[]).unwrap(),
+        Value::U64(1_500_000_000)
+    );
+
+    // sleeping advances the fake clock without blocking
+    let start = Instant::now();
+    assert_eq!(
+        instance.call("nap", &[Value::U64(250)]).unwrap(),
+        Value::U64(250)
+    );
+    assert_eq!(0, instance.run(false));
+    assert!(start.elapsed() < Duration::from_millis(1000));
+    assert_eq!(
+        instance.call("clock", &[]).unwrap(),
+        Value::U64(2_750_000_000)
+    );
+    // durations shorter than a millisecond are not truncated
+    assert_eq!(
+        instance.call("nap_nanos", &[Value::U64(1500)]).unwrap(),
+        Value::U64(1500)
+    );
+
+    let mut instance = InterpInstance::new(&bytecode);
+    instance.advance_clock(Duration::from_secs(60));
+    let Value::U64(now) = instance.call("clock", &[]).unwrap() else {
+        panic!("`clock` returns `u64`");
+    };
+    assert!(now < 60_000_000_000);
+}
+
+const RAND: &str = "
+use core::rand;
+
+roll: () -> u64 {
+    let rng = Rng::new();
+    rng.next_u64()
+}
+
+main: () -> i32 {
+    if roll() == roll() { 1 } else { 0 }
+}
+";
+
+#[test]
+fn seed() {
+    use pebblec::interp::InterpInstance;
+
+    let bytecode = CompUnit::new(Config::default().no_capture(true))
+        .compile_string("rand.peb", RAND.to_string())
+        .unwrap();
+    let rolls =
+        |instance: &mut InterpInstance| [(); 4].map(|_| instance.call("roll", &[]).unwrap());
+
+    let mut first = InterpInstance::new(&bytecode).seed(7);
+    let mut second = InterpInstance::new(&bytecode).seed(7);
+    let mut other = InterpInstance::new(&bytecode).seed(8);
+    let expected = rolls(&mut first);
+    assert_eq!(expected, rolls(&mut second));
+    assert_ne!(expected, rolls(&mut other));
+    assert_ne!(expected, rolls(&mut InterpInstance::new(&bytecode)));
+    assert_eq!(0, first.run(false));
+}